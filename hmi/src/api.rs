@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use dioxus::fullstack::{WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::kpis::FleetKpis;
+use vda5050_analysis::recording::AgvId;
+use vda5050_data_types::topic::Topic;
+use vda5050_store::live::{LiveBatch, LiveSubscription};
+use vda5050_store::query::{
+    MessageQuery, Stored, StoredAgv, StoredOrder, TaggedMessage, TimeWindow,
+};
+
+/// The most messages a single call returns, so that neither side holds a whole recording.
+#[cfg(feature = "server")]
+const MAX_MESSAGES: usize = 10_000;
+
+/// Every AGV with stored messages, ordered by manufacturer and serial number.
+#[post("/api/agvs")]
+pub async fn list_agvs() -> Result<Vec<StoredAgv>> {
+    Ok(crate::store::with_store_async(|store| store.agvs()).await?)
+}
+
+/// The latest stored messages received in the window, of one AGV and one topic if given, ordered
+/// by receive time.
+#[post("/api/messages")]
+pub async fn get_messages(
+    agv: Option<AgvId>,
+    topic: Option<Topic>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+) -> Result<Vec<Stored<TaggedMessage>>> {
+    query_messages(MessageQuery {
+        agv,
+        topics: topic.into_iter().collect(),
+        from,
+        to,
+        latest: true,
+        limit,
+        ..MessageQuery::default()
+    })
+    .await
+}
+
+/// The stored messages selected by the query, at most 10 000 per call.
+#[post("/api/messages/query")]
+pub async fn query_messages(query: MessageQuery) -> Result<Vec<Stored<TaggedMessage>>> {
+    let query = MessageQuery {
+        limit: Some(query.limit.unwrap_or(MAX_MESSAGES).min(MAX_MESSAGES)),
+        ..query
+    };
+    let messages = crate::store::with_store_async(move |store| store.messages(&query)).await?;
+    Ok(messages
+        .into_iter()
+        .map(|stored| Stored {
+            sequence: stored.sequence,
+            received_at: stored.received_at,
+            message: stored.message.into(),
+        })
+        .collect())
+}
+
+/// Every stored update of an order and the latest state of the AGV working on it.
+#[post("/api/orders")]
+pub async fn get_order(order_id: String) -> Result<Option<StoredOrder>> {
+    Ok(crate::store::with_store_async(move |store| store.order(&order_id)).await?)
+}
+
+/// Computes the fleet KPIs of the stored messages received in the window.
+#[post("/api/kpis")]
+pub async fn get_kpis(window: TimeWindow) -> Result<FleetKpis> {
+    use vda5050_analysis::analytics::kpis::KpiConfig;
+    use vda5050_analysis::recording::Recording;
+
+    let config = KpiConfig {
+        from: window.from,
+        to: window.to,
+        ..KpiConfig::default()
+    };
+    // The last states before the window tell what the AGVs were doing when it starts.
+    let from = window.from.map(|from| from - config.max_state_interval);
+    // Reads the window page by page, so that the store is free for others in between.
+    let mut recording = Recording::default();
+    let mut after_sequence = None;
+    loop {
+        let query = MessageQuery {
+            from,
+            to: window.to,
+            after_sequence,
+            by_sequence: true,
+            limit: Some(MAX_MESSAGES),
+            ..MessageQuery::default()
+        };
+        let page = crate::store::with_store_async(move |store| store.query(&query)).await?;
+        let complete = page.len() < MAX_MESSAGES;
+        for message in page {
+            after_sequence = Some(message.sequence);
+            recording.push_entry(message.to_entry(), format!("store:{}", message.sequence));
+        }
+        if complete {
+            break;
+        }
+    }
+    recording.sort();
+    Ok(tokio::task::spawn_blocking(move || FleetKpis::compute(&recording, &config)).await?)
+}
+
+/// Pushes the latest state and visualization of every AGV as they are stored, throttled per AGV
+/// to the rate the client subscribes with. After a reconnect, the client resumes after the last
+/// sequence number it received.
+#[get("/api/live")]
+pub async fn live_updates(
+    options: WebSocketOptions,
+) -> Result<Websocket<LiveSubscription, LiveBatch>> {
+    Ok(options.on_upgrade(crate::live::serve))
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::battery::{AgvBattery, BatteryAnalysis};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 100.0;
+
+/// Shows the battery statistics of every AGV and its charge curve over time, with the periods it
+/// was driving or charging shaded. All curves share the same time axis so the fleet can be compared.
+#[component]
+pub fn BatteryChart(battery: BatteryAnalysis) -> Element {
+    let fleet_rate = battery.fleet_discharge_per_hour();
+    let times = battery
+        .agvs
+        .iter()
+        .flat_map(|agv| agv.samples.iter().map(|sample| sample.time));
+    let (Some(start), Some(end)) = (times.clone().min(), times.max()) else {
+        return rsx! {
+            div {
+                class: "battery-chart",
+                h3 { "Battery" }
+                p { "No battery states were reported." }
+            }
+        };
+    };
+    let span = (end - start).num_milliseconds().max(1) as f64;
+    let x = move |time: DateTime<Utc>| (time - start).num_milliseconds() as f64 / span * WIDTH;
+
+    rsx! {
+        div {
+            class: "battery-chart",
+            h3 { "Battery" }
+            table {
+                tr {
+                    th { "AGV" }
+                    th { "Charge" }
+                    th { "Discharge" }
+                    th { "Per meter" }
+                    th { "Charging sessions" }
+                    th { "Low battery" }
+                    th { "Remaining runtime" }
+                }
+                for agv in battery.agvs.iter() {
+                    tr {
+                        class: if is_degraded(agv, fleet_rate) { "warning" } else { "" },
+                        td { "{agv.agv}" }
+                        td { {agv.last_charge().map(|charge| format!("{charge:.0} %")).unwrap_or_default()} }
+                        td { {agv.discharge_per_hour.map(|rate| format!("{rate:.1} %/h")).unwrap_or_default()} }
+                        td { {agv.discharge_per_meter.map(|rate| format!("{rate:.3} %/m")).unwrap_or_default()} }
+                        td { "{agv.charging_sessions.len()}" }
+                        td { "{agv.low_battery.len()}" }
+                        td { {agv.remaining_runtime_secs.map(format_runtime).unwrap_or_default()} }
+                    }
+                }
+            }
+            for agv in battery.agvs.iter() {
+                figure {
+                    svg {
+                        width: "{WIDTH}",
+                        height: "{HEIGHT}",
+                        for (from, to, charging) in bands(agv) {
+                            rect {
+                                class: if charging { "charging" } else { "driving" },
+                                x: "{x(from):.1}",
+                                y: "0",
+                                width: "{(x(to) - x(from)).max(1.0):.1}",
+                                height: "{HEIGHT}",
+                            }
+                        }
+                        line {
+                            class: "low-charge",
+                            x1: "0",
+                            x2: "{WIDTH}",
+                            y1: "{y(battery.low_charge):.1}",
+                            y2: "{y(battery.low_charge):.1}",
+                        }
+                        polyline {
+                            class: "charge",
+                            points: agv
+                                .samples
+                                .iter()
+                                .map(|sample| format!("{:.1},{:.1}", x(sample.time), y(sample.charge)))
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                        }
+                    }
+                    figcaption { "{agv.agv}" }
+                }
+            }
+        }
+    }
+}
+
+fn y(charge: f64) -> f64 {
+    HEIGHT - charge.clamp(0.0, 100.0) / 100.0 * HEIGHT
+}
+
+/// The periods the AGV was driving or charging, with a flag that is set for charging.
+fn bands(agv: &AgvBattery) -> Vec<(DateTime<Utc>, DateTime<Utc>, bool)> {
+    let mut bands = Vec::new();
+    for pair in agv.samples.windows(2) {
+        let (sample, next) = (&pair[0], &pair[1]);
+        if sample.charging || sample.driving {
+            match bands.last_mut() {
+                Some((_, to, charging)) if *to == sample.time && *charging == sample.charging => {
+                    *to = next.time
+                }
+                _ => bands.push((sample.time, next.time, sample.charging)),
+            }
+        }
+    }
+    bands
+}
+
+/// A battery that drains more than one and a half times as fast as the fleet median.
+fn is_degraded(agv: &AgvBattery, fleet_rate: Option<f64>) -> bool {
+    match (agv.discharge_per_hour, fleet_rate) {
+        (Some(rate), Some(fleet_rate)) => fleet_rate > 0.0 && rate > fleet_rate * 1.5,
+        _ => false,
+    }
+}
+
+fn format_runtime(secs: f64) -> String {
+    let minutes = (secs / 60.0).round() as i64;
+    format!("{}:{:02} h", minutes / 60, minutes % 60)
+}
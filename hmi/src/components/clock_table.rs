@@ -0,0 +1,31 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::clock::ClockModel;
+
+/// Shows the estimated offset and drift of the clock of every sender relative to the capture host.
+#[component]
+pub fn ClockTable(clocks: ClockModel) -> Element {
+    rsx! {
+        div {
+            class: "clock-table",
+            h3 { "Clocks" }
+            table {
+                tr {
+                    th { "Sender" }
+                    th { "Offset" }
+                    th { "Drift" }
+                    th { "Jitter" }
+                    th { "Messages" }
+                }
+                for estimate in clocks.estimates.iter() {
+                    tr {
+                        td { "{estimate.sender}" }
+                        td { "{estimate.offset_ms:.1} ms" }
+                        td { "{estimate.drift_ppm:.1} ppm" }
+                        td { "{estimate.jitter_ms:.1} ms" }
+                        td { "{estimate.samples}" }
+                    }
+                }
+            }
+        }
+    }
+}
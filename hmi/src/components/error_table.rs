@@ -0,0 +1,149 @@
+use dioxus::prelude::*;
+use std::cmp::Ordering;
+use vda5050_analysis::analytics::errors::{ErrorTimeline, Interval};
+
+/// The columns the error table can be sorted by.
+#[derive(Clone, Copy, PartialEq)]
+enum Column {
+    Agv,
+    Kind,
+    Type,
+    Level,
+    Raised,
+    Duration,
+    Reference,
+}
+
+impl Column {
+    fn compare(self, a: &Interval, b: &Interval) -> Ordering {
+        match self {
+            Column::Agv => a.agv.cmp(&b.agv),
+            Column::Kind => a.kind.cmp(&b.kind),
+            Column::Type => a.event_type.cmp(&b.event_type),
+            Column::Level => a.level.cmp(&b.level),
+            Column::Raised => a.raised_at.cmp(&b.raised_at),
+            Column::Duration => a.duration_secs().total_cmp(&b.duration_secs()),
+            Column::Reference => reference(a).cmp(&reference(b)),
+        }
+    }
+}
+
+/// Shows the errors and information reported by the AGVs as a table that is sorted by clicking
+/// on a column header, followed by the statistics per type.
+#[component]
+pub fn ErrorTable(timeline: ErrorTimeline) -> Element {
+    let mut sort = use_signal(|| (Column::Raised, true));
+
+    let (column, ascending) = sort();
+    let mut intervals: Vec<&Interval> = timeline.intervals.iter().collect();
+    intervals.sort_by(|a, b| {
+        let ordering = column.compare(a, b);
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+    let fleet = timeline
+        .summaries
+        .iter()
+        .filter(|summary| summary.agv.is_none());
+
+    let header = move |label: &'static str, by: Column| {
+        let marker = match sort() {
+            (column, true) if column == by => " ▲",
+            (column, false) if column == by => " ▼",
+            _ => "",
+        };
+        rsx! {
+            th {
+                class: "sortable",
+                onclick: move |_| {
+                    let (column, ascending) = sort();
+                    sort.set((by, column != by || !ascending));
+                },
+                "{label}{marker}"
+            }
+        }
+    };
+
+    rsx! {
+        div {
+            class: "error-table",
+            h3 { "Errors and information" }
+            if timeline.intervals.is_empty() {
+                p { "No errors or information were reported." }
+            } else {
+                table {
+                    tr {
+                        {header("AGV", Column::Agv)}
+                        {header("Kind", Column::Kind)}
+                        {header("Type", Column::Type)}
+                        {header("Level", Column::Level)}
+                        {header("Raised", Column::Raised)}
+                        {header("Duration", Column::Duration)}
+                        {header("Reference", Column::Reference)}
+                        th { "Description" }
+                    }
+                    for interval in intervals {
+                        tr {
+                            class: if interval.is_fatal() { "error" } else { "" },
+                            td { "{interval.agv}" }
+                            td { "{interval.kind}" }
+                            td { "{interval.event_type}" }
+                            td { "{interval.level}" }
+                            td { "{interval.raised_at.to_rfc3339()}" }
+                            td {
+                                "{interval.duration_secs():.1} s"
+                                if interval.cleared_at.is_none() { " (active)" }
+                            }
+                            td { {reference(interval)} }
+                            td { {interval.description.clone().unwrap_or_default()} }
+                        }
+                    }
+                }
+                h3 { "Per type" }
+                table {
+                    tr {
+                        th { "Kind" }
+                        th { "Type" }
+                        th { "Occurrences" }
+                        th { "Per hour" }
+                        th { "Total" }
+                        th { "Mean" }
+                        th { "Longest" }
+                    }
+                    for summary in fleet {
+                        tr {
+                            td { "{summary.kind}" }
+                            td { "{summary.event_type}" }
+                            td { "{summary.occurrences}" }
+                            td { "{summary.per_hour:.1}" }
+                            td { "{summary.total_secs:.1} s" }
+                            td { "{summary.mean_secs:.1} s" }
+                            td { "{summary.max_secs:.1} s" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The node, edge or action the interval refers to, or the last node of the AGV.
+fn reference(interval: &Interval) -> String {
+    if let Some(action_id) = &interval.action_id {
+        match &interval.action_type {
+            Some(action_type) => format!("action {action_id} ({action_type})"),
+            None => format!("action {action_id}"),
+        }
+    } else if let Some(edge_id) = &interval.edge_id {
+        format!("edge {edge_id}")
+    } else if let Some(node_id) = &interval.node_id {
+        format!("node {node_id}")
+    } else if let Some(last_node_id) = &interval.last_node_id {
+        format!("after node {last_node_id}")
+    } else {
+        String::new()
+    }
+}
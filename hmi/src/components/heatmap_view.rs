@@ -0,0 +1,40 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::heatmap::Heatmap;
+
+const SIZE: f64 = 400.0;
+
+/// Draws a heatmap as a grid whose cells get more opaque the more values they hold.
+#[component]
+pub fn HeatmapView(heatmap: Heatmap, caption: String) -> Element {
+    let Some(((min_column, max_column), (min_row, max_row))) = heatmap.bounds() else {
+        return rsx! {};
+    };
+    let columns = (max_column - min_column + 1) as f64;
+    let rows = (max_row - min_row + 1) as f64;
+    let cell = SIZE / columns.max(rows);
+    let max_count = heatmap.max_count().max(1) as f64;
+
+    rsx! {
+        figure {
+            class: "heatmap",
+            svg {
+                width: "{columns * cell}",
+                height: "{rows * cell}",
+                for heat in heatmap.cells.iter() {
+                    rect {
+                        // World Y points up, SVG Y points down.
+                        x: "{(heat.column - min_column) as f64 * cell:.1}",
+                        y: "{(max_row - heat.row) as f64 * cell:.1}",
+                        width: "{cell:.1}",
+                        height: "{cell:.1}",
+                        fill_opacity: "{0.15 + 0.85 * heat.count as f64 / max_count:.2}",
+                        title {
+                            "({heat.column as f64 * heatmap.cell_size:.1}, {heat.row as f64 * heatmap.cell_size:.1}): {heat.count}, mean {heat.mean():.1}"
+                        }
+                    }
+                }
+            }
+            figcaption { "{caption} on map {heatmap.map_id} ({heatmap.cell_size} m cells)" }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::kpis::{AgvKpis, FleetKpis};
+
+const WIDTH: f64 = 600.0;
+const BAR_HEIGHT: f64 = 18.0;
+
+/// Shows the fleet KPIs: how every AGV spent the window as a stacked bar, the time per operating
+/// mode, and the distance, order and availability figures as a table.
+#[component]
+pub fn KpiChart(kpis: FleetKpis) -> Element {
+    let window = match (kpis.from, kpis.to) {
+        (Some(from), Some(to)) => format!("{} - {}", from.to_rfc3339(), to.to_rfc3339()),
+        _ => String::new(),
+    };
+
+    rsx! {
+        div {
+            class: "kpi-chart",
+            h3 { "Fleet KPIs" }
+            p { "{window}" }
+            table {
+                tr {
+                    th { "AGV" }
+                    th { "Time" }
+                    th { "Operating modes" }
+                }
+                for agv in kpis.agvs.iter() {
+                    tr {
+                        td { "{agv.agv}" }
+                        td {
+                            svg {
+                                width: "{WIDTH}",
+                                height: "{BAR_HEIGHT}",
+                                for (class, label, x, width) in segments(agv, activities(agv)) {
+                                    rect {
+                                        class: "{class}",
+                                        x: "{x:.1}",
+                                        y: "0",
+                                        width: "{width:.1}",
+                                        height: "{BAR_HEIGHT}",
+                                        title { "{label}" }
+                                    }
+                                }
+                            }
+                        }
+                        td {
+                            svg {
+                                width: "{WIDTH / 2.0}",
+                                height: "{BAR_HEIGHT}",
+                                for (class, label, x, width) in segments(agv, modes(agv)) {
+                                    rect {
+                                        class: "{class}",
+                                        x: "{x / 2.0:.1}",
+                                        y: "0",
+                                        width: "{width / 2.0:.1}",
+                                        height: "{BAR_HEIGHT}",
+                                        title { "{label}" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            p {
+                class: "legend",
+                span { class: "driving", "driving" }
+                span { class: "idle", "idle" }
+                span { class: "paused", "paused" }
+                span { class: "error", "error" }
+                span { class: "unobserved", "no state" }
+            }
+            table {
+                tr {
+                    th { "AGV" }
+                    th { "Distance" }
+                    th { "Orders" }
+                    th { "Orders per hour" }
+                    th { "Mean order duration" }
+                    th { "Availability" }
+                }
+                for agv in kpis.agvs.iter() {
+                    tr {
+                        td { "{agv.agv}" }
+                        td { "{agv.distance:.1} m" }
+                        td { "{agv.orders_completed}" }
+                        td { "{agv.orders_per_hour:.1}" }
+                        td { {agv.mean_order_secs.map(|secs| format!("{secs:.0} s")).unwrap_or_default()} }
+                        td { {agv.availability.map(|availability| format!("{:.1} %", availability * 100.0)).unwrap_or_default()} }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn activities(agv: &AgvKpis) -> Vec<(String, f64)> {
+    vec![
+        ("driving".to_string(), agv.driving_secs),
+        ("idle".to_string(), agv.idle_secs),
+        ("paused".to_string(), agv.paused_secs),
+        ("error".to_string(), agv.error_secs),
+        (
+            "unobserved".to_string(),
+            (agv.period_secs - agv.observed_secs).max(0.0),
+        ),
+    ]
+}
+
+fn modes(agv: &AgvKpis) -> Vec<(String, f64)> {
+    agv.operating_modes
+        .iter()
+        .map(|(mode, secs)| (format!("mode {}", mode.to_lowercase()), *secs))
+        .collect()
+}
+
+/// Lays out the parts of the period side by side as class, tooltip, x and width.
+fn segments(agv: &AgvKpis, parts: Vec<(String, f64)>) -> Vec<(String, String, f64, f64)> {
+    if agv.period_secs <= 0.0 {
+        return Vec::new();
+    }
+    let mut x = 0.0;
+    parts
+        .into_iter()
+        .filter(|(_, secs)| *secs > 0.0)
+        .map(|(class, secs)| {
+            let width = secs / agv.period_secs * WIDTH;
+            let label = format!("{}: {secs:.0} s", class.trim_start_matches("mode "));
+            let segment = (class, label, x, width);
+            x += width;
+            segment
+        })
+        .collect()
+}
@@ -0,0 +1,46 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::Distribution;
+use vda5050_analysis::analytics::latency::LatencyAnalysis;
+
+/// Shows how long every AGV took to acknowledge orders and instant actions, measured with the
+/// receive times and with the header timestamps, and marks AGVs whose clock drifts.
+#[component]
+pub fn LatencyTable(latency: LatencyAnalysis) -> Element {
+    rsx! {
+        div {
+            class: "latency-table",
+            h3 { "Acknowledgement latency" }
+            table {
+                tr {
+                    th { "AGV" }
+                    th { "Received p50 / p95 / max" }
+                    th { "Header p50 / p95 / max" }
+                    th { "Not acknowledged" }
+                    th { "Clock offset" }
+                    th { "Clock drift" }
+                }
+                for agv in latency.agvs.iter() {
+                    tr {
+                        class: if agv.clock_flagged { "warning" } else { "" },
+                        td { "{agv.agv}" }
+                        td { {format_distribution(&agv.received)} }
+                        td { {format_distribution(&agv.header)} }
+                        td { "{agv.unacknowledged}" }
+                        td { {agv.clock_offset_ms.map(|offset| format!("{offset:.0} ms")).unwrap_or_default()} }
+                        td { {agv.clock_drift_ppm.map(|drift| format!("{drift:.1} ppm")).unwrap_or_default()} }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn format_distribution(distribution: &Option<Distribution>) -> String {
+    match distribution {
+        Some(distribution) => format!(
+            "{:.0} / {:.0} / {:.0} ms (n={})",
+            distribution.p50, distribution.p95, distribution.max, distribution.count
+        ),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,122 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::spatial::{SpatialAnalysis, SpatialLayer};
+
+const SIZE: f64 = 500.0;
+
+/// Draws every map with the nodes sent in orders, overlaid with the heatmap of the selected
+/// layer. Cells get more opaque the higher their value.
+#[component]
+pub fn MapView(spatial: SpatialAnalysis) -> Element {
+    let mut selected = use_signal(|| SpatialLayer::Occupancy);
+    let layer = selected();
+
+    rsx! {
+        div {
+            class: "map-view",
+            h3 { "Maps" }
+            div {
+                class: "controls",
+                for choice in SpatialLayer::ALL {
+                    button {
+                        class: if choice == layer { "selected" } else { "" },
+                        onclick: move |_| selected.set(choice),
+                        "{choice}"
+                    }
+                }
+            }
+            for map_id in spatial.map_ids() {
+                {render_map(&spatial, map_id, layer)}
+            }
+        }
+    }
+}
+
+fn render_map(spatial: &SpatialAnalysis, map_id: &str, layer: SpatialLayer) -> Element {
+    let heatmap = spatial
+        .layer(layer)
+        .iter()
+        .find(|heatmap| heatmap.map_id == map_id);
+    let nodes: Vec<_> = spatial
+        .nodes
+        .iter()
+        .filter(|node| node.location.map_id == map_id)
+        .collect();
+
+    // The world area covered by the cells and the nodes, in [m].
+    let cell_size = spatial.cell_size;
+    let corners = heatmap
+        .into_iter()
+        .flat_map(|heatmap| &heatmap.cells)
+        .flat_map(|cell| {
+            let (x, y) = (cell.column as f64 * cell_size, cell.row as f64 * cell_size);
+            [(x, y), (x + cell_size, y + cell_size)]
+        })
+        .chain(nodes.iter().map(|node| (node.location.x, node.location.y)));
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for (x, y) in corners {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    let (min_x, min_y) = (min_x - cell_size, min_y - cell_size);
+    let (max_x, max_y) = (max_x + cell_size, max_y + cell_size);
+    let scale = SIZE / (max_x - min_x).max(max_y - min_y);
+    // World Y points up, SVG Y points down.
+    let to_svg = move |x: f64, y: f64| ((x - min_x) * scale, (max_y - y) * scale);
+
+    let max_value = heatmap
+        .into_iter()
+        .flat_map(|heatmap| &heatmap.cells)
+        .map(|cell| layer.value(cell))
+        .fold(0.0, f64::max);
+
+    rsx! {
+        figure {
+            svg {
+                width: "{(max_x - min_x) * scale:.0}",
+                height: "{(max_y - min_y) * scale:.0}",
+                if let Some(heatmap) = heatmap {
+                    for cell in heatmap.cells.iter() {
+                        {
+                            let value = layer.value(cell);
+                            let (x, y) = to_svg(
+                                cell.column as f64 * cell_size,
+                                (cell.row + 1) as f64 * cell_size,
+                            );
+                            let opacity = if max_value > 0.0 { 0.15 + 0.85 * value / max_value } else { 0.15 };
+                            rsx! {
+                                rect {
+                                    class: "{layer}",
+                                    x: "{x:.1}",
+                                    y: "{y:.1}",
+                                    width: "{cell_size * scale:.1}",
+                                    height: "{cell_size * scale:.1}",
+                                    fill_opacity: "{opacity:.2}",
+                                    title {
+                                        "({cell.column as f64 * cell_size:.1}, {cell.row as f64 * cell_size:.1}): {value:.1} {layer.unit()} (n={cell.count})"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                for node in nodes.iter() {
+                    {
+                        let (x, y) = to_svg(node.location.x, node.location.y);
+                        rsx! {
+                            circle {
+                                class: "node",
+                                cx: "{x:.1}",
+                                cy: "{y:.1}",
+                                r: "3",
+                                title { "{node.node_id} ({node.location.x:.2}, {node.location.y:.2})" }
+                            }
+                        }
+                    }
+                }
+            }
+            figcaption { "{layer} on map {map_id} ({cell_size} m cells)" }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use dioxus::prelude::*;
+use vda5050_analysis::recording::AgvId;
+use vda5050_data_types::message::Message;
+use vda5050_store::query::{Stored, TaggedMessage};
+
+/// Shows stored messages in the order they were received, with the payload folded away.
+#[component]
+pub fn MessageTable(messages: ReadSignal<Vec<Stored<TaggedMessage>>>) -> Element {
+    let messages = messages.read();
+    rsx! {
+        div {
+            class: "message-table",
+            h3 { "Messages" }
+            if messages.is_empty() {
+                p { "No stored message matches the filters." }
+            } else {
+                table {
+                    tr {
+                        th { "#" }
+                        th { "Received" }
+                        th { "AGV" }
+                        th { "Topic" }
+                        th { "Header ID" }
+                        th { "Order" }
+                        th { "Errors" }
+                        th { "Payload" }
+                    }
+                    for stored in messages.iter() {
+                        {row(stored)}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn row(stored: &Stored<TaggedMessage>) -> Element {
+    let message = Message::from(stored.message.clone());
+    let header = message.header();
+    let (order_id, error_types) = match &message {
+        Message::Order(order) => (Some(order.order_id.clone()), Vec::new()),
+        Message::State(state) => (
+            state.order_id.clone(),
+            state
+                .errors
+                .iter()
+                .map(|error| error.error_type.clone())
+                .collect(),
+        ),
+        _ => (None, Vec::new()),
+    };
+    rsx! {
+        tr {
+            class: if error_types.is_empty() { "" } else { "error" },
+            td { "{stored.sequence}" }
+            td { "{stored.received_at.to_rfc3339()}" }
+            td { "{AgvId::of(header)}" }
+            td { "{message.topic()}" }
+            td { "{header.header_id}" }
+            td { {order_id.unwrap_or_default()} }
+            td { {error_types.join(", ")} }
+            td {
+                details {
+                    summary { "JSON" }
+                    pre { {serde_json::to_string(&message).unwrap_or_default()} }
+                }
+            }
+        }
+    }
+}
@@ -7,3 +7,42 @@ pub use hero::Hero;
 
 mod echo;
 pub use echo::Echo;
+
+mod report_summary;
+pub use report_summary::ReportSummary;
+
+mod error_table;
+pub use error_table::ErrorTable;
+
+mod battery_chart;
+pub use battery_chart::BatteryChart;
+
+mod kpi_chart;
+pub use kpi_chart::KpiChart;
+
+mod latency_table;
+pub use latency_table::LatencyTable;
+
+mod clock_table;
+pub use clock_table::ClockTable;
+
+mod heatmap_view;
+pub use heatmap_view::HeatmapView;
+
+mod safety_table;
+pub use safety_table::SafetyTable;
+
+mod map_view;
+pub use map_view::MapView;
+
+mod traffic_table;
+pub use traffic_table::TrafficTable;
+
+mod order_response_table;
+pub use order_response_table::OrderResponseTable;
+
+mod retained_table;
+pub use retained_table::RetainedTable;
+
+mod message_table;
+pub use message_table::MessageTable;
@@ -0,0 +1,59 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::order_responses::{OrderOutcome, OrderResponseAnalysis};
+
+/// Shows whether every AGV accepted or rejected the orders it was sent, and marks responses that
+/// deviate from the specification, e.g. a rejection without the expected error or reference.
+#[component]
+pub fn OrderResponseTable(responses: OrderResponseAnalysis) -> Element {
+    rsx! {
+        div {
+            class: "order-response-table",
+            h3 { "Order responses" }
+            table {
+                tr {
+                    th { "AGV" }
+                    th { "Accepted" }
+                    th { "Rejected" }
+                    th { "Unanswered" }
+                    th { "With problems" }
+                }
+                for agv in responses.agvs.iter() {
+                    tr {
+                        class: if agv.with_problems > 0 { "warning" } else { "" },
+                        td { "{agv.agv}" }
+                        td { "{agv.accepted}" }
+                        td { "{agv.rejected}" }
+                        td { "{agv.unanswered}" }
+                        td { "{agv.with_problems}" }
+                    }
+                }
+            }
+            if responses.responses.iter().any(|response| response.outcome == OrderOutcome::Rejected || !response.problems.is_empty()) {
+                table {
+                    tr {
+                        th { "Sent" }
+                        th { "AGV" }
+                        th { "Order" }
+                        th { "Response" }
+                        th { "Expected" }
+                        th { "Problems" }
+                    }
+                    for response in responses.responses.iter().filter(|response| response.outcome == OrderOutcome::Rejected || !response.problems.is_empty()) {
+                        tr {
+                            class: if response.problems.is_empty() { "" } else { "error" },
+                            td { "{response.sent_at.to_rfc3339()}" }
+                            td { "{response.agv}" }
+                            td { "{response.order_id}/{response.order_update_id}" }
+                            td {
+                                "{response.outcome}"
+                                {response.errors.iter().map(|error| format!(" {}", error.error_type)).collect::<String>()}
+                            }
+                            td { {response.expected_error.clone().unwrap_or_else(|| "accepted".to_string())} }
+                            td { {response.problems.join("; ")} }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
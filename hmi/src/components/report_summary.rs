@@ -0,0 +1,57 @@
+use dioxus::prelude::*;
+use vda5050_analysis::checks::Severity;
+use vda5050_analysis::report::Report;
+
+/// Shows the AGVs of an analyzed recording and all findings of the checks.
+#[component]
+pub fn ReportSummary(report: Report) -> Element {
+    let period = match (report.start, report.end) {
+        (Some(start), Some(end)) => format!("{} - {}", start.to_rfc3339(), end.to_rfc3339()),
+        _ => String::new(),
+    };
+
+    rsx! {
+        div {
+            class: "report-summary",
+            p {
+                "{report.message_count} messages, {period}. "
+                "{report.count(Severity::Error)} errors, "
+                "{report.count(Severity::Warning)} warnings, "
+                "{report.count(Severity::Info)} infos."
+            }
+            h3 { "Fleet" }
+            table {
+                tr { th { "AGV" } th { "Errors" } th { "Warnings" } }
+                for agv in report.agvs.iter() {
+                    tr {
+                        td { "{agv}" }
+                        td { "{count(&report, agv, Severity::Error)}" }
+                        td { "{count(&report, agv, Severity::Warning)}" }
+                    }
+                }
+            }
+            h3 { "Findings" }
+            table {
+                tr { th { "Severity" } th { "Check" } th { "Time" } th { "AGV" } th { "Message" } }
+                for finding in report.findings.iter() {
+                    tr {
+                        class: "{finding.severity}",
+                        td { "{finding.severity}" }
+                        td { "{finding.check}" }
+                        td { {finding.time.map(|time| time.to_rfc3339()).unwrap_or_default()} }
+                        td { {finding.agv.as_ref().map(|agv| agv.to_string()).unwrap_or_default()} }
+                        td { "{finding.message}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn count(report: &Report, agv: &vda5050_analysis::recording::AgvId, severity: Severity) -> usize {
+    report
+        .findings
+        .iter()
+        .filter(|finding| finding.severity == severity && finding.agv.as_ref() == Some(agv))
+        .count()
+}
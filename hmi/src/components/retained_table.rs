@@ -0,0 +1,57 @@
+use dioxus::prelude::*;
+use vda5050_broker::retained::RetainedView;
+use vda5050_data_types::connection::ConnectionState;
+
+/// Shows the retained `connection` and `factsheet` messages of every AGV, i.e. what a client
+/// learns about the fleet right after it subscribes.
+#[component]
+pub fn RetainedTable(retained: RetainedView) -> Element {
+    rsx! {
+        div {
+            class: "retained-table",
+            h3 { "Retained messages" }
+            if retained.agvs.is_empty() {
+                p { "No AGV has published a retained connection or factsheet yet." }
+            } else {
+                table {
+                    tr {
+                        th { "AGV" }
+                        th { "Connection" }
+                        th { "Since" }
+                        th { "Factsheet" }
+                        th { "Max speed" }
+                        th { "Actions" }
+                    }
+                    for agv in retained.agvs.iter() {
+                        tr {
+                            class: match agv.connection.as_ref().map(|connection| &connection.connection_state) {
+                                Some(ConnectionState::ConnectionBroken) => "error",
+                                Some(ConnectionState::Online) => "",
+                                _ => "warning",
+                            },
+                            td { "{agv.agv}" }
+                            td { {agv.connection.as_ref().map(|connection| connection_name(&connection.connection_state)).unwrap_or_default()} }
+                            td { {agv.connection.as_ref().map(|connection| connection.received_at.to_rfc3339()).unwrap_or_default()} }
+                            td { {agv.factsheet.as_ref().map(|factsheet| format!("{} {}", factsheet.type_field, factsheet.type_version)).unwrap_or_default()} }
+                            td { {agv.factsheet.as_ref().and_then(|factsheet| factsheet.speed_max).map(|speed| format!("{speed:.2} m/s")).unwrap_or_default()} }
+                            td { {agv.factsheet.as_ref().map(|factsheet| factsheet.actions.to_string()).unwrap_or_default()} }
+                        }
+                    }
+                }
+            }
+            for (topic, error) in retained.undecodable.iter() {
+                p { class: "status", "{topic}: {error}" }
+            }
+        }
+    }
+}
+
+/// The connection state as it appears in the messages, e.g. `CONNECTIONBROKEN`.
+fn connection_name(state: &ConnectionState) -> String {
+    match state {
+        ConnectionState::Online => "ONLINE".to_string(),
+        ConnectionState::Offline => "OFFLINE".to_string(),
+        ConnectionState::ConnectionBroken => "CONNECTIONBROKEN".to_string(),
+        ConnectionState::Unknown(name) => name.clone(),
+    }
+}
@@ -0,0 +1,49 @@
+use crate::components::HeatmapView;
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::safety::SafetyAnalysis;
+
+/// Shows all emergency stops and protective field violations with their location, and where on
+/// the maps the protective fields trigger most.
+#[component]
+pub fn SafetyTable(safety: SafetyAnalysis) -> Element {
+    rsx! {
+        div {
+            class: "safety-table",
+            h3 { "Safety events" }
+            if safety.events.is_empty() {
+                p { "No emergency stops or field violations were reported." }
+            } else {
+                table {
+                    tr {
+                        th { "AGV" }
+                        th { "Event" }
+                        th { "Start" }
+                        th { "Duration" }
+                        th { "Location" }
+                    }
+                    for event in safety.events.iter() {
+                        tr {
+                            class: if event.e_stop.is_some() { "error" } else { "warning" },
+                            td { "{event.agv}" }
+                            td {
+                                "{event.kind}"
+                                if let Some(e_stop) = &event.e_stop { " ({e_stop})" }
+                            }
+                            td { "{event.start.to_rfc3339()}" }
+                            td {
+                                "{event.duration_secs:.1} s"
+                                if event.end.is_none() { " (active)" }
+                            }
+                            td {
+                                {event.location.as_ref().map(|location| format!("{} ({:.2}, {:.2})", location.map_id, location.x, location.y)).unwrap_or_default()}
+                            }
+                        }
+                    }
+                }
+            }
+            for heatmap in safety.field_violations.iter() {
+                HeatmapView { heatmap: heatmap.clone(), caption: "Field violations" }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::traffic::TrafficAnalysis;
+
+/// Shows where the released paths of two AGVs came too close and which AGVs waited on each other
+/// in a cycle.
+#[component]
+pub fn TrafficTable(traffic: TrafficAnalysis) -> Element {
+    rsx! {
+        div {
+            class: "traffic-table",
+            h3 { "Traffic" }
+            if traffic.conflicts.is_empty() && traffic.deadlocks.is_empty() {
+                p { "The released paths never came too close." }
+            }
+            if !traffic.deadlocks.is_empty() {
+                table {
+                    tr {
+                        th { "Deadlock" }
+                        th { "Start" }
+                        th { "Duration" }
+                    }
+                    for deadlock in traffic.deadlocks.iter() {
+                        tr {
+                            class: "error",
+                            td {
+                                {deadlock.agvs.iter().map(|agv| agv.to_string()).collect::<Vec<_>>().join(" → ")}
+                            }
+                            td { "{deadlock.start.to_rfc3339()}" }
+                            td {
+                                "{deadlock.duration_secs:.1} s"
+                                if deadlock.end.is_none() { " (active)" }
+                            }
+                        }
+                    }
+                }
+            }
+            if !traffic.conflicts.is_empty() {
+                table {
+                    tr {
+                        th { "AGVs" }
+                        th { "Start" }
+                        th { "Duration" }
+                        th { "Distance" }
+                        th { "Location" }
+                        th { "Released nodes" }
+                    }
+                    for conflict in traffic.conflicts.iter() {
+                        tr {
+                            class: "warning",
+                            td { "{conflict.first} / {conflict.second}" }
+                            td { "{conflict.start.to_rfc3339()}" }
+                            td {
+                                "{conflict.duration_secs:.1} s"
+                                if conflict.end.is_none() { " (active)" }
+                            }
+                            td { "{conflict.min_distance:.2} m of {conflict.required_distance:.2} m" }
+                            td { "{conflict.location.map_id} ({conflict.location.x:.2}, {conflict.location.y:.2})" }
+                            td { "{conflict.first_nodes.join(\", \")} / {conflict.second_nodes.join(\", \")}" }
+                        }
+                    }
+                }
+            }
+            p {
+                "Footprints: "
+                {traffic.footprints.iter().map(|footprint| format!("{} {:.2} m ({})", footprint.agv, footprint.radius, footprint.source)).collect::<Vec<_>>().join(", ")}
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Serves the live updates of the AGVs to one client of the websocket.
+use dioxus::fullstack::TypedWebsocket;
+use dioxus::logger::tracing;
+use vda5050_store::LiveFeed;
+use vda5050_store::live::{LiveBatch, LiveSubscription};
+
+/// Sends batches of the latest states and visualizations until the client goes away. The client
+/// subscribes first and may subscribe again at any time to change the rate or the AGVs.
+pub async fn serve(mut socket: TypedWebsocket<LiveSubscription, LiveBatch>) {
+    let Ok(subscription) = socket.recv().await else {
+        return;
+    };
+    let mut feed = LiveFeed::new(subscription);
+    let mut last_sequence = crate::store::last_sequence();
+    loop {
+        // Waits for new messages, and keeps listening to the client meanwhile.
+        if !feed.is_behind(*last_sequence.borrow_and_update()) {
+            tokio::select! {
+                changed = last_sequence.changed() => if changed.is_err() {
+                    return;
+                },
+                request = socket.recv() => match request {
+                    Ok(subscription) => feed.resubscribe(subscription),
+                    Err(_) => return,
+                },
+            }
+            continue;
+        }
+        // Reads one page at a time, so that the store is free for others in between.
+        let batch = loop {
+            let paged = crate::store::with_store_async(move |store| {
+                let batch = feed.next_batch(store)?;
+                Ok((feed, batch))
+            })
+            .await;
+            match paged {
+                Ok((paged, Some(batch))) => {
+                    feed = paged;
+                    break batch;
+                }
+                Ok((paged, None)) => feed = paged,
+                Err(err) => {
+                    tracing::error!("live updates: {err}");
+                    return;
+                }
+            }
+        };
+        // Sending waits for a slow client, the next batch then skips to the latest updates.
+        if socket.send(batch).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(feed.period()).await;
+    }
+}
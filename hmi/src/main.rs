@@ -2,12 +2,23 @@
 // need dioxus
 use dioxus::prelude::*;
 
-use views::{Blog, Home, Navbar};
+use views::{Analysis, Blog, Broker, Home, Live, Messages, Navbar};
 
 /// Define a components module that contains all shared components for our app.
 mod components;
 /// Define a views module that contains the UI for all Layouts and Routes for our app.
 mod views;
+/// The typed server functions on the message store, shared by the pages of all platforms.
+mod api;
+/// The message store of the server, filled by the embedded broker and queried by the pages.
+#[cfg(feature = "server")]
+mod store;
+/// The live updates of the AGVs, served from the message store.
+#[cfg(feature = "server")]
+mod live;
+/// The recordings directory of the server, which the pages analyze and import from.
+#[cfg(feature = "server")]
+mod recordings;
 
 /// The Route enum is used to define the structure of internal routes in our app. All route enums need to derive
 /// the [`Routable`] trait, which provides the necessary methods for the router to work.
@@ -30,6 +41,18 @@ enum Route {
         // Fields of the route variant will be passed to the component as props. In this case, the blog component must accept
         // an `id` prop of type `i32`.
         Blog { id: i32 },
+        // The analysis page runs the checks on a recording and exports incident reports.
+        #[route("/analysis")]
+        Analysis {},
+        // The broker page starts the embedded MQTT broker and shows its retained messages.
+        #[route("/broker")]
+        Broker {},
+        // The messages page queries the messages persisted in the store of the server.
+        #[route("/messages")]
+        Messages {},
+        // The live page follows the state and position of every AGV as the messages are stored.
+        #[route("/live")]
+        Live {},
 }
 
 // We can import assets in dioxus with the `asset!` macro. This macro takes a path to an asset relative to the crate root.
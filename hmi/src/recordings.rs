@@ -0,0 +1,70 @@
+//! The recordings the pages may analyze and import. Clients name a recording by its path relative
+//! to the recordings directory of the server, never by a path of their own choosing.
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use vda5050_analysis::capture;
+
+/// The directory of the recordings, from the `VDA5050_RECORDINGS` environment variable.
+pub fn dir() -> PathBuf {
+    std::env::var_os("VDA5050_RECORDINGS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("recordings"))
+}
+
+/// The file of the recording with the given ID. The ID has to be a relative path without `..`,
+/// and the file has to stay inside the recordings directory once links are followed.
+pub fn resolve(id: &str) -> io::Result<PathBuf> {
+    let relative = Path::new(id);
+    let plain = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if id.is_empty() || !plain {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{id}' is not the ID of a recording"),
+        ));
+    }
+    let dir = dir().canonicalize()?;
+    let path = dir.join(relative).canonicalize()?;
+    if !path.starts_with(&dir) || !path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("there is no recording '{id}'"),
+        ));
+    }
+    Ok(path)
+}
+
+/// The IDs of all recordings and captures in the recordings directory and below, sorted. Links
+/// are not followed, so that a link to a parent directory cannot make the walk endless.
+pub fn list() -> io::Result<Vec<String>> {
+    let dir = dir();
+    let mut ids = Vec::new();
+    let mut pending = vec![dir.clone()];
+    while let Some(next) = pending.pop() {
+        for entry in std::fs::read_dir(next)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file()
+                && is_recording(&path)
+                && let Ok(relative) = path.strip_prefix(&dir)
+                && let Some(id) = relative.to_str()
+            {
+                ids.push(id.to_string());
+            }
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+fn is_recording(path: &Path) -> bool {
+    capture::is_capture(path)
+        || path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("jsonl"))
+}
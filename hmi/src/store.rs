@@ -0,0 +1,103 @@
+//! The message store of the server, shared by the pages that show stored messages and the
+//! embedded broker that fills it.
+use dioxus::logger::tracing;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tokio::sync::watch;
+use vda5050_broker::ObservedMessage;
+use vda5050_store::config::StoreConfig;
+use vda5050_store::{Store, StoreError};
+
+/// The store, opened on first use.
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+
+/// The sequence number of the latest message stored from the broker, to wake up the live updates.
+static LAST_SEQUENCE: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::Sender::new(0));
+
+/// How often the retention of the store is applied while messages are stored.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The database file of the store, from the `VDA5050_STORE` environment variable.
+pub fn path() -> String {
+    std::env::var("VDA5050_STORE").unwrap_or_else(|_| "vda5050-messages.db".to_string())
+}
+
+/// Follows the sequence number of the latest message stored from the broker.
+pub fn last_sequence() -> watch::Receiver<u64> {
+    LAST_SEQUENCE.subscribe()
+}
+
+/// Runs `f` on the store, opening it first if needed.
+pub fn with_store<T>(f: impl FnOnce(&mut Store) -> Result<T, StoreError>) -> Result<T, StoreError> {
+    let mut store = lock();
+    if store.is_none() {
+        *store = Some(Store::open(path(), StoreConfig::default())?);
+    }
+    f(store.as_mut().expect("the store was opened above"))
+}
+
+/// Runs `f` on the store on a blocking thread, so that SQLite never stalls the async runtime.
+pub async fn with_store_async<T, F>(f: F) -> Result<T, StoreError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Store) -> Result<T, StoreError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || with_store(f))
+        .await
+        .expect("the store task panicked")
+}
+
+fn lock() -> MutexGuard<'static, Option<Store>> {
+    STORE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Stores every message the broker routes, on a thread of its own, until the broker is dropped.
+pub fn persist(mut messages: broadcast::Receiver<ObservedMessage>) {
+    std::thread::spawn(move || {
+        let mut retained_at = Instant::now();
+        while let Some(first) = next(&mut messages) {
+            // Stores what arrived in the meantime in the same transaction.
+            let mut batch = vec![first];
+            loop {
+                match messages.try_recv() {
+                    Ok(message) => batch.push(message),
+                    Err(TryRecvError::Lagged(missed)) => {
+                        tracing::warn!("message store: missed {missed} messages of the broker");
+                    }
+                    Err(_) => break,
+                }
+            }
+            let entries: Vec<_> = batch.iter().filter_map(ObservedMessage::to_entry).collect();
+            let stored = with_store(|store| {
+                store.insert_all(&entries)?;
+                if let Some(last_sequence) = store.last_sequence()? {
+                    LAST_SEQUENCE.send_replace(last_sequence);
+                }
+                if retained_at.elapsed() >= RETENTION_INTERVAL {
+                    retained_at = Instant::now();
+                    store.apply_retention()?;
+                }
+                Ok(())
+            });
+            if let Err(err) = stored {
+                tracing::error!("message store: {err}");
+            }
+        }
+    });
+}
+
+/// Waits for the next message, skipping over missed ones, `None` once the broker is gone.
+fn next(messages: &mut broadcast::Receiver<ObservedMessage>) -> Option<ObservedMessage> {
+    loop {
+        match messages.blocking_recv() {
+            Ok(message) => return Some(message),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!("message store: missed {missed} messages of the broker");
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
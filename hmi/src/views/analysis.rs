@@ -0,0 +1,308 @@
+use crate::components::{
+    BatteryChart, ClockTable, ErrorTable, KpiChart, LatencyTable, MapView, OrderResponseTable,
+    ReportSummary, SafetyTable, TrafficTable,
+};
+use chrono::{DateTime, Utc};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use vda5050_analysis::analytics::battery::BatteryAnalysis;
+use vda5050_analysis::analytics::clock::ClockModel;
+use vda5050_analysis::analytics::errors::ErrorTimeline;
+use vda5050_analysis::analytics::kpis::FleetKpis;
+use vda5050_analysis::analytics::latency::LatencyAnalysis;
+use vda5050_analysis::analytics::order_responses::OrderResponseAnalysis;
+use vda5050_analysis::analytics::safety::SafetyAnalysis;
+use vda5050_analysis::analytics::spatial::SpatialAnalysis;
+use vda5050_analysis::analytics::traffic::TrafficAnalysis;
+use vda5050_analysis::report::Report;
+
+const ANALYSIS_CSS: Asset = asset!("/assets/styling/analysis.css");
+
+/// The Analysis page component that will be rendered when the current route is `[Route::Analysis]`
+///
+/// It analyzes a recording of the recordings directory on the server and shows the findings. The same results can be
+/// exported as a self-contained HTML incident report.
+#[component]
+pub fn Analysis() -> Element {
+    let mut recording = use_signal(String::new);
+    let mut recordings = use_signal(Vec::<String>::new);
+    let mut exported = use_signal(|| None::<String>);
+    let mut analysis = use_signal(|| None::<RecordingAnalysis>);
+    let mut kpis = use_signal(|| None::<FleetKpis>);
+    let mut realign = use_signal(|| false);
+    let mut from = use_signal(String::new);
+    let mut to = use_signal(String::new);
+    let mut status = use_signal(String::new);
+
+    use_future(move || async move {
+        match list_recordings().await {
+            Ok(listed) => recordings.set(listed),
+            Err(err) => status.set(format!("Listing the recordings failed: {err}")),
+        }
+    });
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: ANALYSIS_CSS }
+
+        div {
+            id: "analysis",
+            h2 { "Recording analysis" }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "Recording (.jsonl) or capture (.pcap, .pcapng) in the recordings directory",
+                    list: "recordings",
+                    value: "{recording}",
+                    oninput: move |event| recording.set(event.value()),
+                }
+                datalist {
+                    id: "recordings",
+                    for id in recordings().iter() {
+                        option { value: "{id}" }
+                    }
+                }
+                button {
+                    disabled: recording().is_empty(),
+                    onclick: move |_| async move {
+                        status.set("Analyzing...".to_string());
+                        exported.set(None);
+                        analysis.set(None);
+                        kpis.set(None);
+                        match analyze_recording(recording(), realign(), parse_time(&from()), parse_time(&to())).await {
+                            Ok(analyzed) => {
+                                kpis.set(Some(analyzed.kpis.clone()));
+                                analysis.set(Some(analyzed));
+                                status.set(String::new());
+                            }
+                            Err(err) => status.set(format!("Analysis failed: {err}")),
+                        }
+                    },
+                    "Analyze"
+                }
+                button {
+                    disabled: analysis().is_none(),
+                    onclick: move |_| async move {
+                        match export_incident_report(recording(), realign()).await {
+                            Ok(html) => exported.set(Some(html)),
+                            Err(err) => status.set(format!("Export failed: {err}")),
+                        }
+                    },
+                    "Export report"
+                }
+                label {
+                    input {
+                        r#type: "checkbox",
+                        checked: realign(),
+                        onchange: move |event| realign.set(event.checked()),
+                    }
+                    "Re-align clocks"
+                }
+            }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "KPI window start (RFC 3339, optional)",
+                    value: "{from}",
+                    oninput: move |event| from.set(event.value()),
+                }
+                input {
+                    placeholder: "KPI window end (RFC 3339, optional)",
+                    value: "{to}",
+                    oninput: move |event| to.set(event.value()),
+                }
+                button {
+                    disabled: analysis().is_none(),
+                    onclick: move |_| async move {
+                        match fleet_kpis(recording(), realign(), parse_time(&from()), parse_time(&to())).await {
+                            Ok(computed) => kpis.set(Some(computed)),
+                            Err(err) => status.set(format!("KPI computation failed: {err}")),
+                        }
+                    },
+                    "Compute KPIs"
+                }
+            }
+            if !status().is_empty() {
+                p { class: "status", "{status}" }
+            }
+            if let Some(html) = exported() {
+                a {
+                    href: data_url(&html),
+                    download: "{report_name(&recording())}",
+                    "Download the incident report"
+                }
+            }
+            if let Some(analysis) = analysis() {
+                ReportSummary { report: analysis.report }
+                if let Some(kpis) = kpis() {
+                    KpiChart { kpis }
+                }
+                ErrorTable { timeline: analysis.timeline }
+                BatteryChart { battery: analysis.battery }
+                LatencyTable { latency: analysis.latency }
+                OrderResponseTable { responses: analysis.order_responses }
+                SafetyTable { safety: analysis.safety }
+                MapView { spatial: analysis.spatial }
+                TrafficTable { traffic: analysis.traffic }
+                ClockTable { clocks: analysis.clocks }
+            }
+        }
+    }
+}
+
+/// The IDs of the recordings and captures in the recordings directory of the server.
+#[post("/api/recordings")]
+pub(super) async fn list_recordings() -> Result<Vec<String>> {
+    Ok(crate::recordings::list()?)
+}
+
+/// Everything the page shows about a recording, computed on the server from a single load.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RecordingAnalysis {
+    report: Report,
+    timeline: ErrorTimeline,
+    battery: BatteryAnalysis,
+    kpis: FleetKpis,
+    latency: LatencyAnalysis,
+    order_responses: OrderResponseAnalysis,
+    clocks: ClockModel,
+    safety: SafetyAnalysis,
+    spatial: SpatialAnalysis,
+    traffic: TrafficAnalysis,
+}
+
+/// Loads a recording of the recordings directory once, runs all checks on it and derives every
+/// analysis, over the timelines corrected by the estimated clocks if asked to. The checks and the
+/// clock estimate always see the timelines as recorded.
+#[post("/api/analysis/analyze")]
+async fn analyze_recording(
+    recording: String,
+    realign: bool,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<RecordingAnalysis> {
+    use vda5050_analysis::analytics::battery::BatteryConfig;
+    use vda5050_analysis::analytics::kpis::KpiConfig;
+    use vda5050_analysis::analytics::latency::LatencyConfig;
+    use vda5050_analysis::analytics::order_responses::OrderResponseConfig;
+    use vda5050_analysis::analytics::safety::SafetyConfig;
+    use vda5050_analysis::analytics::spatial::SpatialConfig;
+    use vda5050_analysis::analytics::traffic::TrafficConfig;
+
+    // Loading and analyzing a recording takes long, it must not stall the async runtime.
+    let analysis = tokio::task::spawn_blocking(move || -> std::io::Result<RecordingAnalysis> {
+        let (mut recording, report) = analyze(&recording)?;
+        let clocks = ClockModel::estimate(&recording);
+        if realign {
+            clocks.realign(&mut recording);
+        }
+        let kpi_config = KpiConfig {
+            from,
+            to,
+            ..KpiConfig::default()
+        };
+        Ok(RecordingAnalysis {
+            report,
+            timeline: ErrorTimeline::analyze(&recording),
+            battery: BatteryAnalysis::analyze(&recording, &BatteryConfig::default()),
+            kpis: FleetKpis::compute(&recording, &kpi_config),
+            latency: LatencyAnalysis::analyze(&recording, &LatencyConfig::default()),
+            order_responses: OrderResponseAnalysis::analyze(
+                &recording,
+                &OrderResponseConfig::default(),
+            ),
+            safety: SafetyAnalysis::analyze(&recording, &SafetyConfig::default()),
+            spatial: SpatialAnalysis::analyze(&recording, &SpatialConfig::default()),
+            traffic: TrafficAnalysis::analyze(&recording, &TrafficConfig::default()),
+            clocks,
+        })
+    });
+    Ok(analysis.await??)
+}
+
+/// Computes the fleet KPIs of a recording on the server, over the whole recording unless a
+/// window is given.
+#[post("/api/analysis/kpis")]
+async fn fleet_kpis(
+    recording: String,
+    realign: bool,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<FleetKpis> {
+    use vda5050_analysis::analytics::kpis::KpiConfig;
+    let config = KpiConfig {
+        from,
+        to,
+        ..KpiConfig::default()
+    };
+    let kpis = tokio::task::spawn_blocking(move || {
+        load(&recording, realign).map(|recording| FleetKpis::compute(&recording, &config))
+    });
+    Ok(kpis.await??)
+}
+
+/// Renders the incident report of a recording on the server as a self-contained HTML page.
+#[post("/api/analysis/export")]
+async fn export_incident_report(recording: String, realign: bool) -> Result<String> {
+    use vda5050_analysis::incident_report::{self, IncidentReportOptions};
+
+    let html = tokio::task::spawn_blocking(move || -> std::io::Result<String> {
+        let (mut recording, report) = analyze(&recording)?;
+        if realign {
+            ClockModel::estimate(&recording).realign(&mut recording);
+        }
+        Ok(incident_report::render(
+            &recording,
+            &report,
+            &IncidentReportOptions::default(),
+        ))
+    });
+    Ok(html.await??)
+}
+
+/// Loads a recording of the recordings directory, with the timelines corrected by the estimated
+/// clocks if asked to.
+#[cfg(feature = "server")]
+fn load(id: &str, realign: bool) -> std::io::Result<vda5050_analysis::recording::Recording> {
+    let path = crate::recordings::resolve(id)?;
+    let mut recording = vda5050_analysis::recording::Recording::load(&[path])?;
+    if realign {
+        ClockModel::estimate(&recording).realign(&mut recording);
+    }
+    Ok(recording)
+}
+
+/// Loads a recording of the recordings directory and runs all checks on it. The report names
+/// the recording by its ID rather than by its path on the server.
+#[cfg(feature = "server")]
+fn analyze(id: &str) -> std::io::Result<(vda5050_analysis::recording::Recording, Report)> {
+    use vda5050_analysis::checks::CheckConfig;
+
+    let path = crate::recordings::resolve(id)?;
+    let (recording, mut report) = Report::analyze(&[path], &CheckConfig::default())?;
+    report.files = vec![id.to_string()];
+    Ok((recording, report))
+}
+
+/// A `data:` URL of an HTML page, so that the page can be downloaded without a round trip.
+fn data_url(html: &str) -> String {
+    let mut url = String::from("data:text/html;charset=utf-8,");
+    for byte in html.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    url
+}
+
+/// The file name of the incident report of a recording.
+fn report_name(recording: &str) -> String {
+    let name = recording.rsplit(['/', '\\']).next().unwrap_or(recording);
+    format!("{name}.report.html")
+}
+
+/// An optional time entered by the user; anything that is not RFC 3339 counts as not given.
+pub(super) fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    text.trim().parse().ok()
+}
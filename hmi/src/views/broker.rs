@@ -0,0 +1,148 @@
+use crate::components::RetainedTable;
+use dioxus::prelude::*;
+use std::net::SocketAddr;
+use vda5050_broker::retained::RetainedView;
+
+const BROKER_CSS: Asset = asset!("/assets/styling/broker.css");
+
+/// The Broker page component that will be rendered when the current route is `[Route::Broker]`
+///
+/// It starts the MQTT broker embedded in the server, so that simulators and master control can run on one machine
+/// without an external broker, and shows the retained connection and factsheet messages of the AGVs. The broker
+/// listens on the loopback interface unless another address is entered, e.g. `0.0.0.0:1883` for AGVs on the network.
+#[component]
+pub fn Broker() -> Element {
+    let mut bind = use_signal(|| "127.0.0.1:1883".to_string());
+    let mut address = use_signal(|| None::<String>);
+    let mut clients = use_signal(Vec::<String>::new);
+    let mut retained = use_signal(|| None::<RetainedView>);
+    let mut status = use_signal(String::new);
+
+    let refresh = move || async move {
+        match broker_address().await {
+            Ok(running) => address.set(running),
+            Err(err) => status.set(format!("Broker status failed: {err}")),
+        }
+        clients.set(broker_clients().await.unwrap_or_default());
+        retained.set(retained_view().await.ok());
+    };
+    use_future(move || async move { refresh().await });
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: BROKER_CSS }
+
+        div {
+            id: "broker",
+            h2 { "Embedded MQTT broker" }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "Address to listen on",
+                    value: "{bind}",
+                    disabled: address().is_some(),
+                    oninput: move |event| bind.set(event.value()),
+                }
+                button {
+                    disabled: address().is_some() || bind().parse::<SocketAddr>().is_err(),
+                    onclick: move |_| async move {
+                        let Ok(bind) = bind().parse::<SocketAddr>() else {
+                            return;
+                        };
+                        match start_broker(bind).await {
+                            Ok(started) => {
+                                address.set(Some(started));
+                                status.set(String::new());
+                            }
+                            Err(err) => status.set(format!("Starting the broker failed: {err}")),
+                        }
+                    },
+                    "Start"
+                }
+                button {
+                    onclick: move |_| async move { refresh().await },
+                    "Refresh"
+                }
+            }
+            match address() {
+                Some(address) => rsx! {
+                    p { "Listening on {address} with {clients().len()} client(s): {clients().join(\", \")}" }
+                },
+                None => rsx! {
+                    p { "The broker is not running." }
+                },
+            }
+            if !status().is_empty() {
+                p { class: "status", "{status}" }
+            }
+            if let Some(retained) = retained() {
+                RetainedTable { retained }
+            }
+        }
+    }
+}
+
+/// The broker embedded in the server, once started.
+#[cfg(feature = "server")]
+static EMBEDDED_BROKER: std::sync::Mutex<Option<vda5050_broker::Broker>> =
+    std::sync::Mutex::new(None);
+
+/// Starts the embedded broker on the given address, unless it runs already, and returns the
+/// address it listens on.
+#[post("/api/broker/start")]
+async fn start_broker(bind: SocketAddr) -> Result<String> {
+    use vda5050_broker::BrokerConfig;
+
+    if let Some(address) = broker_address().await? {
+        return Ok(address);
+    }
+    let broker = vda5050_broker::Broker::start(BrokerConfig {
+        bind,
+        ..BrokerConfig::default()
+    })
+    .await?;
+    let mut embedded = EMBEDDED_BROKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    // Of two concurrent starts on different addresses, the first one to finish wins.
+    let broker = embedded.get_or_insert_with(|| {
+        // Everything routed by the broker ends up in the message store.
+        crate::store::persist(broker.observe());
+        broker
+    });
+    Ok(broker.local_addr().to_string())
+}
+
+/// The address of the embedded broker, `None` if it was not started.
+#[post("/api/broker/address")]
+async fn broker_address() -> Result<Option<String>> {
+    let embedded = EMBEDDED_BROKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    Ok(embedded
+        .as_ref()
+        .map(|broker| broker.local_addr().to_string()))
+}
+
+/// The client IDs of the clients connected to the embedded broker.
+#[post("/api/broker/clients")]
+async fn broker_clients() -> Result<Vec<String>> {
+    let embedded = EMBEDDED_BROKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    Ok(embedded
+        .as_ref()
+        .map(|broker| broker.clients())
+        .unwrap_or_default())
+}
+
+/// The retained connection and factsheet messages of the embedded broker.
+#[post("/api/broker/retained")]
+async fn retained_view() -> Result<RetainedView> {
+    let embedded = EMBEDDED_BROKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    Ok(embedded
+        .as_ref()
+        .map(|broker| broker.retained_view())
+        .unwrap_or_default())
+}
@@ -0,0 +1,266 @@
+use crate::api::live_updates;
+use chrono::{DateTime, Utc};
+use dioxus::fullstack::{WebSocketOptions, use_websocket};
+use dioxus::prelude::*;
+use std::collections::BTreeMap;
+use vda5050_analysis::recording::AgvId;
+use vda5050_data_types::common::AgvPosition;
+use vda5050_data_types::state::{OperatingMode, State};
+use vda5050_store::live::{LiveBatch, LiveMessage, LiveSubscription};
+
+const LIVE_CSS: Asset = asset!("/assets/styling/live.css");
+
+/// How often in a row reconnecting may fail before the page waits for the user.
+const MAX_RECONNECTS: u32 = 3;
+
+/// The size of the fleet map in SVG units, the positions are scaled to fit.
+const MAP_WIDTH: f64 = 600.0;
+const MAP_HEIGHT: f64 = 400.0;
+
+/// What the page knows about an AGV from its live updates.
+#[derive(Clone)]
+struct LiveAgv {
+    /// The latest state.
+    state: Option<State>,
+    /// The latest position, from a state or a visualization message.
+    position: Option<AgvPosition>,
+    /// When the latest position was received.
+    position_at: Option<DateTime<Utc>>,
+    /// When the latest update was received.
+    updated_at: DateTime<Utc>,
+}
+
+impl LiveAgv {
+    fn update_position(&mut self, position: Option<&AgvPosition>, received_at: DateTime<Utc>) {
+        if let Some(position) = position
+            && self.position_at.is_none_or(|at| at <= received_at)
+        {
+            self.position = Some(position.clone());
+            self.position_at = Some(received_at);
+        }
+    }
+}
+
+/// The Live page component that will be rendered when the current route is `[Route::Live]`
+///
+/// It follows the fleet as the server stores the messages of the embedded broker: the server pushes the latest state
+/// and visualization of every AGV over a websocket, throttled to the chosen rate, and the page resumes where it left off
+/// after the connection was lost.
+#[component]
+pub fn Live() -> Element {
+    let mut socket = use_websocket(|| live_updates(WebSocketOptions::new()));
+    let mut agvs = use_signal(BTreeMap::<AgvId, LiveAgv>::new);
+    let mut last_sequence = use_signal(|| None::<u64>);
+    let mut skipped = use_signal(|| 0usize);
+    let mut rate = use_signal(|| "5".to_string());
+    let mut status = use_signal(String::new);
+
+    let subscription = move || LiveSubscription {
+        after_sequence: last_sequence(),
+        max_rate_hz: rate()
+            .trim()
+            .parse()
+            .unwrap_or(LiveSubscription::default().max_rate_hz),
+        agvs: Vec::new(),
+    };
+    let mut apply = move |batch: LiveBatch| {
+        let mut agvs = agvs.write();
+        for update in batch.updates {
+            let agv = agvs.entry(update.agv).or_insert_with(|| LiveAgv {
+                state: None,
+                position: None,
+                position_at: None,
+                updated_at: update.received_at,
+            });
+            agv.updated_at = agv.updated_at.max(update.received_at);
+            match update.message {
+                LiveMessage::State(state) => {
+                    agv.update_position(state.agv_position.as_ref(), update.received_at);
+                    agv.state = Some(state);
+                }
+                LiveMessage::Visualization(visualization) => {
+                    agv.update_position(visualization.agv_position.as_ref(), update.received_at);
+                }
+            }
+        }
+        last_sequence.set(Some(batch.last_sequence));
+        skipped += batch.skipped;
+    };
+    let mut follow = use_future(move || async move {
+        let mut failures = 0;
+        loop {
+            if socket.send(subscription()).await.is_ok() {
+                status.set(String::new());
+                while let Ok(batch) = socket.recv().await {
+                    failures = 0;
+                    apply(batch);
+                }
+            }
+            failures += 1;
+            if failures > MAX_RECONNECTS {
+                status.set("The connection to the server is lost.".to_string());
+                return;
+            }
+            status.set("Reconnecting...".to_string());
+            socket.set(live_updates(WebSocketOptions::new()).await);
+        }
+    });
+
+    let points: Vec<MapPoint> = agvs()
+        .iter()
+        .filter_map(|(agv, live)| {
+            let position = live.position.as_ref()?;
+            Some(MapPoint {
+                label: agv.serial_number.clone(),
+                x: position.x,
+                y: position.y,
+                theta: position.theta,
+            })
+        })
+        .collect();
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: LIVE_CSS }
+
+        div {
+            id: "live",
+            h2 { "Live fleet" }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "Updates per AGV and second",
+                    value: "{rate}",
+                    oninput: move |event| rate.set(event.value()),
+                }
+                button {
+                    onclick: move |_| async move {
+                        if socket.send(subscription()).await.is_err() {
+                            status.set("Changing the rate failed.".to_string());
+                        }
+                    },
+                    "Apply rate"
+                }
+                button {
+                    onclick: move |_| async move {
+                        socket.set(live_updates(WebSocketOptions::new()).await);
+                        follow.restart();
+                    },
+                    "Reconnect"
+                }
+            }
+            p {
+                {last_sequence().map(|sequence| format!("Up to message {sequence}, ")).unwrap_or_default()}
+                "{skipped} updates skipped to keep to the rate"
+            }
+            if !status().is_empty() {
+                p { class: "status", "{status}" }
+            }
+            if !points.is_empty() {
+                FleetMap { points }
+            }
+            if agvs().is_empty() {
+                p { "No AGV has sent a state or visualization since the page was opened." }
+            } else {
+                table {
+                    tr {
+                        th { "AGV" }
+                        th { "Updated" }
+                        th { "Map" }
+                        th { "Position" }
+                        th { "Mode" }
+                        th { "Driving" }
+                        th { "Battery" }
+                        th { "Errors" }
+                    }
+                    for (agv, live) in agvs().iter() {
+                        tr {
+                            class: if live.state.as_ref().is_some_and(|state| !state.errors.is_empty()) { "error" } else { "" },
+                            td { "{agv}" }
+                            td { "{live.updated_at.to_rfc3339()}" }
+                            td { {live.position.as_ref().map(|position| position.map_id.clone()).unwrap_or_default()} }
+                            td {
+                                {live.position.as_ref().map(|position| match position.theta {
+                                    Some(theta) => format!("{:.2}, {:.2}, {:.2} rad", position.x, position.y, theta),
+                                    None => format!("{:.2}, {:.2}", position.x, position.y),
+                                }).unwrap_or_default()}
+                            }
+                            td { {live.state.as_ref().map(|state| mode_name(&state.operating_mode)).unwrap_or_default()} }
+                            td { {live.state.as_ref().map(|state| if state.driving { "yes" } else { "no" }).unwrap_or_default()} }
+                            td { {live.state.as_ref().and_then(|state| state.battery_state.as_ref()).map(|battery| format!("{:.0} %", battery.battery_charge)).unwrap_or_default()} }
+                            td { {live.state.as_ref().map(|state| state.errors.iter().map(|error| error.error_type.as_str()).collect::<Vec<_>>().join(", ")).unwrap_or_default()} }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The position of an AGV on the fleet map.
+#[derive(Clone, PartialEq)]
+struct MapPoint {
+    label: String,
+    x: f64,
+    y: f64,
+    theta: Option<f64>,
+}
+
+/// Draws the latest position of every AGV, scaled to fit all of them.
+#[component]
+fn FleetMap(points: Vec<MapPoint>) -> Element {
+    let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) - 1.0;
+    let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) + 1.0;
+    let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) - 1.0;
+    let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) + 1.0;
+    let scale = (MAP_WIDTH / (max_x - min_x)).min(MAP_HEIGHT / (max_y - min_y));
+    // The y axis of the map points up, the one of SVG down.
+    let to_svg = move |point: &MapPoint| {
+        (
+            (point.x - min_x) * scale,
+            MAP_HEIGHT - (point.y - min_y) * scale,
+        )
+    };
+
+    rsx! {
+        svg {
+            class: "fleet-map",
+            width: "{MAP_WIDTH}",
+            height: "{MAP_HEIGHT}",
+            view_box: "0 0 {MAP_WIDTH} {MAP_HEIGHT}",
+            for point in points.iter() {
+                g {
+                    circle {
+                        cx: "{to_svg(point).0}",
+                        cy: "{to_svg(point).1}",
+                        r: "6",
+                    }
+                    if let Some(theta) = point.theta {
+                        line {
+                            x1: "{to_svg(point).0}",
+                            y1: "{to_svg(point).1}",
+                            x2: "{to_svg(point).0 + 14.0 * theta.cos()}",
+                            y2: "{to_svg(point).1 - 14.0 * theta.sin()}",
+                        }
+                    }
+                    text {
+                        x: "{to_svg(point).0 + 9.0}",
+                        y: "{to_svg(point).1 - 9.0}",
+                        "{point.label}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The operating mode as it appears in the messages, e.g. `AUTOMATIC`.
+fn mode_name(mode: &OperatingMode) -> String {
+    match mode {
+        OperatingMode::Automatic => "AUTOMATIC".to_string(),
+        OperatingMode::Semiautomatic => "SEMIAUTOMATIC".to_string(),
+        OperatingMode::Manual => "MANUAL".to_string(),
+        OperatingMode::Service => "SERVICE".to_string(),
+        OperatingMode::Teachin => "TEACHIN".to_string(),
+        OperatingMode::Unknown(name) => name.clone(),
+    }
+}
@@ -0,0 +1,343 @@
+use super::analysis::list_recordings;
+use crate::api::{get_kpis, get_messages, get_order, list_agvs, query_messages};
+use crate::components::{KpiChart, MessageTable};
+use dioxus::prelude::*;
+use vda5050_analysis::analytics::kpis::FleetKpis;
+use vda5050_data_types::topic::Topic;
+use vda5050_store::config::StoreConfig;
+use vda5050_store::query::{
+    ImportSummary, MessageQuery, Stored, StoredAgv, StoredOrder, TaggedMessage, TimeWindow,
+};
+
+const MESSAGES_CSS: Asset = asset!("/assets/styling/messages.css");
+
+/// The Messages page component that will be rendered when the current route is `[Route::Messages]`
+///
+/// It queries the messages persisted in the store of the server, which holds imported recordings and everything
+/// routed by the embedded broker, shows single orders and the KPIs of a time window, and sets how long messages are
+/// kept.
+#[component]
+pub fn Messages() -> Element {
+    let mut agvs = use_signal(Vec::<StoredAgv>::new);
+    let mut messages = use_signal(|| None::<Vec<Stored<TaggedMessage>>>);
+    let mut order = use_signal(|| None::<StoredOrder>);
+    let mut kpis = use_signal(|| None::<FleetKpis>);
+    let mut import_id = use_signal(String::new);
+    let mut recordings = use_signal(Vec::<String>::new);
+    let mut agv = use_signal(String::new);
+    let mut topic = use_signal(String::new);
+    let mut from = use_signal(String::new);
+    let mut to = use_signal(String::new);
+    let mut order_id = use_signal(String::new);
+    let mut error_type = use_signal(String::new);
+    let mut limit = use_signal(|| "200".to_string());
+    let mut latest = use_signal(|| true);
+    let mut max_age_hours = use_signal(String::new);
+    let mut max_messages = use_signal(String::new);
+    let mut status = use_signal(String::new);
+
+    let refresh = move || async move {
+        match list_agvs().await {
+            Ok(stored) => agvs.set(stored),
+            Err(err) => status.set(format!("Opening the store failed: {err}")),
+        }
+    };
+    use_future(move || async move {
+        refresh().await;
+        if let Ok(listed) = list_recordings().await {
+            recordings.set(listed);
+        }
+        if let Ok(config) = store_config().await {
+            let hours = config.max_age_secs.map(|secs| (secs / 3600).to_string());
+            max_age_hours.set(hours.unwrap_or_default());
+            max_messages.set(
+                config
+                    .max_messages
+                    .map(|max| max.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+    });
+
+    let query = move || MessageQuery {
+        agv: agv()
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| agvs().get(index).map(|stored| stored.agv.clone())),
+        topics: topic().parse::<Topic>().into_iter().collect(),
+        from: super::analysis::parse_time(&from()),
+        to: super::analysis::parse_time(&to()),
+        order_id: Some(order_id().trim().to_string()).filter(|id| !id.is_empty()),
+        error_type: Some(error_type().trim().to_string()).filter(|error| !error.is_empty()),
+        after_sequence: None,
+        by_sequence: false,
+        latest: latest(),
+        limit: limit().trim().parse().ok(),
+    };
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: MESSAGES_CSS }
+
+        div {
+            id: "messages",
+            h2 { "Stored messages" }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "Recording (.jsonl) or capture (.pcap, .pcapng) in the recordings directory to import",
+                    list: "import-recordings",
+                    value: "{import_id}",
+                    oninput: move |event| import_id.set(event.value()),
+                }
+                datalist {
+                    id: "import-recordings",
+                    for id in recordings().iter() {
+                        option { value: "{id}" }
+                    }
+                }
+                button {
+                    disabled: import_id().is_empty(),
+                    onclick: move |_| async move {
+                        match import_recording(import_id()).await {
+                            Ok(summary) => status.set(format!(
+                                "Stored {} messages, skipped {} undecodable entries",
+                                summary.stored, summary.skipped
+                            )),
+                            Err(err) => status.set(format!("Import failed: {err}")),
+                        }
+                        refresh().await;
+                    },
+                    "Import"
+                }
+            }
+            if !agvs().is_empty() {
+                table {
+                    tr {
+                        th { "AGV" }
+                        th { "Messages" }
+                        th { "First received" }
+                        th { "Last received" }
+                        th {}
+                    }
+                    for stored in agvs().iter() {
+                        tr {
+                            td { "{stored.agv}" }
+                            td { "{stored.messages}" }
+                            td { "{stored.first_received_at.to_rfc3339()}" }
+                            td { "{stored.last_received_at.to_rfc3339()}" }
+                            td {
+                                button {
+                                    onclick: {
+                                        let agv = stored.agv.clone();
+                                        move |_| {
+                                            let agv = agv.clone();
+                                            async move {
+                                                match get_messages(Some(agv), None, None, None, limit().trim().parse().ok()).await {
+                                                    Ok(found) => messages.set(Some(found)),
+                                                    Err(err) => status.set(format!("Query failed: {err}")),
+                                                }
+                                            }
+                                        }
+                                    },
+                                    "Latest"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            div {
+                class: "controls",
+                select {
+                    onchange: move |event| agv.set(event.value()),
+                    option { value: "", "All AGVs" }
+                    for (index, stored) in agvs().iter().enumerate() {
+                        option { value: "{index}", "{stored.agv}" }
+                    }
+                }
+                select {
+                    onchange: move |event| topic.set(event.value()),
+                    option { value: "", "All topics" }
+                    for choice in Topic::ALL {
+                        option { value: "{choice}", "{choice}" }
+                    }
+                }
+                input {
+                    placeholder: "From (RFC 3339, optional)",
+                    value: "{from}",
+                    oninput: move |event| from.set(event.value()),
+                }
+                input {
+                    placeholder: "To (RFC 3339, optional)",
+                    value: "{to}",
+                    oninput: move |event| to.set(event.value()),
+                }
+                button {
+                    onclick: move |_| async move {
+                        let window = TimeWindow {
+                            from: super::analysis::parse_time(&from()),
+                            to: super::analysis::parse_time(&to()),
+                        };
+                        match get_kpis(window).await {
+                            Ok(computed) => kpis.set(Some(computed)),
+                            Err(err) => status.set(format!("KPI computation failed: {err}")),
+                        }
+                    },
+                    "Compute KPIs"
+                }
+            }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "Order ID",
+                    value: "{order_id}",
+                    oninput: move |event| order_id.set(event.value()),
+                }
+                button {
+                    disabled: order_id().trim().is_empty(),
+                    onclick: move |_| async move {
+                        match get_order(order_id().trim().to_string()).await {
+                            Ok(Some(found)) => order.set(Some(found)),
+                            Ok(None) => status.set(format!("No order {} is stored", order_id().trim())),
+                            Err(err) => status.set(format!("Order lookup failed: {err}")),
+                        }
+                    },
+                    "Show order"
+                }
+                input {
+                    placeholder: "Error type",
+                    value: "{error_type}",
+                    oninput: move |event| error_type.set(event.value()),
+                }
+                input {
+                    placeholder: "Limit",
+                    value: "{limit}",
+                    oninput: move |event| limit.set(event.value()),
+                }
+                label {
+                    input {
+                        r#type: "checkbox",
+                        checked: latest(),
+                        onchange: move |event| latest.set(event.checked()),
+                    }
+                    "Only the latest"
+                }
+                button {
+                    onclick: move |_| async move {
+                        match query_messages(query()).await {
+                            Ok(found) => {
+                                messages.set(Some(found));
+                                status.set(String::new());
+                            }
+                            Err(err) => status.set(format!("Query failed: {err}")),
+                        }
+                    },
+                    "Query"
+                }
+            }
+            div {
+                class: "controls",
+                input {
+                    placeholder: "Keep hours (optional)",
+                    value: "{max_age_hours}",
+                    oninput: move |event| max_age_hours.set(event.value()),
+                }
+                input {
+                    placeholder: "Keep messages (optional)",
+                    value: "{max_messages}",
+                    oninput: move |event| max_messages.set(event.value()),
+                }
+                button {
+                    onclick: move |_| async move {
+                        let config = StoreConfig {
+                            max_age_secs: max_age_hours().trim().parse::<u64>().ok().map(|hours| hours.saturating_mul(3600)),
+                            max_messages: max_messages().trim().parse().ok(),
+                            ..StoreConfig::default()
+                        };
+                        match configure_store(config).await {
+                            Ok(deleted) => status.set(format!("Retention deleted {deleted} messages")),
+                            Err(err) => status.set(format!("Retention failed: {err}")),
+                        }
+                        refresh().await;
+                    },
+                    "Apply retention"
+                }
+            }
+            if !status().is_empty() {
+                p { class: "status", "{status}" }
+            }
+            if let Some(order) = order() {
+                div {
+                    class: "order",
+                    h3 { "Order {order.order_id} of {order.agv}" }
+                    table {
+                        tr {
+                            th { "#" }
+                            th { "Received" }
+                            th { "Update" }
+                            th { "Released nodes" }
+                            th { "Horizon nodes" }
+                        }
+                        for update in order.updates.iter() {
+                            tr {
+                                td { "{update.sequence}" }
+                                td { "{update.received_at.to_rfc3339()}" }
+                                td { "{update.message.order_update_id}" }
+                                td { {update.message.nodes.iter().filter(|node| node.released).map(|node| node.node_id.as_str()).collect::<Vec<_>>().join(" ")} }
+                                td { {update.message.nodes.iter().filter(|node| !node.released).map(|node| node.node_id.as_str()).collect::<Vec<_>>().join(" ")} }
+                            }
+                        }
+                    }
+                    match order.last_state.as_ref() {
+                        Some(state) => rsx! {
+                            p {
+                                "Latest state at {state.received_at.to_rfc3339()}: update "
+                                {state.message.order_update_id.map(|id| id.to_string()).unwrap_or_default()}
+                                ", last node "
+                                {state.message.last_node_id.clone().unwrap_or_default()}
+                                ", {state.message.node_states.len()} nodes to go"
+                                if state.message.driving { ", driving" }
+                                {state.message.errors.iter().map(|error| format!(", {}", error.error_type)).collect::<String>()}
+                            }
+                        },
+                        None => rsx! {
+                            p { "No state refers to the order." }
+                        },
+                    }
+                }
+            }
+            if let Some(kpis) = kpis() {
+                KpiChart { kpis }
+            }
+            if let Some(messages) = messages() {
+                MessageTable { messages }
+            }
+        }
+    }
+}
+
+/// Stores the messages of a recording of the recordings directory on the server.
+#[post("/api/store/import")]
+async fn import_recording(recording: String) -> Result<ImportSummary> {
+    use vda5050_analysis::recording::Recording;
+
+    let recording = Recording::load(&[crate::recordings::resolve(&recording)?])?;
+    Ok(crate::store::with_store_async(move |store| store.insert_recording(&recording)).await?)
+}
+
+/// Which messages the store keeps.
+#[post("/api/store/config")]
+async fn store_config() -> Result<StoreConfig> {
+    Ok(crate::store::with_store_async(|store| Ok(store.config().clone())).await?)
+}
+
+/// Changes which messages the store keeps, applies the retention right away and returns the
+/// number of deleted messages.
+#[post("/api/store/configure")]
+async fn configure_store(config: StoreConfig) -> Result<u64> {
+    Ok(crate::store::with_store_async(move |store| {
+        store.set_config(config);
+        store.apply_retention()
+    })
+    .await?)
+}
@@ -16,3 +16,15 @@ pub use blog::Blog;
 
 mod navbar;
 pub use navbar::Navbar;
+
+mod analysis;
+pub use analysis::Analysis;
+
+mod broker;
+pub use broker::Broker;
+
+mod messages;
+pub use messages::Messages;
+
+mod live;
+pub use live::Live;
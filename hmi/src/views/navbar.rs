@@ -23,6 +23,22 @@ pub fn Navbar() -> Element {
                 to: Route::Blog { id: 1 },
                 "Blog"
             }
+            Link {
+                to: Route::Analysis {},
+                "Analysis"
+            }
+            Link {
+                to: Route::Broker {},
+                "Broker"
+            }
+            Link {
+                to: Route::Messages {},
+                "Messages"
+            }
+            Link {
+                to: Route::Live {},
+                "Live"
+            }
         }
 
         // The `Outlet` component is used to render the next component inside the layout. In this case, it will render either
@@ -0,0 +1,321 @@
+//! Battery and charging statistics derived from the battery state of the AGVs.
+use super::Location;
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use vda5050_data_types::message::Message;
+
+/// Thresholds used by the battery analysis.
+#[derive(Debug, Clone)]
+pub struct BatteryConfig {
+    /// Below this charge in percent the battery is considered low.
+    pub low_charge: f64,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        BatteryConfig { low_charge: 20.0 }
+    }
+}
+
+/// The battery state of one state message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatterySample {
+    /// The receive time of the state.
+    pub time: DateTime<Utc>,
+    /// The charge in percent.
+    pub charge: f64,
+    /// The voltage in [V].
+    pub voltage: Option<f64>,
+    /// The current in [A].
+    pub current: Option<f64>,
+    /// Whether the battery was charging.
+    pub charging: bool,
+    /// Whether the AGV was driving.
+    pub driving: bool,
+    /// The remaining range reported by the AGV in [m].
+    pub reach: Option<f64>,
+}
+
+/// A period in which the battery was charging.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChargingSession {
+    /// The first state that reported charging.
+    pub start: DateTime<Utc>,
+    /// The first state that no longer reported charging.
+    /// `None` if the battery was still charging at the end of the recording.
+    pub end: Option<DateTime<Utc>>,
+    /// The charge in percent when charging started.
+    pub start_charge: f64,
+    /// The last charge in percent reported while charging.
+    pub end_charge: f64,
+    /// How long the battery was charging, in seconds.
+    pub duration_secs: f64,
+}
+
+impl ChargingSession {
+    /// The charged percent per hour.
+    pub fn rate_per_hour(&self) -> Option<f64> {
+        (self.duration_secs > 0.0)
+            .then(|| (self.end_charge - self.start_charge) / self.duration_secs * 3600.0)
+    }
+}
+
+/// A period in which the charge was below the low battery threshold.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LowBatteryIncident {
+    /// The first state with a low charge.
+    pub start: DateTime<Utc>,
+    /// The first state with a charge above the threshold again.
+    /// `None` if the charge was still low at the end of the recording.
+    pub end: Option<DateTime<Utc>>,
+    /// The lowest charge in percent.
+    pub min_charge: f64,
+    /// Where the AGV was when the charge became low.
+    pub location: Option<Location>,
+}
+
+/// The battery statistics of one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgvBattery {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The battery state of every state message, in receive order.
+    pub samples: Vec<BatterySample>,
+    /// The distance driven while not charging, in [m].
+    pub distance: f64,
+    /// The time spent not charging, in seconds.
+    pub discharging_secs: f64,
+    /// The charge lost per hour while not charging, in percent.
+    pub discharge_per_hour: Option<f64>,
+    /// The charge lost per meter driven, in percent.
+    pub discharge_per_meter: Option<f64>,
+    /// All charging sessions.
+    pub charging_sessions: Vec<ChargingSession>,
+    /// All periods with a low charge.
+    pub low_battery: Vec<LowBatteryIncident>,
+    /// How long the last charge lasts at the mean discharge rate until it falls below the low
+    /// battery threshold, in seconds.
+    pub remaining_runtime_secs: Option<f64>,
+}
+
+impl AgvBattery {
+    /// The last reported charge in percent.
+    pub fn last_charge(&self) -> Option<f64> {
+        self.samples.last().map(|sample| sample.charge)
+    }
+}
+
+/// The battery statistics of all AGVs in a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BatteryAnalysis {
+    /// The low battery threshold in percent the analysis used.
+    pub low_charge: f64,
+    /// The statistics of every AGV that reported a battery state.
+    pub agvs: Vec<AgvBattery>,
+}
+
+impl BatteryAnalysis {
+    /// Collects the battery states of the recording and derives the statistics.
+    pub fn analyze(recording: &Recording, config: &BatteryConfig) -> Self {
+        let mut by_agv: BTreeMap<&AgvId, Vec<(BatterySample, Option<Location>)>> = BTreeMap::new();
+        for recorded in &recording.messages {
+            let Message::State(state) = &recorded.message else {
+                continue;
+            };
+            let Some(battery) = &state.battery_state else {
+                continue;
+            };
+            let sample = BatterySample {
+                time: recorded.received_at,
+                charge: battery.battery_charge,
+                voltage: battery.battery_voltage,
+                current: battery.battery_current,
+                charging: battery.charging.unwrap_or(false),
+                driving: state.driving,
+                reach: battery.reach,
+            };
+            let location = state.agv_position.as_ref().map(Location::of);
+            by_agv
+                .entry(&recorded.agv)
+                .or_default()
+                .push((sample, location));
+        }
+        BatteryAnalysis {
+            low_charge: config.low_charge,
+            agvs: by_agv
+                .into_iter()
+                .map(|(agv, samples)| analyze_agv(agv, samples, config))
+                .collect(),
+        }
+    }
+
+    /// The median discharge per hour of all AGVs, to spot batteries that drain faster than the
+    /// rest of the fleet.
+    pub fn fleet_discharge_per_hour(&self) -> Option<f64> {
+        let mut rates: Vec<f64> = self
+            .agvs
+            .iter()
+            .filter_map(|agv| agv.discharge_per_hour)
+            .collect();
+        if rates.is_empty() {
+            return None;
+        }
+        rates.sort_by(f64::total_cmp);
+        Some(rates[rates.len() / 2])
+    }
+}
+
+fn analyze_agv(
+    agv: &AgvId,
+    samples: Vec<(BatterySample, Option<Location>)>,
+    config: &BatteryConfig,
+) -> AgvBattery {
+    let mut distance = 0.0;
+    let mut discharging_secs = 0.0;
+    let mut discharged = 0.0;
+    let mut charging_sessions: Vec<ChargingSession> = Vec::new();
+    let mut low_battery: Vec<LowBatteryIncident> = Vec::new();
+
+    for (index, (sample, location)) in samples.iter().enumerate() {
+        if let Some((previous, previous_location)) = index.checked_sub(1).map(|i| &samples[i])
+            && !previous.charging
+            && !sample.charging
+        {
+            discharging_secs += (sample.time - previous.time).num_milliseconds() as f64 / 1000.0;
+            discharged += previous.charge - sample.charge;
+            if let (Some(from), Some(to)) = (previous_location, location)
+                && from.map_id == to.map_id
+            {
+                distance += (to.x - from.x).hypot(to.y - from.y);
+            }
+        }
+
+        match charging_sessions.last_mut() {
+            Some(session) if session.end.is_none() => {
+                session.duration_secs =
+                    (sample.time - session.start).num_milliseconds() as f64 / 1000.0;
+                if sample.charging {
+                    session.end_charge = sample.charge;
+                } else {
+                    session.end = Some(sample.time);
+                }
+            }
+            _ if sample.charging => charging_sessions.push(ChargingSession {
+                start: sample.time,
+                end: None,
+                start_charge: sample.charge,
+                end_charge: sample.charge,
+                duration_secs: 0.0,
+            }),
+            _ => {}
+        }
+
+        let low = sample.charge < config.low_charge;
+        match low_battery.last_mut() {
+            Some(incident) if incident.end.is_none() => {
+                if low {
+                    incident.min_charge = incident.min_charge.min(sample.charge);
+                } else {
+                    incident.end = Some(sample.time);
+                }
+            }
+            _ if low => low_battery.push(LowBatteryIncident {
+                start: sample.time,
+                end: None,
+                min_charge: sample.charge,
+                location: location.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    let discharge_per_hour =
+        (discharging_secs > 0.0).then(|| discharged / discharging_secs * 3600.0);
+    // Positioning noise of a standing AGV would make the rate per meter meaningless.
+    let discharge_per_meter = (distance >= 1.0).then(|| discharged / distance);
+    let last_charge = samples.last().map(|(sample, _)| sample.charge);
+    let remaining_runtime_secs = match (last_charge, discharge_per_hour) {
+        (Some(charge), Some(rate)) if rate > 0.0 => {
+            Some(((charge - config.low_charge) / rate * 3600.0).max(0.0))
+        }
+        _ => None,
+    };
+    AgvBattery {
+        agv: agv.clone(),
+        samples: samples.into_iter().map(|(sample, _)| sample).collect(),
+        distance,
+        discharging_secs,
+        discharge_per_hour,
+        discharge_per_meter,
+        charging_sessions,
+        low_battery,
+        remaining_runtime_secs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{at, push, state};
+    use serde_json::json;
+
+    #[test]
+    fn follows_the_charge_through_discharging_and_charging() {
+        let mut recording = Recording::default();
+        // Seconds, charge, charging, x.
+        for (secs, charge, charging, x) in [
+            (0.0, 30.0, false, 0.0),
+            (1800.0, 25.0, false, 100.0),
+            (3600.0, 18.0, false, 100.0),
+            (3660.0, 18.0, true, 100.0),
+            (5460.0, 48.0, true, 100.0),
+            (5520.0, 48.0, false, 100.0),
+        ] {
+            let body = state(json!({
+                "agvPosition": {"x": x, "y": 0.0, "mapId": "hall"},
+                "batteryState": {"batteryCharge": charge, "charging": charging},
+            }));
+            push(&mut recording, "state", secs, secs, body);
+        }
+
+        let analysis = BatteryAnalysis::analyze(&recording, &BatteryConfig::default());
+        assert_eq!(analysis.agvs.len(), 1);
+        let battery = &analysis.agvs[0];
+        let curve: Vec<f64> = battery.samples.iter().map(|sample| sample.charge).collect();
+        assert_eq!(curve, [30.0, 25.0, 18.0, 18.0, 48.0, 48.0]);
+        assert_eq!(battery.last_charge(), Some(48.0));
+
+        // 12 % in the hour without charging, over 100 m.
+        assert_eq!(battery.discharging_secs, 3600.0);
+        assert_eq!(battery.distance, 100.0);
+        assert_eq!(battery.discharge_per_hour, Some(12.0));
+        assert_eq!(battery.discharge_per_meter, Some(0.12));
+        assert_eq!(battery.remaining_runtime_secs, Some(8400.0));
+
+        assert_eq!(
+            battery.charging_sessions,
+            [ChargingSession {
+                start: at(3660.0),
+                end: Some(at(5520.0)),
+                start_charge: 18.0,
+                end_charge: 48.0,
+                duration_secs: 1860.0,
+            }]
+        );
+        let rate = battery.charging_sessions[0].rate_per_hour().unwrap();
+        assert!((rate - 30.0 / 1860.0 * 3600.0).abs() < 1e-9);
+
+        assert_eq!(battery.low_battery.len(), 1);
+        let low = &battery.low_battery[0];
+        assert_eq!((low.start, low.end), (at(3600.0), Some(at(5460.0))));
+        assert_eq!(low.min_charge, 18.0);
+        assert_eq!(analysis.fleet_discharge_per_hour(), Some(12.0));
+    }
+}
@@ -0,0 +1,275 @@
+//! Offset and drift of the clocks that set the header timestamps, relative to the capture host.
+//!
+//! For every message the difference between the header timestamp and the receive time is the
+//! offset of the sender's clock minus the transmission delay. A straight line fitted through these
+//! differences over time gives the offset at the start of the recording and the drift. The
+//! transmission delay makes every offset look smaller by the mean delay, which is usually a few
+//! milliseconds.
+use crate::recording::{AgvId, RecordedMessage, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::message::Message;
+
+/// Whose clock set a header timestamp.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum Sender {
+    /// The master control, which sends orders and instant actions.
+    MasterControl,
+    /// An AGV, which sends all other messages.
+    Agv(AgvId),
+}
+
+impl Sender {
+    /// The sender of a recorded message.
+    pub fn of(recorded: &RecordedMessage) -> Self {
+        match recorded.message {
+            Message::Order(_) | Message::InstantActions(_) => Sender::MasterControl,
+            _ => Sender::Agv(recorded.agv.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Sender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sender::MasterControl => f.write_str("master control"),
+            Sender::Agv(agv) => agv.fmt(f),
+        }
+    }
+}
+
+/// The estimated clock of one sender.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockEstimate {
+    /// The sender.
+    pub sender: Sender,
+    /// The number of messages with a valid timestamp.
+    pub samples: usize,
+    /// The receive time of the first of these messages.
+    pub reference: DateTime<Utc>,
+    /// The offset at the reference time in milliseconds. Positive if the clock is ahead.
+    pub offset_ms: f64,
+    /// How much the clock gains per second, in parts per million.
+    pub drift_ppm: f64,
+    /// The standard deviation of the measured offsets around the fitted line, in milliseconds.
+    pub jitter_ms: f64,
+}
+
+impl ClockEstimate {
+    /// The offset at the given time, in milliseconds.
+    pub fn offset_at(&self, time: DateTime<Utc>) -> f64 {
+        self.offset_ms + self.drift_ppm * secs(time - self.reference) / 1000.0
+    }
+
+    /// A timestamp of this sender converted to the clock of the capture host. The offset is the
+    /// one at the time of the capture host, not at the timestamp, which the drift has moved.
+    pub fn correct(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let elapsed = secs(timestamp - self.reference) - self.offset_ms / 1000.0;
+        let host = elapsed / (1.0 + self.drift_ppm / 1_000_000.0);
+        self.reference + Duration::microseconds((host * 1_000_000.0).round() as i64)
+    }
+}
+
+/// The clock estimates of all senders in a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockModel {
+    /// One estimate per sender with at least one valid timestamp.
+    pub estimates: Vec<ClockEstimate>,
+}
+
+impl ClockModel {
+    /// Estimates the clocks of all senders in the recording.
+    pub fn estimate(recording: &Recording) -> Self {
+        let mut samples: BTreeMap<Sender, Vec<(DateTime<Utc>, f64)>> = BTreeMap::new();
+        for recorded in &recording.messages {
+            // The broker publishes the last will with the timestamp of the connect.
+            if let Message::Connection(connection) = &recorded.message
+                && connection.connection_state == ConnectionState::ConnectionBroken
+            {
+                continue;
+            }
+            let Some(timestamp) = recorded.message.header().timestamp_utc() else {
+                continue;
+            };
+            samples
+                .entry(Sender::of(recorded))
+                .or_default()
+                .push((recorded.received_at, secs(timestamp - recorded.received_at)));
+        }
+        ClockModel {
+            estimates: samples
+                .into_iter()
+                .map(|(sender, samples)| fit(sender, &samples))
+                .collect(),
+        }
+    }
+
+    /// The estimate of a sender.
+    pub fn get(&self, sender: &Sender) -> Option<&ClockEstimate> {
+        self.estimates
+            .iter()
+            .find(|estimate| &estimate.sender == sender)
+    }
+
+    /// Replaces the receive time of every message by its header timestamp converted to the clock
+    /// of the capture host, which removes the delays of the network and the broker from the
+    /// timelines. Messages without a valid timestamp keep their receive time.
+    pub fn realign(&self, recording: &mut Recording) {
+        for recorded in &mut recording.messages {
+            let Some(timestamp) = recorded.message.header().timestamp_utc() else {
+                continue;
+            };
+            if let Some(estimate) = self.get(&Sender::of(recorded)) {
+                recorded.received_at = estimate.correct(timestamp);
+            }
+        }
+        recording.sort();
+    }
+
+    /// A human readable table of the estimates.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for estimate in &self.estimates {
+            let _ = writeln!(
+                text,
+                "{:<24} offset {:>9.1} ms  drift {:>8.1} ppm  jitter {:>7.1} ms  ({} messages)",
+                estimate.sender.to_string(),
+                estimate.offset_ms,
+                estimate.drift_ppm,
+                estimate.jitter_ms,
+                estimate.samples
+            );
+        }
+        text
+    }
+}
+
+/// Fits a line through the offsets in milliseconds over the seconds since the first sample.
+fn fit(sender: Sender, samples: &[(DateTime<Utc>, f64)]) -> ClockEstimate {
+    let reference = samples[0].0;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(time, offset)| (secs(*time - reference), offset * 1000.0))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+    let offset_ms = mean_y - slope * mean_x;
+    let residuals: f64 = points
+        .iter()
+        .map(|(x, y)| (y - offset_ms - slope * x).powi(2))
+        .sum();
+    ClockEstimate {
+        sender,
+        samples: points.len(),
+        reference,
+        offset_ms,
+        // Milliseconds per second are thousandths, the drift is reported in millionths.
+        drift_ppm: slope * 1000.0,
+        jitter_ms: (residuals / n).sqrt(),
+    }
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{at, push, state};
+    use serde_json::json;
+
+    /// States of an AGV whose clock is 1500 ms ahead at the start and gains 200 µs per second,
+    /// orders of a master control in sync with the capture host, and a last will stamped at the
+    /// connect long before.
+    fn skewed() -> Recording {
+        let mut recording = Recording::default();
+        for i in 0..=10 {
+            let received = f64::from(i) * 10.0;
+            push(
+                &mut recording,
+                "state",
+                received,
+                received + 1.5 + received * 200e-6,
+                state(json!({})),
+            );
+            let order = json!({
+                "orderId": format!("o{i}"),
+                "orderUpdateId": 0,
+                "nodes": [{"nodeId": "n1", "sequenceId": 0, "released": true, "actions": []}],
+                "edges": [],
+            });
+            push(
+                &mut recording,
+                "order",
+                received + 5.0,
+                received + 5.0,
+                order,
+            );
+        }
+        push(
+            &mut recording,
+            "connection",
+            100.0,
+            -3600.0,
+            json!({
+                "connectionState": "CONNECTIONBROKEN",
+                "lastStateChange": "2023-12-31T23:00:00Z",
+            }),
+        );
+        recording
+    }
+
+    #[test]
+    fn estimates_the_offset_and_the_drift_of_every_sender() {
+        let recording = skewed();
+        let model = ClockModel::estimate(&recording);
+        let agv = Sender::Agv(recording.messages[0].agv.clone());
+        let estimate = model.get(&agv).unwrap();
+        assert_eq!(estimate.samples, 11);
+        assert_eq!(estimate.reference, at(0.0));
+        assert!((estimate.offset_ms - 1500.0).abs() < 0.01, "{estimate:?}");
+        assert!((estimate.drift_ppm - 200.0).abs() < 0.01, "{estimate:?}");
+        assert!(estimate.jitter_ms < 0.01);
+        assert!((estimate.offset_at(at(100.0)) - 1520.0).abs() < 0.01);
+
+        let master = model.get(&Sender::MasterControl).unwrap();
+        assert_eq!(master.samples, 11);
+        assert!(master.offset_ms.abs() < 0.01 && master.drift_ppm.abs() < 0.01);
+    }
+
+    #[test]
+    fn realigns_the_messages_to_the_clock_of_the_capture_host() {
+        let mut recording = skewed();
+        let model = ClockModel::estimate(&recording);
+        model.realign(&mut recording);
+        let states: Vec<DateTime<Utc>> = recording
+            .messages
+            .iter()
+            .filter(|recorded| matches!(recorded.message, Message::State(_)))
+            .map(|recorded| recorded.received_at)
+            .collect();
+        assert_eq!(states.len(), 11);
+        for (i, received_at) in states.into_iter().enumerate() {
+            let error = received_at - at(i as f64 * 10.0);
+            assert!(error.num_microseconds().unwrap().abs() < 10, "{error}");
+        }
+    }
+}
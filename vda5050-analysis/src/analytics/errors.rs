@@ -0,0 +1,435 @@
+//! Error and information intervals derived from the states of the AGVs.
+//!
+//! The state repeats all active errors and information with every message. An error is raised with
+//! the first state that contains it and cleared with the first state of the same AGV that no longer
+//! does. Errors are told apart by their type and references, information by its type.
+use super::Location;
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::{ErrorLevel, InfoLevel, State};
+
+/// Whether an interval comes from the errors or the information of the state.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum EventKind {
+    /// An entry of `State::errors`.
+    Error,
+    /// An entry of `State::information`.
+    Information,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EventKind::Error => "error",
+            EventKind::Information => "information",
+        })
+    }
+}
+
+/// The time between raising and clearing one error or information.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Interval {
+    /// The AGV that reported it.
+    pub agv: AgvId,
+    /// Whether it is an error or an information.
+    pub kind: EventKind,
+    /// The error or information type.
+    pub event_type: String,
+    /// The highest level reported, e.g. `FATAL`.
+    pub level: String,
+    /// The first description reported.
+    pub description: Option<String>,
+    /// The error references as key and value.
+    pub references: Vec<(String, String)>,
+    /// The receive time of the first state that contained it.
+    pub raised_at: DateTime<Utc>,
+    /// The receive time of the first state that no longer contained it.
+    /// `None` if it was still active at the end of the recording.
+    pub cleared_at: Option<DateTime<Utc>>,
+    /// The receive time of the last state that contained it.
+    pub last_seen: DateTime<Utc>,
+    /// The order referenced by the error, or the order of the AGV when it was raised.
+    pub order_id: Option<String>,
+    /// The node referenced by the error.
+    pub node_id: Option<String>,
+    /// The edge referenced by the error.
+    pub edge_id: Option<String>,
+    /// The action referenced by the error.
+    pub action_id: Option<String>,
+    /// The type of the referenced action, if it was reported or sent.
+    pub action_type: Option<String>,
+    /// The last node the AGV passed when it was raised.
+    pub last_node_id: Option<String>,
+    /// Where the AGV was when it was raised.
+    pub location: Option<Location>,
+}
+
+impl Interval {
+    /// The end of the interval: when it was cleared, or when it was last seen if it never was.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.cleared_at.unwrap_or(self.last_seen)
+    }
+
+    /// How long it was active, in seconds.
+    pub fn duration_secs(&self) -> f64 {
+        (self.end() - self.raised_at).num_milliseconds() as f64 / 1000.0
+    }
+
+    /// Whether it is a fatal error.
+    pub fn is_fatal(&self) -> bool {
+        self.kind == EventKind::Error && self.level == "FATAL"
+    }
+}
+
+/// How often and how long an error or information type was active.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    /// The AGV, or `None` for the whole fleet.
+    pub agv: Option<AgvId>,
+    /// Whether it is an error or an information.
+    pub kind: EventKind,
+    /// The error or information type.
+    pub event_type: String,
+    /// How often it was raised.
+    pub occurrences: usize,
+    /// How often it was raised per hour of the recording.
+    pub per_hour: f64,
+    /// The total time it was active, in seconds.
+    pub total_secs: f64,
+    /// The mean time it was active, in seconds.
+    pub mean_secs: f64,
+    /// The longest time it was active, in seconds.
+    pub max_secs: f64,
+}
+
+/// All error and information intervals of a recording with their statistics.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorTimeline {
+    /// All intervals, ordered by the time they were raised.
+    pub intervals: Vec<Interval>,
+    /// The statistics per type for the whole fleet, followed by the statistics per AGV and type.
+    pub summaries: Vec<Summary>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    agv: AgvId,
+    kind: EventKind,
+    event_type: String,
+    references: Vec<(String, String)>,
+}
+
+impl ErrorTimeline {
+    /// Derives the intervals from the states in the recording.
+    pub fn analyze(recording: &Recording) -> Self {
+        let mut intervals: Vec<Interval> = Vec::new();
+        let mut open: HashMap<Key, usize> = HashMap::new();
+        let mut action_types: HashMap<(AgvId, String), String> = HashMap::new();
+
+        for recorded in &recording.messages {
+            let agv = &recorded.agv;
+            let state = match &recorded.message {
+                Message::State(state) => state,
+                Message::Order(order) => {
+                    let actions = order
+                        .nodes
+                        .iter()
+                        .flat_map(|node| &node.actions)
+                        .chain(order.edges.iter().flat_map(|edge| &edge.actions));
+                    for action in actions {
+                        action_types.insert(
+                            (agv.clone(), action.action_id.clone()),
+                            action.action_type.clone(),
+                        );
+                    }
+                    continue;
+                }
+                Message::InstantActions(instant_actions) => {
+                    for action in &instant_actions.instant_actions {
+                        action_types.insert(
+                            (agv.clone(), action.action_id.clone()),
+                            action.action_type.clone(),
+                        );
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            for action_state in &state.action_states {
+                if let Some(action_type) = &action_state.action_type {
+                    action_types.insert(
+                        (agv.clone(), action_state.action_id.clone()),
+                        action_type.clone(),
+                    );
+                }
+            }
+
+            let mut reported = HashSet::new();
+            for reported_event in events(state) {
+                let key = Key {
+                    agv: agv.clone(),
+                    kind: reported_event.kind,
+                    event_type: reported_event.event_type.clone(),
+                    references: reported_event.references.clone(),
+                };
+                if let Some(&index) = open.get(&key) {
+                    let interval = &mut intervals[index];
+                    interval.last_seen = recorded.received_at;
+                    if reported_event.level == "FATAL" {
+                        interval.level = reported_event.level;
+                    }
+                    if interval.description.is_none() {
+                        interval.description = reported_event.description;
+                    }
+                    reported.insert(index);
+                    continue;
+                }
+                let reference = |key: &str| {
+                    reported_event
+                        .references
+                        .iter()
+                        .find(|(reference_key, _)| reference_key == key)
+                        .map(|(_, value)| value.clone())
+                };
+                let action_id = reference("actionId");
+                let action_type = action_id.as_ref().and_then(|action_id| {
+                    action_types.get(&(agv.clone(), action_id.clone())).cloned()
+                });
+                reported.insert(intervals.len());
+                open.insert(key, intervals.len());
+                intervals.push(Interval {
+                    agv: agv.clone(),
+                    kind: reported_event.kind,
+                    order_id: reference("orderId").or_else(|| state.order_id.clone()),
+                    node_id: reference("nodeId"),
+                    edge_id: reference("edgeId"),
+                    action_id,
+                    action_type,
+                    last_node_id: state.last_node_id.clone(),
+                    location: state.agv_position.as_ref().map(Location::of),
+                    event_type: reported_event.event_type,
+                    level: reported_event.level,
+                    description: reported_event.description,
+                    references: reported_event.references,
+                    raised_at: recorded.received_at,
+                    cleared_at: None,
+                    last_seen: recorded.received_at,
+                });
+            }
+            open.retain(|key, index| {
+                if &key.agv != agv || reported.contains(index) {
+                    return true;
+                }
+                intervals[*index].cleared_at = Some(recorded.received_at);
+                false
+            });
+        }
+
+        let hours = recording
+            .time_range()
+            .map(|(start, end)| (end - start).num_milliseconds() as f64 / 3_600_000.0)
+            .unwrap_or_default();
+        let summaries = summarize(&intervals, hours);
+        ErrorTimeline {
+            intervals,
+            summaries,
+        }
+    }
+
+    /// The intervals of one AGV.
+    pub fn of_agv<'a>(&'a self, agv: &'a AgvId) -> impl Iterator<Item = &'a Interval> {
+        self.intervals
+            .iter()
+            .filter(move |interval| &interval.agv == agv)
+    }
+}
+
+/// An error or information as reported in a single state.
+struct ReportedEvent {
+    kind: EventKind,
+    event_type: String,
+    level: String,
+    description: Option<String>,
+    references: Vec<(String, String)>,
+}
+
+fn events(state: &State) -> impl Iterator<Item = ReportedEvent> + '_ {
+    let errors = state.errors.iter().map(|error| {
+        let mut references: Vec<(String, String)> = error
+            .error_references
+            .iter()
+            .map(|reference| {
+                (
+                    reference.reference_key.clone(),
+                    reference.reference_value.clone(),
+                )
+            })
+            .collect();
+        references.sort();
+        ReportedEvent {
+            kind: EventKind::Error,
+            event_type: error.error_type.clone(),
+            level: match &error.error_level {
+                ErrorLevel::Warning => "WARNING".to_string(),
+                ErrorLevel::Fatal => "FATAL".to_string(),
+                ErrorLevel::Unknown(level) => level.clone(),
+            },
+            description: error.error_description.clone(),
+            references,
+        }
+    });
+    let information = state
+        .information
+        .iter()
+        .flatten()
+        .map(|info| ReportedEvent {
+            kind: EventKind::Information,
+            event_type: info.info_type.clone(),
+            level: match &info.info_level {
+                InfoLevel::Debug => "DEBUG".to_string(),
+                InfoLevel::Info => "INFO".to_string(),
+                InfoLevel::Unknown(level) => level.clone(),
+            },
+            description: info.info_description.clone(),
+            references: Vec::new(),
+        });
+    errors.chain(information)
+}
+
+fn summarize(intervals: &[Interval], hours: f64) -> Vec<Summary> {
+    let mut groups: BTreeMap<(Option<&AgvId>, EventKind, &str), Vec<f64>> = BTreeMap::new();
+    for interval in intervals {
+        let duration = interval.duration_secs();
+        for agv in [None, Some(&interval.agv)] {
+            groups
+                .entry((agv, interval.kind, &interval.event_type))
+                .or_default()
+                .push(duration);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((agv, kind, event_type), durations)| {
+            let total_secs: f64 = durations.iter().sum();
+            Summary {
+                agv: agv.cloned(),
+                kind,
+                event_type: event_type.to_string(),
+                occurrences: durations.len(),
+                per_hour: if hours > 0.0 {
+                    durations.len() as f64 / hours
+                } else {
+                    0.0
+                },
+                total_secs,
+                mean_secs: total_secs / durations.len() as f64,
+                max_secs: durations.iter().copied().fold(0.0, f64::max),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{at, push, push_agv, state};
+    use serde_json::{Value, json};
+
+    fn blocked(level: &str, node_id: &str) -> Value {
+        json!({
+            "errorType": "pathBlocked",
+            "errorLevel": level,
+            "errorDescription": "obstacle ahead",
+            "errorReferences": [{"referenceKey": "nodeId", "referenceValue": node_id}],
+        })
+    }
+
+    #[test]
+    fn opens_and_closes_intervals_per_type_and_references() {
+        let mut recording = Recording::default();
+        push(&mut recording, "state", 0.0, 0.0, state(json!({})));
+        push(
+            &mut recording,
+            "state",
+            1.0,
+            1.0,
+            state(json!({
+                "orderId": "o1",
+                "lastNodeId": "n1",
+                "errors": [blocked("WARNING", "n2")],
+                "information": [{"infoType": "charging", "infoLevel": "INFO"}],
+            })),
+        );
+        // The state of another AGV clears nothing.
+        push_agv(&mut recording, "agv2", "state", 1.5, 1.5, state(json!({})));
+        push(
+            &mut recording,
+            "state",
+            2.0,
+            2.0,
+            state(json!({"errors": [blocked("FATAL", "n2"), blocked("WARNING", "n3")]})),
+        );
+        push(
+            &mut recording,
+            "state",
+            4.0,
+            4.0,
+            state(json!({"errors": [blocked("WARNING", "n3")]})),
+        );
+
+        let timeline = ErrorTimeline::analyze(&recording);
+        let intervals: Vec<_> = timeline
+            .intervals
+            .iter()
+            .map(|interval| {
+                (
+                    interval.kind,
+                    interval.node_id.as_deref(),
+                    interval.level.as_str(),
+                    interval.raised_at,
+                    interval.cleared_at,
+                )
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            [
+                (
+                    EventKind::Error,
+                    Some("n2"),
+                    "FATAL",
+                    at(1.0),
+                    Some(at(4.0))
+                ),
+                (EventKind::Information, None, "INFO", at(1.0), Some(at(2.0))),
+                (EventKind::Error, Some("n3"), "WARNING", at(2.0), None),
+            ]
+        );
+
+        let first = &timeline.intervals[0];
+        assert!(first.is_fatal());
+        assert_eq!(first.order_id.as_deref(), Some("o1"));
+        assert_eq!(first.last_node_id.as_deref(), Some("n1"));
+        assert_eq!(first.last_seen, at(2.0));
+        assert_eq!(first.duration_secs(), 3.0);
+        // An interval still active at the end lasts until it was last seen.
+        assert_eq!(timeline.intervals[2].end(), at(4.0));
+
+        let fleet = timeline
+            .summaries
+            .iter()
+            .find(|summary| summary.agv.is_none() && summary.event_type == "pathBlocked")
+            .unwrap();
+        assert_eq!(fleet.occurrences, 2);
+        assert_eq!(fleet.total_secs, 5.0);
+        assert_eq!(fleet.max_secs, 3.0);
+    }
+}
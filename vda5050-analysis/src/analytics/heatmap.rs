@@ -0,0 +1,235 @@
+//! Values collected in a square grid per map.
+use super::Location;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The most pixels of a heatmap image, 64 MiB of RGBA.
+const MAX_PIXELS: u64 = 16 * 1024 * 1024;
+
+/// One cell of a heatmap.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapCell {
+    /// The column of the cell; the cell spans `column * cell_size` to `(column + 1) * cell_size`
+    /// on the X-axis.
+    pub column: i64,
+    /// The row of the cell, on the Y-axis.
+    pub row: i64,
+    /// How many values fell into the cell.
+    pub count: usize,
+    /// The sum of the values.
+    pub total: f64,
+}
+
+impl HeatmapCell {
+    /// The mean of the values.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total / self.count as f64
+        }
+    }
+}
+
+/// The cells of one map that received at least one value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    /// Identifier of the map.
+    pub map_id: String,
+    /// The edge length of a cell in [m].
+    pub cell_size: f64,
+    /// The cells, ordered by column and row.
+    pub cells: Vec<HeatmapCell>,
+}
+
+impl Heatmap {
+    /// The highest count of all cells.
+    pub fn max_count(&self) -> usize {
+        self.cells.iter().map(|cell| cell.count).max().unwrap_or(0)
+    }
+
+    /// The lowest and highest column and row, or `None` if the heatmap is empty.
+    pub fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let columns = self.cells.iter().map(|cell| cell.column);
+        let rows = self.cells.iter().map(|cell| cell.row);
+        Some((
+            (columns.clone().min()?, columns.max()?),
+            (rows.clone().min()?, rows.max()?),
+        ))
+    }
+
+    /// Renders the heatmap as a PNG image with the given number of pixels per cell. Empty cells
+    /// are transparent, the others are colored from blue for the lowest to red for the highest
+    /// value. The Y-axis points up, as in the world coordinate system.
+    pub fn to_png(
+        &self,
+        value: impl Fn(&HeatmapCell) -> f64,
+        pixels_per_cell: u32,
+    ) -> std::io::Result<Vec<u8>> {
+        let ((min_column, max_column), (min_row, max_row)) =
+            self.bounds().unwrap_or(((0, 0), (0, 0)));
+        let too_large = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "the heatmap of {} is larger than {MAX_PIXELS} pixels, use larger cells",
+                    self.map_id
+                ),
+            )
+        };
+        let cells = |min: i64, max: i64| {
+            max.checked_sub(min)
+                .and_then(|span| u32::try_from(span).ok())
+                .and_then(|span| span.checked_add(1))
+        };
+        let width = cells(min_column, max_column)
+            .and_then(|columns| columns.checked_mul(pixels_per_cell))
+            .ok_or_else(too_large)?;
+        let height = cells(min_row, max_row)
+            .and_then(|rows| rows.checked_mul(pixels_per_cell))
+            .ok_or_else(too_large)?;
+        let pixel_count = (width as u64) * (height as u64);
+        if pixel_count > MAX_PIXELS {
+            return Err(too_large());
+        }
+        let values: Vec<f64> = self.cells.iter().map(&value).collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        let mut pixels = vec![0u8; pixel_count as usize * 4];
+        for (cell, value) in self.cells.iter().zip(values) {
+            let share = if max > min {
+                (value - min) / (max - min)
+            } else {
+                1.0
+            };
+            let color = ramp(share);
+            let left = (cell.column - min_column) as u32 * pixels_per_cell;
+            let top = (max_row - cell.row) as u32 * pixels_per_cell;
+            for y in top..top + pixels_per_cell {
+                for x in left..left + pixels_per_cell {
+                    let offset = (y as usize * width as usize + x as usize) * 4;
+                    pixels[offset..offset + 4].copy_from_slice(&color);
+                }
+            }
+        }
+
+        let mut image = Vec::new();
+        let mut encoder = png::Encoder::new(&mut image, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer.write_image_data(&pixels)?;
+                writer.finish()
+            })
+            .map_err(std::io::Error::other)?;
+        Ok(image)
+    }
+}
+
+/// A color between blue for 0.0 and red for 1.0, passing through yellow.
+fn ramp(share: f64) -> [u8; 4] {
+    let share = share.clamp(0.0, 1.0);
+    let (red, green, blue) = if share < 0.5 {
+        let t = share * 2.0;
+        (t, t, 1.0 - t)
+    } else {
+        let t = (share - 0.5) * 2.0;
+        (1.0, 1.0 - t, 0.0)
+    };
+    let byte = |channel: f64| (channel * 255.0).round() as u8;
+    [byte(red), byte(green), byte(blue), 255]
+}
+
+/// Collects values into heatmaps, one per map.
+#[derive(Debug, Clone)]
+pub struct HeatmapBuilder {
+    cell_size: f64,
+    maps: BTreeMap<String, BTreeMap<(i64, i64), HeatmapCell>>,
+}
+
+impl HeatmapBuilder {
+    /// Starts empty heatmaps with the given cell size in [m].
+    pub fn new(cell_size: f64) -> Self {
+        HeatmapBuilder {
+            cell_size,
+            maps: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a value to the cell of the location.
+    pub fn add(&mut self, location: &Location, value: f64) {
+        let column = (location.x / self.cell_size).floor() as i64;
+        let row = (location.y / self.cell_size).floor() as i64;
+        let cell = self
+            .maps
+            .entry(location.map_id.clone())
+            .or_default()
+            .entry((column, row))
+            .or_insert(HeatmapCell {
+                column,
+                row,
+                count: 0,
+                total: 0.0,
+            });
+        cell.count += 1;
+        cell.total += value;
+    }
+
+    /// Finishes the heatmaps, ordered by map ID.
+    pub fn build(self) -> Vec<Heatmap> {
+        self.maps
+            .into_iter()
+            .map(|(map_id, cells)| Heatmap {
+                map_id,
+                cell_size: self.cell_size,
+                cells: cells.into_values().collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heatmap(columns: &[i64]) -> Heatmap {
+        Heatmap {
+            map_id: "hall".to_string(),
+            cell_size: 1.0,
+            cells: columns
+                .iter()
+                .map(|&column| HeatmapCell {
+                    column,
+                    row: 0,
+                    count: 1,
+                    total: 1.0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn renders_small_heatmaps() {
+        let png = heatmap(&[0, 3]).to_png(HeatmapCell::mean, 4).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn refuses_images_that_are_too_large() {
+        for (columns, pixels_per_cell) in [
+            (&[0, 100_000][..], 1_000),
+            (&[i64::MIN, i64::MAX][..], 1),
+            (&[0][..], u32::MAX),
+        ] {
+            let err = heatmap(columns)
+                .to_png(HeatmapCell::mean, pixels_per_cell)
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+}
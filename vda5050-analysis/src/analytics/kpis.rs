@@ -0,0 +1,412 @@
+//! Fleet key performance indicators over a time window.
+//!
+//! Every state is taken to hold until the next state of the same AGV, but at most for the maximum
+//! state interval, so a silent AGV does not count as driving or idle. Only the part of an interval
+//! that lies in the window is counted.
+use crate::recording::{AgvId, RecordedMessage, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::{ErrorLevel, OperatingMode, State};
+
+/// The time window and thresholds of the KPI computation.
+#[derive(Debug, Clone)]
+pub struct KpiConfig {
+    /// The start of the window. Defaults to the first message of the recording.
+    pub from: Option<DateTime<Utc>>,
+    /// The end of the window. Defaults to the last message of the recording.
+    pub to: Option<DateTime<Utc>>,
+    /// The longest time a single state is taken to hold.
+    pub max_state_interval: Duration,
+}
+
+impl Default for KpiConfig {
+    fn default() -> Self {
+        KpiConfig {
+            from: None,
+            to: None,
+            max_state_interval: Duration::seconds(30),
+        }
+    }
+}
+
+/// The KPIs of one AGV. All times are in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgvKpis {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The part of the window after the first message of the AGV.
+    pub period_secs: f64,
+    /// The time covered by states.
+    pub observed_secs: f64,
+    /// The time in each operating mode, by the name used in the specification.
+    pub operating_modes: BTreeMap<String, f64>,
+    /// The time spent driving.
+    pub driving_secs: f64,
+    /// The time spent paused.
+    pub paused_secs: f64,
+    /// The time with at least one fatal error.
+    pub error_secs: f64,
+    /// The time spent neither driving, paused nor with a fatal error.
+    pub idle_secs: f64,
+    /// The distance driven in [m].
+    pub distance: f64,
+    /// The number of orders completed in the window. An order is completed by the first state
+    /// without node states, edge states and actions that are not finished or failed, after a
+    /// state of the order that still had any of them.
+    pub orders_completed: usize,
+    /// The orders completed per hour of the period.
+    pub orders_per_hour: f64,
+    /// The mean time from receiving an order to completing it.
+    pub mean_order_secs: Option<f64>,
+    /// The time the connection was reported as `OFFLINE`.
+    pub offline_secs: f64,
+    /// The time the connection was reported as `CONNECTIONBROKEN`.
+    pub connection_broken_secs: f64,
+    /// The share of the period, excluding offline time, in which the AGV was connected and had no
+    /// fatal error. Range: [0.0 ... 1.0].
+    pub availability: Option<f64>,
+}
+
+/// The KPIs of all AGVs over a time window.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetKpis {
+    /// The start of the window.
+    pub from: Option<DateTime<Utc>>,
+    /// The end of the window.
+    pub to: Option<DateTime<Utc>>,
+    /// The KPIs of every AGV with at least one message before the end of the window.
+    pub agvs: Vec<AgvKpis>,
+}
+
+impl FleetKpis {
+    /// Computes the KPIs of all AGVs in the recording.
+    pub fn compute(recording: &Recording, config: &KpiConfig) -> Self {
+        let time_range = recording.time_range();
+        let (Some(from), Some(to)) = (
+            config.from.or(time_range.map(|(start, _)| start)),
+            config.to.or(time_range.map(|(_, end)| end)),
+        ) else {
+            return FleetKpis::default();
+        };
+        let mut agvs = Vec::new();
+        for agv in recording.agvs() {
+            let messages: Vec<_> = recording
+                .messages
+                .iter()
+                .filter(|recorded| recorded.agv == agv)
+                .collect();
+            let first = messages[0].received_at;
+            if first > to {
+                continue;
+            }
+            let mut kpis = AgvKpis::new(agv, secs(to - from.max(first)));
+            let window = (from.max(first), to);
+
+            let states: Vec<(DateTime<Utc>, &State)> = messages
+                .iter()
+                .filter_map(|recorded| match &recorded.message {
+                    Message::State(state) => Some((recorded.received_at, state)),
+                    _ => None,
+                })
+                .collect();
+            for (index, (time, state)) in states.iter().enumerate() {
+                let next = states.get(index + 1).map(|(next, _)| *next);
+                let end = next
+                    .unwrap_or(to)
+                    .min(*time + config.max_state_interval)
+                    .max(*time);
+                let duration = overlap((*time, end), window);
+                kpis.observed_secs += duration;
+                *kpis
+                    .operating_modes
+                    .entry(mode_name(&state.operating_mode))
+                    .or_default() += duration;
+                if state
+                    .errors
+                    .iter()
+                    .any(|error| error.error_level == ErrorLevel::Fatal)
+                {
+                    kpis.error_secs += duration;
+                } else if state.paused == Some(true) {
+                    kpis.paused_secs += duration;
+                } else if state.driving {
+                    kpis.driving_secs += duration;
+                } else {
+                    kpis.idle_secs += duration;
+                }
+                if let Some((previous_time, previous)) = index.checked_sub(1).map(|i| states[i])
+                    && previous_time >= window.0
+                    && *time <= window.1
+                {
+                    kpis.distance += distance(previous, state);
+                }
+            }
+
+            let mut connection: Option<(DateTime<Utc>, &ConnectionState)> = None;
+            for recorded in &messages {
+                if let Message::Connection(message) = &recorded.message {
+                    if let Some(previous) = connection {
+                        kpis.add_connection(previous, recorded.received_at, window);
+                    }
+                    connection = Some((recorded.received_at, &message.connection_state));
+                }
+            }
+            if let Some(last) = connection {
+                kpis.add_connection(last, to, window);
+            }
+
+            kpis.add_orders(&messages, window);
+            let available = kpis.period_secs - kpis.offline_secs;
+            if available > 0.0 {
+                kpis.availability = Some(
+                    ((available - kpis.connection_broken_secs - kpis.error_secs) / available)
+                        .clamp(0.0, 1.0),
+                );
+            }
+            agvs.push(kpis);
+        }
+        FleetKpis {
+            from: Some(from),
+            to: Some(to),
+            agvs,
+        }
+    }
+
+    /// A human readable table of the KPIs.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            let _ = writeln!(text, "Window: {} - {}", from.to_rfc3339(), to.to_rfc3339());
+        }
+        for kpis in &self.agvs {
+            let _ = writeln!(text, "\n{}", kpis.agv);
+            let _ = writeln!(
+                text,
+                "  driving {}  idle {}  paused {}  error {}",
+                hours(kpis.driving_secs),
+                hours(kpis.idle_secs),
+                hours(kpis.paused_secs),
+                hours(kpis.error_secs)
+            );
+            let modes: Vec<String> = kpis
+                .operating_modes
+                .iter()
+                .map(|(mode, secs)| format!("{mode} {}", hours(*secs)))
+                .collect();
+            let _ = writeln!(text, "  modes   {}", modes.join("  "));
+            let _ = writeln!(text, "  distance {:.1} m", kpis.distance);
+            let _ = writeln!(
+                text,
+                "  orders  {} completed, {:.1}/h, mean {}",
+                kpis.orders_completed,
+                kpis.orders_per_hour,
+                kpis.mean_order_secs.map(hours).unwrap_or("-".to_string())
+            );
+            let _ = writeln!(
+                text,
+                "  availability {}  (offline {}, connection broken {})",
+                kpis.availability
+                    .map(|availability| format!("{:.1} %", availability * 100.0))
+                    .unwrap_or("-".to_string()),
+                hours(kpis.offline_secs),
+                hours(kpis.connection_broken_secs)
+            );
+        }
+        text
+    }
+}
+
+impl AgvKpis {
+    fn new(agv: AgvId, period_secs: f64) -> Self {
+        AgvKpis {
+            agv,
+            period_secs,
+            observed_secs: 0.0,
+            operating_modes: BTreeMap::new(),
+            driving_secs: 0.0,
+            paused_secs: 0.0,
+            error_secs: 0.0,
+            idle_secs: 0.0,
+            distance: 0.0,
+            orders_completed: 0,
+            orders_per_hour: 0.0,
+            mean_order_secs: None,
+            offline_secs: 0.0,
+            connection_broken_secs: 0.0,
+            availability: None,
+        }
+    }
+
+    fn add_connection(
+        &mut self,
+        (since, state): (DateTime<Utc>, &ConnectionState),
+        until: DateTime<Utc>,
+        window: (DateTime<Utc>, DateTime<Utc>),
+    ) {
+        let duration = overlap((since, until), window);
+        match state {
+            ConnectionState::Offline => self.offline_secs += duration,
+            ConnectionState::ConnectionBroken => self.connection_broken_secs += duration,
+            _ => {}
+        }
+    }
+
+    /// Counts the orders completed in the window. An order is completed with the first state
+    /// that reports it without any node or edge states left and with every action state final,
+    /// after an earlier state of it still reported work to do; its duration starts with the first
+    /// order message or state that mentions it.
+    fn add_orders(
+        &mut self,
+        messages: &[&RecordedMessage],
+        (from, to): (DateTime<Utc>, DateTime<Utc>),
+    ) {
+        let mut started: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        // Orders with a state that reported work to do, only those can be completed.
+        let mut in_progress: HashSet<&str> = HashSet::new();
+        let mut completed: HashSet<&str> = HashSet::new();
+        let mut durations = Vec::new();
+        for recorded in messages {
+            let time = recorded.received_at;
+            match &recorded.message {
+                Message::Order(order) => {
+                    started.entry(order.order_id.as_str()).or_insert(time);
+                }
+                Message::State(state) => {
+                    let Some(order_id) = state.order_id.as_deref().filter(|id| !id.is_empty())
+                    else {
+                        continue;
+                    };
+                    let start = *started.entry(order_id).or_insert(time);
+                    let pending = !state.node_states.is_empty()
+                        || !state.edge_states.is_empty()
+                        || state
+                            .action_states
+                            .iter()
+                            .any(|action| !action.action_status.is_final());
+                    if pending {
+                        in_progress.insert(order_id);
+                    } else if in_progress.contains(order_id)
+                        && completed.insert(order_id)
+                        && time >= from
+                        && time <= to
+                    {
+                        durations.push(secs(time - start));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.orders_completed = durations.len();
+        if self.period_secs > 0.0 {
+            self.orders_per_hour = durations.len() as f64 / self.period_secs * 3600.0;
+        }
+        if !durations.is_empty() {
+            self.mean_order_secs = Some(durations.iter().sum::<f64>() / durations.len() as f64);
+        }
+    }
+}
+
+/// The distance between two states, from the positions if both have one on the same map, or
+/// from the distance since the last node otherwise.
+fn distance(previous: &State, state: &State) -> f64 {
+    if let (Some(from), Some(to)) = (&previous.agv_position, &state.agv_position)
+        && from.map_id == to.map_id
+    {
+        return (to.x - from.x).hypot(to.y - from.y);
+    }
+    match (
+        previous.distance_since_last_node,
+        state.distance_since_last_node,
+    ) {
+        (Some(from), Some(to)) if previous.last_node_sequence_id == state.last_node_sequence_id => {
+            (to - from).max(0.0)
+        }
+        (_, Some(to)) => to,
+        _ => 0.0,
+    }
+}
+
+fn mode_name(mode: &OperatingMode) -> String {
+    match mode {
+        OperatingMode::Automatic => "AUTOMATIC",
+        OperatingMode::Semiautomatic => "SEMIAUTOMATIC",
+        OperatingMode::Manual => "MANUAL",
+        OperatingMode::Service => "SERVICE",
+        OperatingMode::Teachin => "TEACHIN",
+        OperatingMode::Unknown(mode) => mode,
+    }
+    .to_string()
+}
+
+fn overlap(
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+) -> f64 {
+    secs(end.min(to) - start.max(from)).max(0.0)
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.0
+}
+
+fn hours(secs: f64) -> String {
+    let secs = secs.round() as i64;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, push};
+    use serde_json::{Value, json};
+
+    fn state(order_id: &str, nodes: usize, action_status: &str) -> Value {
+        let node = json!({"nodeId": "n1", "sequenceId": 0, "released": true, "actions": []});
+        fixtures::state(json!({
+            "orderId": order_id,
+            "nodeStates": vec![node; nodes],
+            "actionStates": [{"actionId": "pick", "actionStatus": action_status}],
+        }))
+    }
+
+    fn orders_completed(states: &[Value]) -> usize {
+        let mut recording = Recording::default();
+        for (index, state) in states.iter().enumerate() {
+            push(&mut recording, "state", index as f64, 0.0, state.clone());
+        }
+        FleetKpis::compute(&recording, &KpiConfig::default()).agvs[0].orders_completed
+    }
+
+    #[test]
+    fn completes_orders_once_all_work_is_done() {
+        assert_eq!(
+            orders_completed(&[
+                state("o1", 2, "WAITING"),
+                // Driven to the end, but the action is still running.
+                state("o1", 0, "RUNNING"),
+                state("o1", 0, "FINISHED"),
+                state("o1", 0, "FINISHED"),
+            ]),
+            1
+        );
+    }
+
+    #[test]
+    fn does_not_complete_orders_without_work_seen() {
+        // An AGV that was idle with the order of before the recording started.
+        assert_eq!(
+            orders_completed(&[state("o1", 0, "FINISHED"), state("o1", 0, "FAILED")]),
+            0
+        );
+        assert_eq!(
+            orders_completed(&[state("o1", 1, "RUNNING"), state("o1", 0, "PAUSED")]),
+            0
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! The time an AGV takes to reflect an order or instant actions in its state.
+//!
+//! An order is acknowledged by the first state that reports its order ID with at least its order
+//! update ID, instant actions by the first state that lists all of their actions in the action
+//! states. Latencies are measured twice: between the receive times at the capture host, which
+//! share one clock, and between the header timestamps, which come from the clocks of the master
+//! control and the AGV. A large difference between the two points to a clock that is off, and an
+//! AGV whose clock drifts against the capture host, next to the broker, is flagged.
+use super::Distribution;
+use super::clock::{ClockModel, Sender};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::State;
+
+/// Thresholds used by the latency analysis.
+#[derive(Debug, Clone)]
+pub struct LatencyConfig {
+    /// How long to wait for the acknowledgement before a message counts as unacknowledged.
+    pub timeout: Duration,
+    /// AGVs whose clock gains or loses more than this many parts per million against the capture
+    /// host are flagged.
+    pub max_clock_drift_ppm: f64,
+    /// The drift is only judged for AGVs that sent messages over at least this long, shorter
+    /// recordings do not tell drift from jitter.
+    pub min_drift_span: Duration,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        LatencyConfig {
+            timeout: Duration::seconds(30),
+            max_clock_drift_ppm: 100.0,
+            min_drift_span: Duration::minutes(5),
+        }
+    }
+}
+
+/// What was sent to the AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum DispatchKind {
+    /// An order with order update ID 0.
+    Order,
+    /// An update of an order.
+    OrderUpdate,
+    /// Instant actions.
+    InstantActions,
+}
+
+impl fmt::Display for DispatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DispatchKind::Order => "order",
+            DispatchKind::OrderUpdate => "orderUpdate",
+            DispatchKind::InstantActions => "instantActions",
+        })
+    }
+}
+
+/// A message sent to an AGV and the state that acknowledged it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Acknowledgement {
+    /// The AGV the message was sent to.
+    pub agv: AgvId,
+    /// What was sent.
+    pub kind: DispatchKind,
+    /// The order ID and update ID, or the action IDs of the instant actions.
+    pub reference: String,
+    /// The receive time of the sent message.
+    pub sent_at: DateTime<Utc>,
+    /// The receive time of the acknowledging state, `None` if there was none within the timeout.
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    /// The latency between the receive times, in milliseconds.
+    pub received_latency_ms: Option<f64>,
+    /// The latency between the header timestamps, in milliseconds.
+    pub header_latency_ms: Option<f64>,
+}
+
+/// The latencies of one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgvLatency {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The latencies between the receive times, in milliseconds.
+    pub received: Option<Distribution>,
+    /// The latencies between the header timestamps, in milliseconds.
+    pub header: Option<Distribution>,
+    /// The number of messages without acknowledgement.
+    pub unacknowledged: usize,
+    /// The median difference between the header timestamps of the messages sent by the AGV and
+    /// their receive times, in milliseconds. Positive if the clock of the AGV is ahead.
+    pub clock_offset_ms: Option<f64>,
+    /// How much the clock of the AGV gains per second against the capture host, in parts per
+    /// million, from the clock model.
+    pub clock_drift_ppm: Option<f64>,
+    /// Whether the clock drifts faster than the configured maximum.
+    pub clock_flagged: bool,
+}
+
+/// The acknowledgement latencies of all AGVs in a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyAnalysis {
+    /// Every order, order update and instant actions message sent, in the order they were sent.
+    pub acknowledgements: Vec<Acknowledgement>,
+    /// The statistics of every AGV that was sent a message.
+    pub agvs: Vec<AgvLatency>,
+}
+
+/// What a state has to report to acknowledge a sent message.
+enum Expected {
+    Order {
+        order_id: String,
+        order_update_id: u32,
+    },
+    Actions(Vec<String>),
+}
+
+impl Expected {
+    fn is_acknowledged_by(&self, state: &State) -> bool {
+        match self {
+            Expected::Order {
+                order_id,
+                order_update_id,
+            } => {
+                state.order_id.as_ref() == Some(order_id)
+                    && state
+                        .order_update_id
+                        .is_some_and(|update_id| update_id >= *order_update_id)
+            }
+            Expected::Actions(action_ids) => action_ids.iter().all(|action_id| {
+                state
+                    .action_states
+                    .iter()
+                    .any(|action_state| &action_state.action_id == action_id)
+            }),
+        }
+    }
+}
+
+struct Pending {
+    index: usize,
+    sent_timestamp: Option<DateTime<Utc>>,
+    expected: Expected,
+}
+
+impl LatencyAnalysis {
+    /// Pairs every order and instant actions message with the state that acknowledged it.
+    pub fn analyze(recording: &Recording, config: &LatencyConfig) -> Self {
+        let mut acknowledgements: Vec<Acknowledgement> = Vec::new();
+        let mut pending: BTreeMap<&AgvId, Vec<Pending>> = BTreeMap::new();
+        let mut offsets: BTreeMap<&AgvId, Vec<f64>> = BTreeMap::new();
+        // The receive times of the first and the last message sent by every AGV.
+        let mut spans: BTreeMap<&AgvId, (DateTime<Utc>, DateTime<Utc>)> = BTreeMap::new();
+
+        for recorded in &recording.messages {
+            let agv = &recorded.agv;
+            let time = recorded.received_at;
+            let header = recorded.message.header();
+            let sent_to_agv = matches!(
+                recorded.message,
+                Message::Order(_) | Message::InstantActions(_)
+            );
+            if !sent_to_agv && let Some(offset) = offset_ms(header.timestamp_utc(), time) {
+                offsets.entry(agv).or_default().push(offset);
+                spans.entry(agv).or_insert((time, time)).1 = time;
+            }
+            let (kind, reference, expected) = match &recorded.message {
+                Message::Order(order) => {
+                    let kind = if order.order_update_id == 0 {
+                        DispatchKind::Order
+                    } else {
+                        DispatchKind::OrderUpdate
+                    };
+                    (
+                        kind,
+                        format!("{} ({})", order.order_id, order.order_update_id),
+                        Expected::Order {
+                            order_id: order.order_id.clone(),
+                            order_update_id: order.order_update_id,
+                        },
+                    )
+                }
+                // Nothing to acknowledge, the next state would count as an acknowledgement.
+                Message::InstantActions(instant_actions)
+                    if instant_actions.instant_actions.is_empty() =>
+                {
+                    continue;
+                }
+                Message::InstantActions(instant_actions) => {
+                    let action_ids: Vec<String> = instant_actions
+                        .instant_actions
+                        .iter()
+                        .map(|action| action.action_id.clone())
+                        .collect();
+                    (
+                        DispatchKind::InstantActions,
+                        action_ids.join(", "),
+                        Expected::Actions(action_ids),
+                    )
+                }
+                Message::State(state) => {
+                    let state_timestamp = header.timestamp_utc();
+                    pending.entry(agv).or_default().retain(|waiting| {
+                        let acknowledgement = &mut acknowledgements[waiting.index];
+                        if time - acknowledgement.sent_at > config.timeout {
+                            return false;
+                        }
+                        if !waiting.expected.is_acknowledged_by(state) {
+                            return true;
+                        }
+                        acknowledgement.acknowledged_at = Some(time);
+                        acknowledgement.received_latency_ms =
+                            offset_ms(Some(time), acknowledgement.sent_at);
+                        acknowledgement.header_latency_ms = state_timestamp.and_then(|timestamp| {
+                            offset_ms(Some(timestamp), waiting.sent_timestamp?)
+                        });
+                        false
+                    });
+                    continue;
+                }
+                _ => continue,
+            };
+            pending.entry(agv).or_default().push(Pending {
+                index: acknowledgements.len(),
+                sent_timestamp: header.timestamp_utc(),
+                expected,
+            });
+            acknowledgements.push(Acknowledgement {
+                agv: agv.clone(),
+                kind,
+                reference,
+                sent_at: time,
+                acknowledged_at: None,
+                received_latency_ms: None,
+                header_latency_ms: None,
+            });
+        }
+
+        let mut by_agv: BTreeMap<&AgvId, Vec<&Acknowledgement>> = BTreeMap::new();
+        for acknowledgement in &acknowledgements {
+            by_agv
+                .entry(&acknowledgement.agv)
+                .or_default()
+                .push(acknowledgement);
+        }
+        let clocks = ClockModel::estimate(recording);
+        let agvs = by_agv
+            .into_iter()
+            .map(|(agv, sent)| {
+                let clock_offset_ms = offsets
+                    .remove(agv)
+                    .and_then(Distribution::of)
+                    .map(|offsets| offsets.p50);
+                let clock_drift_ppm = clocks
+                    .get(&Sender::Agv(agv.clone()))
+                    .map(|estimate| estimate.drift_ppm);
+                let long_enough = spans
+                    .get(agv)
+                    .is_some_and(|(first, last)| *last - *first >= config.min_drift_span);
+                AgvLatency {
+                    agv: agv.clone(),
+                    received: Distribution::of(
+                        sent.iter()
+                            .filter_map(|acknowledgement| acknowledgement.received_latency_ms)
+                            .collect(),
+                    ),
+                    header: Distribution::of(
+                        sent.iter()
+                            .filter_map(|acknowledgement| acknowledgement.header_latency_ms)
+                            .collect(),
+                    ),
+                    unacknowledged: sent
+                        .iter()
+                        .filter(|acknowledgement| acknowledgement.acknowledged_at.is_none())
+                        .count(),
+                    clock_offset_ms,
+                    clock_drift_ppm,
+                    clock_flagged: long_enough
+                        && clock_drift_ppm
+                            .is_some_and(|drift| drift.abs() > config.max_clock_drift_ppm),
+                }
+            })
+            .collect();
+        LatencyAnalysis {
+            acknowledgements,
+            agvs,
+        }
+    }
+}
+
+impl LatencyAnalysis {
+    /// A human readable table of the latencies per AGV.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for agv in &self.agvs {
+            let _ = writeln!(text, "{}", agv.agv);
+            for (name, distribution) in [("received", &agv.received), ("header", &agv.header)] {
+                if let Some(distribution) = distribution {
+                    let _ = writeln!(
+                        text,
+                        "  {name:<8} n={} min {:.0} ms  p50 {:.0} ms  p95 {:.0} ms  max {:.0} ms",
+                        distribution.count,
+                        distribution.min,
+                        distribution.p50,
+                        distribution.p95,
+                        distribution.max
+                    );
+                }
+            }
+            if agv.unacknowledged > 0 {
+                let _ = writeln!(text, "  {} message(s) not acknowledged", agv.unacknowledged);
+            }
+            if let Some(offset) = agv.clock_offset_ms {
+                let drift = agv
+                    .clock_drift_ppm
+                    .map(|drift| format!(", drift {drift:.1} ppm"))
+                    .unwrap_or_default();
+                let flag = if agv.clock_flagged {
+                    "  CLOCK DRIFTS"
+                } else {
+                    ""
+                };
+                let _ = writeln!(text, "  clock offset {offset:.0} ms{drift}{flag}");
+            }
+        }
+        text
+    }
+}
+
+fn offset_ms(time: Option<DateTime<Utc>>, reference: DateTime<Utc>) -> Option<f64> {
+    Some((time? - reference).num_microseconds()? as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, push};
+    use serde_json::json;
+
+    /// An AGV that reports its state every 10 seconds for 10 minutes with a clock that gains the
+    /// given milliseconds per second, and is sent instant actions without and with an action.
+    fn recording(drift_ms_per_sec: f64) -> Recording {
+        let mut recording = Recording::default();
+        let action = json!({"actionId": "a1", "actionType": "startPause", "blockingType": "HARD"});
+        for actions in [json!([]), json!([action])] {
+            let instant_actions = json!({ "instantActions": actions });
+            push(&mut recording, "instantActions", 0.0, 0.0, instant_actions);
+        }
+        for secs in (1..=600).step_by(10) {
+            let stamped = secs as f64 * (1.0 + drift_ms_per_sec / 1000.0);
+            let state = fixtures::state(json!({
+                "actionStates": [{"actionId": "a1", "actionStatus": "FINISHED"}],
+            }));
+            push(&mut recording, "state", secs as f64, stamped, state);
+        }
+        recording
+    }
+
+    #[test]
+    fn flags_clocks_that_drift_against_the_capture_host() {
+        let latency = LatencyAnalysis::analyze(&recording(1.0), &LatencyConfig::default());
+        let agv = &latency.agvs[0];
+        assert!((agv.clock_drift_ppm.unwrap() - 1000.0).abs() < 1.0);
+        assert!(agv.clock_flagged);
+    }
+
+    #[test]
+    fn does_not_flag_a_constant_offset() {
+        let mut recording = recording(0.0);
+        for recorded in &mut recording.messages {
+            recorded.received_at -= Duration::seconds(2);
+        }
+        let latency = LatencyAnalysis::analyze(&recording, &LatencyConfig::default());
+        assert!(latency.agvs.iter().all(|agv| !agv.clock_flagged));
+    }
+
+    #[test]
+    fn skips_instant_actions_without_actions() {
+        let latency = LatencyAnalysis::analyze(&recording(0.0), &LatencyConfig::default());
+        let references: Vec<_> = latency
+            .acknowledgements
+            .iter()
+            .map(|acknowledgement| acknowledgement.reference.as_str())
+            .collect();
+        assert_eq!(references, ["a1"]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Analyses that turn a recording into statistics and events, as opposed to the checks, which
+//! look for problems in the communication.
+use serde::{Deserialize, Serialize};
+use vda5050_data_types::common::AgvPosition;
+
+pub mod battery;
+pub mod clock;
+pub mod errors;
+pub mod heatmap;
+pub mod kpis;
+pub mod latency;
+pub mod order_responses;
+pub mod safety;
+pub mod spatial;
+pub mod traffic;
+
+/// Where an AGV was when something happened.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    /// Identifier of the map.
+    pub map_id: String,
+    /// X-coordinate in the world coordinate system. In [m].
+    pub x: f64,
+    /// Y-coordinate in the world coordinate system. In [m].
+    pub y: f64,
+}
+
+impl Location {
+    /// The location of a reported position.
+    pub fn of(position: &AgvPosition) -> Self {
+        Location {
+            map_id: position.map_id.clone(),
+            x: position.x,
+            y: position.y,
+        }
+    }
+}
+
+/// Summary statistics of a set of values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Distribution {
+    /// The number of values.
+    pub count: usize,
+    /// The smallest value.
+    pub min: f64,
+    /// The mean of all values.
+    pub mean: f64,
+    /// The median.
+    pub p50: f64,
+    /// The 95th percentile.
+    pub p95: f64,
+    /// The largest value.
+    pub max: f64,
+}
+
+impl Distribution {
+    /// The distribution of the values, or `None` if there are none.
+    pub fn of(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+        Some(Distribution {
+            count: values.len(),
+            min: values[0],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            max: values[values.len() - 1],
+        })
+    }
+}
@@ -0,0 +1,648 @@
+//! How AGVs respond to the orders they are sent: whether they accept or reject them, and whether
+//! rejections are reported the way the specification asks for.
+//!
+//! An order is accepted by the first state that reports its order ID with at least its order
+//! update ID, and rejected by the first state that raises a `validationError`, `noRouteError` or
+//! `orderUpdateError`, or any other error referencing the order. What the AGV should have done is
+//! derived from the order and the last state before it: an invalid order has to be rejected with
+//! a `validationError`, an update that is outdated or does not continue the base, or a new order
+//! while the current one is active, with an `orderUpdateError`, and a new order that starts away
+//! from the AGV with a `noRouteError`. Rejections have to reference the order with an `orderId`
+//! error reference.
+use crate::checks::{Severity, validate_order};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Write};
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::Order;
+use vda5050_data_types::state::{Error, State};
+
+/// The error types the specification defines for rejected orders.
+pub const REJECTION_ERRORS: [&str; 3] = ["validationError", "noRouteError", "orderUpdateError"];
+
+/// Thresholds used by the order response analysis.
+#[derive(Debug, Clone)]
+pub struct OrderResponseConfig {
+    /// How long to wait for the response before an order counts as unanswered.
+    pub timeout: Duration,
+    /// How far the first node of a new order may be from the AGV, in meters, if the node does
+    /// not give an allowed deviation.
+    pub max_start_distance: f64,
+}
+
+impl Default for OrderResponseConfig {
+    fn default() -> Self {
+        OrderResponseConfig {
+            timeout: Duration::seconds(30),
+            max_start_distance: 1.0,
+        }
+    }
+}
+
+/// What the AGV did with an order.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderOutcome {
+    /// A state reported the order.
+    Accepted,
+    /// A state reported an error rejecting the order.
+    Rejected,
+    /// Neither happened within the timeout.
+    Unanswered,
+}
+
+impl fmt::Display for OrderOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OrderOutcome::Accepted => "accepted",
+            OrderOutcome::Rejected => "rejected",
+            OrderOutcome::Unanswered => "unanswered",
+        })
+    }
+}
+
+/// An error the AGV raised in response to an order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportedError {
+    /// The error type.
+    pub error_type: String,
+    /// The description of the error.
+    pub description: Option<String>,
+    /// The error references as key and value.
+    pub references: Vec<(String, String)>,
+}
+
+/// An order sent to an AGV and the response of the AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponse {
+    /// The AGV the order was sent to.
+    pub agv: AgvId,
+    /// The order ID.
+    pub order_id: String,
+    /// The order update ID.
+    pub order_update_id: u32,
+    /// The receive time of the order.
+    pub sent_at: DateTime<Utc>,
+    /// The error type the order should have been rejected with, `None` if it should have been
+    /// accepted.
+    pub expected_error: Option<String>,
+    /// Why the order should have been rejected.
+    pub expected_reason: Option<String>,
+    /// What the AGV did.
+    pub outcome: OrderOutcome,
+    /// The receive time of the state that accepted or rejected the order.
+    pub responded_at: Option<DateTime<Utc>>,
+    /// The time between the order and the response, in milliseconds.
+    pub response_ms: Option<f64>,
+    /// The errors that rejected the order.
+    pub errors: Vec<ReportedError>,
+    /// Where the response deviates from the specification.
+    pub problems: Vec<String>,
+}
+
+/// The responses of one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgvOrderResponses {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The number of accepted orders.
+    pub accepted: usize,
+    /// The number of rejected orders.
+    pub rejected: usize,
+    /// The number of unanswered orders.
+    pub unanswered: usize,
+    /// The number of responses with problems.
+    pub with_problems: usize,
+}
+
+/// The responses to all orders in a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponseAnalysis {
+    /// Every order sent, in the order they were sent.
+    pub responses: Vec<OrderResponse>,
+    /// The counts of every AGV that was sent an order.
+    pub agvs: Vec<AgvOrderResponses>,
+}
+
+/// An error as it identifies itself across states.
+type ErrorKey = (String, Vec<(String, String)>);
+
+fn key(error: &Error) -> ErrorKey {
+    (
+        error.error_type.clone(),
+        error
+            .error_references
+            .iter()
+            .map(|reference| {
+                (
+                    reference.reference_key.clone(),
+                    reference.reference_value.clone(),
+                )
+            })
+            .collect(),
+    )
+}
+
+impl OrderResponseAnalysis {
+    /// Pairs every order with the state that accepted or rejected it.
+    pub fn analyze(recording: &Recording, config: &OrderResponseConfig) -> Self {
+        let mut responses: Vec<OrderResponse> = Vec::new();
+        let mut pending: BTreeMap<&AgvId, Vec<usize>> = BTreeMap::new();
+        let mut last_states: HashMap<&AgvId, &State> = HashMap::new();
+
+        for recorded in &recording.messages {
+            let agv = &recorded.agv;
+            let time = recorded.received_at;
+            match &recorded.message {
+                Message::Order(order) => {
+                    let expected = expected_error(order, last_states.get(agv).copied(), config);
+                    let (expected_error, expected_reason) = match expected {
+                        Some((error_type, reason)) => (Some(error_type.to_string()), Some(reason)),
+                        None => (None, None),
+                    };
+                    pending.entry(agv).or_default().push(responses.len());
+                    responses.push(OrderResponse {
+                        agv: agv.clone(),
+                        order_id: order.order_id.clone(),
+                        order_update_id: order.order_update_id,
+                        sent_at: time,
+                        expected_error,
+                        expected_reason,
+                        outcome: OrderOutcome::Unanswered,
+                        responded_at: None,
+                        response_ms: None,
+                        errors: Vec::new(),
+                        problems: Vec::new(),
+                    });
+                }
+                Message::State(state) => {
+                    let known: HashSet<ErrorKey> = last_states
+                        .get(agv)
+                        .map(|last| last.errors.iter().map(key).collect())
+                        .unwrap_or_default();
+                    // An error without reference rejects only the oldest waiting order.
+                    let mut unreferenced_taken = false;
+                    pending.entry(agv).or_default().retain(|&index| {
+                        let response = &mut responses[index];
+                        if time - response.sent_at > config.timeout {
+                            return false;
+                        }
+                        let errors = rejecting_errors(state, response, &known, !unreferenced_taken);
+                        if !errors.is_empty() {
+                            unreferenced_taken |= errors
+                                .iter()
+                                .all(|error| !references_order(error, &response.order_id));
+                            response.outcome = OrderOutcome::Rejected;
+                            response.errors = errors.iter().map(|error| reported(error)).collect();
+                        } else if state.order_id.as_ref() == Some(&response.order_id)
+                            && state
+                                .order_update_id
+                                .is_some_and(|update_id| update_id >= response.order_update_id)
+                        {
+                            response.outcome = OrderOutcome::Accepted;
+                        } else {
+                            return true;
+                        }
+                        response.responded_at = Some(time);
+                        response.response_ms = (time - response.sent_at)
+                            .num_microseconds()
+                            .map(|us| us as f64 / 1000.0);
+                        response.problems = problems(response, state);
+                        false
+                    });
+                    last_states.insert(agv, state);
+                }
+                _ => {}
+            }
+        }
+        for response in &mut responses {
+            if response.outcome == OrderOutcome::Unanswered {
+                response.problems.push(format!(
+                    "no state accepted or rejected the order within {} s",
+                    config.timeout.num_seconds()
+                ));
+            }
+        }
+
+        let mut agvs: BTreeMap<&AgvId, AgvOrderResponses> = BTreeMap::new();
+        for response in &responses {
+            let counts = agvs
+                .entry(&response.agv)
+                .or_insert_with(|| AgvOrderResponses {
+                    agv: response.agv.clone(),
+                    accepted: 0,
+                    rejected: 0,
+                    unanswered: 0,
+                    with_problems: 0,
+                });
+            match response.outcome {
+                OrderOutcome::Accepted => counts.accepted += 1,
+                OrderOutcome::Rejected => counts.rejected += 1,
+                OrderOutcome::Unanswered => counts.unanswered += 1,
+            }
+            if !response.problems.is_empty() {
+                counts.with_problems += 1;
+            }
+        }
+        let agvs = agvs.into_values().collect();
+        OrderResponseAnalysis { responses, agvs }
+    }
+
+    /// A human readable list of the responses per AGV.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for agv in &self.agvs {
+            let _ = writeln!(
+                text,
+                "{}: {} accepted, {} rejected, {} unanswered, {} with problems",
+                agv.agv, agv.accepted, agv.rejected, agv.unanswered, agv.with_problems
+            );
+            for response in self
+                .responses
+                .iter()
+                .filter(|response| response.agv == agv.agv)
+            {
+                let errors: Vec<&str> = response
+                    .errors
+                    .iter()
+                    .map(|error| error.error_type.as_str())
+                    .collect();
+                let with = if errors.is_empty() {
+                    String::new()
+                } else {
+                    format!(" with {}", errors.join(", "))
+                };
+                let after = response
+                    .response_ms
+                    .map(|ms| format!(" after {ms:.0} ms"))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    text,
+                    "  {} order {}/{}: {}{with}{after}",
+                    response.sent_at.to_rfc3339(),
+                    response.order_id,
+                    response.order_update_id,
+                    response.outcome
+                );
+                for problem in &response.problems {
+                    let _ = writeln!(text, "    {problem}");
+                }
+            }
+        }
+        text
+    }
+}
+
+/// The error the order should be rejected with and why, given the last state of the AGV.
+fn expected_error(
+    order: &Order,
+    last: Option<&State>,
+    config: &OrderResponseConfig,
+) -> Option<(&'static str, String)> {
+    if let Some((_, problem)) = validate_order(order)
+        .into_iter()
+        .find(|(severity, _)| *severity == Severity::Error)
+    {
+        return Some(("validationError", problem));
+    }
+    let last = last?;
+    let is_update = last.order_id.as_ref() == Some(&order.order_id);
+    if is_update {
+        let current = last.order_update_id.unwrap_or(0);
+        if order.order_update_id < current {
+            return Some((
+                "orderUpdateError",
+                format!(
+                    "order update {} is older than the current update {current}",
+                    order.order_update_id
+                ),
+            ));
+        }
+        if order.order_update_id == current {
+            return None;
+        }
+        // The update has to start at the last released node of the current order.
+        let stitch = last
+            .node_states
+            .iter()
+            .filter(|node| node.released)
+            .max_by_key(|node| node.sequence_id)
+            .map(|node| (node.node_id.as_str(), node.sequence_id))
+            .or(last.last_node_id.as_deref().zip(last.last_node_sequence_id));
+        let first = order.nodes.first()?;
+        if let Some((node_id, sequence_id)) = stitch
+            && (node_id != first.node_id || sequence_id != first.sequence_id)
+        {
+            return Some((
+                "orderUpdateError",
+                format!(
+                    "the update starts at {}/{}, but the base ends at {node_id}/{sequence_id}",
+                    first.node_id, first.sequence_id
+                ),
+            ));
+        }
+        return None;
+    }
+    if !last.node_states.is_empty() {
+        return Some((
+            "orderUpdateError",
+            format!(
+                "order {} is still active",
+                last.order_id.as_deref().unwrap_or_default()
+            ),
+        ));
+    }
+    let first = order.nodes.first()?;
+    let (Some(node), Some(agv)) = (&first.node_position, &last.agv_position) else {
+        return None;
+    };
+    if node.map_id != agv.map_id {
+        return Some((
+            "noRouteError",
+            format!(
+                "the first node {} is on map {}, the AGV on map {}",
+                first.node_id, node.map_id, agv.map_id
+            ),
+        ));
+    }
+    let allowed = node
+        .allowed_deviation_xy
+        .unwrap_or(config.max_start_distance)
+        .max(agv.deviation_range.unwrap_or(0.0));
+    let distance = (node.x - agv.x).hypot(node.y - agv.y);
+    if distance > allowed {
+        return Some((
+            "noRouteError",
+            format!(
+                "the first node {} is {distance:.2} m away from the AGV, allowed are {allowed:.2} m",
+                first.node_id
+            ),
+        ));
+    }
+    None
+}
+
+/// The errors of the state that reject the waiting order: errors of the rejection types or new
+/// errors that reference the order, and new rejection errors without an order reference.
+fn rejecting_errors<'a>(
+    state: &'a State,
+    response: &OrderResponse,
+    known: &HashSet<ErrorKey>,
+    take_unreferenced: bool,
+) -> Vec<&'a Error> {
+    state
+        .errors
+        .iter()
+        .filter(|error| {
+            let is_rejection = REJECTION_ERRORS.contains(&error.error_type.as_str());
+            let is_new = !known.contains(&key(error));
+            let reference = |reference_key: &str| {
+                error
+                    .error_references
+                    .iter()
+                    .find(|reference| reference.reference_key == reference_key)
+                    .map(|reference| reference.reference_value.as_str())
+            };
+            match reference("orderId") {
+                Some(order_id) => {
+                    let same_update = reference("orderUpdateId")
+                        .is_none_or(|update_id| update_id == response.order_update_id.to_string());
+                    order_id == response.order_id && same_update && (is_rejection || is_new)
+                }
+                None => is_rejection && is_new && take_unreferenced,
+            }
+        })
+        .collect()
+}
+
+fn references_order(error: &Error, order_id: &str) -> bool {
+    error.error_references.iter().any(|reference| {
+        reference.reference_key == "orderId" && reference.reference_value == order_id
+    })
+}
+
+fn reported(error: &Error) -> ReportedError {
+    let (error_type, references) = key(error);
+    ReportedError {
+        error_type,
+        description: error.error_description.clone(),
+        references,
+    }
+}
+
+/// Where the response deviates from what the order should have caused.
+fn problems(response: &OrderResponse, state: &State) -> Vec<String> {
+    let mut problems = Vec::new();
+    match (response.outcome, &response.expected_error) {
+        (OrderOutcome::Accepted, Some(expected)) => problems.push(format!(
+            "accepted, but should have been rejected with {expected}: {}",
+            response.expected_reason.as_deref().unwrap_or_default()
+        )),
+        (OrderOutcome::Rejected, expected) => {
+            let types: Vec<&str> = response
+                .errors
+                .iter()
+                .map(|error| error.error_type.as_str())
+                .collect();
+            match expected {
+                Some(expected) if !types.contains(&expected.as_str()) => problems.push(format!(
+                    "rejected with {}, expected {expected}: {}",
+                    types.join(", "),
+                    response.expected_reason.as_deref().unwrap_or_default()
+                )),
+                None if !types
+                    .iter()
+                    .any(|error_type| REJECTION_ERRORS.contains(error_type)) =>
+                {
+                    problems.push(format!(
+                        "rejected with {}, which is none of {}",
+                        types.join(", "),
+                        REJECTION_ERRORS.join(", ")
+                    ))
+                }
+                _ => {}
+            }
+            let referenced = response.errors.iter().any(|error| {
+                error
+                    .references
+                    .iter()
+                    .any(|(key, value)| key == "orderId" && *value == response.order_id)
+            });
+            if !referenced {
+                problems.push(format!(
+                    "no error references the order with orderId {}",
+                    response.order_id
+                ));
+            }
+            if state.order_id.as_ref() == Some(&response.order_id)
+                && state.order_update_id == Some(response.order_update_id)
+                && response.expected_error.is_some()
+            {
+                problems.push("the state reports the rejected order as current".to_string());
+            }
+        }
+        _ => {}
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, push};
+    use serde_json::{Value, json};
+
+    /// An order from (0, 0) to (1, 0) on map `m`.
+    fn order(order_id: &str, order_update_id: u32, first_sequence_id: u32) -> Value {
+        let node = |index: u32| {
+            json!({
+                "nodeId": format!("n{index}"),
+                "sequenceId": first_sequence_id + 2 * index,
+                "released": true,
+                "nodePosition": {"x": f64::from(index), "y": 0.0, "mapId": "m"},
+                "actions": [],
+            })
+        };
+        json!({
+            "orderId": order_id,
+            "orderUpdateId": order_update_id,
+            "nodes": [node(0), node(1)],
+            "edges": [{
+                "edgeId": "e0",
+                "sequenceId": first_sequence_id + 1,
+                "startNodeId": "n0",
+                "endNodeId": "n1",
+                "released": true,
+                "actions": [],
+            }],
+        })
+    }
+
+    fn error(error_type: &str, references: &[(&str, &str)]) -> Value {
+        let references: Vec<Value> = references
+            .iter()
+            .map(|(key, value)| json!({"referenceKey": key, "referenceValue": value}))
+            .collect();
+        json!({"errorType": error_type, "errorLevel": "WARNING", "errorReferences": references})
+    }
+
+    fn responses(recording: &Recording) -> Vec<OrderResponse> {
+        OrderResponseAnalysis::analyze(recording, &OrderResponseConfig::default()).responses
+    }
+
+    #[test]
+    fn accepts_an_order_reported_by_a_state() {
+        let mut recording = Recording::default();
+        push(&mut recording, "order", 0.0, 0.0, order("o1", 0, 0));
+        let state = fixtures::state(json!({"orderId": "o1", "orderUpdateId": 0}));
+        push(&mut recording, "state", 1.0, 1.0, state);
+
+        let responses = responses(&recording);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].outcome, OrderOutcome::Accepted);
+        assert_eq!(responses[0].responded_at, Some(fixtures::at(1.0)));
+        assert_eq!(responses[0].response_ms, Some(1000.0));
+        assert_eq!(responses[0].problems, Vec::<String>::new());
+    }
+
+    #[test]
+    fn expects_the_rejection_error_of_the_specification() {
+        let invalid = (None, order("o1", 0, 1), "validationError");
+        let away = fixtures::state(json!({
+            "agvPosition": {"x": 10.0, "y": 0.0, "mapId": "m", "positionInitialized": true},
+        }));
+        let no_route = (Some(away), order("o1", 0, 0), "noRouteError");
+        let current = fixtures::state(json!({"orderId": "o1", "orderUpdateId": 2}));
+        let outdated = (Some(current), order("o1", 1, 0), "orderUpdateError");
+
+        for (last, order, error_type) in [invalid, no_route, outdated] {
+            let mut recording = Recording::default();
+            if let Some(last) = &last {
+                push(&mut recording, "state", 0.0, 0.0, last.clone());
+            }
+            push(&mut recording, "order", 1.0, 1.0, order);
+            let rejecting = fixtures::merge(
+                last.unwrap_or_else(|| fixtures::state(json!({}))),
+                json!({"errors": [error(error_type, &[("orderId", "o1")])]}),
+            );
+            push(&mut recording, "state", 2.0, 2.0, rejecting);
+
+            let responses = responses(&recording);
+            assert_eq!(responses.len(), 1, "{error_type}");
+            let response = &responses[0];
+            assert_eq!(response.expected_error.as_deref(), Some(error_type));
+            assert_eq!(response.outcome, OrderOutcome::Rejected, "{error_type}");
+            assert_eq!(response.errors[0].error_type, error_type);
+            assert_eq!(response.problems, Vec::<String>::new(), "{error_type}");
+        }
+    }
+
+    #[test]
+    fn reports_a_rejection_without_order_reference() {
+        let mut recording = Recording::default();
+        push(&mut recording, "order", 0.0, 0.0, order("o1", 0, 1));
+        let state = fixtures::state(json!({"errors": [error("validationError", &[])]}));
+        push(&mut recording, "state", 1.0, 1.0, state);
+
+        let responses = responses(&recording);
+        assert_eq!(responses[0].outcome, OrderOutcome::Rejected);
+        assert_eq!(
+            responses[0].problems,
+            ["no error references the order with orderId o1"]
+        );
+    }
+
+    #[test]
+    fn leaves_an_order_unanswered_after_the_timeout() {
+        let mut recording = Recording::default();
+        push(&mut recording, "order", 0.0, 0.0, order("o1", 0, 0));
+        // The order is reported, but too late.
+        let state = fixtures::state(json!({"orderId": "o1", "orderUpdateId": 0}));
+        push(&mut recording, "state", 31.0, 31.0, state);
+
+        let analysis = OrderResponseAnalysis::analyze(&recording, &OrderResponseConfig::default());
+        let response = &analysis.responses[0];
+        assert_eq!(response.outcome, OrderOutcome::Unanswered);
+        assert_eq!(response.responded_at, None);
+        assert_eq!(
+            response.problems,
+            ["no state accepted or rejected the order within 30 s"]
+        );
+        assert_eq!(analysis.agvs[0].unanswered, 1);
+    }
+
+    #[test]
+    fn takes_an_unreferenced_error_for_the_oldest_waiting_order_only() {
+        let mut recording = Recording::default();
+        push(&mut recording, "order", 0.0, 0.0, order("o1", 0, 0));
+        push(&mut recording, "order", 1.0, 1.0, order("o2", 0, 0));
+        let error = error("validationError", &[]);
+        let rejecting = fixtures::state(json!({"errors": [error]}));
+        push(&mut recording, "state", 2.0, 2.0, rejecting);
+        let accepting = fixtures::state(json!({
+            "orderId": "o2",
+            "orderUpdateId": 0,
+            "errors": [error],
+        }));
+        push(&mut recording, "state", 3.0, 3.0, accepting);
+
+        let responses = responses(&recording);
+        let outcomes: Vec<(&str, OrderOutcome)> = responses
+            .iter()
+            .map(|response| (response.order_id.as_str(), response.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("o1", OrderOutcome::Rejected),
+                ("o2", OrderOutcome::Accepted)
+            ]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Emergency stops and protective field violations derived from the safety state of the AGVs.
+use super::Location;
+use super::heatmap::{Heatmap, HeatmapBuilder};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::EStop;
+
+/// Settings of the safety analysis.
+#[derive(Debug, Clone)]
+pub struct SafetyConfig {
+    /// The edge length of a heatmap cell in [m].
+    pub cell_size: f64,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        SafetyConfig { cell_size: 1.0 }
+    }
+}
+
+/// What kind of safety event happened.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum SafetyEventKind {
+    /// An emergency stop was active.
+    EmergencyStop,
+    /// A protective field was violated.
+    FieldViolation,
+}
+
+impl fmt::Display for SafetyEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SafetyEventKind::EmergencyStop => "emergency stop",
+            SafetyEventKind::FieldViolation => "field violation",
+        })
+    }
+}
+
+/// A period in which an emergency stop was active or a protective field was violated.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyEvent {
+    /// The AGV.
+    pub agv: AgvId,
+    /// What happened.
+    pub kind: SafetyEventKind,
+    /// The type of the emergency stop, e.g. `MANUAL`.
+    pub e_stop: Option<String>,
+    /// The first state that reported it.
+    pub start: DateTime<Utc>,
+    /// The first state that no longer reported it.
+    /// `None` if it was still active at the end of the recording.
+    pub end: Option<DateTime<Utc>>,
+    /// How long it was active, until the last state that reported it if it never ended, in seconds.
+    pub duration_secs: f64,
+    /// The last position reported by the AGV when it started.
+    pub location: Option<Location>,
+}
+
+/// All safety events of a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyAnalysis {
+    /// All events, ordered by their start.
+    pub events: Vec<SafetyEvent>,
+    /// Per map, the number of field violations that started in every cell, with their total
+    /// duration in seconds as value.
+    pub field_violations: Vec<Heatmap>,
+}
+
+#[derive(Default)]
+struct Active {
+    e_stop: Option<usize>,
+    field_violation: Option<usize>,
+}
+
+impl SafetyAnalysis {
+    /// Derives the safety events from the states in the recording.
+    pub fn analyze(recording: &Recording, config: &SafetyConfig) -> Self {
+        let mut events: Vec<SafetyEvent> = Vec::new();
+        let mut active: HashMap<&AgvId, Active> = HashMap::new();
+        let mut positions: HashMap<&AgvId, Location> = HashMap::new();
+
+        for recorded in &recording.messages {
+            let agv = &recorded.agv;
+            let time = recorded.received_at;
+            let state = match &recorded.message {
+                Message::State(state) => state,
+                Message::Visualization(visualization) => {
+                    if let Some(position) = &visualization.agv_position {
+                        positions.insert(agv, Location::of(position));
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(position) = &state.agv_position {
+                positions.insert(agv, Location::of(position));
+            }
+            let Some(safety_state) = &state.safety_state else {
+                continue;
+            };
+            let e_stop = match &safety_state.e_stop {
+                EStop::None => None,
+                EStop::AutoAck => Some("AUTOACK".to_string()),
+                EStop::Manual => Some("MANUAL".to_string()),
+                EStop::Remote => Some("REMOTE".to_string()),
+                EStop::Unknown(e_stop) => Some(e_stop.clone()),
+            };
+            let active = active.entry(agv).or_default();
+            let location = positions.get(agv);
+            track(
+                &mut events,
+                &mut active.e_stop,
+                e_stop.map(|e_stop| (SafetyEventKind::EmergencyStop, Some(e_stop))),
+                agv,
+                time,
+                location,
+            );
+            track(
+                &mut events,
+                &mut active.field_violation,
+                safety_state
+                    .field_violation
+                    .then_some((SafetyEventKind::FieldViolation, None)),
+                agv,
+                time,
+                location,
+            );
+        }
+
+        let mut heatmap = HeatmapBuilder::new(config.cell_size);
+        for event in &events {
+            if event.kind == SafetyEventKind::FieldViolation
+                && let Some(location) = &event.location
+            {
+                heatmap.add(location, event.duration_secs);
+            }
+        }
+        SafetyAnalysis {
+            events,
+            field_violations: heatmap.build(),
+        }
+    }
+}
+
+/// Continues, ends or starts the event in the slot, depending on what the state reports.
+fn track(
+    events: &mut Vec<SafetyEvent>,
+    slot: &mut Option<usize>,
+    reported: Option<(SafetyEventKind, Option<String>)>,
+    agv: &AgvId,
+    time: DateTime<Utc>,
+    location: Option<&Location>,
+) {
+    if let Some(index) = *slot {
+        let event = &mut events[index];
+        event.duration_secs = (time - event.start).num_milliseconds() as f64 / 1000.0;
+        if reported
+            .as_ref()
+            .is_some_and(|(kind, e_stop)| *kind == event.kind && *e_stop == event.e_stop)
+        {
+            return;
+        }
+        event.end = Some(time);
+        *slot = None;
+    }
+    if let Some((kind, e_stop)) = reported {
+        *slot = Some(events.len());
+        events.push(SafetyEvent {
+            agv: agv.clone(),
+            kind,
+            e_stop,
+            start: time,
+            end: None,
+            duration_secs: 0.0,
+            location: location.cloned(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::heatmap::HeatmapCell;
+    use crate::fixtures::{at, push, state};
+    use serde_json::json;
+
+    #[test]
+    fn tracks_emergency_stops_and_field_violations() {
+        let mut recording = Recording::default();
+        // Seconds, emergency stop, field violation, x.
+        for (secs, e_stop, field_violation, x) in [
+            (0.0, "NONE", false, 0.5),
+            (1.0, "MANUAL", true, 2.5),
+            (2.0, "REMOTE", true, 2.5),
+            (4.0, "NONE", false, 2.5),
+            (5.0, "NONE", true, 7.5),
+            (6.0, "NONE", true, 7.5),
+        ] {
+            let body = state(json!({
+                "agvPosition": {"x": x, "y": 0.5, "mapId": "hall"},
+                "safetyState": {"eStop": e_stop, "fieldViolation": field_violation},
+            }));
+            push(&mut recording, "state", secs, secs, body);
+        }
+
+        let analysis = SafetyAnalysis::analyze(&recording, &SafetyConfig::default());
+        let events: Vec<_> = analysis
+            .events
+            .iter()
+            .map(|event| {
+                (
+                    event.kind,
+                    event.e_stop.as_deref(),
+                    event.start,
+                    event.end,
+                    event.duration_secs,
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (
+                    SafetyEventKind::EmergencyStop,
+                    Some("MANUAL"),
+                    at(1.0),
+                    Some(at(2.0)),
+                    1.0
+                ),
+                (
+                    SafetyEventKind::FieldViolation,
+                    None,
+                    at(1.0),
+                    Some(at(4.0)),
+                    3.0
+                ),
+                (
+                    SafetyEventKind::EmergencyStop,
+                    Some("REMOTE"),
+                    at(2.0),
+                    Some(at(4.0)),
+                    2.0
+                ),
+                (SafetyEventKind::FieldViolation, None, at(5.0), None, 1.0),
+            ]
+        );
+        assert_eq!(analysis.events[1].location.as_ref().unwrap().x, 2.5);
+
+        assert_eq!(analysis.field_violations.len(), 1);
+        let heatmap = &analysis.field_violations[0];
+        assert_eq!(heatmap.map_id, "hall");
+        assert_eq!(
+            heatmap.cells,
+            [
+                HeatmapCell {
+                    column: 2,
+                    row: 0,
+                    count: 1,
+                    total: 3.0
+                },
+                HeatmapCell {
+                    column: 7,
+                    row: 0,
+                    count: 1,
+                    total: 1.0
+                },
+            ]
+        );
+    }
+}
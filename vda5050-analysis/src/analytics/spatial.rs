@@ -0,0 +1,276 @@
+//! Where on the maps the AGVs spend their time, how fast they drive there, and where they stop
+//! and fail.
+use super::Location;
+use super::errors::{ErrorTimeline, EventKind};
+use super::heatmap::{Heatmap, HeatmapBuilder, HeatmapCell};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use vda5050_data_types::message::Message;
+
+/// Settings of the spatial analysis.
+#[derive(Debug, Clone)]
+pub struct SpatialConfig {
+    /// The edge length of a heatmap cell in [m].
+    pub cell_size: f64,
+    /// A position counts for at most this long, so that an AGV that went offline does not seem to
+    /// stay at its last position.
+    pub max_sample_interval: Duration,
+}
+
+impl Default for SpatialConfig {
+    fn default() -> Self {
+        SpatialConfig {
+            cell_size: 1.0,
+            max_sample_interval: Duration::seconds(5),
+        }
+    }
+}
+
+/// One of the heatmaps of the spatial analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum SpatialLayer {
+    /// The time the AGVs spent in a cell.
+    Occupancy,
+    /// The mean speed reported in a cell.
+    Speed,
+    /// The number of stops in a cell.
+    Stops,
+    /// The number of errors raised in a cell.
+    Errors,
+}
+
+impl SpatialLayer {
+    /// All layers, in the order they are shown.
+    pub const ALL: [SpatialLayer; 4] = [
+        SpatialLayer::Occupancy,
+        SpatialLayer::Speed,
+        SpatialLayer::Stops,
+        SpatialLayer::Errors,
+    ];
+
+    /// The value of a cell of this layer.
+    pub fn value(&self, cell: &HeatmapCell) -> f64 {
+        match self {
+            SpatialLayer::Occupancy => cell.total,
+            SpatialLayer::Speed => cell.mean(),
+            SpatialLayer::Stops | SpatialLayer::Errors => cell.count as f64,
+        }
+    }
+
+    /// The unit of the values.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            SpatialLayer::Occupancy => "s",
+            SpatialLayer::Speed => "m/s",
+            SpatialLayer::Stops => "stops",
+            SpatialLayer::Errors => "errors",
+        }
+    }
+}
+
+impl fmt::Display for SpatialLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SpatialLayer::Occupancy => "occupancy",
+            SpatialLayer::Speed => "speed",
+            SpatialLayer::Stops => "stops",
+            SpatialLayer::Errors => "errors",
+        })
+    }
+}
+
+/// A node with a position, as sent in an order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MapNode {
+    /// Identifier of the node.
+    pub node_id: String,
+    /// Where the node is.
+    pub location: Location,
+}
+
+/// The heatmaps of a recording, each with one heatmap per map.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SpatialAnalysis {
+    /// The edge length of a cell in [m].
+    pub cell_size: f64,
+    /// One value per reported position, with the seconds until the next position of the AGV as
+    /// value.
+    pub occupancy: Vec<Heatmap>,
+    /// One value per state with a velocity, with the speed in [m/s] as value.
+    pub speed: Vec<Heatmap>,
+    /// One value per period in which an AGV reported that it was not driving, with its duration in
+    /// seconds as value.
+    pub stops: Vec<Heatmap>,
+    /// One value per error with a location, with its duration in seconds as value.
+    pub errors: Vec<Heatmap>,
+    /// All nodes with a position that were sent in orders, ordered by map and node ID.
+    pub nodes: Vec<MapNode>,
+}
+
+/// The stop an AGV is in.
+struct Stop {
+    start: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    location: Location,
+}
+
+impl SpatialAnalysis {
+    /// Collects the positions, velocities, stops and errors of the recording into heatmaps.
+    pub fn analyze(recording: &Recording, config: &SpatialConfig) -> Self {
+        let mut occupancy = HeatmapBuilder::new(config.cell_size);
+        let mut speed = HeatmapBuilder::new(config.cell_size);
+        let mut stops = HeatmapBuilder::new(config.cell_size);
+        let mut errors = HeatmapBuilder::new(config.cell_size);
+        let mut nodes: BTreeMap<(String, String), MapNode> = BTreeMap::new();
+        let mut positions: HashMap<&AgvId, (DateTime<Utc>, Location)> = HashMap::new();
+        let mut stopped: HashMap<&AgvId, Stop> = HashMap::new();
+        let max_interval = secs(config.max_sample_interval);
+
+        for recorded in &recording.messages {
+            let agv = &recorded.agv;
+            let time = recorded.received_at;
+            let position = match &recorded.message {
+                Message::State(state) => state.agv_position.as_ref(),
+                Message::Visualization(visualization) => visualization.agv_position.as_ref(),
+                Message::Order(order) => {
+                    for node in &order.nodes {
+                        if let Some(position) = &node.node_position {
+                            nodes.insert(
+                                (position.map_id.clone(), node.node_id.clone()),
+                                MapNode {
+                                    node_id: node.node_id.clone(),
+                                    location: Location {
+                                        map_id: position.map_id.clone(),
+                                        x: position.x,
+                                        y: position.y,
+                                    },
+                                },
+                            );
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(position) = position {
+                let location = Location::of(position);
+                if let Some((since, previous)) = positions.insert(agv, (time, location)) {
+                    occupancy.add(&previous, secs(time - since).min(max_interval));
+                }
+            }
+            let Message::State(state) = &recorded.message else {
+                continue;
+            };
+            let Some((_, location)) = positions.get(agv) else {
+                continue;
+            };
+            if let Some(velocity) = &state.velocity
+                && (velocity.vx.is_some() || velocity.vy.is_some())
+            {
+                let vx = velocity.vx.unwrap_or(0.0);
+                let vy = velocity.vy.unwrap_or(0.0);
+                speed.add(location, vx.hypot(vy));
+            }
+            if state.driving {
+                if let Some(stop) = stopped.remove(agv) {
+                    stops.add(&stop.location, secs(time - stop.start));
+                }
+            } else {
+                stopped
+                    .entry(agv)
+                    .or_insert_with(|| Stop {
+                        start: time,
+                        last_seen: time,
+                        location: location.clone(),
+                    })
+                    .last_seen = time;
+            }
+        }
+        for (_, location) in positions.values() {
+            occupancy.add(location, 0.0);
+        }
+        for stop in stopped.values() {
+            stops.add(&stop.location, secs(stop.last_seen - stop.start));
+        }
+        for interval in ErrorTimeline::analyze(recording).intervals {
+            if interval.kind == EventKind::Error
+                && let Some(location) = &interval.location
+            {
+                errors.add(location, interval.duration_secs());
+            }
+        }
+
+        SpatialAnalysis {
+            cell_size: config.cell_size,
+            occupancy: occupancy.build(),
+            speed: speed.build(),
+            stops: stops.build(),
+            errors: errors.build(),
+            nodes: nodes.into_values().collect(),
+        }
+    }
+
+    /// The heatmaps of a layer.
+    pub fn layer(&self, layer: SpatialLayer) -> &[Heatmap] {
+        match layer {
+            SpatialLayer::Occupancy => &self.occupancy,
+            SpatialLayer::Speed => &self.speed,
+            SpatialLayer::Stops => &self.stops,
+            SpatialLayer::Errors => &self.errors,
+        }
+    }
+
+    /// The IDs of all maps with a heatmap or a node.
+    pub fn map_ids(&self) -> Vec<&str> {
+        let mut map_ids: Vec<&str> = SpatialLayer::ALL
+            .iter()
+            .flat_map(|layer| self.layer(*layer))
+            .map(|heatmap| heatmap.map_id.as_str())
+            .chain(self.nodes.iter().map(|node| node.location.map_id.as_str()))
+            .collect();
+        map_ids.sort();
+        map_ids.dedup();
+        map_ids
+    }
+
+    /// All cells of all layers as CSV, with the lower left corner of every cell in [m].
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("mapId,layer,x,y,count,value,unit\n");
+        for layer in SpatialLayer::ALL {
+            for heatmap in self.layer(layer) {
+                for cell in &heatmap.cells {
+                    let _ = writeln!(
+                        csv,
+                        "{},{layer},{},{},{},{},{}",
+                        csv_field(&heatmap.map_id),
+                        cell.column as f64 * heatmap.cell_size,
+                        cell.row as f64 * heatmap.cell_size,
+                        cell.count,
+                        layer.value(cell),
+                        layer.unit()
+                    );
+                }
+            }
+        }
+        csv
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.0
+}
@@ -0,0 +1,710 @@
+//! Conflicts between the released paths of the AGVs and deadlocks in which they wait on each
+//! other.
+//!
+//! The released path of an AGV runs from its position through the released nodes of its state,
+//! following the curve of edges with a trajectory. Around the path, the AGV needs the
+//! radius of the circle around its footprint. Two AGVs are in conflict while their paths come
+//! closer than the sum of their radii plus the clearance, and at least one of them still has
+//! released nodes ahead. An AGV that stopped with released nodes ahead waits on every AGV whose
+//! footprint is on its path further away than its own radius; a cycle of waiting AGVs is a
+//! deadlock.
+use super::Location;
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Write};
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::State;
+
+/// The number of straight pieces a trajectory is sampled into per control point.
+const SAMPLES_PER_CONTROL_POINT: usize = 16;
+
+/// Settings of the traffic analysis.
+#[derive(Debug, Clone)]
+pub struct TrafficConfig {
+    /// The distance in [m] that has to remain between the footprints of two AGVs.
+    pub clearance: f64,
+    /// The radius in [m] of AGVs that reported neither an outline nor a factsheet.
+    pub default_radius: f64,
+    /// A cycle of waiting AGVs has to last at least this long to count as a deadlock.
+    pub min_deadlock: Duration,
+}
+
+impl Default for TrafficConfig {
+    fn default() -> Self {
+        TrafficConfig {
+            clearance: 0.2,
+            default_radius: 0.5,
+            min_deadlock: Duration::seconds(10),
+        }
+    }
+}
+
+/// Where the footprint of an AGV comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FootprintSource {
+    /// The outline of the last visualization message.
+    Outline,
+    /// The dimensions of the factsheet.
+    Factsheet,
+    /// The configured default.
+    Default,
+}
+
+impl fmt::Display for FootprintSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FootprintSource::Outline => "outline",
+            FootprintSource::Factsheet => "factsheet",
+            FootprintSource::Default => "default",
+        })
+    }
+}
+
+/// The circle around the footprint of an AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Footprint {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The radius in [m], the last one reported.
+    pub radius: f64,
+    /// Where the radius comes from.
+    pub source: FootprintSource,
+}
+
+/// A period in which the released paths of two AGVs came too close.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    /// The first AGV, in the order of the AGV IDs.
+    pub first: AgvId,
+    /// The second AGV.
+    pub second: AgvId,
+    /// The state that brought the paths too close.
+    pub start: DateTime<Utc>,
+    /// The first state of either AGV after which the paths were far enough apart again.
+    /// `None` if they were still too close at the end of the recording.
+    pub end: Option<DateTime<Utc>>,
+    /// How long the conflict lasted, until the last state of either AGV if it never ended, in
+    /// seconds.
+    pub duration_secs: f64,
+    /// The smallest distance between the paths during the conflict, in [m].
+    pub min_distance: f64,
+    /// The distance the paths needed to keep, in [m].
+    pub required_distance: f64,
+    /// The point of the first path closest to the second path when they came closest.
+    pub location: Location,
+    /// The released nodes of the first AGV when the conflict started.
+    pub first_nodes: Vec<String>,
+    /// The released nodes of the second AGV when the conflict started.
+    pub second_nodes: Vec<String>,
+}
+
+/// A period in which stopped AGVs waited on each other in a cycle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Deadlock {
+    /// The AGVs of the cycle, each waiting on the next and the last on the first.
+    pub agvs: Vec<AgvId>,
+    /// The state that closed the cycle.
+    pub start: DateTime<Utc>,
+    /// The first state after which the cycle was broken.
+    /// `None` if it still existed at the end of the recording.
+    pub end: Option<DateTime<Utc>>,
+    /// How long the cycle lasted, until the last state if it never ended, in seconds.
+    pub duration_secs: f64,
+}
+
+/// The traffic conflicts and deadlocks of a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficAnalysis {
+    /// The footprint of every AGV that reported a state.
+    pub footprints: Vec<Footprint>,
+    /// All conflicts, ordered by their start.
+    pub conflicts: Vec<Conflict>,
+    /// All deadlocks that lasted at least the configured time, ordered by their start.
+    pub deadlocks: Vec<Deadlock>,
+}
+
+/// What is known about an AGV at a point in the recording.
+#[derive(Default)]
+struct Vehicle {
+    position: Option<Location>,
+    outline_radius: Option<f64>,
+    factsheet_radius: Option<f64>,
+    /// The positions of the nodes of the orders sent to the AGV, for states without them.
+    order_nodes: HashMap<String, Location>,
+    /// The path from the position through the released nodes.
+    path: Vec<Location>,
+    released_nodes: Vec<String>,
+    stopped: bool,
+}
+
+impl Vehicle {
+    fn footprint(&self, config: &TrafficConfig) -> (f64, FootprintSource) {
+        match (self.outline_radius, self.factsheet_radius) {
+            (Some(radius), _) => (radius, FootprintSource::Outline),
+            (None, Some(radius)) => (radius, FootprintSource::Factsheet),
+            (None, None) => (config.default_radius, FootprintSource::Default),
+        }
+    }
+
+    fn update(&mut self, state: &State) {
+        if let Some(position) = &state.agv_position {
+            self.position = Some(Location::of(position));
+        }
+        self.stopped = !state.driving;
+        self.released_nodes.clear();
+        self.path.clear();
+        self.path.extend(self.position.clone());
+
+        let mut steps: Vec<(u32, Vec<Location>, Option<&str>)> = Vec::new();
+        for node in state.node_states.iter().filter(|node| node.released) {
+            let location = match &node.node_position {
+                Some(position) => Some(Location {
+                    map_id: position.map_id.clone(),
+                    x: position.x,
+                    y: position.y,
+                }),
+                None => self.order_nodes.get(&node.node_id).cloned(),
+            };
+            steps.push((
+                node.sequence_id,
+                location.into_iter().collect(),
+                Some(&node.node_id),
+            ));
+        }
+        for edge in state.edge_states.iter().filter(|edge| edge.released) {
+            let (Some(trajectory), Some(map_id)) = (&edge.trajectory, self.map_id()) else {
+                continue;
+            };
+            // A trajectory that cannot be evaluated is followed along its control points.
+            let points = trajectory
+                .sample(SAMPLES_PER_CONTROL_POINT)
+                .unwrap_or_else(|| {
+                    trajectory
+                        .control_points
+                        .iter()
+                        .map(|point| (point.x, point.y))
+                        .collect()
+                })
+                .into_iter()
+                .map(|(x, y)| Location {
+                    map_id: map_id.clone(),
+                    x,
+                    y,
+                })
+                .collect();
+            steps.push((edge.sequence_id, points, None));
+        }
+        steps.sort_by_key(|(sequence_id, _, _)| *sequence_id);
+        for (_, points, node_id) in steps {
+            self.path.extend(points);
+            self.released_nodes.extend(node_id.map(str::to_string));
+        }
+    }
+
+    fn map_id(&self) -> Option<String> {
+        self.position
+            .as_ref()
+            .map(|position| position.map_id.clone())
+    }
+}
+
+impl TrafficAnalysis {
+    /// Follows the released paths of all AGVs through the recording.
+    pub fn analyze(recording: &Recording, config: &TrafficConfig) -> Self {
+        let mut vehicles: BTreeMap<&AgvId, Vehicle> = BTreeMap::new();
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        let mut open_conflicts: HashMap<(&AgvId, &AgvId), usize> = HashMap::new();
+        let mut deadlocks: Vec<Deadlock> = Vec::new();
+        let mut open_deadlocks: HashMap<Vec<&AgvId>, usize> = HashMap::new();
+
+        for recorded in &recording.messages {
+            let agv = &recorded.agv;
+            let time = recorded.received_at;
+            let vehicle = vehicles.entry(agv).or_default();
+            let state = match &recorded.message {
+                Message::State(state) => state,
+                Message::Visualization(visualization) => {
+                    if let Some(position) = &visualization.agv_position {
+                        vehicle.position = Some(Location::of(position));
+                    }
+                    if let Some(outline) = &visualization.agv_outline
+                        && !outline.is_empty()
+                    {
+                        let radius = outline
+                            .iter()
+                            .map(|point| point.x.hypot(point.y))
+                            .fold(0.0, f64::max);
+                        vehicle.outline_radius = Some(radius);
+                    }
+                    continue;
+                }
+                Message::Factsheet(factsheet) => {
+                    let dimensions = &factsheet.agv_dimensions;
+                    vehicle.factsheet_radius =
+                        Some(dimensions.length.hypot(dimensions.width) / 2.0);
+                    continue;
+                }
+                Message::Order(order) => {
+                    vehicle
+                        .order_nodes
+                        .extend(order.nodes.iter().filter_map(|node| {
+                            let position = node.node_position.as_ref()?;
+                            Some((
+                                node.node_id.clone(),
+                                Location {
+                                    map_id: position.map_id.clone(),
+                                    x: position.x,
+                                    y: position.y,
+                                },
+                            ))
+                        }));
+                    continue;
+                }
+                _ => continue,
+            };
+            vehicle.update(state);
+
+            let vehicle = &vehicles[agv];
+            let (radius, _) = vehicle.footprint(config);
+            for (other_agv, other) in &vehicles {
+                if *other_agv == agv {
+                    continue;
+                }
+                let key = if agv < *other_agv {
+                    (agv, *other_agv)
+                } else {
+                    (*other_agv, agv)
+                };
+                let (other_radius, _) = other.footprint(config);
+                let required_distance = radius + other_radius + config.clearance;
+                let closest =
+                    if vehicle.released_nodes.is_empty() && other.released_nodes.is_empty() {
+                        None
+                    } else {
+                        let (first, second) = if key.0 == agv {
+                            (vehicle, other)
+                        } else {
+                            (other, vehicle)
+                        };
+                        closest_approach(&first.path, &second.path)
+                            .filter(|(distance, _)| *distance < required_distance)
+                    };
+                match (open_conflicts.get(&key), closest) {
+                    (Some(&index), Some((distance, location))) => {
+                        let conflict = &mut conflicts[index];
+                        conflict.duration_secs = secs(time - conflict.start);
+                        if distance < conflict.min_distance {
+                            conflict.min_distance = distance;
+                            conflict.location = location;
+                        }
+                    }
+                    (Some(&index), None) => {
+                        let conflict = &mut conflicts[index];
+                        conflict.duration_secs = secs(time - conflict.start);
+                        conflict.end = Some(time);
+                        open_conflicts.remove(&key);
+                    }
+                    (None, Some((distance, location))) => {
+                        open_conflicts.insert(key, conflicts.len());
+                        conflicts.push(Conflict {
+                            first: key.0.clone(),
+                            second: key.1.clone(),
+                            start: time,
+                            end: None,
+                            duration_secs: 0.0,
+                            min_distance: distance,
+                            required_distance,
+                            location,
+                            first_nodes: vehicles[key.0].released_nodes.clone(),
+                            second_nodes: vehicles[key.1].released_nodes.clone(),
+                        });
+                    }
+                    (None, None) => {}
+                }
+            }
+
+            let cycles = wait_cycles(&vehicles, config);
+            open_deadlocks.retain(|cycle, index| {
+                let deadlock = &mut deadlocks[*index];
+                deadlock.duration_secs = secs(time - deadlock.start);
+                if cycles.contains(cycle) {
+                    return true;
+                }
+                deadlock.end = Some(time);
+                false
+            });
+            for cycle in cycles {
+                if !open_deadlocks.contains_key(&cycle) {
+                    open_deadlocks.insert(cycle.clone(), deadlocks.len());
+                    deadlocks.push(Deadlock {
+                        agvs: cycle.into_iter().cloned().collect(),
+                        start: time,
+                        end: None,
+                        duration_secs: 0.0,
+                    });
+                }
+            }
+        }
+
+        let min_deadlock = secs(config.min_deadlock);
+        deadlocks.retain(|deadlock| deadlock.duration_secs >= min_deadlock);
+        let footprints = vehicles
+            .iter()
+            .filter(|(_, vehicle)| vehicle.position.is_some())
+            .map(|(agv, vehicle)| {
+                let (radius, source) = vehicle.footprint(config);
+                Footprint {
+                    agv: (*agv).clone(),
+                    radius,
+                    source,
+                }
+            })
+            .collect();
+        TrafficAnalysis {
+            footprints,
+            conflicts,
+            deadlocks,
+        }
+    }
+
+    /// A human readable list of the conflicts and deadlocks.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for footprint in &self.footprints {
+            let _ = writeln!(
+                text,
+                "{:<24} radius {:.2} m ({})",
+                footprint.agv.to_string(),
+                footprint.radius,
+                footprint.source
+            );
+        }
+        let _ = writeln!(text, "{} conflict(s)", self.conflicts.len());
+        for conflict in &self.conflicts {
+            let _ = writeln!(
+                text,
+                "  {} {} / {}: {:.1} s, {:.2} m of {:.2} m at {} ({:.2}, {:.2}){}",
+                conflict.start.to_rfc3339(),
+                conflict.first,
+                conflict.second,
+                conflict.duration_secs,
+                conflict.min_distance,
+                conflict.required_distance,
+                conflict.location.map_id,
+                conflict.location.x,
+                conflict.location.y,
+                if conflict.end.is_none() {
+                    " (active)"
+                } else {
+                    ""
+                }
+            );
+        }
+        let _ = writeln!(text, "{} deadlock(s)", self.deadlocks.len());
+        for deadlock in &self.deadlocks {
+            let agvs: Vec<String> = deadlock.agvs.iter().map(AgvId::to_string).collect();
+            let _ = writeln!(
+                text,
+                "  {} {}: {:.1} s{}",
+                deadlock.start.to_rfc3339(),
+                agvs.join(" -> "),
+                deadlock.duration_secs,
+                if deadlock.end.is_none() {
+                    " (active)"
+                } else {
+                    ""
+                }
+            );
+        }
+        text
+    }
+}
+
+/// All cycles of stopped AGVs waiting on each other, each starting with its smallest AGV ID.
+fn wait_cycles<'a>(
+    vehicles: &BTreeMap<&'a AgvId, Vehicle>,
+    config: &TrafficConfig,
+) -> BTreeSet<Vec<&'a AgvId>> {
+    let mut waits_on: BTreeMap<&AgvId, Vec<&AgvId>> = BTreeMap::new();
+    for (agv, vehicle) in vehicles {
+        let Some(own_position) = &vehicle.position else {
+            continue;
+        };
+        if !vehicle.stopped || vehicle.released_nodes.is_empty() {
+            continue;
+        }
+        let (radius, _) = vehicle.footprint(config);
+        for (other_agv, other) in vehicles {
+            if other_agv == agv {
+                continue;
+            }
+            let Some(position) = &other.position else {
+                continue;
+            };
+            let (other_radius, _) = other.footprint(config);
+            // Only AGVs ahead on the path block it, not those beside the AGV.
+            if closest_approach(&vehicle.path, std::slice::from_ref(position)).is_some_and(
+                |(distance, closest)| {
+                    distance < radius + other_radius + config.clearance
+                        && (closest.x - own_position.x).hypot(closest.y - own_position.y) > radius
+                },
+            ) {
+                waits_on.entry(*agv).or_default().push(*other_agv);
+            }
+        }
+    }
+
+    // Every cycle is found from its smallest AGV, visiting only larger ones.
+    let mut cycles = BTreeSet::new();
+    for start in waits_on.keys() {
+        let mut stack: Vec<(&AgvId, usize)> = vec![(*start, 0)];
+        while let Some((agv, next)) = stack.last_mut() {
+            let Some(&successor) = waits_on
+                .get(agv)
+                .and_then(|successors| successors.get(*next))
+            else {
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            if successor == *start {
+                cycles.insert(stack.iter().map(|(agv, _)| *agv).collect());
+            } else if successor > *start && stack.iter().all(|(agv, _)| *agv != successor) {
+                stack.push((successor, 0));
+            }
+        }
+    }
+    cycles
+}
+
+/// The smallest distance between two paths on the same map and the point of the first path where
+/// it occurs. A path of one point is the point itself.
+fn closest_approach(first: &[Location], second: &[Location]) -> Option<(f64, Location)> {
+    let mut closest: Option<(f64, Location)> = None;
+    for a in segments(first) {
+        for b in segments(second) {
+            if a.0.map_id != b.0.map_id || a.1.map_id != b.1.map_id {
+                continue;
+            }
+            let (distance, (x, y)) = segment_distance(a, b);
+            if closest.as_ref().is_none_or(|(min, _)| distance < *min) {
+                closest = Some((
+                    distance,
+                    Location {
+                        map_id: a.0.map_id.clone(),
+                        x,
+                        y,
+                    },
+                ));
+            }
+        }
+    }
+    closest
+}
+
+/// The segments between consecutive points on the same map, or the single point as a segment.
+fn segments(path: &[Location]) -> Vec<(&Location, &Location)> {
+    if path.len() == 1 {
+        return vec![(&path[0], &path[0])];
+    }
+    path.windows(2)
+        .filter(|pair| pair[0].map_id == pair[1].map_id)
+        .map(|pair| (&pair[0], &pair[1]))
+        .collect()
+}
+
+/// The distance between two segments and the point of the first segment closest to the second.
+fn segment_distance(a: (&Location, &Location), b: (&Location, &Location)) -> (f64, (f64, f64)) {
+    let (a0, a1) = ((a.0.x, a.0.y), (a.1.x, a.1.y));
+    let (b0, b1) = ((b.0.x, b.0.y), (b.1.x, b.1.y));
+    if let Some(crossing) = intersection(a0, a1, b0, b1) {
+        return (0.0, crossing);
+    }
+    let candidates = [
+        (point_distance(a0, b0, b1), a0),
+        (point_distance(a1, b0, b1), a1),
+        (point_distance(b0, a0, a1), closest_point(b0, a0, a1)),
+        (point_distance(b1, a0, a1), closest_point(b1, a0, a1)),
+    ];
+    candidates
+        .into_iter()
+        .min_by(|x, y| x.0.total_cmp(&y.0))
+        .unwrap_or((f64::INFINITY, a0))
+}
+
+type Point = (f64, f64);
+
+fn closest_point(p: Point, s0: Point, s1: Point) -> Point {
+    let (dx, dy) = (s1.0 - s0.0, s1.1 - s0.1);
+    let length = dx * dx + dy * dy;
+    if length == 0.0 {
+        return s0;
+    }
+    let t = (((p.0 - s0.0) * dx + (p.1 - s0.1) * dy) / length).clamp(0.0, 1.0);
+    (s0.0 + t * dx, s0.1 + t * dy)
+}
+
+fn point_distance(p: Point, s0: Point, s1: Point) -> f64 {
+    let closest = closest_point(p, s0, s1);
+    (p.0 - closest.0).hypot(p.1 - closest.1)
+}
+
+/// Where two segments cross, if they do.
+fn intersection(a0: Point, a1: Point, b0: Point, b1: Point) -> Option<Point> {
+    let (r, s) = ((a1.0 - a0.0, a1.1 - a0.1), (b1.0 - b0.0, b1.1 - b0.1));
+    let denominator = r.0 * s.1 - r.1 * s.0;
+    if denominator == 0.0 {
+        return None;
+    }
+    let (qx, qy) = (b0.0 - a0.0, b0.1 - a0.1);
+    let t = (qx * s.1 - qy * s.0) / denominator;
+    let u = (qx * r.1 - qy * r.0) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u))
+        .then_some((a0.0 + t * r.0, a0.1 + t * r.1))
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{push_agv, state};
+    use serde_json::{Value, json};
+
+    /// A state of an AGV at `from` with the released node `to` ahead, along the given trajectory
+    /// if there is one.
+    fn heading(
+        from: (f64, f64),
+        to: (f64, f64),
+        driving: bool,
+        trajectory: Option<Value>,
+    ) -> Value {
+        let mut edge = json!({"edgeId": "e1", "sequenceId": 1, "released": true, "actions": []});
+        if let Some(trajectory) = trajectory {
+            edge["trajectory"] = trajectory;
+        }
+        state(json!({
+            "driving": driving,
+            "agvPosition": {"x": from.0, "y": from.1, "theta": 0.0, "mapId": "hall",
+                "positionInitialized": true},
+            "nodeStates": [{"nodeId": "n2", "sequenceId": 2, "released": true, "actions": [],
+                "nodePosition": {"x": to.0, "y": to.1, "mapId": "hall"}}],
+            "edgeStates": [edge],
+        }))
+    }
+
+    fn analyze(states: &[(&str, f64, Value)]) -> TrafficAnalysis {
+        let mut recording = Recording::default();
+        for (serial_number, secs, body) in states {
+            push_agv(
+                &mut recording,
+                serial_number,
+                "state",
+                *secs,
+                *secs,
+                body.clone(),
+            );
+        }
+        TrafficAnalysis::analyze(&recording, &TrafficConfig::default())
+    }
+
+    #[test]
+    fn finds_crossing_paths() {
+        let analysis = analyze(&[
+            ("agv1", 0.0, heading((0.0, 0.0), (10.0, 0.0), true, None)),
+            ("agv2", 1.0, heading((5.0, -5.0), (5.0, 5.0), true, None)),
+        ]);
+        assert_eq!(analysis.conflicts.len(), 1);
+        let conflict = &analysis.conflicts[0];
+        assert_eq!(
+            (conflict.first.to_string(), conflict.second.to_string()),
+            ("acme/agv1".to_string(), "acme/agv2".to_string())
+        );
+        assert_eq!(conflict.start, crate::fixtures::at(1.0));
+        assert_eq!(conflict.min_distance, 0.0);
+        assert!((conflict.required_distance - 1.2).abs() < 1e-9);
+        assert_eq!((conflict.location.x, conflict.location.y), (5.0, 0.0));
+        assert_eq!(conflict.end, None);
+    }
+
+    #[test]
+    fn parallel_paths_conflict_only_within_the_clearance() {
+        // Two default footprints of 0.5 m and the clearance of 0.2 m need 1.2 m.
+        let beyond = analyze(&[
+            ("agv1", 0.0, heading((0.0, 0.0), (10.0, 0.0), true, None)),
+            ("agv2", 1.0, heading((0.0, 1.25), (10.0, 1.25), true, None)),
+        ]);
+        assert!(beyond.conflicts.is_empty());
+
+        let within = analyze(&[
+            ("agv1", 0.0, heading((0.0, 0.0), (10.0, 0.0), true, None)),
+            ("agv2", 1.0, heading((0.0, 1.15), (10.0, 1.15), true, None)),
+        ]);
+        assert_eq!(within.conflicts.len(), 1);
+        assert!((within.conflicts[0].min_distance - 1.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn follows_the_curve_of_a_trajectory() {
+        // A quadratic curve from (0, 0) to (10, 0) whose apex is at (5, 3), half way to its
+        // middle control point.
+        let curve = json!({
+            "headerId": 0,
+            "degree": 2,
+            "knotVector": [0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            "controlPoints": [{"x": 0.0, "y": 0.0}, {"x": 5.0, "y": 6.0}, {"x": 10.0, "y": 0.0}],
+        });
+        let on_curve = analyze(&[
+            (
+                "agv1",
+                0.0,
+                heading((0.0, 0.0), (10.0, 0.0), true, Some(curve.clone())),
+            ),
+            ("agv2", 1.0, heading((5.0, 3.0), (5.5, 3.0), true, None)),
+        ]);
+        assert_eq!(on_curve.conflicts.len(), 1);
+        assert!(on_curve.conflicts[0].min_distance < 0.1);
+
+        let near_control_point = analyze(&[
+            (
+                "agv1",
+                0.0,
+                heading((0.0, 0.0), (10.0, 0.0), true, Some(curve)),
+            ),
+            ("agv2", 1.0, heading((5.0, 5.5), (5.5, 5.5), true, None)),
+        ]);
+        assert!(near_control_point.conflicts.is_empty());
+    }
+
+    #[test]
+    fn finds_two_agvs_waiting_on_each_other() {
+        let facing = |secs: f64| {
+            [
+                ("agv1", secs, heading((0.0, 0.0), (4.0, 0.0), false, None)),
+                ("agv2", secs, heading((4.0, 0.0), (0.0, 0.0), false, None)),
+            ]
+        };
+        let mut states = facing(0.0).to_vec();
+        states.extend(facing(12.0));
+        let analysis = analyze(&states);
+        assert_eq!(analysis.deadlocks.len(), 1);
+        let deadlock = &analysis.deadlocks[0];
+        let agvs: Vec<String> = deadlock.agvs.iter().map(AgvId::to_string).collect();
+        assert_eq!(agvs, ["acme/agv1", "acme/agv2"]);
+        assert_eq!(deadlock.start, crate::fixtures::at(0.0));
+        assert_eq!(deadlock.end, None);
+        assert_eq!(deadlock.duration_secs, 12.0);
+
+        // A cycle shorter than the configured time is no deadlock.
+        assert!(analyze(&facing(0.0)).deadlocks.is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+//! Analyzes VDA 5050 recordings without the HMI, e.g. in nightly commissioning pipelines.
+use clap::{Parser, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use vda5050_analysis::analytics::clock::ClockModel;
+use vda5050_analysis::analytics::kpis::{FleetKpis, KpiConfig};
+use vda5050_analysis::analytics::latency::{LatencyAnalysis, LatencyConfig};
+use vda5050_analysis::analytics::order_responses::{OrderResponseAnalysis, OrderResponseConfig};
+use vda5050_analysis::analytics::spatial::{SpatialAnalysis, SpatialConfig, SpatialLayer};
+use vda5050_analysis::analytics::traffic::{TrafficAnalysis, TrafficConfig};
+use vda5050_analysis::checks::{CheckConfig, Severity};
+use vda5050_analysis::incident_report::{self, IncidentReportOptions};
+use vda5050_analysis::report::Report;
+
+#[derive(Parser)]
+#[command(name = "vda5050-analyze", version, about)]
+struct Args {
+    /// Recording files in JSON Lines format, or pcap and pcapng captures of MQTT on port 1883.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Write the output to a file instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// The longest allowed time between two state messages in seconds.
+    #[arg(long, default_value_t = 30)]
+    max_state_interval: i64,
+    /// How far the clock of a sender may be off the capture host in milliseconds.
+    #[arg(long, default_value_t = 1000)]
+    max_clock_offset: i64,
+    /// The time of the incident for the HTML report (RFC 3339).
+    /// Defaults to the first error found.
+    #[arg(long)]
+    incident_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// Seconds before and after the incident covered by the HTML report.
+    #[arg(long, default_value_t = 60)]
+    incident_window: i64,
+    /// Print the fleet KPIs instead of the findings (text or json format).
+    #[arg(long)]
+    kpis: bool,
+    /// Print the acknowledgement latencies instead of the findings (text or json format).
+    #[arg(long, conflicts_with = "kpis")]
+    latency: bool,
+    /// Print the estimated clock offset and drift of every sender instead of the findings
+    /// (text or json format).
+    #[arg(long, conflicts_with_all = ["kpis", "latency"])]
+    clocks: bool,
+    /// Print the conflicts between the released paths and the deadlocks of the AGVs instead of
+    /// the findings (text or json format).
+    #[arg(long, conflicts_with_all = ["kpis", "latency", "clocks"])]
+    traffic: bool,
+    /// Print whether the AGVs accepted or rejected every order and whether the rejections carry
+    /// the expected errors, instead of the findings (text or json format).
+    #[arg(long, conflicts_with_all = ["kpis", "latency", "clocks", "traffic"])]
+    order_responses: bool,
+    /// Correct the timelines of the HTML report, the KPIs, the latencies and the traffic with the
+    /// estimated clocks of the senders. The checks always use the receive times.
+    #[arg(long)]
+    realign_clocks: bool,
+    /// The start of the KPI window (RFC 3339). Defaults to the first message.
+    #[arg(long, requires = "kpis")]
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    /// The end of the KPI window (RFC 3339). Defaults to the last message.
+    #[arg(long, requires = "kpis")]
+    to: Option<chrono::DateTime<chrono::Utc>>,
+    /// Also write the occupancy, speed, stop and error heatmaps of every map to this directory, as
+    /// `heatmaps.csv` and one `<map>-<layer>.png` per map and layer.
+    #[arg(long)]
+    heatmaps: Option<PathBuf>,
+    /// The edge length of a heatmap cell in meters.
+    #[arg(long, default_value_t = 1.0, requires = "heatmaps", value_parser = parse_cell_size)]
+    cell_size: f64,
+    /// Exit with code 1 if a finding of at least this severity is found.
+    #[arg(long, value_enum, default_value_t = FailOn::Error)]
+    fail_on: FailOn,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+    Junit,
+    /// A self-contained HTML incident report.
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FailOn {
+    Error,
+    Warning,
+    Never,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = CheckConfig {
+        max_state_interval: chrono::Duration::seconds(args.max_state_interval),
+        max_clock_offset: chrono::Duration::milliseconds(args.max_clock_offset),
+    };
+    let (mut recording, report) = match Report::analyze(&args.files, &config) {
+        Ok(analyzed) => analyzed,
+        Err(err) => {
+            eprintln!("error: failed to read recording: {err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let clocks = ClockModel::estimate(&recording);
+    if args.realign_clocks {
+        clocks.realign(&mut recording);
+    }
+
+    if let Some(dir) = &args.heatmaps {
+        let spatial = SpatialAnalysis::analyze(
+            &recording,
+            &SpatialConfig {
+                cell_size: args.cell_size,
+                ..SpatialConfig::default()
+            },
+        );
+        if let Err(err) = write_heatmaps(dir, &spatial) {
+            eprintln!(
+                "error: failed to write heatmaps to {}: {err}",
+                dir.display()
+            );
+            return ExitCode::from(2);
+        }
+    }
+
+    let analytics = if args.clocks {
+        Some((clocks.to_text(), serde_json::to_string_pretty(&clocks)))
+    } else if args.kpis {
+        let kpis = FleetKpis::compute(
+            &recording,
+            &KpiConfig {
+                from: args.from,
+                to: args.to,
+                max_state_interval: config.max_state_interval,
+            },
+        );
+        Some((kpis.to_text(), serde_json::to_string_pretty(&kpis)))
+    } else if args.latency {
+        let latency = LatencyAnalysis::analyze(&recording, &LatencyConfig::default());
+        Some((latency.to_text(), serde_json::to_string_pretty(&latency)))
+    } else if args.traffic {
+        let traffic = TrafficAnalysis::analyze(&recording, &TrafficConfig::default());
+        Some((traffic.to_text(), serde_json::to_string_pretty(&traffic)))
+    } else if args.order_responses {
+        let responses = OrderResponseAnalysis::analyze(&recording, &OrderResponseConfig::default());
+        Some((
+            responses.to_text(),
+            serde_json::to_string_pretty(&responses),
+        ))
+    } else {
+        None
+    };
+    if let Some((text, json)) = analytics {
+        let output = match args.format {
+            Format::Text => text,
+            Format::Json => json.unwrap_or_default(),
+            Format::Junit | Format::Html => {
+                eprintln!("error: this analysis can only be written as text or json");
+                return ExitCode::from(2);
+            }
+        };
+        if !write_output(&args, output) {
+            return ExitCode::from(2);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let output = match args.format {
+        Format::Text => report.to_text(),
+        Format::Json => report.to_json(),
+        Format::Junit => report.to_junit(),
+        Format::Html => {
+            let options = IncidentReportOptions {
+                incident_time: args.incident_time,
+                window: chrono::Duration::seconds(args.incident_window),
+                ..IncidentReportOptions::default()
+            };
+            incident_report::render(&recording, &report, &options)
+        }
+    };
+    if !write_output(&args, output) {
+        return ExitCode::from(2);
+    }
+
+    let threshold = match args.fail_on {
+        FailOn::Error => Some(Severity::Error),
+        FailOn::Warning => Some(Severity::Warning),
+        FailOn::Never => None,
+    };
+    match (threshold, report.max_severity()) {
+        (Some(threshold), Some(max)) if max >= threshold => ExitCode::FAILURE,
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// Writes the output to the output file or stdout and reports whether that worked.
+fn write_output(args: &Args, output: String) -> bool {
+    match &args.output {
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, output) {
+                eprintln!("error: failed to write {}: {err}", path.display());
+                return false;
+            }
+        }
+        None => print!("{output}"),
+    }
+    true
+}
+
+/// Writes the CSV and one image per map and layer into the directory.
+fn write_heatmaps(dir: &Path, spatial: &SpatialAnalysis) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join("heatmaps.csv"), spatial.to_csv())?;
+    for layer in SpatialLayer::ALL {
+        for heatmap in spatial.layer(layer) {
+            let map: String = heatmap
+                .map_id
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let image = heatmap.to_png(|cell| layer.value(cell), 8)?;
+            std::fs::write(dir.join(format!("{map}-{layer}.png")), image)?;
+        }
+    }
+    Ok(())
+}
+
+/// A cell size must be a positive number of meters, anything else leaves no grid to fill.
+fn parse_cell_size(value: &str) -> Result<f64, String> {
+    let cell_size: f64 = value.parse().map_err(|err| format!("{err}"))?;
+    if cell_size.is_finite() && cell_size > 0.0 {
+        Ok(cell_size)
+    } else {
+        Err(format!("must be a positive number of meters, not {value}"))
+    }
+}
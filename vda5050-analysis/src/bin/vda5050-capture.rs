@@ -0,0 +1,132 @@
+//! Converts the MQTT messages of pcap and pcapng captures into a JSON Lines recording, e.g. to
+//! analyze a Wireshark capture from a site with `vda5050-analyze` or to import it into a store.
+use clap::Parser;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use vda5050_analysis::capture::{Capture, CaptureOptions, CapturedPublish};
+use vda5050_analysis::recording::RecordEntry;
+use vda5050_data_types::topic::Topic;
+
+#[derive(Parser)]
+#[command(name = "vda5050-capture", version, about)]
+struct Args {
+    /// Capture files in pcap or pcapng format.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Write the recording to a file instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// The TCP ports of the broker.
+    #[arg(long = "port", value_delimiter = ',', default_value = "1883")]
+    ports: Vec<u16>,
+    /// Keep every copy of a message, e.g. the deliveries to every subscriber in a capture taken on
+    /// the broker host, instead of the first one.
+    #[arg(long)]
+    keep_duplicates: bool,
+    /// Also write the messages on topics that are not VDA 5050 topics.
+    #[arg(long)]
+    all_topics: bool,
+    /// Only write the retained messages, e.g. to see the last connection and factsheet messages
+    /// the broker handed out.
+    #[arg(long)]
+    retained_only: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let options = CaptureOptions {
+        ports: args.ports.clone(),
+        keep_duplicates: args.keep_duplicates,
+    };
+    let mut publishes: Vec<CapturedPublish> = Vec::new();
+    for path in &args.files {
+        let capture = match Capture::read(path, &options) {
+            Ok(capture) => capture,
+            Err(err) => {
+                eprintln!("error: failed to read {}: {err}", path.display());
+                return ExitCode::from(2);
+            }
+        };
+        let summary = capture.summary;
+        eprintln!(
+            "{}: {} frames, {} connections to the broker, {} publishes, {} duplicates dropped",
+            path.display(),
+            summary.frames,
+            summary.connections,
+            summary.publishes,
+            summary.duplicates
+        );
+        if summary.gaps > 0 || summary.skipped_bytes > 0 {
+            eprintln!(
+                "{}: {} gaps in the TCP streams, {} bytes skipped",
+                path.display(),
+                summary.gaps,
+                summary.skipped_bytes
+            );
+        }
+        if summary.unknown_topic_aliases > 0 {
+            eprintln!(
+                "{}: {} publishes dropped with a topic alias set before the capture started",
+                path.display(),
+                summary.unknown_topic_aliases
+            );
+        }
+        if summary.truncated {
+            eprintln!(
+                "{}: the capture ends in the middle of a frame",
+                path.display()
+            );
+        }
+        publishes.extend(capture.publishes);
+    }
+    publishes.sort_by_key(|publish| publish.captured_at);
+
+    let mut entries = Vec::new();
+    let (mut other_topics, mut not_json) = (0, 0);
+    let mut qos = [0; 3];
+    for publish in &publishes {
+        if args.retained_only && !publish.retain {
+            continue;
+        }
+        if !args.all_topics && Topic::from_mqtt_topic(&publish.topic).is_none() {
+            other_topics += 1;
+            continue;
+        }
+        match publish.to_entry() {
+            Ok(entry) => {
+                qos[publish.qos as usize] += 1;
+                entries.push(entry);
+            }
+            Err(_) => not_json += 1,
+        }
+    }
+    eprintln!(
+        "wrote {} messages (QoS 0: {}, QoS 1: {}, QoS 2: {}), skipped {} on other topics and {} that are not JSON",
+        entries.len(),
+        qos[0],
+        qos[1],
+        qos[2],
+        other_topics,
+        not_json
+    );
+
+    let written = match &args.output {
+        Some(path) => File::create(path).and_then(|file| write(BufWriter::new(file), &entries)),
+        None => write(io::stdout().lock(), &entries),
+    };
+    if let Err(err) = written {
+        eprintln!("error: failed to write the recording: {err}");
+        return ExitCode::from(2);
+    }
+    ExitCode::SUCCESS
+}
+
+fn write<W: Write>(mut writer: W, entries: &[RecordEntry]) -> io::Result<()> {
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
@@ -0,0 +1,276 @@
+//! Small captures built byte by byte for the tests.
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+pub const CLIENT: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 50_000);
+pub const BROKER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1883);
+
+const FLAG_FIN: u8 = 0x01;
+const FLAG_SYN: u8 = 0x02;
+const FLAG_ACK: u8 = 0x10;
+
+/// A TCP segment of the connection between [`CLIENT`] and [`BROKER`], captured at the given
+/// microseconds since the epoch.
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub micros: u64,
+    pub from_client: bool,
+    pub sequence: u32,
+    pub syn: bool,
+    pub fin: bool,
+    pub payload: Vec<u8>,
+}
+
+impl Packet {
+    pub fn data(micros: u64, from_client: bool, sequence: u32, payload: &[u8]) -> Self {
+        Packet {
+            micros,
+            from_client,
+            sequence,
+            syn: false,
+            fin: false,
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub fn syn(micros: u64, from_client: bool, sequence: u32) -> Self {
+        Packet {
+            syn: true,
+            ..Packet::data(micros, from_client, sequence, &[])
+        }
+    }
+
+    /// The frame as Ethernet, IPv4 and TCP.
+    pub fn frame(&self) -> Vec<u8> {
+        let (source, destination) = if self.from_client {
+            (CLIENT, BROKER)
+        } else {
+            (BROKER, CLIENT)
+        };
+        let mut frame = vec![0x02, 0, 0, 0, 0, 1, 0x02, 0, 0, 0, 0, 2, 0x08, 0x00];
+        let total_len = (20 + 20 + self.payload.len()) as u16;
+        frame.extend([0x45, 0]);
+        frame.extend(total_len.to_be_bytes());
+        frame.extend([0, 0, 0x40, 0, 64, 6, 0, 0]);
+        for address in [source, destination] {
+            let IpAddr::V4(ip) = address.ip() else {
+                unreachable!("IPv4 fixtures")
+            };
+            frame.extend(ip.octets());
+        }
+        frame.extend(source.port().to_be_bytes());
+        frame.extend(destination.port().to_be_bytes());
+        frame.extend(self.sequence.to_be_bytes());
+        frame.extend([0, 0, 0, 0, 0x50]);
+        let mut flags = FLAG_ACK;
+        if self.syn {
+            flags |= FLAG_SYN;
+        }
+        if self.fin {
+            flags |= FLAG_FIN;
+        }
+        frame.extend([flags, 0xff, 0xff, 0, 0, 0, 0]);
+        frame.extend(&self.payload);
+        frame
+    }
+}
+
+/// Writes integers in the byte order of the file.
+struct Writer {
+    bytes: Vec<u8>,
+    big_endian: bool,
+}
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.bytes.extend(bytes);
+    }
+
+    fn u32(&mut self, value: u32) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.bytes.extend(bytes);
+    }
+
+    fn i64(&mut self, value: i64) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.bytes.extend(bytes);
+    }
+
+    /// A pcapng block with its type, length, body padded to four bytes and trailing length.
+    fn block(&mut self, block_type: u32, body: &[u8]) {
+        let padded = body.len().next_multiple_of(4);
+        let len = (12 + padded) as u32;
+        self.u32(block_type);
+        self.u32(len);
+        self.bytes.extend(body);
+        self.bytes.resize(self.bytes.len() + padded - body.len(), 0);
+        self.u32(len);
+    }
+}
+
+/// A pcap file of Ethernet frames with timestamps in micro- or nanoseconds.
+pub fn pcap(packets: &[Packet], big_endian: bool, nanos: bool) -> Vec<u8> {
+    let mut file = Writer {
+        bytes: Vec::new(),
+        big_endian,
+    };
+    file.u32(if nanos { 0xa1b2_3c4d } else { 0xa1b2_c3d4 });
+    file.u16(2);
+    file.u16(4);
+    file.u32(0);
+    file.u32(0);
+    file.u32(65_535);
+    file.u32(1);
+    for packet in packets {
+        let frame = packet.frame();
+        let fraction = packet.micros % 1_000_000;
+        file.u32((packet.micros / 1_000_000) as u32);
+        file.u32(if nanos { fraction * 1000 } else { fraction } as u32);
+        file.u32(frame.len() as u32);
+        file.u32(frame.len() as u32);
+        file.bytes.extend(frame);
+    }
+    file.bytes
+}
+
+/// A pcapng file with one Ethernet interface of the given `if_tsresol` and `if_tsoffset`.
+/// Without an offset, the timestamps are the microseconds of the packets in that resolution.
+pub fn pcapng(
+    packets: &[Packet],
+    big_endian: bool,
+    resolution: Option<u8>,
+    offset_secs: Option<i64>,
+) -> Vec<u8> {
+    let mut file = Writer {
+        bytes: Vec::new(),
+        big_endian,
+    };
+    let mut section = Writer {
+        bytes: Vec::new(),
+        big_endian,
+    };
+    section.u32(0x1a2b_3c4d);
+    section.u16(1);
+    section.u16(0);
+    section.i64(-1);
+    file.block(0x0a0d_0d0a, &section.bytes);
+
+    let mut interface = Writer {
+        bytes: Vec::new(),
+        big_endian,
+    };
+    interface.u16(1);
+    interface.u16(0);
+    interface.u32(65_535);
+    if let Some(resolution) = resolution {
+        interface.u16(9);
+        interface.u16(1);
+        interface.bytes.extend([resolution, 0, 0, 0]);
+    }
+    if let Some(offset_secs) = offset_secs {
+        interface.u16(14);
+        interface.u16(8);
+        interface.i64(offset_secs);
+    }
+    interface.u16(0);
+    interface.u16(0);
+    file.block(1, &interface.bytes);
+
+    let units_per_micro = match resolution {
+        Some(resolution) if resolution & 0x80 == 0 && resolution >= 6 => {
+            10u64.pow(resolution as u32 - 6)
+        }
+        _ => 1,
+    };
+    for packet in packets {
+        let frame = packet.frame();
+        let units = packet.micros * units_per_micro;
+        let mut body = Writer {
+            bytes: Vec::new(),
+            big_endian,
+        };
+        body.u32(0);
+        body.u32((units >> 32) as u32);
+        body.u32(units as u32);
+        body.u32(frame.len() as u32);
+        body.u32(frame.len() as u32);
+        body.bytes.extend(frame);
+        file.block(6, &body.bytes);
+    }
+    file.bytes
+}
+
+/// A CONNECT packet of the given protocol level.
+pub fn connect(level: u8) -> Vec<u8> {
+    let mut body = b"\x00\x04MQTT".to_vec();
+    body.extend([level, 0x02, 0, 60]);
+    if level == 5 {
+        body.push(0);
+    }
+    body.extend(b"\x00\x03agv");
+    packet(0x10, &body)
+}
+
+/// A PUBLISH packet of MQTT 3.1.1.
+pub fn publish(topic: &str, payload: &[u8], qos: u8, packet_id: u16) -> Vec<u8> {
+    let mut body = string(topic);
+    if qos > 0 {
+        body.extend(packet_id.to_be_bytes());
+    }
+    body.extend(payload);
+    packet(0x30 | qos << 1, &body)
+}
+
+/// A PUBLISH packet of MQTT 5 at QoS 0 with a user property and maybe a topic alias.
+pub fn publish_v5(topic: &str, topic_alias: Option<u16>, payload: &[u8]) -> Vec<u8> {
+    let mut properties = vec![0x26];
+    properties.extend(string("source"));
+    properties.extend(string("test"));
+    if let Some(alias) = topic_alias {
+        properties.push(0x23);
+        properties.extend(alias.to_be_bytes());
+    }
+    let mut body = string(topic);
+    body.extend(varint(properties.len()));
+    body.extend(properties);
+    body.extend(payload);
+    packet(0x30, &body)
+}
+
+fn packet(first: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![first];
+    packet.extend(varint(body.len()));
+    packet.extend(body);
+    packet
+}
+
+fn string(value: &str) -> Vec<u8> {
+    let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+    bytes.extend(value.as_bytes());
+    bytes
+}
+
+fn varint(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value % 128) as u8;
+        value /= 128;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
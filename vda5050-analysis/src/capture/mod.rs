@@ -0,0 +1,564 @@
+//! Importing the MQTT messages of network captures, e.g. taken with Wireshark or tcpdump on site.
+//!
+//! The frames of pcap and pcapng files are put back together into the TCP streams of the
+//! connections to the broker, and the PUBLISH packets of MQTT 3.1.1 and 5 in them become the
+//! entries of a recording, received at the time their last frame was captured.
+use crate::recording::RecordEntry;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, Read};
+use std::net::SocketAddr;
+use std::path::Path;
+
+#[cfg(test)]
+mod fixtures;
+mod mqtt;
+mod pcap;
+mod tcp;
+
+use mqtt::{Decoded, Packet, TopicAliases};
+use pcap::FrameReader;
+use tcp::{NotTcp, Stream};
+
+/// Whether the file is a capture by its extension, e.g. `site.pcapng`.
+pub fn is_capture(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ["pcap", "pcapng", "cap"]
+                .iter()
+                .any(|capture| extension.eq_ignore_ascii_case(capture))
+        })
+}
+
+/// Why a capture could not be read.
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not a pcap or pcapng file, or is broken.
+    Format(String),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Io(err) => write!(f, "{err}"),
+            CaptureError::Format(message) => write!(f, "invalid capture: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(err: io::Error) -> Self {
+        CaptureError::Io(err)
+    }
+}
+
+impl From<CaptureError> for io::Error {
+    fn from(err: CaptureError) -> Self {
+        match err {
+            CaptureError::Io(err) => err,
+            CaptureError::Format(_) => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// What to take from a capture.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureOptions {
+    /// The TCP ports of the broker. MQTT over TLS cannot be decoded.
+    pub ports: Vec<u16>,
+    /// Keep every copy of a message instead of the first one. A capture on the broker host holds
+    /// a message once as published and once more for every subscriber it is delivered to.
+    pub keep_duplicates: bool,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            ports: vec![1883],
+            keep_duplicates: false,
+        }
+    }
+}
+
+/// A PUBLISH packet found in a capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedPublish {
+    /// The number of the frame that completed the packet, starting at 1 like in Wireshark.
+    pub frame: u64,
+    /// The time that frame was captured.
+    pub captured_at: DateTime<Utc>,
+    /// The sender, the client or the broker.
+    pub source: SocketAddr,
+    /// The receiver, the broker or the client.
+    pub destination: SocketAddr,
+    /// The full MQTT topic, with topic aliases of MQTT 5 resolved.
+    pub topic: String,
+    /// The quality of service, 0 to 2.
+    pub qos: u8,
+    /// Whether the message is retained, or is delivered because it was retained.
+    pub retain: bool,
+    /// Whether the packet is sent again, after the first one was not acknowledged.
+    pub dup: bool,
+    /// The packet identifier of QoS 1 and 2.
+    pub packet_id: Option<u16>,
+    /// The payload as published.
+    pub payload: Vec<u8>,
+}
+
+impl CapturedPublish {
+    /// The message as a line of a recording file, if the payload is JSON.
+    pub fn to_entry(&self) -> Result<RecordEntry, serde_json::Error> {
+        Ok(RecordEntry {
+            received_at: self.captured_at,
+            topic: self.topic.clone(),
+            payload: serde_json::from_slice(&self.payload)?,
+        })
+    }
+}
+
+/// What was found in a capture besides the messages.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureSummary {
+    /// The number of frames.
+    pub frames: u64,
+    /// The number of frames that could not be used, because of an unknown link type or because
+    /// they are fragments of an IP packet.
+    pub unsupported_frames: u64,
+    /// The number of TCP segments to or from the broker.
+    pub segments: u64,
+    /// The number of TCP connections to the broker.
+    pub connections: usize,
+    /// The number of PUBLISH packets, including the duplicates.
+    pub publishes: usize,
+    /// The number of PUBLISH packets dropped as copies of an earlier one.
+    pub duplicates: usize,
+    /// The number of places where bytes of a stream are missing from the capture.
+    pub gaps: usize,
+    /// The number of stream bytes skipped because they are not part of a complete packet, e.g.
+    /// around a gap or when the capture starts in the middle of a packet.
+    pub skipped_bytes: u64,
+    /// The number of PUBLISH packets of MQTT 5 with a topic alias set before the capture started.
+    pub unknown_topic_aliases: usize,
+    /// Whether the file ends in the middle of a frame, e.g. because the capture was killed.
+    pub truncated: bool,
+}
+
+/// The messages of a capture, ordered by capture time.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    /// Every PUBLISH packet found, without the duplicates unless asked for.
+    pub publishes: Vec<CapturedPublish>,
+    /// What was found besides.
+    pub summary: CaptureSummary,
+}
+
+/// One direction of a connection to the broker.
+#[derive(Default)]
+struct Direction {
+    stream: Stream,
+    /// The bytes of the stream that are not decoded yet.
+    buffer: Vec<u8>,
+    /// Whether the buffer starts with a packet, which is known after the SYN.
+    synced: bool,
+    aliases: TopicAliases,
+    /// The frame and time of the latest segment.
+    last_frame: (u64, DateTime<Utc>),
+}
+
+/// A connection to the broker.
+#[derive(Default)]
+struct Connection {
+    /// The protocol level from the CONNECT packet, e.g. 4 for MQTT 3.1.1 or 5.
+    level: Option<u8>,
+    to_broker: Direction,
+    from_broker: Direction,
+}
+
+impl Capture {
+    /// Reads a pcap or pcapng file.
+    pub fn read(path: &Path, options: &CaptureOptions) -> Result<Self, CaptureError> {
+        Capture::read_from(BufReader::new(File::open(path)?), options)
+    }
+
+    /// Reads a pcap or pcapng file from a reader.
+    pub fn read_from<R: Read>(reader: R, options: &CaptureOptions) -> Result<Self, CaptureError> {
+        let mut frames = FrameReader::new(reader)?;
+        let mut capture = Capture::default();
+        // By client and broker address.
+        let mut connections: HashMap<(SocketAddr, SocketAddr), Connection> = HashMap::new();
+        loop {
+            let frame = match frames.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(CaptureError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    capture.summary.truncated = true;
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+            capture.summary.frames += 1;
+            let segment = match tcp::segment(frame.link_type, &frame.data) {
+                Ok(segment) => segment,
+                Err(NotTcp::UnknownLinkType(_) | NotTcp::Fragment) => {
+                    capture.summary.unsupported_frames += 1;
+                    continue;
+                }
+                Err(NotTcp::OtherProtocol | NotTcp::Truncated) => continue,
+            };
+            let to_broker = options.ports.contains(&segment.destination.port());
+            if !to_broker && !options.ports.contains(&segment.source.port()) {
+                continue;
+            }
+            capture.summary.segments += 1;
+            let key = if to_broker {
+                (segment.source, segment.destination)
+            } else {
+                (segment.destination, segment.source)
+            };
+            let connection = connections.entry(key).or_insert_with(|| {
+                capture.summary.connections += 1;
+                Connection::default()
+            });
+            if to_broker && segment.syn && connection.to_broker.last_frame.0 != 0 {
+                // The client connects again from the same port.
+                *connection = Connection::default();
+                capture.summary.connections += 1;
+            }
+            let Connection {
+                level,
+                to_broker: client,
+                from_broker: broker,
+            } = connection;
+            let direction = if to_broker { client } else { broker };
+            direction.last_frame = (frame.number, frame.captured_at);
+            if segment.syn {
+                direction.buffer.clear();
+                direction.synced = true;
+            }
+            let (data, gap) = direction.stream.push(&segment);
+            if gap {
+                capture.skip_gap(direction);
+            }
+            direction.buffer.extend(data);
+            let addresses = (segment.source, segment.destination);
+            capture.decode(direction, level, addresses);
+            if segment.closed && direction.stream.has_gap() {
+                capture.skip_gap(direction);
+                let data = direction.stream.skip_gap();
+                direction.buffer.extend(data);
+                capture.decode(direction, level, addresses);
+            }
+        }
+        // Segments still waiting for missing bytes at the end of the capture.
+        for ((client, broker), mut connection) in connections {
+            for (direction, addresses) in [
+                (&mut connection.to_broker, (client, broker)),
+                (&mut connection.from_broker, (broker, client)),
+            ] {
+                if !direction.stream.has_gap() {
+                    continue;
+                }
+                capture.skip_gap(direction);
+                let data = direction.stream.skip_gap();
+                direction.buffer.extend(data);
+                capture.decode(direction, &mut connection.level, addresses);
+            }
+        }
+        capture.finish(options);
+        Ok(capture)
+    }
+
+    /// Drops the bytes before a gap, the next packet is searched after it.
+    fn skip_gap(&mut self, direction: &mut Direction) {
+        self.summary.gaps += 1;
+        self.summary.skipped_bytes += direction.buffer.len() as u64;
+        direction.buffer.clear();
+        direction.synced = false;
+    }
+
+    /// Decodes the complete packets at the start of the buffer, sent from the first to the second
+    /// address.
+    fn decode(
+        &mut self,
+        direction: &mut Direction,
+        level: &mut Option<u8>,
+        (source, destination): (SocketAddr, SocketAddr),
+    ) {
+        loop {
+            if !direction.synced {
+                // The first place where a packet may start, waiting for more bytes if needed.
+                let start =
+                    (0..direction.buffer.len()).find_map(|at| {
+                        match mqtt::plausible_start(&direction.buffer[at..]) {
+                            Some(true) => Some((at, true)),
+                            Some(false) => None,
+                            None => Some((at, false)),
+                        }
+                    });
+                let (at, plausible) = start.unwrap_or((direction.buffer.len(), false));
+                self.summary.skipped_bytes += at as u64;
+                direction.buffer.drain(..at);
+                if !plausible {
+                    return;
+                }
+                direction.synced = true;
+            }
+            match mqtt::decode(&direction.buffer, *level) {
+                Decoded::Incomplete => return,
+                Decoded::Invalid => {
+                    self.summary.skipped_bytes += 1;
+                    direction.buffer.remove(0);
+                    direction.synced = false;
+                }
+                Decoded::Packet(packet, len) => {
+                    direction.buffer.drain(..len);
+                    match packet {
+                        Packet::Connect { level: connected } => *level = Some(connected),
+                        Packet::Publish(mut publish) => {
+                            self.summary.publishes += 1;
+                            if !direction.aliases.resolve(&mut publish) {
+                                self.summary.unknown_topic_aliases += 1;
+                                continue;
+                            }
+                            let (frame, captured_at) = direction.last_frame;
+                            self.publishes.push(CapturedPublish {
+                                frame,
+                                captured_at,
+                                source,
+                                destination,
+                                topic: publish.topic,
+                                qos: publish.qos,
+                                retain: publish.retain,
+                                dup: publish.dup,
+                                packet_id: publish.packet_id,
+                                payload: publish.payload,
+                            });
+                        }
+                        Packet::Other => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Orders the messages by capture time and drops the copies unless asked to keep them.
+    fn finish(&mut self, options: &CaptureOptions) {
+        self.publishes
+            .sort_by_key(|publish| (publish.captured_at, publish.frame));
+        if options.keep_duplicates {
+            return;
+        }
+        // VDA 5050 messages carry a header ID and a timestamp, so equal payloads on a topic are
+        // copies of one message: delivered to another subscriber, or sent again with QoS 1 or 2.
+        let mut seen = HashSet::new();
+        let before = self.publishes.len();
+        self.publishes.retain(|publish| {
+            let mut hasher = DefaultHasher::new();
+            (&publish.topic, &publish.payload).hash(&mut hasher);
+            seen.insert(hasher.finish())
+        });
+        self.summary.duplicates = before - self.publishes.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{self, BROKER, CLIENT, Packet};
+    use super::*;
+
+    /// 2023-11-14T22:13:20Z in microseconds.
+    const START: u64 = 1_700_000_000_000_000;
+    const STATE: &str = "uagv/v2/acme/agv1/state";
+    const ORDER: &str = "uagv/v2/acme/agv1/order";
+
+    fn read(file: &[u8]) -> Capture {
+        Capture::read_from(file, &CaptureOptions::default()).expect("valid capture")
+    }
+
+    fn topics(capture: &Capture) -> Vec<&str> {
+        capture
+            .publishes
+            .iter()
+            .map(|publish| publish.topic.as_str())
+            .collect()
+    }
+
+    /// A client that connects with MQTT 3.1.1, publishes a state and receives an order.
+    fn session() -> Vec<Packet> {
+        let connect = fixtures::connect(4);
+        let state = fixtures::publish(STATE, br#"{"headerId":1}"#, 0, 0);
+        let order = fixtures::publish(ORDER, br#"{"headerId":7}"#, 1, 42);
+        vec![
+            Packet::syn(START, true, 1000),
+            Packet::syn(START + 100, false, 5000),
+            Packet::data(START + 200, true, 1001, &connect),
+            Packet::data(START + 250_000, true, 1001 + connect.len() as u32, &state),
+            Packet::data(START + 500_000, false, 5001, &order),
+        ]
+    }
+
+    #[test]
+    fn reads_pcap_in_both_byte_orders() {
+        for big_endian in [false, true] {
+            for nanos in [false, true] {
+                let capture = read(&fixtures::pcap(&session(), big_endian, nanos));
+                assert_eq!(topics(&capture), [STATE, ORDER]);
+                let state = &capture.publishes[0];
+                assert_eq!((state.source, state.destination), (CLIENT, BROKER));
+                assert_eq!(state.frame, 4);
+                assert_eq!(
+                    state.captured_at.to_rfc3339(),
+                    "2023-11-14T22:13:20.250+00:00"
+                );
+                let order = &capture.publishes[1];
+                assert_eq!(
+                    (order.source, order.qos, order.packet_id),
+                    (BROKER, 1, Some(42))
+                );
+                assert_eq!(capture.summary.connections, 1);
+                assert_eq!(capture.summary.skipped_bytes, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_pcapng_in_both_byte_orders() {
+        for big_endian in [false, true] {
+            let capture = read(&fixtures::pcapng(&session(), big_endian, Some(9), None));
+            assert_eq!(topics(&capture), [STATE, ORDER]);
+            assert_eq!(
+                capture.publishes[0].captured_at.to_rfc3339(),
+                "2023-11-14T22:13:20.250+00:00"
+            );
+            // Microseconds relative to an offset of the interface.
+            let mut packets = session();
+            for packet in &mut packets {
+                packet.micros -= START;
+            }
+            let capture = read(&fixtures::pcapng(
+                &packets,
+                big_endian,
+                None,
+                Some((START / 1_000_000) as i64),
+            ));
+            assert_eq!(
+                capture.publishes[1].captured_at.to_rfc3339(),
+                "2023-11-14T22:13:20.500+00:00"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_timestamps_out_of_range() {
+        let file = fixtures::pcapng(&session(), false, Some(0), Some(i64::MAX));
+        let err = Capture::read_from(&file[..], &CaptureOptions::default()).unwrap_err();
+        assert!(matches!(err, CaptureError::Format(_)), "{err}");
+    }
+
+    #[test]
+    fn puts_reordered_and_retransmitted_segments_in_order() {
+        let first = fixtures::publish(STATE, br#"{"headerId":1}"#, 1, 1);
+        let second = fixtures::publish(STATE, br#"{"headerId":2}"#, 1, 2);
+        let mut stream = first.clone();
+        stream.extend(&second);
+        let (a, b, c) = (&stream[..10], &stream[10..30], &stream[30..]);
+        let packets = [
+            Packet::syn(START, true, 99),
+            Packet::data(START + 1, true, 130, c),
+            Packet::data(START + 2, true, 100, a),
+            // The first segment sent again, with more bytes than before.
+            Packet::data(START + 3, true, 100, &stream[..20]),
+            Packet::data(START + 4, true, 110, b),
+            // The whole packet sent again with the DUP flag is a copy.
+            Packet::data(START + 5, true, 100 + stream.len() as u32, &{
+                let mut again = second.clone();
+                again[0] |= 0x08;
+                again
+            }),
+        ];
+        let capture = read(&fixtures::pcap(&packets, false, false));
+        let payloads: Vec<_> = capture
+            .publishes
+            .iter()
+            .map(|publish| publish.payload.as_slice())
+            .collect();
+        assert_eq!(payloads, [&br#"{"headerId":1}"#[..], br#"{"headerId":2}"#]);
+        // Both are complete with the frame that filled the gap.
+        assert_eq!(capture.publishes[0].frame, 5);
+        assert_eq!(capture.summary.duplicates, 1);
+        assert_eq!(capture.summary.gaps, 0);
+    }
+
+    #[test]
+    fn resolves_topic_aliases_of_mqtt_5() {
+        let connect = fixtures::connect(5);
+        let packets = [
+            Packet::syn(START, true, 0),
+            Packet::data(START + 1, true, 1, &connect),
+            Packet::data(START + 2, true, 1 + connect.len() as u32, &{
+                let mut bytes = fixtures::publish_v5(STATE, Some(3), br#"{"headerId":1}"#);
+                bytes.extend(fixtures::publish_v5("", Some(3), br#"{"headerId":2}"#));
+                bytes
+            }),
+        ];
+        let capture = read(&fixtures::pcapng(&packets, true, None, None));
+        assert_eq!(topics(&capture), [STATE, STATE]);
+        assert_eq!(capture.publishes[1].payload, br#"{"headerId":2}"#);
+        assert_eq!(capture.summary.unknown_topic_aliases, 0);
+    }
+
+    #[test]
+    fn starts_in_the_middle_of_a_packet() {
+        let cut = fixtures::publish(STATE, br#"{"headerId":1}"#, 0, 0);
+        let mut stream = cut[9..].to_vec();
+        stream.extend(fixtures::publish_v5(ORDER, Some(2), br#"{"headerId":2}"#));
+        // Set before the capture started, so the topic is not known.
+        stream.extend(fixtures::publish_v5("", Some(1), br#"{"headerId":3}"#));
+        stream.extend(fixtures::publish_v5("", Some(2), br#"{"headerId":4}"#));
+        let packets = [Packet::data(START, true, 7_000, &stream)];
+        let capture = read(&fixtures::pcap(&packets, false, false));
+        assert_eq!(topics(&capture), [ORDER, ORDER]);
+        assert_eq!(capture.summary.skipped_bytes, (cut.len() - 9) as u64);
+        assert_eq!(capture.summary.unknown_topic_aliases, 1);
+    }
+
+    #[test]
+    fn continues_after_a_segment_missing_from_the_capture() {
+        let state = fixtures::publish(STATE, br#"{"headerId":1}"#, 0, 0);
+        let order = fixtures::publish(ORDER, br#"{"headerId":2}"#, 0, 0);
+        let lost = state.len() as u32;
+        let mut packets = vec![
+            Packet::syn(START, true, 0),
+            Packet::data(START + 1, true, 1, &state[..5]),
+            // The rest of the state is missing.
+            Packet::data(START + 2, true, 1 + lost, &order),
+        ];
+        let mut fin = Packet::data(START + 3, true, 1 + lost + order.len() as u32, &[]);
+        fin.fin = true;
+        packets.push(fin);
+        let capture = read(&fixtures::pcap(&packets, false, false));
+        assert_eq!(topics(&capture), [ORDER]);
+        assert_eq!(capture.summary.gaps, 1);
+        assert_eq!(capture.summary.skipped_bytes, 5);
+    }
+
+    #[test]
+    fn keeps_what_was_read_from_a_truncated_file() {
+        let file = fixtures::pcap(&session(), false, false);
+        let capture = read(&file[..file.len() - 3]);
+        assert_eq!(topics(&capture), [STATE]);
+        assert!(capture.summary.truncated);
+    }
+}
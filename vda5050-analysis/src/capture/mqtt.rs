@@ -0,0 +1,297 @@
+//! Decoding the MQTT packets of a TCP stream, as far as needed to find the published messages.
+use std::collections::HashMap;
+
+const CONNECT: u8 = 1;
+const PUBLISH: u8 = 3;
+const PUBREL: u8 = 6;
+const SUBSCRIBE: u8 = 8;
+const UNSUBSCRIBE: u8 = 10;
+const AUTH: u8 = 15;
+
+/// The protocol level of MQTT 5.
+pub const MQTT_5: u8 = 5;
+/// The property of a PUBLISH packet of MQTT 5 that stands for its topic.
+const TOPIC_ALIAS: u8 = 0x23;
+
+/// A PUBLISH packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Publish {
+    /// The topic, empty if the packet refers to a topic alias set before.
+    pub topic: String,
+    /// The topic alias of MQTT 5 that is set or used.
+    pub topic_alias: Option<u16>,
+    pub qos: u8,
+    pub retain: bool,
+    pub dup: bool,
+    pub packet_id: Option<u16>,
+    pub payload: Vec<u8>,
+}
+
+/// A packet as far as it is of interest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    /// A CONNECT packet with the protocol level of the connection, 4 for MQTT 3.1.1.
+    Connect {
+        level: u8,
+    },
+    Publish(Publish),
+    Other,
+}
+
+/// The result of decoding the start of a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    /// A packet and the number of bytes it takes up.
+    Packet(Packet, usize),
+    /// The packet is not complete yet.
+    Incomplete,
+    /// The bytes are not an MQTT packet.
+    Invalid,
+}
+
+/// Decodes the packet at the start of the bytes. Without the protocol level, e.g. because the
+/// capture started after the CONNECT, a PUBLISH is taken for MQTT 5 only if its payload does not
+/// look like JSON right after the topic.
+pub fn decode(bytes: &[u8], level: Option<u8>) -> Decoded {
+    let Some(&first) = bytes.first() else {
+        return Decoded::Incomplete;
+    };
+    let (kind, flags) = (first >> 4, first & 0x0f);
+    let flags_valid = match kind {
+        0 => false,
+        PUBLISH => flags & 0x06 != 0x06,
+        PUBREL | SUBSCRIBE | UNSUBSCRIBE => flags == 0x02,
+        AUTH => level.is_none_or(|level| level == MQTT_5) && flags == 0,
+        _ => flags == 0,
+    };
+    if !flags_valid {
+        return Decoded::Invalid;
+    }
+    let (remaining, header_len) = match varint(&bytes[1..]) {
+        Some(Ok(varint)) => varint,
+        Some(Err(())) => return Decoded::Invalid,
+        None => return Decoded::Incomplete,
+    };
+    let len = 1 + header_len + remaining;
+    let Some(body) = bytes.get(1 + header_len..len) else {
+        return Decoded::Incomplete;
+    };
+    let packet = match kind {
+        CONNECT => connect(body),
+        PUBLISH => publish(flags, body, level),
+        _ => Some(Packet::Other),
+    };
+    match packet {
+        Some(packet) => Decoded::Packet(packet, len),
+        None => Decoded::Invalid,
+    }
+}
+
+/// Whether a packet may start at the beginning of the bytes, checked more strictly than by
+/// [`decode`] to find the next packet after bytes missing from the capture. `None` if more bytes
+/// are needed to tell.
+pub fn plausible_start(bytes: &[u8]) -> Option<bool> {
+    let &first = bytes.first()?;
+    let (remaining, header_len) = match varint(&bytes[1..])? {
+        Ok(varint) => varint,
+        Err(()) => return Some(false),
+    };
+    let body = &bytes[1 + header_len..];
+    match (first >> 4, first & 0x0f) {
+        (CONNECT, 0) => {
+            let name = body.get(..6)?;
+            Some(name == b"\x00\x04MQTT" || name == b"\x00\x06MQIs")
+        }
+        (PUBLISH, flags) if flags & 0x06 != 0x06 => {
+            let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+            if topic_len == 0 || 2 + topic_len > remaining {
+                return Some(false);
+            }
+            let topic = body.get(2..2 + topic_len)?;
+            Some(
+                std::str::from_utf8(topic)
+                    .is_ok_and(|topic| topic.contains('/') && !topic.contains(char::is_control)),
+            )
+        }
+        _ => Some(false),
+    }
+}
+
+/// The topics of the topic aliases one side of an MQTT 5 connection set.
+#[derive(Debug, Default)]
+pub struct TopicAliases(HashMap<u16, String>);
+
+impl TopicAliases {
+    /// Sets the topic of a PUBLISH from its alias, or remembers the alias of its topic. `false` if
+    /// the alias was never set.
+    pub fn resolve(&mut self, publish: &mut Publish) -> bool {
+        let Some(alias) = publish.topic_alias else {
+            return true;
+        };
+        if publish.topic.is_empty() {
+            match self.0.get(&alias) {
+                Some(topic) => publish.topic = topic.clone(),
+                None => return false,
+            }
+        } else {
+            self.0.insert(alias, publish.topic.clone());
+        }
+        true
+    }
+}
+
+fn connect(body: &[u8]) -> Option<Packet> {
+    let name_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let level = *body.get(2 + name_len)?;
+    Some(Packet::Connect { level })
+}
+
+fn publish(flags: u8, body: &[u8], level: Option<u8>) -> Option<Packet> {
+    let qos = (flags >> 1) & 0x03;
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = std::str::from_utf8(body.get(2..2 + topic_len)?).ok()?;
+    let mut rest = &body[2 + topic_len..];
+    let packet_id = if qos > 0 {
+        let id = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]);
+        rest = &rest[2..];
+        Some(id)
+    } else {
+        None
+    };
+    let properties = match level {
+        Some(MQTT_5) => Some(properties(rest)?),
+        Some(_) => None,
+        None if rest.first() == Some(&b'{') => None,
+        None => properties(rest),
+    };
+    let topic_alias = match properties {
+        Some((topic_alias, len)) => {
+            rest = &rest[len..];
+            topic_alias
+        }
+        None => None,
+    };
+    if topic.is_empty() && topic_alias.is_none() {
+        return None;
+    }
+    Some(Packet::Publish(Publish {
+        topic: topic.to_string(),
+        topic_alias,
+        qos,
+        retain: flags & 0x01 != 0,
+        dup: flags & 0x08 != 0,
+        packet_id,
+        payload: rest.to_vec(),
+    }))
+}
+
+/// Reads the properties of MQTT 5 and returns the topic alias and the number of bytes they take
+/// up, or `None` if they are malformed.
+fn properties(bytes: &[u8]) -> Option<(Option<u16>, usize)> {
+    let (len, header_len) = varint(bytes)?.ok()?;
+    let mut properties = bytes.get(header_len..header_len + len)?;
+    let mut topic_alias = None;
+    while let Some((&id, rest)) = properties.split_first() {
+        let value_len = match id {
+            // Byte properties.
+            0x01 | 0x17 | 0x19 | 0x24 | 0x25 | 0x28 | 0x29 | 0x2a => 1,
+            // Two byte integers.
+            0x13 | 0x21 | 0x22 | 0x23 => 2,
+            // Four byte integers.
+            0x02 | 0x11 | 0x18 | 0x27 => 4,
+            // Variable byte integers.
+            0x0b => varint(rest)?.ok()?.1,
+            // Strings and binary data.
+            0x03 | 0x08 | 0x09 | 0x12 | 0x15 | 0x16 | 0x1a | 0x1c | 0x1f => {
+                2 + u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize
+            }
+            // User properties, a pair of strings.
+            0x26 => {
+                let key = 2 + u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+                let value = rest.get(key..key + 2)?;
+                key + 2 + u16::from_be_bytes([value[0], value[1]]) as usize
+            }
+            _ => return None,
+        };
+        let value = rest.get(..value_len)?;
+        if id == TOPIC_ALIAS {
+            topic_alias = Some(u16::from_be_bytes([value[0], value[1]]));
+        }
+        properties = &rest[value_len..];
+    }
+    Some((topic_alias, header_len + len))
+}
+
+/// Reads a variable byte integer and returns its value and length, `None` if more bytes are needed
+/// and an error if it is longer than four bytes.
+fn varint(bytes: &[u8]) -> Option<Result<(usize, usize), ()>> {
+    let mut value = 0;
+    for (index, &byte) in bytes.iter().enumerate().take(4) {
+        value |= ((byte & 0x7f) as usize) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Some(Ok((value, index + 1)));
+        }
+    }
+    if bytes.len() >= 4 {
+        Some(Err(()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures;
+    use super::*;
+
+    #[test]
+    fn decodes_publish_packets() {
+        let bytes = fixtures::publish("uagv/v2/acme/agv1/state", b"{}", 1, 9);
+        let Decoded::Packet(Packet::Publish(publish), len) = decode(&bytes, Some(4)) else {
+            panic!("a PUBLISH packet");
+        };
+        assert_eq!(len, bytes.len());
+        assert_eq!(
+            (publish.topic.as_str(), publish.qos, publish.packet_id),
+            ("uagv/v2/acme/agv1/state", 1, Some(9))
+        );
+        assert_eq!(publish.payload, b"{}");
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1], Some(4)),
+            Decoded::Incomplete
+        );
+        assert_eq!(decode(&[0x00, 0x00], Some(4)), Decoded::Invalid);
+    }
+
+    #[test]
+    fn reads_the_topic_alias_of_mqtt_5_without_the_connect() {
+        let bytes = fixtures::publish_v5("", Some(7), b"{}");
+        for level in [Some(MQTT_5), None] {
+            let Decoded::Packet(Packet::Publish(publish), _) = decode(&bytes, level) else {
+                panic!("a PUBLISH packet");
+            };
+            assert_eq!(publish.topic_alias, Some(7));
+            assert_eq!(publish.payload, b"{}");
+        }
+        let mut aliases = TopicAliases::default();
+        let Decoded::Packet(Packet::Publish(mut publish), _) = decode(&bytes, None) else {
+            panic!("a PUBLISH packet");
+        };
+        assert!(!aliases.resolve(&mut publish));
+    }
+
+    #[test]
+    fn finds_a_plausible_start_after_a_gap() {
+        let publish = fixtures::publish("uagv/v2/acme/agv1/state", b"{}", 0, 0);
+        assert_eq!(plausible_start(&publish), Some(true));
+        assert_eq!(plausible_start(&fixtures::connect(4)), Some(true));
+        // Not enough bytes to tell.
+        assert_eq!(plausible_start(&publish[..3]), None);
+        // Bytes of a payload, and a topic without a level separator.
+        assert_eq!(plausible_start(b"\"headerId\":1}"), Some(false));
+        assert_eq!(
+            plausible_start(&fixtures::publish("state", b"{}", 0, 0)),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Reading the frames of pcap and pcapng files.
+use super::CaptureError;
+use chrono::{DateTime, Utc};
+use std::io::{self, Read};
+
+/// The magic number of a pcap file with timestamps in microseconds.
+const PCAP_MICROS: u32 = 0xa1b2_c3d4;
+/// The magic number of a pcap file with timestamps in nanoseconds.
+const PCAP_NANOS: u32 = 0xa1b2_3c4d;
+/// The type of the section header block that starts every pcapng file.
+const SECTION_HEADER: u32 = 0x0a0d_0d0a;
+/// The magic number of a section header block, which tells its byte order.
+const BYTE_ORDER: u32 = 0x1a2b_3c4d;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const OBSOLETE_PACKET: u32 = 2;
+const ENHANCED_PACKET: u32 = 6;
+/// Blocks are larger than this only in broken files, so they are not read into memory.
+const MAX_BLOCK_LEN: usize = 256 * 1024 * 1024;
+
+/// A frame as captured.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The number of the frame in the file, starting at 1 like in Wireshark.
+    pub number: u64,
+    /// The time the frame was captured.
+    pub captured_at: DateTime<Utc>,
+    /// The link-layer header type, e.g. 1 for Ethernet.
+    pub link_type: u32,
+    /// The captured bytes, which may be cut at the snapshot length.
+    pub data: Vec<u8>,
+}
+
+/// An interface of a pcapng section.
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: u32,
+    /// The timestamp units per second.
+    units_per_sec: u64,
+    /// Seconds to add to every timestamp.
+    offset_secs: i64,
+}
+
+enum Format {
+    Pcap {
+        big_endian: bool,
+        units_per_sec: u64,
+        link_type: u32,
+    },
+    Pcapng {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+/// Reads the frames of a pcap or pcapng file one after the other.
+pub struct FrameReader<R> {
+    reader: R,
+    format: Format,
+    number: u64,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Reads the file header and tells the format by its magic number.
+    pub fn new(mut reader: R) -> Result<Self, CaptureError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let format = if u32::from_be_bytes(magic) == SECTION_HEADER {
+            let big_endian = read_section_header(&mut reader)?;
+            Format::Pcapng {
+                big_endian,
+                interfaces: Vec::new(),
+            }
+        } else {
+            let (big_endian, units_per_sec) =
+                match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                    (PCAP_MICROS, _) => (false, 1_000_000),
+                    (PCAP_NANOS, _) => (false, 1_000_000_000),
+                    (_, PCAP_MICROS) => (true, 1_000_000),
+                    (_, PCAP_NANOS) => (true, 1_000_000_000),
+                    _ => {
+                        return Err(CaptureError::Format(
+                            "not a pcap or pcapng file".to_string(),
+                        ));
+                    }
+                };
+            // Version, time zone, significant figures and snapshot length are not needed.
+            let mut header = [0; 20];
+            reader.read_exact(&mut header)?;
+            Format::Pcap {
+                big_endian,
+                units_per_sec,
+                // The upper bits may tell about frame check sequences, which is not needed.
+                link_type: u32_at(&header, 16, big_endian) & 0xffff,
+            }
+        };
+        Ok(FrameReader {
+            reader,
+            format,
+            number: 0,
+        })
+    }
+
+    /// The next frame, or `None` at the end of the file.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, CaptureError> {
+        match &mut self.format {
+            Format::Pcap {
+                big_endian,
+                units_per_sec,
+                link_type,
+            } => {
+                let mut header = [0; 16];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None);
+                }
+                let secs = u32_at(&header, 0, *big_endian) as i64;
+                let fraction = u32_at(&header, 4, *big_endian) as u64;
+                let len = u32_at(&header, 8, *big_endian) as usize;
+                if len > MAX_BLOCK_LEN {
+                    return Err(CaptureError::Format(format!(
+                        "frame {} is {len} bytes long",
+                        self.number + 1
+                    )));
+                }
+                let mut data = vec![0; len];
+                self.reader.read_exact(&mut data)?;
+                self.number += 1;
+                Ok(Some(Frame {
+                    number: self.number,
+                    captured_at: timestamp(secs, fraction, *units_per_sec),
+                    link_type: *link_type,
+                    data,
+                }))
+            }
+            Format::Pcapng {
+                big_endian,
+                interfaces,
+            } => loop {
+                let mut header = [0; 4];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None);
+                }
+                let block_type = u32_at(&header, 0, *big_endian);
+                if block_type == SECTION_HEADER {
+                    // A new section may have another byte order and defines its own interfaces.
+                    *big_endian = read_section_header(&mut self.reader)?;
+                    interfaces.clear();
+                    continue;
+                }
+                let body = read_block_body(&mut self.reader, *big_endian)?;
+                match block_type {
+                    INTERFACE_DESCRIPTION => {
+                        interfaces.push(read_interface(&body, *big_endian)?);
+                    }
+                    ENHANCED_PACKET | OBSOLETE_PACKET if body.len() >= 20 => {
+                        let interface = if block_type == ENHANCED_PACKET {
+                            u32_at(&body, 0, *big_endian)
+                        } else {
+                            u16_at(&body, 0, *big_endian) as u32
+                        };
+                        let Some(interface) = interfaces.get(interface as usize) else {
+                            return Err(CaptureError::Format(format!(
+                                "frame {} refers to the unknown interface {interface}",
+                                self.number + 1
+                            )));
+                        };
+                        let units = (u32_at(&body, 4, *big_endian) as u64) << 32
+                            | u32_at(&body, 8, *big_endian) as u64;
+                        let len = (u32_at(&body, 12, *big_endian) as usize).min(body.len() - 20);
+                        self.number += 1;
+                        let secs = i64::try_from(units / interface.units_per_sec)
+                            .ok()
+                            .and_then(|secs| secs.checked_add(interface.offset_secs))
+                            .ok_or_else(|| {
+                                CaptureError::Format(format!(
+                                    "frame {} has a timestamp out of range",
+                                    self.number
+                                ))
+                            })?;
+                        return Ok(Some(Frame {
+                            number: self.number,
+                            captured_at: timestamp(
+                                secs,
+                                units % interface.units_per_sec,
+                                interface.units_per_sec,
+                            ),
+                            link_type: interface.link_type,
+                            data: body[20..20 + len].to_vec(),
+                        }));
+                    }
+                    // Simple packet blocks have no timestamp, name resolution and statistics
+                    // blocks carry no frames.
+                    _ => {}
+                }
+            },
+        }
+    }
+}
+
+/// Reads the rest of a section header block after its type and returns whether it is big-endian.
+fn read_section_header<R: Read>(reader: &mut R) -> Result<bool, CaptureError> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+        (BYTE_ORDER, _) => false,
+        (_, BYTE_ORDER) => true,
+        _ => {
+            return Err(CaptureError::Format(
+                "invalid byte order of a pcapng section".to_string(),
+            ));
+        }
+    };
+    let len = u32_at(&len, 0, big_endian) as usize;
+    if !(16..=MAX_BLOCK_LEN).contains(&len) {
+        return Err(CaptureError::Format(format!(
+            "invalid length {len} of a pcapng section header"
+        )));
+    }
+    // The version, the section length and the options are not needed.
+    io::copy(&mut reader.take(len as u64 - 12), &mut io::sink())?;
+    Ok(big_endian)
+}
+
+/// Reads the length, the body and the trailing length of a block whose type was read.
+fn read_block_body<R: Read>(reader: &mut R, big_endian: bool) -> Result<Vec<u8>, CaptureError> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32_at(&len, 0, big_endian) as usize;
+    if !(12..=MAX_BLOCK_LEN).contains(&len) || !len.is_multiple_of(4) {
+        return Err(CaptureError::Format(format!(
+            "invalid length {len} of a pcapng block"
+        )));
+    }
+    let mut body = vec![0; len - 8];
+    reader.read_exact(&mut body)?;
+    body.truncate(len - 12);
+    Ok(body)
+}
+
+/// Reads an interface description block with its timestamp resolution and offset.
+fn read_interface(body: &[u8], big_endian: bool) -> Result<Interface, CaptureError> {
+    if body.len() < 8 {
+        return Err(CaptureError::Format(
+            "truncated pcapng interface description".to_string(),
+        ));
+    }
+    let mut interface = Interface {
+        link_type: u16_at(body, 0, big_endian) as u32,
+        units_per_sec: 1_000_000,
+        offset_secs: 0,
+    };
+    let mut options = &body[8..];
+    while options.len() >= 4 {
+        let code = u16_at(options, 0, big_endian);
+        let len = u16_at(options, 2, big_endian) as usize;
+        let Some(value) = options.get(4..4 + len) else {
+            break;
+        };
+        match (code, value) {
+            // The end of the options.
+            (0, _) => break,
+            // if_tsresol: a negative power of ten, or of two if the high bit is set.
+            (9, &[resolution]) => {
+                let exponent = (resolution & 0x7f) as u32;
+                interface.units_per_sec = if resolution & 0x80 == 0 {
+                    10u64.checked_pow(exponent)
+                } else {
+                    2u64.checked_pow(exponent)
+                }
+                .filter(|units| *units > 0)
+                .ok_or_else(|| {
+                    CaptureError::Format(format!("unsupported timestamp resolution {resolution}"))
+                })?;
+            }
+            // if_tsoffset: seconds since the epoch to add to every timestamp.
+            (14, value) if value.len() == 8 => {
+                let bytes = value.try_into().expect("8 bytes");
+                interface.offset_secs = if big_endian {
+                    i64::from_be_bytes(bytes)
+                } else {
+                    i64::from_le_bytes(bytes)
+                };
+            }
+            _ => {}
+        }
+        options = options
+            .get(4 + len.next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+    Ok(interface)
+}
+
+/// Fills the buffer, or returns `false` if the file ends right before it.
+fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+fn timestamp(secs: i64, fraction: u64, units_per_sec: u64) -> DateTime<Utc> {
+    let nanos = (fraction as u128 * 1_000_000_000 / units_per_sec as u128) as u32;
+    DateTime::from_timestamp(secs, nanos.min(999_999_999)).unwrap_or_default()
+}
+
+fn u16_at(bytes: &[u8], at: usize, big_endian: bool) -> u16 {
+    let bytes = [bytes[at], bytes[at + 1]];
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize, big_endian: bool) -> u32 {
+    let bytes = [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
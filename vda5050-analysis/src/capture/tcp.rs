@@ -0,0 +1,343 @@
+//! Finding the TCP segments in captured frames and putting the streams back together.
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+/// The raw IP link type of OpenBSD, which some tools still write.
+const DLT_RAW: u32 = 12;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+const PROTOCOL_TCP: u8 = 6;
+
+const FLAG_FIN: u8 = 0x01;
+const FLAG_SYN: u8 = 0x02;
+const FLAG_RST: u8 = 0x04;
+
+/// The most out-of-order segments kept per stream. Beyond that, a segment is assumed to be
+/// missing from the capture and the stream continues after the gap.
+const MAX_PENDING: usize = 256;
+
+/// A TCP segment of a captured frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment<'a> {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub sequence: u32,
+    pub syn: bool,
+    /// Whether the sender closed or reset the connection.
+    pub closed: bool,
+    pub payload: &'a [u8],
+}
+
+/// Why a frame holds no TCP segment that can be used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotTcp {
+    /// Another protocol, e.g. ARP or UDP.
+    OtherProtocol,
+    /// A link type that is not supported.
+    UnknownLinkType(u32),
+    /// A fragment of an IP packet, which are not put back together.
+    Fragment,
+    /// The frame is shorter than its headers tell.
+    Truncated,
+}
+
+/// Finds the TCP segment in a frame of the given link type.
+pub fn segment(link_type: u32, frame: &[u8]) -> Result<Segment<'_>, NotTcp> {
+    let (ethertype, packet) = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = u16_at(frame, 12)?;
+            let mut at = 14;
+            while matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+                ethertype = u16_at(frame, at + 2)?;
+                at += 4;
+            }
+            (Some(ethertype), frame.get(at..).ok_or(NotTcp::Truncated)?)
+        }
+        LINKTYPE_LINUX_SLL => (
+            Some(u16_at(frame, 14)?),
+            frame.get(16..).ok_or(NotTcp::Truncated)?,
+        ),
+        LINKTYPE_LINUX_SLL2 => (
+            Some(u16_at(frame, 0)?),
+            frame.get(20..).ok_or(NotTcp::Truncated)?,
+        ),
+        // The address family of the loopback header is in the byte order of the capture host, or
+        // big-endian for LOOP, so it is told by the IP version instead.
+        LINKTYPE_NULL | LINKTYPE_LOOP => (None, frame.get(4..).ok_or(NotTcp::Truncated)?),
+        LINKTYPE_RAW | DLT_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => (None, frame),
+        _ => return Err(NotTcp::UnknownLinkType(link_type)),
+    };
+    let version = packet.first().ok_or(NotTcp::Truncated)? >> 4;
+    match (ethertype, version) {
+        (Some(ETHERTYPE_IPV4) | None, 4) => ipv4(packet),
+        (Some(ETHERTYPE_IPV6) | None, 6) => ipv6(packet),
+        _ => Err(NotTcp::OtherProtocol),
+    }
+}
+
+fn ipv4(packet: &[u8]) -> Result<Segment<'_>, NotTcp> {
+    let header_len = ((packet.first().ok_or(NotTcp::Truncated)? & 0x0f) as usize) * 4;
+    let total_len = u16_at(packet, 2)? as usize;
+    let fragment = u16_at(packet, 6)?;
+    if packet.get(9) != Some(&PROTOCOL_TCP) {
+        return Err(NotTcp::OtherProtocol);
+    }
+    // More fragments follow, or this is not the first one.
+    if fragment & 0x2000 != 0 || fragment & 0x1fff != 0 {
+        return Err(NotTcp::Fragment);
+    }
+    let source = Ipv4Addr::from(addr::<4>(packet, 12)?);
+    let destination = Ipv4Addr::from(addr::<4>(packet, 16)?);
+    // Ethernet pads short frames, the total length tells where the packet ends. A length of zero
+    // is written by hosts that offload segmentation to the network card.
+    let end = if total_len == 0 {
+        packet.len()
+    } else {
+        total_len.min(packet.len())
+    };
+    let body = packet.get(header_len..end).ok_or(NotTcp::Truncated)?;
+    tcp(source.into(), destination.into(), body)
+}
+
+fn ipv6(packet: &[u8]) -> Result<Segment<'_>, NotTcp> {
+    let payload_len = u16_at(packet, 4)? as usize;
+    let mut next_header = *packet.get(6).ok_or(NotTcp::Truncated)?;
+    let source = Ipv6Addr::from(addr::<16>(packet, 8)?);
+    let destination = Ipv6Addr::from(addr::<16>(packet, 24)?);
+    let end = if payload_len == 0 {
+        packet.len()
+    } else {
+        (40 + payload_len).min(packet.len())
+    };
+    let mut at = 40;
+    loop {
+        match next_header {
+            PROTOCOL_TCP => break,
+            // Hop-by-hop, routing and destination options.
+            0 | 43 | 60 => {
+                next_header = *packet.get(at).ok_or(NotTcp::Truncated)?;
+                at += (*packet.get(at + 1).ok_or(NotTcp::Truncated)? as usize + 1) * 8;
+            }
+            44 => return Err(NotTcp::Fragment),
+            _ => return Err(NotTcp::OtherProtocol),
+        }
+    }
+    let body = packet.get(at..end).ok_or(NotTcp::Truncated)?;
+    tcp(source.into(), destination.into(), body)
+}
+
+fn tcp(source: IpAddr, destination: IpAddr, segment: &[u8]) -> Result<Segment<'_>, NotTcp> {
+    let header_len = ((*segment.get(12).ok_or(NotTcp::Truncated)? >> 4) as usize) * 4;
+    let flags = *segment.get(13).ok_or(NotTcp::Truncated)?;
+    Ok(Segment {
+        source: SocketAddr::new(source, u16_at(segment, 0)?),
+        destination: SocketAddr::new(destination, u16_at(segment, 2)?),
+        sequence: u32::from_be_bytes(addr::<4>(segment, 4)?),
+        syn: flags & FLAG_SYN != 0,
+        closed: flags & (FLAG_FIN | FLAG_RST) != 0,
+        payload: segment.get(header_len..).ok_or(NotTcp::Truncated)?,
+    })
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, NotTcp> {
+    Ok(u16::from_be_bytes(addr::<2>(bytes, at)?))
+}
+
+fn addr<const N: usize>(bytes: &[u8], at: usize) -> Result<[u8; N], NotTcp> {
+    bytes
+        .get(at..at + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(NotTcp::Truncated)
+}
+
+/// One direction of a TCP connection, put back together in sequence order.
+#[derive(Debug, Default)]
+pub struct Stream {
+    /// The sequence number of the next byte expected, unknown until the first segment.
+    next: Option<u32>,
+    /// Segments that arrived before an earlier one, by sequence number.
+    pending: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Stream {
+    /// Adds a segment and returns the bytes that now follow the stream in order, and whether a
+    /// gap was skipped before them.
+    pub fn push(&mut self, segment: &Segment) -> (Vec<u8>, bool) {
+        let mut sequence = segment.sequence;
+        if segment.syn {
+            // The SYN takes up one sequence number.
+            sequence = sequence.wrapping_add(1);
+            self.next = Some(sequence);
+            self.pending.clear();
+        }
+        let next = *self.next.get_or_insert(sequence);
+        let mut payload = segment.payload;
+        let offset = sequence.wrapping_sub(next) as i32;
+        if offset < 0 {
+            // A retransmission, of which only the part after what was seen is new.
+            let seen = offset.unsigned_abs() as usize;
+            if seen >= payload.len() {
+                return (Vec::new(), false);
+            }
+            payload = &payload[seen..];
+            sequence = next;
+        }
+        if payload.is_empty() {
+            return (Vec::new(), false);
+        }
+        if sequence != next {
+            self.pending
+                .entry(sequence)
+                .and_modify(|pending| {
+                    if pending.len() < payload.len() {
+                        *pending = payload.to_vec();
+                    }
+                })
+                .or_insert_with(|| payload.to_vec());
+            if self.pending.len() <= MAX_PENDING {
+                return (Vec::new(), false);
+            }
+            return (self.skip_gap(), true);
+        }
+        let mut data = payload.to_vec();
+        self.next = Some(next.wrapping_add(payload.len() as u32));
+        data.extend(self.drain_pending());
+        (data, false)
+    }
+
+    /// Continues after the bytes missing before the earliest pending segment, e.g. at the end of
+    /// the capture.
+    pub fn skip_gap(&mut self) -> Vec<u8> {
+        let Some(next) = self.next else {
+            return Vec::new();
+        };
+        // The pending segment closest after the expected sequence number, allowing for wrap around.
+        let Some(&earliest) = self
+            .pending
+            .keys()
+            .min_by_key(|sequence| sequence.wrapping_sub(next))
+        else {
+            return Vec::new();
+        };
+        self.next = Some(earliest);
+        self.drain_pending()
+    }
+
+    /// Whether segments are waiting for bytes missing before them.
+    pub fn has_gap(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn drain_pending(&mut self) -> Vec<u8> {
+        let mut data = Vec::new();
+        while let Some(mut next) = self.next {
+            let mut advanced = false;
+            self.pending.retain(|&sequence, pending| {
+                let offset = sequence.wrapping_sub(next) as i32;
+                if offset > 0 {
+                    return true;
+                }
+                let seen = offset.unsigned_abs() as usize;
+                if seen < pending.len() {
+                    data.extend_from_slice(&pending[seen..]);
+                    next = next.wrapping_add((pending.len() - seen) as u32);
+                    advanced = true;
+                }
+                false
+            });
+            self.next = Some(next);
+            if !advanced {
+                break;
+            }
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{self, BROKER, CLIENT};
+    use super::*;
+
+    fn data(sequence: u32, payload: &[u8]) -> Segment<'_> {
+        Segment {
+            source: CLIENT,
+            destination: BROKER,
+            sequence,
+            syn: false,
+            closed: false,
+            payload,
+        }
+    }
+
+    #[test]
+    fn finds_the_segment_of_an_ethernet_frame() {
+        let frame = fixtures::Packet::data(0, true, 77, b"mqtt").frame();
+        assert_eq!(segment(LINKTYPE_ETHERNET, &frame), Ok(data(77, b"mqtt")));
+        assert_eq!(
+            segment(LINKTYPE_ETHERNET, &frame[..40]),
+            Err(NotTcp::Truncated)
+        );
+        assert_eq!(segment(7, &frame), Err(NotTcp::UnknownLinkType(7)));
+    }
+
+    #[test]
+    fn continues_across_the_wrap_around_of_sequence_numbers() {
+        let mut stream = Stream::default();
+        let syn = Segment {
+            syn: true,
+            ..data(u32::MAX - 4, &[])
+        };
+        assert_eq!(stream.push(&syn), (Vec::new(), false));
+        // The second segment starts after the wrap around and arrives first.
+        assert_eq!(stream.push(&data(2, b"world")), (Vec::new(), false));
+        assert_eq!(
+            stream.push(&data(u32::MAX - 3, b"hello ")),
+            (b"hello world".to_vec(), false)
+        );
+        assert!(!stream.has_gap());
+        // A retransmission of bytes before the wrap around.
+        assert_eq!(
+            stream.push(&data(u32::MAX, b"lo world!")),
+            (b"!".to_vec(), false)
+        );
+    }
+
+    #[test]
+    fn skips_a_gap_when_too_many_segments_are_pending() {
+        let mut stream = Stream::default();
+        assert_eq!(stream.push(&data(1000, b"a")), (b"a".to_vec(), false));
+        // The byte at 1001 is never captured.
+        for index in 0..MAX_PENDING as u32 {
+            assert_eq!(stream.push(&data(1002 + index, b"b")), (Vec::new(), false));
+        }
+        let (bytes, gap) = stream.push(&data(1002 + MAX_PENDING as u32, b"c"));
+        assert!(gap);
+        assert_eq!(bytes.len(), MAX_PENDING + 1);
+        assert_eq!(bytes.last(), Some(&b'c'));
+        assert!(!stream.has_gap());
+    }
+
+    #[test]
+    fn skips_to_the_closest_pending_segment_across_the_wrap_around() {
+        let mut stream = Stream::default();
+        stream.push(&data(u32::MAX - 1, b"x"));
+        stream.push(&data(10, b"later"));
+        stream.push(&data(3, b"first "));
+        assert!(stream.has_gap());
+        assert_eq!(stream.skip_gap(), b"first ");
+        assert_eq!(stream.skip_gap(), b"later");
+        assert!(!stream.has_gap());
+    }
+}
@@ -0,0 +1,147 @@
+use super::{Check, Finding, Severity};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use vda5050_data_types::common::Action;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::ActionStatus;
+
+struct TrackedAction {
+    action_type: String,
+    sent_at: DateTime<Utc>,
+    last_status: Option<ActionStatus>,
+}
+
+/// Follows every action from the order or instant actions message it was sent with through
+/// the action states of the AGV and reports invalid status transitions and unfinished actions.
+pub(super) fn check(recording: &Recording) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut actions: BTreeMap<(AgvId, String), TrackedAction> = BTreeMap::new();
+    let mut last_state_at: HashMap<AgvId, DateTime<Utc>> = HashMap::new();
+
+    for recorded in &recording.messages {
+        let agv = &recorded.agv;
+        let finding = |severity, message| {
+            Finding::new(
+                Check::ActionLifecycle,
+                severity,
+                agv,
+                recorded.received_at,
+                message,
+            )
+        };
+        let sent: Vec<&Action> = match &recorded.message {
+            Message::Order(order) => order
+                .nodes
+                .iter()
+                .flat_map(|node| &node.actions)
+                .chain(order.edges.iter().flat_map(|edge| &edge.actions))
+                .collect(),
+            Message::InstantActions(instant_actions) => {
+                instant_actions.instant_actions.iter().collect()
+            }
+            Message::State(state) => {
+                last_state_at.insert(agv.clone(), recorded.received_at);
+                for action_state in &state.action_states {
+                    let key = (agv.clone(), action_state.action_id.clone());
+                    let tracked = actions.entry(key).or_insert_with(|| TrackedAction {
+                        action_type: action_state.action_type.clone().unwrap_or_default(),
+                        sent_at: recorded.received_at,
+                        last_status: None,
+                    });
+                    let status = &action_state.action_status;
+                    if let ActionStatus::Unknown(value) = status {
+                        findings.push(finding(
+                            Severity::Warning,
+                            format!("action {}: unknown status {value}", action_state.action_id),
+                        ));
+                    }
+                    if let Some(last_status) = &tracked.last_status
+                        && last_status != status
+                    {
+                        if last_status.is_final() {
+                            findings.push(finding(
+                                Severity::Error,
+                                format!(
+                                    "action {}: changed from final status {last_status:?} to {status:?}",
+                                    action_state.action_id
+                                ),
+                            ));
+                        } else if rank(status) < rank(last_status) {
+                            findings.push(finding(
+                                Severity::Warning,
+                                format!(
+                                    "action {}: went back from {last_status:?} to {status:?}",
+                                    action_state.action_id
+                                ),
+                            ));
+                        }
+                    }
+                    if *status == ActionStatus::Failed
+                        && tracked.last_status.as_ref() != Some(status)
+                    {
+                        findings.push(finding(
+                            Severity::Info,
+                            format!(
+                                "action {} ({}) failed",
+                                action_state.action_id, tracked.action_type
+                            ),
+                        ));
+                    }
+                    tracked.last_status = Some(status.clone());
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        for action in sent {
+            let key = (agv.clone(), action.action_id.clone());
+            actions.entry(key).or_insert_with(|| TrackedAction {
+                action_type: action.action_type.clone(),
+                sent_at: recorded.received_at,
+                last_status: None,
+            });
+        }
+    }
+
+    for ((agv, action_id), tracked) in actions {
+        let reported_after_sending = last_state_at
+            .get(&agv)
+            .is_some_and(|last_state_at| *last_state_at > tracked.sent_at);
+        let (severity, message) = match &tracked.last_status {
+            None if reported_after_sending => (
+                Severity::Warning,
+                format!(
+                    "action {action_id} ({}) was never reported in the state",
+                    tracked.action_type
+                ),
+            ),
+            Some(status) if !status.is_final() => (
+                Severity::Info,
+                format!(
+                    "action {action_id} ({}) is still {status:?} at the end of the recording",
+                    tracked.action_type
+                ),
+            ),
+            _ => continue,
+        };
+        findings.push(Finding::new(
+            Check::ActionLifecycle,
+            severity,
+            &agv,
+            tracked.sent_at,
+            message,
+        ));
+    }
+    findings
+}
+
+/// The position of a status in the lifecycle; running and paused may alternate.
+fn rank(status: &ActionStatus) -> u8 {
+    match status {
+        ActionStatus::Waiting => 0,
+        ActionStatus::Initializing => 1,
+        ActionStatus::Running | ActionStatus::Paused | ActionStatus::Unknown(_) => 2,
+        ActionStatus::Finished | ActionStatus::Failed => 3,
+    }
+}
@@ -0,0 +1,124 @@
+use super::{Check, Finding, Severity};
+use crate::recording::{AgvId, Recording};
+use std::collections::{HashMap, HashSet};
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::ErrorLevel;
+
+/// An error as it identifies itself across states: its type and references.
+type ErrorKey = (String, Vec<(String, String)>);
+
+/// Reports every fatal error an AGV raises in its state, once when it appears.
+pub(super) fn check(recording: &Recording) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    // The fatal errors of the last state per AGV.
+    let mut raised: HashMap<AgvId, HashSet<ErrorKey>> = HashMap::new();
+
+    for recorded in &recording.messages {
+        let Message::State(state) = &recorded.message else {
+            continue;
+        };
+        let last = raised.remove(&recorded.agv).unwrap_or_default();
+        let mut current = HashSet::new();
+        for error in &state.errors {
+            if error.error_level != ErrorLevel::Fatal {
+                continue;
+            }
+            let references: Vec<(String, String)> = error
+                .error_references
+                .iter()
+                .map(|reference| {
+                    (
+                        reference.reference_key.clone(),
+                        reference.reference_value.clone(),
+                    )
+                })
+                .collect();
+            let key: ErrorKey = (error.error_type.clone(), references);
+            if !last.contains(&key) && !current.contains(&key) {
+                let references: Vec<String> = key
+                    .1
+                    .iter()
+                    .map(|(key, value)| format!("{key} {value}"))
+                    .collect();
+                let mut message = format!("fatal error {}", error.error_type);
+                if !references.is_empty() {
+                    message.push_str(&format!(" at {}", references.join(", ")));
+                }
+                if let Some(description) = &error.error_description {
+                    message.push_str(&format!(": {description}"));
+                }
+                findings.push(Finding::new(
+                    Check::FatalErrors,
+                    Severity::Error,
+                    &recorded.agv,
+                    recorded.received_at,
+                    message,
+                ));
+            }
+            current.insert(key);
+        }
+        raised.insert(recorded.agv.clone(), current);
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, push};
+    use serde_json::json;
+
+    fn with_errors(errors: serde_json::Value) -> serde_json::Value {
+        fixtures::state(json!({ "errors": errors }))
+    }
+
+    #[test]
+    fn reports_a_fatal_error_once_when_raised() {
+        let fatal = json!({
+            "errorType": "laserFailure",
+            "errorLevel": "FATAL",
+            "errorDescription": "front scanner",
+            "errorReferences": [{"referenceKey": "nodeId", "referenceValue": "A"}],
+        });
+        let warning =
+            json!({"errorType": "lowBattery", "errorLevel": "WARNING", "errorReferences": []});
+        let mut recording = Recording::default();
+        push(
+            &mut recording,
+            "state",
+            0.0,
+            0.0,
+            with_errors(json!([warning])),
+        );
+        push(
+            &mut recording,
+            "state",
+            1.0,
+            1.0,
+            with_errors(json!([fatal])),
+        );
+        push(
+            &mut recording,
+            "state",
+            2.0,
+            2.0,
+            with_errors(json!([fatal])),
+        );
+        push(&mut recording, "state", 3.0, 3.0, with_errors(json!([])));
+        push(
+            &mut recording,
+            "state",
+            4.0,
+            4.0,
+            with_errors(json!([fatal])),
+        );
+
+        let findings = check(&recording);
+        let times: Vec<_> = findings.iter().map(|finding| finding.time).collect();
+        assert_eq!(times, [Some(fixtures::at(1.0)), Some(fixtures::at(4.0))]);
+        assert_eq!(
+            findings[0].message,
+            "fatal error laserFailure at nodeId A: front scanner"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use super::{Check, Finding, Severity};
+use crate::recording::{AgvId, Recording};
+use std::collections::HashMap;
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+
+/// Reports missing header IDs and header IDs that do not increase, per AGV and topic.
+pub(super) fn check(recording: &Recording) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut last_ids: HashMap<(AgvId, Topic), u32> = HashMap::new();
+
+    for recorded in &recording.messages {
+        // The last will is registered at connect time and carries the header ID of back then.
+        if let Message::Connection(connection) = &recorded.message
+            && connection.connection_state == ConnectionState::ConnectionBroken
+        {
+            continue;
+        }
+        let header_id = recorded.message.header().header_id;
+        let topic = recorded.message.topic();
+        let key = (recorded.agv.clone(), topic);
+        if let Some(&last_id) = last_ids.get(&key) {
+            let finding = |severity, message| {
+                Finding::new(
+                    Check::HeaderGaps,
+                    severity,
+                    &recorded.agv,
+                    recorded.received_at,
+                    message,
+                )
+            };
+            // Header IDs wrap around after u32::MAX, so a step forward is the shorter way round.
+            let step = header_id.wrapping_sub(last_id);
+            if step == 0 {
+                findings.push(finding(
+                    Severity::Warning,
+                    format!("{topic}: header ID {header_id} received twice"),
+                ));
+            } else if (step as i32) < 0 {
+                findings.push(finding(
+                    Severity::Warning,
+                    format!("{topic}: header ID went back from {last_id} to {header_id}"),
+                ));
+            } else if step > 1 {
+                findings.push(finding(
+                    Severity::Warning,
+                    format!(
+                        "{topic}: {} message(s) missing, header IDs {} to {}",
+                        step - 1,
+                        last_id.wrapping_add(1),
+                        header_id.wrapping_sub(1)
+                    ),
+                ));
+            }
+        }
+        last_ids.insert(key, header_id);
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::RecordEntry;
+    use serde_json::json;
+
+    fn recording(header_ids: &[u32]) -> Recording {
+        let mut recording = Recording::default();
+        for &header_id in header_ids {
+            let entry = RecordEntry {
+                received_at: chrono::DateTime::UNIX_EPOCH,
+                topic: "uagv/v2/acme/agv1/connection".to_string(),
+                payload: json!({
+                    "headerId": header_id,
+                    "timestamp": "2024-01-01T00:00:00Z",
+                    "version": "2.0.0",
+                    "manufacturer": "acme",
+                    "serialNumber": "agv1",
+                    "connectionState": "ONLINE",
+                    "lastStateChange": "2024-01-01T00:00:00Z",
+                }),
+            };
+            recording.push_entry(entry, "test".to_string());
+        }
+        recording
+    }
+
+    fn messages(header_ids: &[u32]) -> Vec<String> {
+        check(&recording(header_ids))
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    #[test]
+    fn continues_across_the_wrap_around_of_header_ids() {
+        assert!(messages(&[u32::MAX - 1, u32::MAX, 0, 1]).is_empty());
+        assert_eq!(
+            messages(&[u32::MAX, 2]),
+            ["connection: 2 message(s) missing, header IDs 0 to 1"]
+        );
+    }
+
+    #[test]
+    fn reports_repeated_and_decreasing_header_ids() {
+        assert_eq!(
+            messages(&[5, 5, 3]),
+            [
+                "connection: header ID 5 received twice",
+                "connection: header ID went back from 5 to 3",
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Checks that run over a whole recording and report findings.
+//!
+//! Every check looks at one aspect of the communication and is independent of the others,
+//! so they can be run and reported separately.
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+mod action_lifecycle;
+mod fatal_errors;
+mod header_gaps;
+mod order_validation;
+mod timing;
+
+pub(crate) use order_validation::validate_order;
+
+/// The checks that can be run on a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum Check {
+    /// Entries of the recording that could not be decoded.
+    Decode,
+    /// Gaps and jumps in the header ID sequence of every topic.
+    HeaderGaps,
+    /// Structural validity of orders and instant actions.
+    OrderValidation,
+    /// Status transitions of the actions reported in the state.
+    ActionLifecycle,
+    /// Message intervals, header timestamps and the clocks setting them.
+    Timing,
+    /// Fatal errors raised by the AGVs.
+    FatalErrors,
+}
+
+impl Check {
+    /// All checks, in the order they are reported.
+    pub const ALL: [Check; 6] = [
+        Check::Decode,
+        Check::HeaderGaps,
+        Check::OrderValidation,
+        Check::ActionLifecycle,
+        Check::Timing,
+        Check::FatalErrors,
+    ];
+
+    /// A short name of the check.
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Decode => "decode",
+            Check::HeaderGaps => "headerGaps",
+            Check::OrderValidation => "orderValidation",
+            Check::ActionLifecycle => "actionLifecycle",
+            Check::Timing => "timing",
+            Check::FatalErrors => "fatalErrors",
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How severe a finding is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// Noteworthy, but not a problem.
+    Info,
+    /// Probably a problem, e.g. a lost message.
+    Warning,
+    /// A violation of the specification.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Something a check noticed in the recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    /// The check that produced the finding.
+    pub check: Check,
+    /// How severe the finding is.
+    pub severity: Severity,
+    /// The AGV concerned, if any.
+    pub agv: Option<AgvId>,
+    /// The receive time of the message that triggered the finding.
+    pub time: Option<DateTime<Utc>>,
+    /// A human readable description.
+    pub message: String,
+}
+
+impl Finding {
+    fn new(
+        check: Check,
+        severity: Severity,
+        agv: &AgvId,
+        time: DateTime<Utc>,
+        message: String,
+    ) -> Self {
+        Finding {
+            check,
+            severity,
+            agv: Some(agv.clone()),
+            time: Some(time),
+            message,
+        }
+    }
+}
+
+/// Thresholds used by the checks.
+#[derive(Debug, Clone)]
+pub struct CheckConfig {
+    /// The longest allowed time between two state messages of an online AGV.
+    /// The specification requires a state at least every 30 seconds.
+    pub max_state_interval: Duration,
+    /// How far the clock of a sender may be off the clock of the capture host, judged by the
+    /// estimated offset and drift of its header timestamps.
+    pub max_clock_offset: Duration,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        CheckConfig {
+            max_state_interval: Duration::seconds(30),
+            max_clock_offset: Duration::seconds(1),
+        }
+    }
+}
+
+/// Runs a single check on the recording.
+pub fn run(check: Check, recording: &Recording, config: &CheckConfig) -> Vec<Finding> {
+    match check {
+        Check::Decode => recording
+            .failures
+            .iter()
+            .map(|failure| Finding {
+                check: Check::Decode,
+                severity: Severity::Error,
+                agv: None,
+                time: failure.received_at,
+                message: format!("{}: {}", failure.source, failure.error),
+            })
+            .collect(),
+        Check::HeaderGaps => header_gaps::check(recording),
+        Check::OrderValidation => order_validation::check(recording),
+        Check::ActionLifecycle => action_lifecycle::check(recording),
+        Check::Timing => timing::check(recording, config),
+        Check::FatalErrors => fatal_errors::check(recording),
+    }
+}
+
+/// Runs all checks on the recording.
+pub fn run_all(recording: &Recording, config: &CheckConfig) -> Vec<Finding> {
+    Check::ALL
+        .into_iter()
+        .flat_map(|check| run(check, recording, config))
+        .collect()
+}
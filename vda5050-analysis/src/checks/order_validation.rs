@@ -0,0 +1,316 @@
+use super::{Check, Finding, Severity};
+use crate::recording::{AgvId, Recording};
+use std::collections::{HashMap, HashSet};
+use vda5050_data_types::common::Action;
+use vda5050_data_types::factsheet::ActionScope;
+use vda5050_data_types::instant_actions::InstantActions;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::{Node, Order};
+use vda5050_data_types::predefined_actions::{ActionError, PredefinedAction};
+
+/// Validates the structure of every order and instant actions message, and that order updates
+/// continue the previous update of the same order.
+pub(super) fn check(recording: &Recording) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    // The last released node of the latest update per AGV and order.
+    let mut last_updates: HashMap<(AgvId, String), (u32, Option<Node>)> = HashMap::new();
+
+    for recorded in &recording.messages {
+        let problems = match &recorded.message {
+            Message::Order(order) => {
+                let key = (recorded.agv.clone(), order.order_id.clone());
+                let mut problems = validate_order(order);
+                if let Some((last_update_id, last_base_node)) = last_updates.get(&key) {
+                    problems.extend(validate_update(order, *last_update_id, last_base_node));
+                }
+                let last_base_node = order.nodes.iter().rev().find(|node| node.released);
+                last_updates.insert(key, (order.order_update_id, last_base_node.cloned()));
+                problems
+            }
+            Message::InstantActions(instant_actions) => validate_instant_actions(instant_actions),
+            _ => continue,
+        };
+        findings.extend(problems.into_iter().map(|(severity, message)| {
+            Finding::new(
+                Check::OrderValidation,
+                severity,
+                &recorded.agv,
+                recorded.received_at,
+                message,
+            )
+        }));
+    }
+    findings
+}
+
+pub(crate) fn validate_order(order: &Order) -> Vec<(Severity, String)> {
+    let id = format!("order {}/{}", order.order_id, order.order_update_id);
+    let mut problems = Vec::new();
+    let mut report =
+        |severity, message: String| problems.push((severity, format!("{id}: {message}")));
+
+    let Some(first) = order.nodes.first() else {
+        report(Severity::Error, "contains no nodes".to_string());
+        return problems;
+    };
+    if order.edges.len() + 1 != order.nodes.len() {
+        report(
+            Severity::Error,
+            format!(
+                "{} nodes need {} edges, got {}",
+                order.nodes.len(),
+                order.nodes.len() - 1,
+                order.edges.len()
+            ),
+        );
+    }
+    if !first.sequence_id.is_multiple_of(2) {
+        report(
+            Severity::Error,
+            format!(
+                "first node {} has odd sequence ID {}",
+                first.node_id, first.sequence_id
+            ),
+        );
+    }
+    // Sequence IDs past the largest one cannot be expected, that is reported once.
+    let mut overflows = false;
+    for (index, node) in order.nodes.iter().enumerate() {
+        let Some(expected) = sequence_id_after(first.sequence_id, 2 * index) else {
+            overflows = true;
+            continue;
+        };
+        if node.sequence_id != expected {
+            report(
+                Severity::Error,
+                format!(
+                    "node {} has sequence ID {}, expected {expected}",
+                    node.node_id, node.sequence_id
+                ),
+            );
+        }
+    }
+    for (index, edge) in order.edges.iter().enumerate() {
+        match sequence_id_after(first.sequence_id, 2 * index + 1) {
+            None => overflows = true,
+            Some(expected) if edge.sequence_id != expected => report(
+                Severity::Error,
+                format!(
+                    "edge {} has sequence ID {}, expected {expected}",
+                    edge.edge_id, edge.sequence_id
+                ),
+            ),
+            Some(_) => {}
+        }
+        let (Some(start), Some(end)) = (order.nodes.get(index), order.nodes.get(index + 1)) else {
+            continue;
+        };
+        if edge.start_node_id != start.node_id || edge.end_node_id != end.node_id {
+            report(
+                Severity::Error,
+                format!(
+                    "edge {} connects {} -> {}, but lies between {} and {}",
+                    edge.edge_id, edge.start_node_id, edge.end_node_id, start.node_id, end.node_id
+                ),
+            );
+        }
+    }
+    if overflows {
+        report(
+            Severity::Error,
+            format!(
+                "{} nodes and edges starting at sequence ID {} run past the largest sequence ID",
+                order.nodes.len() + order.edges.len(),
+                first.sequence_id
+            ),
+        );
+    }
+
+    if !first.released {
+        report(
+            Severity::Error,
+            "first node is not released, the base is empty".to_string(),
+        );
+    }
+    // Walking along the path, released elements must form a prefix that ends with a node.
+    let mut horizon_started = false;
+    for (index, node) in order.nodes.iter().enumerate() {
+        let edge = index
+            .checked_sub(1)
+            .and_then(|index| order.edges.get(index));
+        if let Some(edge) = edge {
+            if edge.released && horizon_started {
+                report(
+                    Severity::Error,
+                    format!("released edge {} follows the horizon", edge.edge_id),
+                );
+            }
+            if edge.released && !node.released {
+                report(
+                    Severity::Error,
+                    format!(
+                        "base ends with edge {}, its end node {} is not released",
+                        edge.edge_id, node.node_id
+                    ),
+                );
+            }
+            horizon_started |= !edge.released;
+        }
+        if node.released && horizon_started {
+            report(
+                Severity::Error,
+                format!("released node {} follows the horizon", node.node_id),
+            );
+        }
+        horizon_started |= !node.released;
+    }
+
+    let mut action_ids = HashSet::new();
+    let node_actions = order.nodes.iter().flat_map(|node| {
+        node.actions
+            .iter()
+            .map(|action| (action, ActionScope::Node))
+    });
+    let edge_actions = order.edges.iter().flat_map(|edge| {
+        edge.actions
+            .iter()
+            .map(|action| (action, ActionScope::Edge))
+    });
+    for (action, scope) in node_actions.chain(edge_actions) {
+        if !action_ids.insert(&action.action_id) {
+            report(
+                Severity::Error,
+                format!("action ID {} is used twice", action.action_id),
+            );
+        }
+        if let Some(message) = validate_action(action, scope) {
+            report(Severity::Warning, message);
+        }
+    }
+    problems
+}
+
+fn validate_update(
+    order: &Order,
+    last_update_id: u32,
+    last_base_node: &Option<Node>,
+) -> Vec<(Severity, String)> {
+    let id = format!("order {}/{}", order.order_id, order.order_update_id);
+    if order.order_update_id < last_update_id {
+        return vec![(
+            Severity::Error,
+            format!("{id}: update ID is lower than the previous update {last_update_id}"),
+        )];
+    }
+    if order.order_update_id == last_update_id {
+        return vec![(Severity::Info, format!("{id}: update was sent again"))];
+    }
+    let (Some(stitching), Some(first)) = (last_base_node, order.nodes.first()) else {
+        return Vec::new();
+    };
+    if stitching.node_id != first.node_id || stitching.sequence_id != first.sequence_id {
+        return vec![(
+            Severity::Error,
+            format!(
+                "{id}: starts at {}/{}, but the previous base ended at {}/{}",
+                first.node_id, first.sequence_id, stitching.node_id, stitching.sequence_id
+            ),
+        )];
+    }
+    Vec::new()
+}
+
+fn validate_instant_actions(instant_actions: &InstantActions) -> Vec<(Severity, String)> {
+    instant_actions
+        .instant_actions
+        .iter()
+        .filter_map(|action| validate_action(action, ActionScope::Instant))
+        .map(|message| {
+            (
+                Severity::Warning,
+                format!(
+                    "instant actions {}: {message}",
+                    instant_actions.header.header_id
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Checks parameters and scope of predefined actions. Vendor specific actions are not checked.
+fn validate_action(action: &Action, scope: ActionScope) -> Option<String> {
+    let result =
+        PredefinedAction::try_from(action).and_then(|predefined| predefined.check_scope(scope));
+    match result {
+        Ok(()) | Err(ActionError::UnknownActionType(_)) => None,
+        Err(err) => Some(format!("action {}: {err}", action.action_id)),
+    }
+}
+
+/// The sequence ID `offset` elements after `first`, `None` past the largest sequence ID.
+fn sequence_id_after(first: u32, offset: usize) -> Option<u32> {
+    u32::try_from(offset)
+        .ok()
+        .and_then(|offset| first.checked_add(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::push;
+    use serde_json::{Value, json};
+
+    fn order(first_sequence_id: u64) -> Value {
+        let node = |index: u64| {
+            json!({
+                "nodeId": format!("n{index}"),
+                "sequenceId": first_sequence_id + 2 * index,
+                "released": true,
+                "actions": [],
+            })
+        };
+        json!({
+            "orderId": "o1",
+            "orderUpdateId": 0,
+            "nodes": [node(0), node(1)],
+            "edges": [{
+                "edgeId": "e0",
+                "sequenceId": first_sequence_id + 1,
+                "startNodeId": "n0",
+                "endNodeId": "n1",
+                "released": true,
+                "actions": [],
+            }],
+        })
+    }
+
+    fn messages(recording: &Recording) -> Vec<String> {
+        check(recording)
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_consecutive_sequence_ids() {
+        let mut recording = Recording::default();
+        push(&mut recording, "order", 0.0, 0.0, order(4));
+        assert_eq!(messages(&recording), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_sequence_ids_past_the_largest_one() {
+        let mut recording = Recording::default();
+        let mut body = order(u64::from(u32::MAX) - 1);
+        // The second node has no sequence ID to expect, keep it in range to decode.
+        body["nodes"][1]["sequenceId"] = json!(u32::MAX);
+        push(&mut recording, "order", 0.0, 0.0, body);
+        assert_eq!(
+            messages(&recording),
+            [
+                "order o1/0: 3 nodes and edges starting at sequence ID 4294967294 run past the \
+                 largest sequence ID"
+            ]
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use super::{Check, CheckConfig, Finding, Severity};
+use crate::analytics::clock::{ClockModel, Sender};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+
+/// Reports online AGVs that stay silent on the state topic for too long, invalid header
+/// timestamps, header timestamps that go back in time and senders whose clock is off.
+pub(super) fn check(recording: &Recording, config: &CheckConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut last_state_at: HashMap<AgvId, DateTime<Utc>> = HashMap::new();
+    let mut online: HashMap<AgvId, bool> = HashMap::new();
+    let mut last_timestamps: HashMap<(AgvId, Topic), DateTime<Utc>> = HashMap::new();
+
+    for recorded in &recording.messages {
+        let agv = &recorded.agv;
+        let topic = recorded.message.topic();
+        let finding = |severity, message| {
+            Finding::new(Check::Timing, severity, agv, recorded.received_at, message)
+        };
+
+        // The last will is registered at connect time and carries the timestamp of back then.
+        let last_will = matches!(
+            &recorded.message,
+            Message::Connection(connection)
+                if connection.connection_state == ConnectionState::ConnectionBroken
+        );
+        let header = recorded.message.header();
+        match header.timestamp_utc() {
+            None => findings.push(finding(
+                Severity::Error,
+                format!(
+                    "{topic}: header {} has invalid timestamp '{}'",
+                    header.header_id, header.timestamp
+                ),
+            )),
+            Some(_) if last_will => {}
+            Some(timestamp) => {
+                let key = (agv.clone(), topic);
+                if let Some(last) = last_timestamps.get(&key)
+                    && timestamp < *last
+                {
+                    findings.push(finding(
+                        Severity::Warning,
+                        format!(
+                            "{topic}: header {} timestamp {} is {} ms before the previous one",
+                            header.header_id,
+                            header.timestamp,
+                            (*last - timestamp).num_milliseconds()
+                        ),
+                    ));
+                }
+                last_timestamps.insert(key, timestamp);
+            }
+        }
+
+        match &recorded.message {
+            Message::Connection(connection) => {
+                let is_online = connection.connection_state == ConnectionState::Online;
+                online.insert(agv.clone(), is_online);
+                if !is_online {
+                    last_state_at.remove(agv);
+                }
+            }
+            Message::State(_) => {
+                if let Some(last) = last_state_at.get(agv) {
+                    let interval = recorded.received_at - *last;
+                    if interval > config.max_state_interval
+                        && online.get(agv).copied().unwrap_or(true)
+                    {
+                        findings.push(finding(
+                            Severity::Warning,
+                            format!(
+                                "no state for {:.1} s, allowed are {} s",
+                                interval.num_milliseconds() as f64 / 1000.0,
+                                config.max_state_interval.num_seconds()
+                            ),
+                        ));
+                    }
+                }
+                last_state_at.insert(agv.clone(), recorded.received_at);
+            }
+            _ => {}
+        }
+    }
+    findings.extend(clock_offsets(recording, config));
+    findings
+}
+
+/// Reports every sender whose estimated clock is off by more than allowed at the start or the end
+/// of the recording, so that a drifting clock counts too.
+fn clock_offsets(recording: &Recording, config: &CheckConfig) -> Vec<Finding> {
+    let Some((_, end)) = recording.time_range() else {
+        return Vec::new();
+    };
+    let max_offset_ms = config.max_clock_offset.num_milliseconds() as f64;
+    ClockModel::estimate(recording)
+        .estimates
+        .into_iter()
+        .filter_map(|estimate| {
+            let worst_ms = [estimate.offset_ms, estimate.offset_at(end)]
+                .into_iter()
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))?;
+            if worst_ms.abs() <= max_offset_ms {
+                return None;
+            }
+            let direction = if worst_ms > 0.0 { "ahead of" } else { "behind" };
+            let agv = match &estimate.sender {
+                Sender::Agv(agv) => Some(agv.clone()),
+                Sender::MasterControl => None,
+            };
+            Some(Finding {
+                check: Check::Timing,
+                severity: Severity::Warning,
+                agv,
+                time: Some(estimate.reference),
+                message: format!(
+                    "the clock of {} is up to {:.0} ms {direction} the capture host: offset {:.0} ms, \
+                     drift {:.0} ppm",
+                    estimate.sender,
+                    worst_ms.abs(),
+                    estimate.offset_ms,
+                    estimate.drift_ppm
+                ),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::push;
+    use serde_json::json;
+
+    fn connection(state: &str) -> serde_json::Value {
+        json!({"connectionState": state, "lastStateChange": "2024-01-01T00:00:00Z"})
+    }
+
+    /// The findings apart from the clocks, which are off on purpose in most tests.
+    fn messages(recording: &Recording) -> Vec<String> {
+        let config = CheckConfig {
+            max_clock_offset: chrono::Duration::MAX,
+            ..CheckConfig::default()
+        };
+        check(recording, &config)
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    #[test]
+    fn skips_the_timestamp_of_the_last_will() {
+        let mut recording = Recording::default();
+        push(&mut recording, "connection", 0.0, 1.5, connection("ONLINE"));
+        push(
+            &mut recording,
+            "connection",
+            10.0,
+            0.0,
+            connection("CONNECTIONBROKEN"),
+        );
+        push(
+            &mut recording,
+            "connection",
+            20.0,
+            20.0,
+            connection("ONLINE"),
+        );
+        assert!(messages(&recording).is_empty());
+    }
+
+    #[test]
+    fn reports_timestamps_that_go_back() {
+        let mut recording = Recording::default();
+        push(&mut recording, "connection", 0.0, 1.5, connection("ONLINE"));
+        push(
+            &mut recording,
+            "connection",
+            10.0,
+            0.0,
+            connection("OFFLINE"),
+        );
+        assert_eq!(
+            messages(&recording),
+            [
+                "connection: header 1 timestamp 2024-01-01T00:00:00+00:00 is 1500 ms before the previous one"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_clocks_that_are_off() {
+        let mut recording = Recording::default();
+        for second in 0..100 {
+            let received = f64::from(second);
+            let stamped = received + 1.5 + 200e-6 * received;
+            push(
+                &mut recording,
+                "connection",
+                received,
+                stamped,
+                connection("ONLINE"),
+            );
+        }
+        let findings = check(&recording, &CheckConfig::default());
+        let messages: Vec<&str> = findings
+            .iter()
+            .map(|finding| finding.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "the clock of acme/agv1 is up to 1520 ms ahead of the capture host: offset 1500 ms, \
+                 drift 200 ppm"
+            ]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Recordings built from JSON payloads for the tests.
+use crate::recording::{RecordEntry, Recording};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{Value, json};
+
+/// The time the recordings of the tests start at.
+pub const START: &str = "2024-01-01T00:00:00Z";
+
+/// The given seconds after [`START`].
+pub fn at(secs: f64) -> DateTime<Utc> {
+    START.parse::<DateTime<Utc>>().unwrap() + Duration::microseconds((secs * 1e6).round() as i64)
+}
+
+/// A state of an idle AGV without an order, with the given fields replacing the defaults.
+pub fn state(fields: Value) -> Value {
+    merge(
+        json!({
+            "orderId": "",
+            "orderUpdateId": 0,
+            "nodeStates": [],
+            "edgeStates": [],
+            "driving": false,
+            "operatingMode": "AUTOMATIC",
+            "actionStates": [],
+            "errors": [],
+        }),
+        fields,
+    )
+}
+
+/// The fields of `base` with those of `fields` added or replaced.
+pub fn merge(mut base: Value, fields: Value) -> Value {
+    base.as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    base
+}
+
+/// Adds a message of AGV `acme/agv1`, see [`push_agv`].
+pub fn push(recording: &mut Recording, subtopic: &str, received: f64, stamped: f64, body: Value) {
+    push_agv(recording, "agv1", subtopic, received, stamped, body);
+}
+
+/// Adds a message of the AGV with the serial number received and stamped the given seconds after
+/// [`START`]. The header IDs count up per AGV and topic unless the body has one, and the message
+/// has to decode.
+pub fn push_agv(
+    recording: &mut Recording,
+    serial_number: &str,
+    subtopic: &str,
+    received: f64,
+    stamped: f64,
+    body: Value,
+) {
+    let topic = format!("uagv/v2/acme/{serial_number}/{subtopic}");
+    let header_id = recording
+        .messages
+        .iter()
+        .filter(|recorded| recorded.mqtt_topic == topic)
+        .count();
+    let header = json!({
+        "headerId": header_id,
+        "timestamp": at(stamped).to_rfc3339(),
+        "version": "2.0.0",
+        "manufacturer": "acme",
+        "serialNumber": serial_number,
+    });
+    let failures = recording.failures.len();
+    let entry = RecordEntry {
+        received_at: at(received),
+        topic,
+        payload: merge(header, body),
+    };
+    recording.push_entry(entry, "test".to_string());
+    assert_eq!(
+        recording.failures.len(),
+        failures,
+        "{:?}",
+        recording.failures.last()
+    );
+}
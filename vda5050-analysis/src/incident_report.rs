@@ -0,0 +1,523 @@
+//! A self-contained HTML report of an incident.
+//!
+//! The report is a single HTML file with inline CSS and SVG, so it can be attached to a ticket or
+//! sent by mail and opened without the tool.
+use crate::analytics::errors::{ErrorTimeline, EventKind};
+use crate::checks::Severity;
+use crate::recording::{AgvId, DecodeFailure, RecordedMessage, Recording};
+use crate::report::{Report, escape};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use vda5050_data_types::common::AgvPosition;
+use vda5050_data_types::message::Message;
+
+const PALETTE: [&str; 8] = [
+    "#1f77b4", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#17becf", "#bcbd22", "#7f7f7f",
+];
+const TIMELINE_WIDTH: f64 = 960.0;
+const LABEL_WIDTH: f64 = 160.0;
+const LANE_HEIGHT: f64 = 26.0;
+const MAP_SIZE: f64 = 480.0;
+
+/// The positions of every AGV on one map, in receive order.
+type Trails<'a> = BTreeMap<&'a AgvId, Vec<(DateTime<Utc>, &'a AgvPosition)>>;
+
+/// What the incident report focuses on.
+#[derive(Debug, Clone)]
+pub struct IncidentReportOptions {
+    /// The time of the incident. Defaults to the first error found, or the end of the recording.
+    pub incident_time: Option<DateTime<Utc>>,
+    /// How much time before and after the incident is shown on the map and in the raw messages.
+    pub window: Duration,
+    /// The maximum number of raw messages included in the report.
+    pub max_raw_messages: usize,
+}
+
+impl Default for IncidentReportOptions {
+    fn default() -> Self {
+        IncidentReportOptions {
+            incident_time: None,
+            window: Duration::seconds(60),
+            max_raw_messages: 200,
+        }
+    }
+}
+
+/// Renders the incident report for an analyzed recording.
+pub fn render(recording: &Recording, report: &Report, options: &IncidentReportOptions) -> String {
+    let incident_time = options
+        .incident_time
+        .or_else(|| first_finding_time(report, Severity::Error))
+        .or_else(|| first_finding_time(report, Severity::Warning))
+        .or(report.end);
+    let window = incident_time.map(|time| (time - options.window, time + options.window));
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>VDA 5050 incident report</title>\n<style>\n");
+    html.push_str(STYLE);
+    html.push_str("</style>\n</head>\n<body>\n<h1>VDA 5050 incident report</h1>\n");
+
+    let _ = writeln!(html, "<table class=\"facts\">");
+    let _ = writeln!(
+        html,
+        "<tr><th>Recordings</th><td>{}</td></tr>",
+        escape(&report.files.join(", "))
+    );
+    if let (Some(start), Some(end)) = (report.start, report.end) {
+        let _ = writeln!(
+            html,
+            "<tr><th>Period</th><td>{} &ndash; {}</td></tr>",
+            start.to_rfc3339(),
+            end.to_rfc3339()
+        );
+    }
+    if let Some(incident_time) = incident_time {
+        let _ = writeln!(
+            html,
+            "<tr><th>Incident</th><td>{} (&plusmn; {} s)</td></tr>",
+            incident_time.to_rfc3339(),
+            options.window.num_seconds()
+        );
+    }
+    let _ = writeln!(
+        html,
+        "<tr><th>Findings</th><td>{} errors, {} warnings, {} infos</td></tr>\n</table>",
+        report.count(Severity::Error),
+        report.count(Severity::Warning),
+        report.count(Severity::Info)
+    );
+
+    html.push_str("<h2>Fleet summary</h2>\n");
+    fleet_summary(&mut html, recording, report);
+    html.push_str("<h2>Error timelines</h2>\n");
+    let timeline = ErrorTimeline::analyze(recording);
+    timelines(&mut html, &timeline, report, incident_time, window);
+    if let (Some(incident_time), Some(window)) = (incident_time, window) {
+        html.push_str("<h2>Positions around the incident</h2>\n");
+        maps(&mut html, recording, report, incident_time, window);
+        html.push_str("<h2>Messages around the incident</h2>\n");
+        raw_messages(&mut html, recording, window, options.max_raw_messages);
+    }
+    html.push_str("<h2>All findings</h2>\n");
+    findings(&mut html, report);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn first_finding_time(report: &Report, severity: Severity) -> Option<DateTime<Utc>> {
+    report
+        .findings
+        .iter()
+        .filter(|finding| finding.severity == severity)
+        .filter_map(|finding| finding.time)
+        .min()
+}
+
+fn color(report: &Report, agv: &AgvId) -> &'static str {
+    let index = report
+        .agvs
+        .iter()
+        .position(|known| known == agv)
+        .unwrap_or(0);
+    PALETTE[index % PALETTE.len()]
+}
+
+fn fleet_summary(html: &mut String, recording: &Recording, report: &Report) {
+    html.push_str("<table>\n<tr><th>AGV</th><th>Messages</th><th>Errors</th><th>Warnings</th>");
+    html.push_str("<th>Operating mode</th><th>Battery</th><th>Last position</th></tr>\n");
+    for agv in &report.agvs {
+        let messages = recording
+            .messages
+            .iter()
+            .filter(|recorded| &recorded.agv == agv)
+            .count();
+        let count = |severity| {
+            report
+                .findings
+                .iter()
+                .filter(|finding| finding.severity == severity && finding.agv.as_ref() == Some(agv))
+                .count()
+        };
+        let last_state = recording
+            .messages
+            .iter()
+            .rev()
+            .filter(|recorded| &recorded.agv == agv)
+            .find_map(|recorded| match &recorded.message {
+                Message::State(state) => Some(state),
+                _ => None,
+            });
+        let mode = last_state
+            .map(|state| format!("{:?}", state.operating_mode))
+            .unwrap_or_default();
+        let battery = last_state
+            .and_then(|state| state.battery_state.as_ref())
+            .map(|battery| format!("{:.0} %", battery.battery_charge))
+            .unwrap_or_default();
+        let position = last_state
+            .and_then(|state| state.agv_position.as_ref())
+            .map(|position| format!("{} ({:.2}, {:.2})", position.map_id, position.x, position.y))
+            .unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<tr><td><span class=\"swatch\" style=\"background:{}\"></span>{}</td><td>{messages}</td><td>{}</td><td>{}</td><td>{}</td><td>{battery}</td><td>{}</td></tr>",
+            color(report, agv),
+            escape(&agv.to_string()),
+            count(Severity::Error),
+            count(Severity::Warning),
+            escape(&mode),
+            escape(&position)
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// One lane per AGV with the errors reported in its state as bars and the findings as dots.
+fn timelines(
+    html: &mut String,
+    timeline: &ErrorTimeline,
+    report: &Report,
+    incident_time: Option<DateTime<Utc>>,
+    window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+) {
+    let (Some(start), Some(end)) = (report.start, report.end) else {
+        html.push_str("<p>The recording contains no messages.</p>\n");
+        return;
+    };
+    let span = (end - start).num_milliseconds().max(1) as f64;
+    let x = |time: DateTime<Utc>| {
+        LABEL_WIDTH + (time - start).num_milliseconds() as f64 / span * TIMELINE_WIDTH
+    };
+    let height = LANE_HEIGHT * report.agvs.len() as f64 + 20.0;
+    let _ = writeln!(
+        html,
+        "<svg width=\"{}\" height=\"{height}\" class=\"timeline\">",
+        LABEL_WIDTH + TIMELINE_WIDTH + 10.0
+    );
+    if let Some((from, to)) = window {
+        let _ = writeln!(
+            html,
+            "<rect x=\"{:.1}\" y=\"0\" width=\"{:.1}\" height=\"{height}\" class=\"window\"/>",
+            x(from.max(start)),
+            (x(to.min(end)) - x(from.max(start))).max(1.0)
+        );
+    }
+    for (lane, agv) in report.agvs.iter().enumerate() {
+        let y = lane as f64 * LANE_HEIGHT;
+        let _ = writeln!(
+            html,
+            "<text x=\"0\" y=\"{:.1}\">{}</text><line x1=\"{LABEL_WIDTH}\" x2=\"{}\" y1=\"{:.1}\" y2=\"{:.1}\" class=\"lane\"/>",
+            y + 17.0,
+            escape(&agv.to_string()),
+            LABEL_WIDTH + TIMELINE_WIDTH,
+            y + 13.0,
+            y + 13.0
+        );
+        for interval in timeline
+            .of_agv(agv)
+            .filter(|interval| interval.kind == EventKind::Error)
+        {
+            let class = if interval.is_fatal() {
+                "fatal"
+            } else {
+                "warning"
+            };
+            let _ = writeln!(
+                html,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"14\" class=\"{class}\"><title>{} {} &ndash; {}</title></rect>",
+                x(interval.raised_at),
+                y + 6.0,
+                (x(interval.end()) - x(interval.raised_at)).max(2.0),
+                escape(&interval.event_type),
+                interval.raised_at.to_rfc3339(),
+                interval.end().to_rfc3339()
+            );
+        }
+        for finding in report
+            .findings
+            .iter()
+            .filter(|finding| finding.agv.as_ref() == Some(agv))
+        {
+            let Some(time) = finding.time else { continue };
+            let _ = writeln!(
+                html,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" class=\"finding {}\"><title>{} {}</title></circle>",
+                x(time),
+                y + 13.0,
+                finding.severity,
+                time.to_rfc3339(),
+                escape(&finding.message)
+            );
+        }
+    }
+    if let Some(incident_time) = incident_time {
+        let _ = writeln!(
+            html,
+            "<line x1=\"{0:.1}\" x2=\"{0:.1}\" y1=\"0\" y2=\"{height}\" class=\"incident\"/>",
+            x(incident_time)
+        );
+    }
+    html.push_str("</svg>\n");
+}
+
+fn position(recorded: &RecordedMessage) -> Option<&AgvPosition> {
+    match &recorded.message {
+        Message::State(state) => state.agv_position.as_ref(),
+        Message::Visualization(visualization) => visualization.agv_position.as_ref(),
+        _ => None,
+    }
+}
+
+/// One map per map ID with the trail of every AGV in the window and its position at the incident.
+fn maps(
+    html: &mut String,
+    recording: &Recording,
+    report: &Report,
+    incident_time: DateTime<Utc>,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+) {
+    let mut trails: BTreeMap<&str, Trails> = BTreeMap::new();
+    for recorded in &recording.messages {
+        if recorded.received_at < from || recorded.received_at > to {
+            continue;
+        }
+        if let Some(position) = position(recorded) {
+            trails
+                .entry(position.map_id.as_str())
+                .or_default()
+                .entry(&recorded.agv)
+                .or_default()
+                .push((recorded.received_at, position));
+        }
+    }
+    if trails.is_empty() {
+        html.push_str("<p>No positions were reported around the incident.</p>\n");
+        return;
+    }
+
+    html.push_str("<div class=\"maps\">\n");
+    for (map_id, agvs) in trails {
+        let positions = agvs.values().flatten().map(|(_, position)| *position);
+        let (mut min_x, mut max_x, mut min_y, mut max_y) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+        for position in positions {
+            min_x = min_x.min(position.x);
+            max_x = max_x.max(position.x);
+            min_y = min_y.min(position.y);
+            max_y = max_y.max(position.y);
+        }
+        let margin = 1.0;
+        let scale = MAP_SIZE / ((max_x - min_x).max(max_y - min_y) + margin);
+        // World Y points up, SVG Y points down.
+        let project = |position: &AgvPosition| {
+            (
+                (position.x - min_x + margin / 2.0) * scale,
+                MAP_SIZE - (position.y - min_y + margin / 2.0) * scale,
+            )
+        };
+
+        let _ = writeln!(
+            html,
+            "<figure><svg width=\"{MAP_SIZE}\" height=\"{MAP_SIZE}\" class=\"map\">"
+        );
+        for (agv, trail) in &agvs {
+            let color = color(report, agv);
+            let points: Vec<String> = trail
+                .iter()
+                .map(|(_, position)| {
+                    let (x, y) = project(position);
+                    format!("{x:.1},{y:.1}")
+                })
+                .collect();
+            let _ = writeln!(
+                html,
+                "<polyline points=\"{}\" stroke=\"{color}\" class=\"trail\"/>",
+                points.join(" ")
+            );
+            let at_incident = trail
+                .iter()
+                .rev()
+                .find(|(time, _)| *time <= incident_time)
+                .or(trail.first());
+            if let Some((time, position)) = at_incident {
+                let (x, y) = project(position);
+                let _ = writeln!(
+                    html,
+                    "<circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"6\" fill=\"{color}\"><title>{} at {} ({:.2}, {:.2})</title></circle><text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+                    escape(&agv.to_string()),
+                    time.to_rfc3339(),
+                    position.x,
+                    position.y,
+                    x + 8.0,
+                    y - 8.0,
+                    escape(&agv.serial_number)
+                );
+            }
+        }
+        let _ = writeln!(
+            html,
+            "</svg><figcaption>Map {} &ndash; {:.1} m &times; {:.1} m</figcaption></figure>",
+            escape(map_id),
+            max_x - min_x,
+            max_y - min_y
+        );
+    }
+    html.push_str("</div>\n");
+}
+
+fn raw_messages(
+    html: &mut String,
+    recording: &Recording,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    max_raw_messages: usize,
+) {
+    let in_window: Vec<&RecordedMessage> = recording
+        .messages
+        .iter()
+        .filter(|recorded| recorded.received_at >= from && recorded.received_at <= to)
+        .collect();
+    if in_window.len() > max_raw_messages {
+        let _ = writeln!(
+            html,
+            "<p>Showing the first {max_raw_messages} of {} messages.</p>",
+            in_window.len()
+        );
+    }
+    html.push_str("<table>\n<tr><th>Received</th><th>AGV</th><th>Topic</th><th>Header ID</th><th>Payload</th></tr>\n");
+    for recorded in in_window.into_iter().take(max_raw_messages) {
+        let payload = serde_json::to_string_pretty(&recorded.payload).unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><details><summary>show</summary><pre>{}</pre></details></td></tr>",
+            recorded.received_at.to_rfc3339(),
+            escape(&recorded.agv.to_string()),
+            recorded.message.topic(),
+            recorded.message.header().header_id,
+            escape(&payload)
+        );
+    }
+    html.push_str("</table>\n");
+
+    let failures: Vec<&DecodeFailure> = recording
+        .failures
+        .iter()
+        .filter(|failure| {
+            failure
+                .received_at
+                .is_some_and(|received_at| received_at >= from && received_at <= to)
+        })
+        .collect();
+    if !failures.is_empty() {
+        let _ = writeln!(
+            html,
+            "<p>{} messages in the window could not be decoded.</p>",
+            failures.len()
+        );
+        html.push_str(
+            "<table>\n<tr><th>Received</th><th>Topic</th><th>Source</th><th>Error</th></tr>\n",
+        );
+        for failure in failures {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                failure
+                    .received_at
+                    .map(|time| time.to_rfc3339())
+                    .unwrap_or_default(),
+                escape(failure.topic.as_deref().unwrap_or_default()),
+                escape(&failure.source),
+                escape(&failure.error)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+}
+
+fn findings(html: &mut String, report: &Report) {
+    if report.findings.is_empty() {
+        html.push_str("<p>No findings.</p>\n");
+        return;
+    }
+    html.push_str("<table>\n<tr><th>Severity</th><th>Check</th><th>Time</th><th>AGV</th><th>Message</th></tr>\n");
+    for finding in &report.findings {
+        let _ = writeln!(
+            html,
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            finding.severity,
+            finding.severity,
+            finding.check,
+            finding
+                .time
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_default(),
+            escape(
+                &finding
+                    .agv
+                    .as_ref()
+                    .map(|agv| agv.to_string())
+                    .unwrap_or_default()
+            ),
+            escape(&finding.message)
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+table.facts th { background: #f4f4f4; }
+pre { max-height: 24em; overflow: auto; margin: 0; }
+.swatch { display: inline-block; width: 10px; height: 10px; margin-right: 6px; }
+svg text { font-size: 12px; }
+.timeline .lane { stroke: #ddd; }
+.timeline .window { fill: #fff3c4; }
+.timeline .warning { fill: #f0a030; }
+.timeline .fatal { fill: #d62728; }
+.timeline .incident { stroke: #d62728; stroke-dasharray: 4 2; }
+.finding.info { fill: #1f77b4; }
+.finding.warning { fill: #ff7f0e; }
+.finding.error { fill: #d62728; }
+.maps { display: flex; flex-wrap: wrap; gap: 1em; }
+.map { border: 1px solid #ccc; background: #fafafa; }
+.trail { fill: none; stroke-width: 2; opacity: 0.7; }
+tr.error td { background: #fde0e0; }
+tr.warning td { background: #fff0d8; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, push};
+    use crate::recording::RecordEntry;
+    use serde_json::json;
+
+    #[test]
+    fn shows_the_messages_around_the_incident_as_published() {
+        let mut recording = Recording::default();
+        let state = fixtures::state(json!({"vendorField": "kept"}));
+        push(&mut recording, "state", 1.0, 1.0, state);
+        recording.push_entry(
+            RecordEntry {
+                received_at: fixtures::at(2.0),
+                topic: "uagv/v2/acme/agv1/state".to_string(),
+                payload: json!({"headerId": "not a number"}),
+            },
+            "session.jsonl:2".to_string(),
+        );
+        let report = Report::new(Vec::new(), &recording, Vec::new());
+        let options = IncidentReportOptions {
+            incident_time: Some(fixtures::at(0.0)),
+            ..IncidentReportOptions::default()
+        };
+
+        let html = render(&recording, &report, &options);
+        assert!(
+            html.contains("&quot;vendorField&quot;: &quot;kept&quot;"),
+            "{html}"
+        );
+        assert!(html.contains("1 messages in the window could not be decoded."));
+        assert!(html.contains("<td>session.jsonl:2</td>"));
+    }
+}
@@ -0,0 +1,8 @@
+pub mod analytics;
+pub mod capture;
+pub mod checks;
+#[cfg(test)]
+mod fixtures;
+pub mod incident_report;
+pub mod recording;
+pub mod report;
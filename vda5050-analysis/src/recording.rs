@@ -0,0 +1,225 @@
+use crate::capture::{self, Capture, CaptureError, CaptureOptions, CaptureSummary};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use vda5050_data_types::common::Header;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+
+/// Identifies an AGV by manufacturer and serial number, like the MQTT topic does.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub struct AgvId {
+    /// Name of the AGV manufacturer.
+    pub manufacturer: String,
+    /// Serial number of the AGV.
+    pub serial_number: String,
+}
+
+impl AgvId {
+    /// The AGV a message belongs to.
+    pub fn of(header: &Header) -> Self {
+        AgvId {
+            manufacturer: header.manufacturer.clone(),
+            serial_number: header.serial_number.clone(),
+        }
+    }
+}
+
+impl fmt::Display for AgvId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.manufacturer, self.serial_number)
+    }
+}
+
+/// One line of a recording file.
+///
+/// Recordings are JSON Lines files, every line holds one MQTT message as received by the capture host.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordEntry {
+    /// The time the message was received by the capture host.
+    pub received_at: DateTime<Utc>,
+    /// The full MQTT topic, e.g. `uagv/v2/Acme/agv-1/state`.
+    pub topic: String,
+    /// The JSON payload as published.
+    pub payload: Value,
+}
+
+/// A decoded message of a recording.
+#[derive(Debug, Clone)]
+pub struct RecordedMessage {
+    /// The time the message was received by the capture host.
+    pub received_at: DateTime<Utc>,
+    /// The full MQTT topic the message was published on.
+    pub mqtt_topic: String,
+    /// The AGV the message was sent to or by.
+    pub agv: AgvId,
+    /// The decoded message.
+    pub message: Message,
+    /// The JSON payload as published, with the fields the decoded message does not keep.
+    pub payload: Value,
+}
+
+/// An entry of a recording that could not be decoded.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeFailure {
+    /// Where the entry came from, e.g. `session.jsonl:42`.
+    pub source: String,
+    /// The time the message was received, if the entry was readable at all.
+    pub received_at: Option<DateTime<Utc>>,
+    /// The MQTT topic, if the entry was readable at all.
+    pub topic: Option<String>,
+    /// Why decoding failed.
+    pub error: String,
+}
+
+/// The decoded messages of one or more recording files, sorted by receive time.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    /// All decoded messages.
+    pub messages: Vec<RecordedMessage>,
+    /// All entries that could not be decoded.
+    pub failures: Vec<DecodeFailure>,
+}
+
+impl Recording {
+    /// Reads and merges the given recording files.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let mut recording = Recording::default();
+        for path in paths {
+            recording.read_file(path.as_ref())?;
+        }
+        recording.sort();
+        Ok(recording)
+    }
+
+    /// Appends the entries of a JSON Lines recording file, or the messages of a pcap or pcapng
+    /// capture on the default MQTT port, told by the file extension.
+    pub fn read_file(&mut self, path: &Path) -> io::Result<()> {
+        if capture::is_capture(path) {
+            self.read_capture(path, &CaptureOptions::default())?;
+            Ok(())
+        } else {
+            self.read_jsonl(path)
+        }
+    }
+
+    /// Appends the entries of a JSON Lines recording file.
+    ///
+    /// Lines that cannot be decoded are collected in [`Recording::failures`] instead of aborting.
+    pub fn read_jsonl(&mut self, path: &Path) -> io::Result<()> {
+        let reader = BufReader::new(std::fs::File::open(path)?);
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let source = format!("{}:{}", path.display(), index + 1);
+            match serde_json::from_str::<RecordEntry>(&line) {
+                Ok(entry) => self.push_entry(entry, source),
+                Err(err) => self.failures.push(DecodeFailure {
+                    source,
+                    received_at: None,
+                    topic: None,
+                    error: err.to_string(),
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends the messages published in a pcap or pcapng capture, received at the time they were
+    /// captured.
+    ///
+    /// Payloads that are not JSON are collected in [`Recording::failures`] like undecodable
+    /// lines of a recording file.
+    pub fn read_capture(
+        &mut self,
+        path: &Path,
+        options: &CaptureOptions,
+    ) -> Result<CaptureSummary, CaptureError> {
+        let capture = Capture::read(path, options)?;
+        for publish in capture.publishes {
+            let source = format!("{}:frame {}", path.display(), publish.frame);
+            match publish.to_entry() {
+                Ok(entry) => self.push_entry(entry, source),
+                Err(err) => self.failures.push(DecodeFailure {
+                    source,
+                    received_at: Some(publish.captured_at),
+                    topic: Some(publish.topic),
+                    error: err.to_string(),
+                }),
+            }
+        }
+        Ok(capture.summary)
+    }
+
+    /// Decodes and appends a single entry.
+    pub fn push_entry(&mut self, entry: RecordEntry, source: String) {
+        let failure = |error: String| DecodeFailure {
+            source: source.clone(),
+            received_at: Some(entry.received_at),
+            topic: Some(entry.topic.clone()),
+            error,
+        };
+        let Some(topic) = Topic::from_mqtt_topic(&entry.topic) else {
+            let failure = failure("not a VDA 5050 topic".to_string());
+            self.failures.push(failure);
+            return;
+        };
+        match Message::from_value(topic, entry.payload.clone()) {
+            Ok(message) => self.messages.push(RecordedMessage {
+                received_at: entry.received_at,
+                agv: AgvId::of(message.header()),
+                mqtt_topic: entry.topic,
+                message,
+                payload: entry.payload,
+            }),
+            Err(err) => {
+                let failure = failure(err.to_string());
+                self.failures.push(failure);
+            }
+        }
+    }
+
+    /// Sorts the messages by receive time, keeping the file order for equal times.
+    pub fn sort(&mut self) {
+        self.messages.sort_by_key(|message| message.received_at);
+    }
+
+    /// All AGVs that appear in the recording.
+    pub fn agvs(&self) -> BTreeSet<AgvId> {
+        self.messages
+            .iter()
+            .map(|message| message.agv.clone())
+            .collect()
+    }
+
+    /// The receive time of the first and the last message.
+    pub fn time_range(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        Some((
+            self.messages.first()?.received_at,
+            self.messages.last()?.received_at,
+        ))
+    }
+
+    /// Writes the decoded messages as a JSON Lines recording.
+    pub fn write_jsonl<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for message in &self.messages {
+            let entry = RecordEntry {
+                received_at: message.received_at,
+                topic: message.mqtt_topic.clone(),
+                payload: serde_json::to_value(&message.message)?,
+            };
+            serde_json::to_writer(&mut writer, &entry)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
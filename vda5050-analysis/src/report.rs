@@ -0,0 +1,222 @@
+use crate::checks::{self, Check, CheckConfig, Finding, Severity};
+use crate::recording::{AgvId, Recording};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// The result of analyzing a recording: what was analyzed and what the checks found.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    /// The analyzed recording files.
+    pub files: Vec<String>,
+    /// All AGVs in the recording.
+    pub agvs: Vec<AgvId>,
+    /// The number of decoded messages.
+    pub message_count: usize,
+    /// The receive time of the first message.
+    pub start: Option<DateTime<Utc>>,
+    /// The receive time of the last message.
+    pub end: Option<DateTime<Utc>>,
+    /// The findings of all checks.
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Collects the report for the given recording and findings.
+    pub fn new(files: Vec<String>, recording: &Recording, findings: Vec<Finding>) -> Self {
+        let time_range = recording.time_range();
+        Report {
+            files,
+            agvs: recording.agvs().into_iter().collect(),
+            message_count: recording.messages.len(),
+            start: time_range.map(|(start, _)| start),
+            end: time_range.map(|(_, end)| end),
+            findings,
+        }
+    }
+
+    /// Loads the recording files and runs all checks on them.
+    pub fn analyze<P: AsRef<Path>>(
+        paths: &[P],
+        config: &CheckConfig,
+    ) -> io::Result<(Recording, Report)> {
+        let recording = Recording::load(paths)?;
+        let findings = checks::run_all(&recording, config);
+        let files = paths
+            .iter()
+            .map(|path| path.as_ref().display().to_string())
+            .collect();
+        let report = Report::new(files, &recording, findings);
+        Ok((recording, report))
+    }
+
+    /// The number of findings with the given severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+
+    /// The highest severity of all findings.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    /// A human readable summary followed by all findings grouped by check.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "Files:    {}", self.files.join(", "));
+        let _ = writeln!(text, "Messages: {}", self.message_count);
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            let _ = writeln!(
+                text,
+                "Period:   {} - {} ({} s)",
+                start.to_rfc3339(),
+                end.to_rfc3339(),
+                (end - start).num_seconds()
+            );
+        }
+        let _ = writeln!(text, "AGVs:     {}", self.agvs.len());
+        for agv in &self.agvs {
+            let _ = writeln!(text, "  {agv}");
+        }
+        let _ = writeln!(
+            text,
+            "Findings: {} errors, {} warnings, {} infos",
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Info)
+        );
+        for (check, findings) in self.by_check() {
+            let _ = writeln!(text, "\n[{check}] {} finding(s)", findings.len());
+            for finding in findings {
+                let time = finding
+                    .time
+                    .map(|time| time.to_rfc3339())
+                    .unwrap_or_default();
+                let agv = finding
+                    .agv
+                    .as_ref()
+                    .map(|agv| agv.to_string())
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    text,
+                    "  {:<7} {time} {agv} {}",
+                    finding.severity, finding.message
+                );
+            }
+        }
+        text
+    }
+
+    /// The report as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// The report as JUnit XML, for CI pipelines.
+    ///
+    /// Every check is a test suite and every AGV a test case, which fails if the check found
+    /// errors or warnings for it.
+    pub fn to_junit(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(xml, "<testsuites name=\"vda5050-analyze\">");
+        for check in Check::ALL {
+            let findings: Vec<&Finding> = self
+                .findings
+                .iter()
+                .filter(|finding| finding.check == check)
+                .collect();
+            let mut cases: BTreeMap<String, Vec<&Finding>> = self
+                .agvs
+                .iter()
+                .map(|agv| (agv.to_string(), Vec::new()))
+                .collect();
+            for finding in &findings {
+                let name = finding
+                    .agv
+                    .as_ref()
+                    .map(|agv| agv.to_string())
+                    .unwrap_or_else(|| "recording".to_string());
+                cases.entry(name).or_default().push(finding);
+            }
+            let failures = cases
+                .values()
+                .filter(|findings| findings.iter().any(|finding| is_failure(finding)))
+                .count();
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{check}\" tests=\"{}\" failures=\"{failures}\">",
+                cases.len()
+            );
+            for (name, findings) in cases {
+                let _ = write!(
+                    xml,
+                    "    <testcase classname=\"{check}\" name=\"{}\"",
+                    escape(&name)
+                );
+                let failing: Vec<&&Finding> = findings
+                    .iter()
+                    .filter(|finding| is_failure(finding))
+                    .collect();
+                if findings.is_empty() {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                xml.push_str(">\n");
+                if !failing.is_empty() {
+                    let _ = writeln!(
+                        xml,
+                        "      <failure message=\"{} finding(s)\">",
+                        failing.len()
+                    );
+                    for finding in &failing {
+                        let _ = writeln!(xml, "{}", escape(&format_line(finding)));
+                    }
+                    xml.push_str("      </failure>\n");
+                }
+                xml.push_str("      <system-out>\n");
+                for finding in &findings {
+                    let _ = writeln!(xml, "{}", escape(&format_line(finding)));
+                }
+                xml.push_str("      </system-out>\n    </testcase>\n");
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    fn by_check(&self) -> BTreeMap<Check, Vec<&Finding>> {
+        let mut by_check: BTreeMap<Check, Vec<&Finding>> = BTreeMap::new();
+        for finding in &self.findings {
+            by_check.entry(finding.check).or_default().push(finding);
+        }
+        by_check
+    }
+}
+
+fn is_failure(finding: &Finding) -> bool {
+    finding.severity >= Severity::Warning
+}
+
+fn format_line(finding: &Finding) -> String {
+    let time = finding
+        .time
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default();
+    format!("{} {time} {}", finding.severity, finding.message)
+}
+
+/// Escapes text for use in XML and HTML.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,68 @@
+//! Runs the embedded MQTT broker on its own, e.g. for the simulators and master control on a laptop.
+use clap::Parser;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+use vda5050_broker::{Broker, BrokerConfig};
+
+#[derive(Parser)]
+#[command(name = "vda5050-broker", version, about)]
+struct Args {
+    /// The address to listen on.
+    #[arg(long, default_value = "127.0.0.1:1883")]
+    bind: SocketAddr,
+    /// Print the retained connection and factsheet messages every this many seconds.
+    #[arg(long)]
+    show_retained: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let broker = match Broker::start(BrokerConfig {
+        bind: args.bind,
+        ..BrokerConfig::default()
+    })
+    .await
+    {
+        Ok(broker) => broker,
+        Err(err) => {
+            eprintln!("error: failed to listen on {}: {err}", args.bind);
+            return ExitCode::from(2);
+        }
+    };
+    eprintln!("listening on {}", broker.local_addr());
+
+    let show_retained = async {
+        let Some(secs) = args.show_retained else {
+            return std::future::pending().await;
+        };
+        let mut interval = tokio::time::interval(Duration::from_secs(secs.max(1)));
+        loop {
+            interval.tick().await;
+            let view = broker.retained_view();
+            for agv in &view.agvs {
+                let connection = agv
+                    .connection
+                    .as_ref()
+                    .and_then(|connection| serde_json::to_value(&connection.connection_state).ok())
+                    .and_then(|state| state.as_str().map(str::to_string))
+                    .unwrap_or_else(|| "-".to_string());
+                let factsheet = agv
+                    .factsheet
+                    .as_ref()
+                    .map(|factsheet| format!("{} {}", factsheet.type_field, factsheet.type_version))
+                    .unwrap_or_else(|| "-".to_string());
+                println!("{} connection {connection} factsheet {factsheet}", agv.agv);
+            }
+            for (topic, error) in &view.undecodable {
+                println!("{topic}: {error}");
+            }
+        }
+    };
+    tokio::select! {
+        _ = show_retained => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    ExitCode::SUCCESS
+}
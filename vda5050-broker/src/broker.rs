@@ -0,0 +1,577 @@
+//! The broker: accepting clients, routing messages, retained messages and last wills.
+use crate::filter;
+use crate::packet::{self, Connect, Packet, Publish};
+use crate::retained::{RetainedMessage, RetainedView};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Notify, broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use vda5050_analysis::recording::RecordEntry;
+
+/// Where the broker listens and what it accepts.
+#[derive(Debug, Clone)]
+pub struct BrokerConfig {
+    /// The address to listen on, `127.0.0.1:1883` unless other machines should connect.
+    pub bind: SocketAddr,
+    /// The largest packet accepted, in bytes. Larger packets close the connection.
+    pub max_packet_size: usize,
+    /// How long a new connection may take to send its CONNECT packet.
+    pub connect_timeout: Duration,
+    /// How many messages an observer may lag behind before it misses messages, see
+    /// [`Broker::observe`].
+    pub observer_capacity: usize,
+    /// How many packets may wait to be sent to a client, including the retained messages sent on
+    /// a subscription. A client that does not keep up is disconnected and its last will is
+    /// published.
+    pub client_capacity: usize,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        BrokerConfig {
+            bind: SocketAddr::from(([127, 0, 0, 1], 1883)),
+            max_packet_size: 16 * 1024 * 1024,
+            connect_timeout: Duration::from_secs(10),
+            observer_capacity: 4096,
+            client_capacity: 16 * 1024,
+        }
+    }
+}
+
+/// A message routed by the broker, as seen by an observer within the process.
+#[derive(Debug, Clone)]
+pub struct ObservedMessage {
+    /// The topic name.
+    pub topic: String,
+    /// The payload as published.
+    pub payload: Vec<u8>,
+    /// Whether the message was published as retained message.
+    pub retain: bool,
+    /// When the broker received it.
+    pub received_at: DateTime<Utc>,
+}
+
+impl ObservedMessage {
+    /// The message as a line of a recording, `None` if the payload is not JSON.
+    pub fn to_entry(&self) -> Option<RecordEntry> {
+        Some(RecordEntry {
+            received_at: self.received_at,
+            topic: self.topic.clone(),
+            payload: serde_json::from_slice(&self.payload).ok()?,
+        })
+    }
+}
+
+struct Client {
+    client_id: String,
+    subscriptions: Vec<String>,
+    outgoing: mpsc::Sender<Vec<u8>>,
+    /// Closes the connection, e.g. when a new connection takes over the client ID.
+    close: Arc<Notify>,
+    /// Closed once the connection is gone and its last will is published.
+    closed: watch::Receiver<()>,
+}
+
+struct Shared {
+    clients: Mutex<HashMap<u64, Client>>,
+    retained: Mutex<BTreeMap<String, RetainedMessage>>,
+    next_connection: AtomicU64,
+    observers: broadcast::Sender<ObservedMessage>,
+}
+
+/// Locks the mutex even if a connection task panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Shared {
+    /// Routes a message to every matching subscription and updates the retained message of the
+    /// topic. An empty retained message deletes the retained message.
+    fn route(&self, topic: &str, payload: &[u8], qos: u8, retain: bool) {
+        let received_at = Utc::now();
+        if self.observers.receiver_count() > 0 {
+            // Observers that lag behind miss messages instead of slowing down the clients.
+            let _ = self.observers.send(ObservedMessage {
+                topic: topic.to_string(),
+                payload: payload.to_vec(),
+                retain,
+                received_at,
+            });
+        }
+        if retain {
+            let mut retained = lock(&self.retained);
+            if payload.is_empty() {
+                retained.remove(topic);
+            } else {
+                retained.insert(
+                    topic.to_string(),
+                    RetainedMessage {
+                        topic: topic.to_string(),
+                        payload: payload.to_vec(),
+                        qos,
+                        received_at,
+                    },
+                );
+            }
+        }
+        let packet = packet::publish(topic, payload, false);
+        for client in lock(&self.clients).values() {
+            if client
+                .subscriptions
+                .iter()
+                .any(|subscription| filter::matches(subscription, topic))
+            {
+                // A client that is gone is cleaned up by its own task.
+                if let Err(mpsc::error::TrySendError::Full(_)) =
+                    client.outgoing.try_send(packet.clone())
+                {
+                    client.close.notify_one();
+                }
+            }
+        }
+    }
+}
+
+/// A running broker. Dropping it stops accepting new connections.
+pub struct Broker {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+    accept: JoinHandle<()>,
+}
+
+impl Broker {
+    /// Binds the listening socket and starts accepting clients on the current tokio runtime.
+    pub async fn start(config: BrokerConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(config.bind).await?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            clients: Mutex::default(),
+            retained: Mutex::default(),
+            next_connection: AtomicU64::default(),
+            observers: broadcast::channel(config.observer_capacity.max(1)).0,
+        });
+        let accept = tokio::spawn({
+            let shared = Arc::clone(&shared);
+            async move {
+                loop {
+                    let Ok((stream, _)) = listener.accept().await else {
+                        continue;
+                    };
+                    let _ = stream.set_nodelay(true);
+                    tokio::spawn(serve(Arc::clone(&shared), stream, config.clone()));
+                }
+            }
+        });
+        Ok(Broker {
+            shared,
+            local_addr,
+            accept,
+        })
+    }
+
+    /// The address the broker listens on, with the actual port if port 0 was configured.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The client IDs of the connected clients, sorted.
+    pub fn clients(&self) -> Vec<String> {
+        let mut clients: Vec<String> = lock(&self.shared.clients)
+            .values()
+            .map(|client| client.client_id.clone())
+            .collect();
+        clients.sort();
+        clients
+    }
+
+    /// All retained messages, ordered by topic.
+    pub fn retained(&self) -> Vec<RetainedMessage> {
+        lock(&self.shared.retained).values().cloned().collect()
+    }
+
+    /// The retained `connection` and `factsheet` messages per AGV.
+    pub fn retained_view(&self) -> RetainedView {
+        RetainedView::of(&self.retained())
+    }
+
+    /// Receives every message the broker routes from now on, including last wills and messages
+    /// published from within the process, e.g. to store them.
+    pub fn observe(&self) -> broadcast::Receiver<ObservedMessage> {
+        self.shared.observers.subscribe()
+    }
+
+    /// Publishes a message from within the process, e.g. to replay a recording.
+    pub fn publish(&self, topic: &str, payload: &[u8], retain: bool) -> io::Result<()> {
+        if !filter::is_valid_topic(topic) || topic.len() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid topic name {topic}"),
+            ));
+        }
+        self.shared.route(topic, payload, 0, retain);
+        Ok(())
+    }
+}
+
+impl Drop for Broker {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+/// Serves one connection until the client disconnects, breaks the protocol or is taken over.
+async fn serve(shared: Arc<Shared>, stream: TcpStream, config: BrokerConfig) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let connect = match tokio::time::timeout(
+        config.connect_timeout,
+        packet::read(&mut reader, config.max_packet_size),
+    )
+    .await
+    {
+        Ok(Ok(Some(Packet::Connect(connect)))) => connect,
+        _ => return,
+    };
+    let Connect {
+        protocol_level,
+        client_id,
+        clean_session,
+        keep_alive_secs,
+        will,
+    } = connect;
+    let refusal = if protocol_level > 4 {
+        // Unacceptable protocol version.
+        Some(1)
+    } else if client_id.is_empty() && !clean_session {
+        // Identifier rejected, a session without ID cannot be resumed.
+        Some(2)
+    } else {
+        None
+    };
+    if let Some(return_code) = refusal {
+        let _ = writer.write_all(&packet::connack(return_code)).await;
+        return;
+    }
+
+    let connection = shared.next_connection.fetch_add(1, Ordering::Relaxed);
+    let client_id = if client_id.is_empty() {
+        format!("auto-{connection}")
+    } else {
+        client_id
+    };
+    let (outgoing, mut queued) = mpsc::channel::<Vec<u8>>(config.client_capacity.max(1));
+    let close = Arc::new(Notify::new());
+    let (gone, closed) = watch::channel(());
+    let taken_over: Vec<watch::Receiver<()>> = {
+        let mut clients = lock(&shared.clients);
+        let taken_over = clients
+            .values()
+            .filter(|client| client.client_id == client_id)
+            .map(|client| {
+                client.close.notify_one();
+                client.closed.clone()
+            })
+            .collect();
+        clients.insert(
+            connection,
+            Client {
+                client_id,
+                subscriptions: Vec::new(),
+                outgoing: outgoing.clone(),
+                close: Arc::clone(&close),
+                closed,
+            },
+        );
+        taken_over
+    };
+    // The last will of the connection taken over goes out before the new connection is accepted.
+    for mut closed in taken_over {
+        let _ = closed.changed().await;
+    }
+    let writing = tokio::spawn(async move {
+        while let Some(bytes) = queued.recv().await {
+            if writer.write_all(&bytes).await.is_err() {
+                break;
+            }
+        }
+    });
+    let _ = outgoing.try_send(packet::connack(0));
+
+    // The client has to send something within one and a half keep alive periods.
+    let keep_alive =
+        (keep_alive_secs > 0).then(|| Duration::from_millis(1500 * u64::from(keep_alive_secs)));
+    let mut graceful = false;
+    loop {
+        let read = packet::read(&mut reader, config.max_packet_size);
+        let next = tokio::select! {
+            _ = close.notified() => break,
+            next = async {
+                match keep_alive {
+                    Some(keep_alive) => tokio::time::timeout(keep_alive, read).await.ok(),
+                    None => Some(read.await),
+                }
+            } => next,
+        };
+        let packet = match next {
+            Some(Ok(Some(packet))) => packet,
+            _ => break,
+        };
+        // A client that does not read its replies is disconnected like one that does not keep up
+        // with its subscriptions.
+        let mut replies = Vec::new();
+        match packet {
+            Packet::Publish(publish) => {
+                if !filter::is_valid_topic(&publish.topic) {
+                    break;
+                }
+                match (publish.qos, publish.packet_id) {
+                    (1, Some(packet_id)) => {
+                        replies.push(packet::puback(packet_id));
+                    }
+                    (2, Some(packet_id)) => {
+                        replies.push(packet::pubrec(packet_id));
+                    }
+                    _ => {}
+                }
+                shared.route(
+                    &publish.topic,
+                    &publish.payload,
+                    publish.qos,
+                    publish.retain,
+                );
+            }
+            Packet::PubRel(packet_id) => {
+                replies.push(packet::pubcomp(packet_id));
+            }
+            // Messages are delivered with QoS 0, so there is nothing to acknowledge.
+            Packet::PubAck | Packet::PubRec | Packet::PubComp => {}
+            Packet::Subscribe { packet_id, filters } => {
+                let return_codes: Vec<u8> = filters
+                    .iter()
+                    .map(|(topic_filter, _)| {
+                        if filter::is_valid_filter(topic_filter) {
+                            0x00
+                        } else {
+                            0x80
+                        }
+                    })
+                    .collect();
+                let accepted: Vec<String> = filters
+                    .into_iter()
+                    .map(|(topic_filter, _)| topic_filter)
+                    .filter(|topic_filter| filter::is_valid_filter(topic_filter))
+                    .collect();
+                if let Some(client) = lock(&shared.clients).get_mut(&connection) {
+                    for topic_filter in &accepted {
+                        if !client.subscriptions.contains(topic_filter) {
+                            client.subscriptions.push(topic_filter.clone());
+                        }
+                    }
+                }
+                replies.push(packet::suback(packet_id, &return_codes));
+                for retained in lock(&shared.retained).values() {
+                    if accepted
+                        .iter()
+                        .any(|topic_filter| filter::matches(topic_filter, &retained.topic))
+                    {
+                        replies.push(packet::publish(&retained.topic, &retained.payload, true));
+                    }
+                }
+            }
+            Packet::Unsubscribe { packet_id, filters } => {
+                if let Some(client) = lock(&shared.clients).get_mut(&connection) {
+                    client
+                        .subscriptions
+                        .retain(|subscription| !filters.contains(subscription));
+                }
+                replies.push(packet::unsuback(packet_id));
+            }
+            Packet::PingReq => {
+                replies.push(packet::pingresp());
+            }
+            Packet::Disconnect => {
+                graceful = true;
+                break;
+            }
+            // A second CONNECT is a protocol violation.
+            Packet::Connect(_) => break,
+        }
+        if !replies
+            .into_iter()
+            .all(|reply| outgoing.try_send(reply).is_ok())
+        {
+            break;
+        }
+    }
+
+    lock(&shared.clients).remove(&connection);
+    drop(outgoing);
+    if !graceful
+        && let Some(Publish {
+            topic,
+            payload,
+            qos,
+            retain,
+            ..
+        }) = will
+        && filter::is_valid_topic(&topic)
+    {
+        shared.route(&topic, &payload, qos, retain);
+    }
+    drop(gone);
+    // Sends what is queued, e.g. the answer to the last packet, before the connection closes.
+    let _ = writing.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    async fn start() -> Broker {
+        Broker::start(BrokerConfig {
+            bind: SocketAddr::from(([127, 0, 0, 1], 0)),
+            ..BrokerConfig::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    /// A packet of less than 128 bytes, as sent by a client.
+    fn frame(first: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![first, u8::try_from(body.len()).unwrap()];
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    fn push_string(body: &mut Vec<u8>, string: &[u8]) {
+        body.extend_from_slice(&u16::try_from(string.len()).unwrap().to_be_bytes());
+        body.extend_from_slice(string);
+    }
+
+    /// Connects a client with a clean session and the given last will, and waits for the CONNACK.
+    async fn connect(broker: &Broker, client_id: &str, will: Option<(&str, &[u8])>) -> TcpStream {
+        let mut stream = TcpStream::connect(broker.local_addr()).await.unwrap();
+        let mut body = vec![0, 4, b'M', b'Q', b'T', b'T', 4];
+        body.push(if will.is_some() { 0x06 } else { 0x02 });
+        body.extend_from_slice(&[0, 0]);
+        push_string(&mut body, client_id.as_bytes());
+        if let Some((topic, payload)) = will {
+            push_string(&mut body, topic.as_bytes());
+            push_string(&mut body, payload);
+        }
+        stream.write_all(&frame(0x10, &body)).await.unwrap();
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack).await.unwrap();
+        assert_eq!(connack, [0x20, 2, 0, 0]);
+        stream
+    }
+
+    async fn subscribe(stream: &mut TcpStream, topic_filter: &str) {
+        let mut body = vec![0, 1];
+        push_string(&mut body, topic_filter.as_bytes());
+        body.push(0);
+        stream.write_all(&frame(0x82, &body)).await.unwrap();
+        let mut suback = [0; 5];
+        stream.read_exact(&mut suback).await.unwrap();
+        assert_eq!(suback, [0x90, 3, 0, 1, 0]);
+    }
+
+    /// Waits until the broker handled everything the client sent before.
+    async fn ping(stream: &mut TcpStream) {
+        stream.write_all(&[0xc0, 0]).await.unwrap();
+        let mut pingresp = [0; 2];
+        stream.read_exact(&mut pingresp).await.unwrap();
+        assert_eq!(pingresp, [0xd0, 0]);
+    }
+
+    async fn next_publish(stream: &mut TcpStream) -> Publish {
+        let next = tokio::time::timeout(Duration::from_secs(5), packet::read(stream, 1024));
+        match next.await {
+            Ok(Ok(Some(Packet::Publish(publish)))) => publish,
+            other => panic!("expected a PUBLISH, got {other:?}"),
+        }
+    }
+
+    async fn wait_for_clients(broker: &Broker, clients: &[&str]) {
+        for _ in 0..500 {
+            if broker.clients() == clients {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("clients {:?}, expected {clients:?}", broker.clients());
+    }
+
+    #[tokio::test]
+    async fn delivers_retained_messages_on_subscription() {
+        let broker = start().await;
+        let mut publisher = connect(&broker, "agv1", None).await;
+        let topic = "uagv/v2/acme/agv1/connection";
+        publisher
+            .write_all(&packet::publish(topic, b"{\"online\":true}", true))
+            .await
+            .unwrap();
+        ping(&mut publisher).await;
+
+        let mut subscriber = connect(&broker, "hmi", None).await;
+        subscribe(&mut subscriber, "uagv/v2/+/+/connection").await;
+        let retained = next_publish(&mut subscriber).await;
+        assert_eq!(retained.topic, topic);
+        assert_eq!(retained.payload, b"{\"online\":true}");
+        assert!(retained.retain);
+
+        // Later messages are routed without the retain flag.
+        publisher
+            .write_all(&packet::publish(topic, b"{}", true))
+            .await
+            .unwrap();
+        let routed = next_publish(&mut subscriber).await;
+        assert_eq!(
+            (routed.payload.as_slice(), routed.retain),
+            (&b"{}"[..], false)
+        );
+    }
+
+    #[tokio::test]
+    async fn publishes_the_last_will_on_abrupt_close_only() {
+        let broker = start().await;
+        let mut subscriber = connect(&broker, "hmi", None).await;
+        subscribe(&mut subscriber, "#").await;
+
+        let mut graceful = connect(&broker, "agv1", Some(("will/agv1", b"broken"))).await;
+        graceful.write_all(&[0xe0, 0]).await.unwrap();
+        wait_for_clients(&broker, &["hmi"]).await;
+        broker.publish("marker", b"", false).unwrap();
+        assert_eq!(next_publish(&mut subscriber).await.topic, "marker");
+
+        let abrupt = connect(&broker, "agv2", Some(("will/agv2", b"broken"))).await;
+        drop(abrupt);
+        let will = next_publish(&mut subscriber).await;
+        assert_eq!(
+            (will.topic.as_str(), will.payload.as_slice()),
+            ("will/agv2", &b"broken"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn publishes_the_last_will_of_a_taken_over_connection_first() {
+        let broker = start().await;
+        let mut subscriber = connect(&broker, "hmi", None).await;
+        subscribe(&mut subscriber, "will/#").await;
+        let _old = connect(&broker, "agv1", Some(("will/agv1", b"old"))).await;
+        let _new = connect(&broker, "agv1", None).await;
+        // The old connection was gone before the new one got its CONNACK.
+        assert_eq!(broker.clients(), ["agv1", "hmi"]);
+        assert_eq!(next_publish(&mut subscriber).await.payload, b"old");
+    }
+}
@@ -0,0 +1,98 @@
+//! MQTT topic names and topic filters with the wildcards `+` and `#`.
+
+/// Whether the topic name matches the topic filter. Wildcards at the first level do not match
+/// topics starting with `$`, which are reserved for the broker.
+pub fn matches(filter: &str, topic: &str) -> bool {
+    if topic.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(filter_level), Some(topic_level)) if filter_level == topic_level => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Whether the topic filter is valid: not empty, `#` only as the last level and wildcards only
+/// as whole levels.
+pub fn is_valid_filter(filter: &str) -> bool {
+    if filter.is_empty() || filter.contains('\0') {
+        return false;
+    }
+    let levels: Vec<&str> = filter.split('/').collect();
+    levels
+        .iter()
+        .enumerate()
+        .all(|(index, level)| match *level {
+            "#" => index == levels.len() - 1,
+            "+" => true,
+            level => !level.contains(['+', '#']),
+        })
+}
+
+/// Whether the topic name is valid to publish to: not empty and without wildcards.
+pub fn is_valid_topic(topic: &str) -> bool {
+    !topic.is_empty() && !topic.contains(['+', '#', '\0'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards_as_whole_levels() {
+        assert!(matches(
+            "uagv/v2/acme/agv1/state",
+            "uagv/v2/acme/agv1/state"
+        ));
+        assert!(matches("uagv/v2/+/+/state", "uagv/v2/acme/agv1/state"));
+        assert!(!matches("uagv/v2/+/state", "uagv/v2/acme/agv1/state"));
+        assert!(matches("uagv/#", "uagv/v2/acme/agv1/state"));
+        // `#` also matches the parent level.
+        assert!(matches("uagv/#", "uagv"));
+        assert!(matches("#", "uagv/v2"));
+        assert!(!matches("uagv/v2/acme/agv1", "uagv/v2/acme/agv1/state"));
+        assert!(!matches(
+            "uagv/v2/acme/agv1/state/+",
+            "uagv/v2/acme/agv1/state"
+        ));
+        // Empty levels are levels too.
+        assert!(matches("+/a", "/a"));
+    }
+
+    #[test]
+    fn keeps_wildcards_at_the_first_level_off_dollar_topics() {
+        assert!(!matches("#", "$SYS/broker/clients"));
+        assert!(!matches("+/broker/clients", "$SYS/broker/clients"));
+        assert!(matches("$SYS/#", "$SYS/broker/clients"));
+        assert!(matches("$SYS/+/clients", "$SYS/broker/clients"));
+    }
+
+    #[test]
+    fn validates_filters() {
+        assert!(is_valid_filter("#"));
+        assert!(is_valid_filter("+"));
+        assert!(is_valid_filter("uagv/+/acme/#"));
+        assert!(is_valid_filter("$SYS/#"));
+        assert!(!is_valid_filter(""));
+        assert!(!is_valid_filter("uagv/#/state"));
+        assert!(!is_valid_filter("uagv/v2#"));
+        assert!(!is_valid_filter("uagv/v2+/state"));
+        assert!(!is_valid_filter("uagv/\0"));
+    }
+
+    #[test]
+    fn validates_topics() {
+        assert!(is_valid_topic("uagv/v2/acme/agv1/state"));
+        assert!(is_valid_topic("$SYS/broker"));
+        assert!(!is_valid_topic(""));
+        assert!(!is_valid_topic("uagv/+/state"));
+        assert!(!is_valid_topic("uagv/#"));
+    }
+}
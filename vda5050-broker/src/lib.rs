@@ -0,0 +1,20 @@
+//! A small MQTT 3.1.1 broker to run the analysis tool, the simulators and master control on one
+//! machine without an external broker, e.g. for offline demos and tests.
+//!
+//! [`Broker`] accepts MQTT 3.1.1 clients on a TCP port, routes their messages to the matching
+//! subscriptions, keeps retained messages and publishes last wills. It keeps no sessions beyond a
+//! connection and delivers every message with QoS 0, which is enough for VDA 5050, where every
+//! topic is sent with QoS 0 except the retained `connection` topic. [`retained::RetainedView`]
+//! shows the retained `connection` and `factsheet` messages per AGV.
+//!
+//! Without the `server` feature only the retained view and the topic filters are built.
+pub mod filter;
+pub mod retained;
+
+#[cfg(feature = "server")]
+mod broker;
+#[cfg(feature = "server")]
+mod packet;
+
+#[cfg(feature = "server")]
+pub use broker::{Broker, BrokerConfig, ObservedMessage};
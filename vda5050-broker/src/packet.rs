@@ -0,0 +1,373 @@
+//! Encoding and decoding of the MQTT 3.1.1 control packets the broker handles.
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// A message to publish, as received in a PUBLISH packet or registered as last will.
+#[derive(Debug, Clone)]
+pub(crate) struct Publish {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: u8,
+    pub retain: bool,
+    /// Present for QoS 1 and 2.
+    pub packet_id: Option<u16>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Connect {
+    pub protocol_level: u8,
+    pub client_id: String,
+    pub clean_session: bool,
+    pub keep_alive_secs: u16,
+    pub will: Option<Publish>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Packet {
+    Connect(Connect),
+    Publish(Publish),
+    PubAck,
+    PubRec,
+    PubRel(u16),
+    PubComp,
+    Subscribe {
+        packet_id: u16,
+        filters: Vec<(String, u8)>,
+    },
+    Unsubscribe {
+        packet_id: u16,
+        filters: Vec<String>,
+    },
+    PingReq,
+    Disconnect,
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Reads the next packet, or `None` if the connection was closed between packets.
+pub(crate) async fn read<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_packet_size: usize,
+) -> io::Result<Option<Packet>> {
+    let first = match reader.read_u8().await {
+        Ok(first) => first,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut length = 0usize;
+    for shift in [0, 7, 14, 21] {
+        let byte = reader.read_u8().await?;
+        length |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if shift == 21 {
+            return Err(invalid("malformed remaining length"));
+        }
+    }
+    if length > max_packet_size {
+        return Err(invalid(format!(
+            "packet of {length} bytes exceeds the limit of {max_packet_size} bytes"
+        )));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    decode(first, &body).map(Some)
+}
+
+fn decode(first: u8, body: &[u8]) -> io::Result<Packet> {
+    let flags = first & 0x0f;
+    let mut body = Body {
+        bytes: body,
+        pos: 0,
+    };
+    let packet = match first >> 4 {
+        1 => Packet::Connect(decode_connect(&mut body)?),
+        3 => {
+            let qos = (flags >> 1) & 0x03;
+            if qos == 3 {
+                return Err(invalid("PUBLISH with QoS 3"));
+            }
+            let topic = body.string()?;
+            let packet_id = if qos > 0 { Some(body.u16()?) } else { None };
+            Packet::Publish(Publish {
+                topic,
+                payload: body.rest().to_vec(),
+                qos,
+                retain: flags & 0x01 != 0,
+                packet_id,
+            })
+        }
+        4 => Packet::PubAck,
+        5 => Packet::PubRec,
+        6 => Packet::PubRel(body.u16()?),
+        7 => Packet::PubComp,
+        8 => {
+            let packet_id = body.u16()?;
+            let mut filters = Vec::new();
+            while !body.is_empty() {
+                filters.push((body.string()?, body.u8()? & 0x03));
+            }
+            if filters.is_empty() {
+                return Err(invalid("SUBSCRIBE without topic filters"));
+            }
+            Packet::Subscribe { packet_id, filters }
+        }
+        10 => {
+            let packet_id = body.u16()?;
+            let mut filters = Vec::new();
+            while !body.is_empty() {
+                filters.push(body.string()?);
+            }
+            Packet::Unsubscribe { packet_id, filters }
+        }
+        12 => Packet::PingReq,
+        14 => Packet::Disconnect,
+        kind => return Err(invalid(format!("unexpected packet type {kind}"))),
+    };
+    Ok(packet)
+}
+
+fn decode_connect(body: &mut Body) -> io::Result<Connect> {
+    let protocol_name = body.string()?;
+    if protocol_name != "MQTT" && protocol_name != "MQIsdp" {
+        return Err(invalid(format!("unknown protocol {protocol_name}")));
+    }
+    let protocol_level = body.u8()?;
+    let flags = body.u8()?;
+    let keep_alive_secs = body.u16()?;
+    // Clients of newer protocol versions are answered with a refusal instead of being parsed.
+    if protocol_level > 4 {
+        return Ok(Connect {
+            protocol_level,
+            client_id: String::new(),
+            clean_session: true,
+            keep_alive_secs,
+            will: None,
+        });
+    }
+    let client_id = body.string()?;
+    let will = if flags & 0x04 != 0 {
+        let topic = body.string()?;
+        let payload = body.binary()?.to_vec();
+        Some(Publish {
+            topic,
+            payload,
+            qos: (flags >> 3) & 0x03,
+            retain: flags & 0x20 != 0,
+            packet_id: None,
+        })
+    } else {
+        None
+    };
+    // User name and password are read but not checked.
+    if flags & 0x80 != 0 {
+        body.string()?;
+    }
+    if flags & 0x40 != 0 {
+        body.binary()?;
+    }
+    Ok(Connect {
+        protocol_level,
+        client_id,
+        clean_session: flags & 0x02 != 0,
+        keep_alive_secs,
+        will,
+    })
+}
+
+struct Body<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Body<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("packet ends early"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn binary(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u16()?;
+        self.take(usize::from(len))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.binary()?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.pos.min(self.bytes.len())..];
+        self.pos = self.bytes.len();
+        rest
+    }
+}
+
+fn encode(first: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![first];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// The CONNACK packet with the given return code, 0 for accepted.
+pub(crate) fn connack(return_code: u8) -> Vec<u8> {
+    encode(0x20, &[0, return_code])
+}
+
+/// A PUBLISH packet with QoS 0.
+pub(crate) fn publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(2 + topic.len() + payload.len());
+    body.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+    body.extend_from_slice(topic.as_bytes());
+    body.extend_from_slice(payload);
+    encode(if retain { 0x31 } else { 0x30 }, &body)
+}
+
+pub(crate) fn puback(packet_id: u16) -> Vec<u8> {
+    encode(0x40, &packet_id.to_be_bytes())
+}
+
+pub(crate) fn pubrec(packet_id: u16) -> Vec<u8> {
+    encode(0x50, &packet_id.to_be_bytes())
+}
+
+pub(crate) fn pubcomp(packet_id: u16) -> Vec<u8> {
+    encode(0x70, &packet_id.to_be_bytes())
+}
+
+/// The SUBACK packet with one return code per topic filter: the granted QoS or 0x80 for failure.
+pub(crate) fn suback(packet_id: u16, return_codes: &[u8]) -> Vec<u8> {
+    let mut body = packet_id.to_be_bytes().to_vec();
+    body.extend_from_slice(return_codes);
+    encode(0x90, &body)
+}
+
+pub(crate) fn unsuback(packet_id: u16) -> Vec<u8> {
+    encode(0xb0, &packet_id.to_be_bytes())
+}
+
+pub(crate) fn pingresp() -> Vec<u8> {
+    encode(0xd0, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_all(bytes: &[u8]) -> io::Result<Option<Packet>> {
+        read(&mut &bytes[..], 1024).await
+    }
+
+    fn error(result: io::Result<Option<Packet>>) -> String {
+        result.expect_err("the packet must not decode").to_string()
+    }
+
+    #[tokio::test]
+    async fn decodes_a_publish_encoded_by_the_broker() {
+        let Some(Packet::Publish(publish)) = read_all(&publish("a/b", b"{}", true)).await.unwrap()
+        else {
+            panic!("not a PUBLISH");
+        };
+        assert_eq!(publish.topic, "a/b");
+        assert_eq!(publish.payload, b"{}");
+        assert_eq!(
+            (publish.qos, publish.retain, publish.packet_id),
+            (0, true, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_a_connect_with_last_will() {
+        let mut body = vec![0, 4, b'M', b'Q', b'T', b'T', 4, 0x2e, 0, 30];
+        body.extend_from_slice(&[0, 4, b'a', b'g', b'v', b'1']);
+        body.extend_from_slice(&[0, 1, b'w', 0, 2, b'{', b'}']);
+        let Some(Packet::Connect(connect)) = read_all(&encode(0x10, &body)).await.unwrap() else {
+            panic!("not a CONNECT");
+        };
+        assert_eq!(connect.client_id, "agv1");
+        assert!(connect.clean_session);
+        assert_eq!(connect.keep_alive_secs, 30);
+        let will = connect.will.unwrap();
+        assert_eq!((will.topic.as_str(), will.qos, will.retain), ("w", 1, true));
+    }
+
+    #[tokio::test]
+    async fn ends_between_packets() {
+        assert!(read_all(&[]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_remaining_lengths() {
+        assert_eq!(
+            error(read_all(&[0x30, 0xff, 0xff, 0xff, 0xff, 0x01]).await),
+            "malformed remaining length"
+        );
+        assert_eq!(
+            error(read_all(&[0x30, 0x81, 0x08]).await),
+            "packet of 1025 bytes exceeds the limit of 1024 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_truncated_packets() {
+        // The connection closes within the remaining length and within the body.
+        assert!(read_all(&[0x30, 0x80]).await.is_err());
+        assert!(read_all(&[0x30, 5, 0, 3, b'a']).await.is_err());
+        // The body ends within the topic name and before the packet ID.
+        assert_eq!(
+            error(read_all(&[0x30, 3, 0, 3, b'a']).await),
+            "packet ends early"
+        );
+        assert_eq!(
+            error(read_all(&[0x32, 3, 0, 1, b'a']).await),
+            "packet ends early"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_publish_with_qos_3() {
+        assert_eq!(
+            error(read_all(&[0x36, 5, 0, 1, b'a', 0, 1]).await),
+            "PUBLISH with QoS 3"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_subscribe_without_filters() {
+        assert_eq!(
+            error(read_all(&[0x82, 2, 0, 1]).await),
+            "SUBSCRIBE without topic filters"
+        );
+    }
+}
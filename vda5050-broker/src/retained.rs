@@ -0,0 +1,142 @@
+//! The retained messages of the broker and what they tell about the AGVs.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use vda5050_analysis::recording::AgvId;
+use vda5050_data_types::connection::{Connection, ConnectionState};
+use vda5050_data_types::factsheet::Factsheet;
+use vda5050_data_types::topic::Topic;
+
+/// A message the broker keeps for future subscribers of its topic.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetainedMessage {
+    /// The topic name.
+    pub topic: String,
+    /// The payload as published.
+    pub payload: Vec<u8>,
+    /// The QoS level it was published with.
+    pub qos: u8,
+    /// When the broker received it.
+    pub received_at: DateTime<Utc>,
+}
+
+/// The retained connection state of an AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetainedConnection {
+    /// The topic name.
+    pub topic: String,
+    /// When the broker received it.
+    pub received_at: DateTime<Utc>,
+    /// The connection state, e.g. `CONNECTIONBROKEN` after the last will.
+    pub connection_state: ConnectionState,
+    /// The timestamp of the header.
+    pub timestamp: String,
+}
+
+/// The retained factsheet of an AGV, summarized.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetainedFactsheet {
+    /// The topic name.
+    pub topic: String,
+    /// When the broker received it.
+    pub received_at: DateTime<Utc>,
+    /// The type of the AGV.
+    pub type_field: String,
+    /// The version of the type.
+    pub type_version: String,
+    /// The highest speed in [m/s], if given.
+    pub speed_max: Option<f64>,
+    /// The number of actions the AGV supports.
+    pub actions: usize,
+}
+
+/// The retained messages of one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetainedAgv {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The retained connection message.
+    pub connection: Option<RetainedConnection>,
+    /// The retained factsheet.
+    pub factsheet: Option<RetainedFactsheet>,
+}
+
+/// The retained `connection` and `factsheet` messages of all AGVs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RetainedView {
+    /// Every AGV with a retained message, ordered by manufacturer and serial number.
+    pub agvs: Vec<RetainedAgv>,
+    /// Retained messages on `connection` and `factsheet` topics that could not be decoded, as
+    /// topic and error.
+    pub undecodable: Vec<(String, String)>,
+}
+
+impl RetainedView {
+    /// Decodes the retained messages on `connection` and `factsheet` topics and ignores all
+    /// others.
+    pub fn of(messages: &[RetainedMessage]) -> Self {
+        let mut agvs: BTreeMap<AgvId, RetainedAgv> = BTreeMap::new();
+        let mut undecodable = Vec::new();
+        for message in messages {
+            let topic = match Topic::from_mqtt_topic(&message.topic) {
+                Some(topic @ (Topic::Connection | Topic::Factsheet)) => topic,
+                _ => continue,
+            };
+            let decoded = match topic {
+                Topic::Connection => {
+                    serde_json::from_slice::<Connection>(&message.payload).map(|connection| {
+                        let agv = AgvId::of(&connection.header);
+                        let retained = RetainedConnection {
+                            topic: message.topic.clone(),
+                            received_at: message.received_at,
+                            connection_state: connection.connection_state,
+                            timestamp: connection.header.timestamp,
+                        };
+                        (agv, Some(retained), None)
+                    })
+                }
+                _ => serde_json::from_slice::<Factsheet>(&message.payload).map(|factsheet| {
+                    let agv = AgvId::of(&factsheet.header);
+                    let retained = RetainedFactsheet {
+                        topic: message.topic.clone(),
+                        received_at: message.received_at,
+                        type_field: factsheet.type_field,
+                        type_version: factsheet.type_version,
+                        speed_max: factsheet
+                            .physical_parameters
+                            .map(|parameters| parameters.speed_max),
+                        actions: factsheet.actions.len(),
+                    };
+                    (agv, None, Some(retained))
+                }),
+            };
+            let (agv, connection, factsheet) = match decoded {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    undecodable.push((message.topic.clone(), err.to_string()));
+                    continue;
+                }
+            };
+            let entry = agvs.entry(agv.clone()).or_insert_with(|| RetainedAgv {
+                agv,
+                connection: None,
+                factsheet: None,
+            });
+            if connection.is_some() {
+                entry.connection = connection;
+            }
+            if factsheet.is_some() {
+                entry.factsheet = factsheet;
+            }
+        }
+        RetainedView {
+            agvs: agvs.into_values().collect(),
+            undecodable,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Compares the JSON Schemas derived from the Rust data types with the official VDA 5050 schema files.
+//!
+//! Usage:
+//!   vda5050-schema-diff <official-schema-dir>   report deviations, exits with 1 if any are found
+//!   vda5050-schema-diff --emit <output-dir>     write the generated schemas as <message>.schema
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use vda5050_data_types::schema::{diff, message_schemas};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [flag, dir] if flag == "--emit" => emit(Path::new(dir)),
+        [dir] => compare(Path::new(dir)),
+        _ => {
+            eprintln!("usage: vda5050-schema-diff <official-schema-dir> | --emit <output-dir>");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn emit(dir: &Path) -> Result<bool, String> {
+    std::fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    for (name, schema) in message_schemas() {
+        let path = dir.join(format!("{name}.schema"));
+        let json = serde_json::to_string_pretty(&schema).map_err(|err| err.to_string())?;
+        std::fs::write(&path, json).map_err(|err| format!("{}: {err}", path.display()))?;
+        println!("wrote {}", path.display());
+    }
+    Ok(true)
+}
+
+fn compare(dir: &Path) -> Result<bool, String> {
+    let mut conforming = true;
+    for (name, generated) in message_schemas() {
+        let Some(path) = official_schema_path(dir, name) else {
+            println!("{name}: no official schema found, skipped");
+            continue;
+        };
+        let content =
+            std::fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let official: serde_json::Value =
+            serde_json::from_str(&content).map_err(|err| format!("{}: {err}", path.display()))?;
+        let differences = diff(&generated, &official);
+        if differences.is_empty() {
+            println!("{name}: ok");
+            continue;
+        }
+        conforming = false;
+        println!("{name}: {} difference(s)", differences.len());
+        for difference in differences {
+            println!("  {difference}");
+        }
+    }
+    Ok(conforming)
+}
+
+/// The official schemas are published as `<message>.schema`, some distributions use `.json`.
+fn official_schema_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    ["schema", "json"]
+        .iter()
+        .map(|extension| dir.join(format!("{name}.{extension}")))
+        .find(|path| path.is_file())
+}
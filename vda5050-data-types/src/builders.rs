@@ -0,0 +1,628 @@
+//! Fluent builders for messages sent to and reported by the AGV.
+//!
+//! The builders take care of the bookkeeping the specification requires, like even sequence IDs
+//! for nodes, odd sequence IDs for edges and the start and end node of every edge.
+use crate::common::{Action, AgvPosition, BlockingType, Header, NodePosition, Velocity};
+use crate::instant_actions::InstantActions;
+use crate::order::{Edge, Node, Order};
+use crate::predefined_actions::PredefinedAction;
+use crate::state::{
+    ActionState, ActionStatus, BatteryState, EdgeState, Error, Info, Load, NodeState,
+    OperatingMode, SafetyState, State,
+};
+use std::fmt;
+
+/// Reasons why an [`OrderBuilder`] cannot produce a valid order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// The order contains no nodes.
+    NoNodes,
+    /// Nodes and edges do not alternate, starting and ending with a node.
+    /// Contains the index of the first misplaced element.
+    NotAlternating(usize),
+    /// The sequence ID of the first node must be even.
+    OddFirstSequenceId(u32),
+    /// The node the horizon should start at is not part of the order.
+    HorizonNodeNotFound(String),
+    /// The horizon starts at the first node, leaving the base empty.
+    EmptyBase,
+    /// An action was added before any node or edge. Contains the action ID.
+    ActionWithoutElement(String),
+    /// The sequence IDs of the nodes and edges run past the largest sequence ID. Contains the
+    /// first sequence ID.
+    SequenceIdOverflow(u32),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::NoNodes => write!(f, "an order needs at least one node"),
+            BuildError::NotAlternating(index) => {
+                write!(f, "element {index} breaks the node-edge-node sequence")
+            }
+            BuildError::OddFirstSequenceId(sequence_id) => {
+                write!(f, "first sequence ID {sequence_id} is not even")
+            }
+            BuildError::HorizonNodeNotFound(node_id) => {
+                write!(f, "horizon node '{node_id}' is not part of the order")
+            }
+            BuildError::EmptyBase => write!(f, "the horizon must not start at the first node"),
+            BuildError::ActionWithoutElement(action_id) => {
+                write!(f, "action '{action_id}' was added before any node or edge")
+            }
+            BuildError::SequenceIdOverflow(sequence_id) => write!(
+                f,
+                "the sequence IDs starting at {sequence_id} run past the largest sequence ID"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+enum Element {
+    Node(Node),
+    Edge(Edge),
+}
+
+/// Builds an [`Order`] from a sequence of nodes and edges.
+///
+/// Nodes get even and edges odd sequence IDs, counting up from the first sequence ID.
+/// Each edge connects the node added before it with the node added after it.
+///
+/// ```
+/// # use vda5050_data_types::builders::OrderBuilder;
+/// # use vda5050_data_types::common::Header;
+/// # let header = Header { header_id: 1, timestamp: String::new(), version: "2.0.0".into(),
+/// #     manufacturer: "Acme".into(), serial_number: "agv-1".into() };
+/// let order = OrderBuilder::new(header, "order-1")
+///     .node("N1")
+///     .edge("E1")
+///     .node("N2")
+///     .edge("E2")
+///     .node("N3")
+///     .horizon_from("N3")
+///     .build()
+///     .unwrap();
+/// assert_eq!(order.edges[1].start_node_id, "N2");
+/// assert!(!order.nodes[2].released);
+/// ```
+pub struct OrderBuilder {
+    header: Header,
+    order_id: String,
+    order_update_id: u32,
+    first_sequence_id: u32,
+    elements: Vec<Element>,
+    horizon_from: Option<String>,
+    /// The ID of the first action added before any node or edge.
+    action_without_element: Option<String>,
+}
+
+impl OrderBuilder {
+    /// Starts a new order with update ID 0.
+    pub fn new(header: Header, order_id: impl Into<String>) -> Self {
+        OrderBuilder {
+            header,
+            order_id: order_id.into(),
+            order_update_id: 0,
+            first_sequence_id: 0,
+            elements: Vec::new(),
+            horizon_from: None,
+            action_without_element: None,
+        }
+    }
+
+    /// Sets the order update ID, for updates of an existing order.
+    pub fn order_update_id(mut self, order_update_id: u32) -> Self {
+        self.order_update_id = order_update_id;
+        self
+    }
+
+    /// Sets the sequence ID of the first node.
+    ///
+    /// Order updates have to start with the last node of the previous base,
+    /// including its sequence ID.
+    pub fn first_sequence_id(mut self, sequence_id: u32) -> Self {
+        self.first_sequence_id = sequence_id;
+        self
+    }
+
+    /// Appends a node without a position.
+    pub fn node(self, node_id: impl Into<String>) -> Self {
+        self.node_with(Node {
+            node_id: node_id.into(),
+            sequence_id: 0,
+            released: true,
+            node_position: None,
+            actions: Vec::new(),
+        })
+    }
+
+    /// Appends a node at the given position.
+    pub fn node_at(self, node_id: impl Into<String>, position: NodePosition) -> Self {
+        self.node_with(Node {
+            node_id: node_id.into(),
+            sequence_id: 0,
+            released: true,
+            node_position: Some(position),
+            actions: Vec::new(),
+        })
+    }
+
+    /// Appends a fully specified node. The sequence ID and released flag are overwritten.
+    pub fn node_with(mut self, node: Node) -> Self {
+        self.elements.push(Element::Node(node));
+        self
+    }
+
+    /// Appends an edge to the node that is added next.
+    pub fn edge(self, edge_id: impl Into<String>) -> Self {
+        self.edge_with(Edge {
+            edge_id: edge_id.into(),
+            sequence_id: 0,
+            released: true,
+            start_node_id: String::new(),
+            end_node_id: String::new(),
+            actions: Vec::new(),
+            trajectory: None,
+            length: None,
+            max_speed: None,
+            max_height: None,
+            min_height: None,
+            orientation: None,
+            direction: None,
+        })
+    }
+
+    /// Appends a fully specified edge.
+    /// The sequence ID, released flag and start and end node are overwritten.
+    pub fn edge_with(mut self, edge: Edge) -> Self {
+        self.elements.push(Element::Edge(edge));
+        self
+    }
+
+    /// Adds an action to the node or edge that was added last. Without one, [`build`] fails.
+    ///
+    /// [`build`]: OrderBuilder::build
+    pub fn action(mut self, action: Action) -> Self {
+        match self.elements.last_mut() {
+            Some(Element::Node(node)) => node.actions.push(action),
+            Some(Element::Edge(edge)) => edge.actions.push(action),
+            None => {
+                self.action_without_element.get_or_insert(action.action_id);
+            }
+        }
+        self
+    }
+
+    /// Makes the first node with the given ID, the edge leading to it and everything
+    /// after it part of the horizon, i.e. not released.
+    pub fn horizon_from(mut self, node_id: impl Into<String>) -> Self {
+        self.horizon_from = Some(node_id.into());
+        self
+    }
+
+    /// Validates the sequence of nodes and edges and assigns the sequence IDs.
+    pub fn build(self) -> Result<Order, BuildError> {
+        if !self.first_sequence_id.is_multiple_of(2) {
+            return Err(BuildError::OddFirstSequenceId(self.first_sequence_id));
+        }
+        if let Some(action_id) = self.action_without_element {
+            return Err(BuildError::ActionWithoutElement(action_id));
+        }
+        if self.elements.is_empty() {
+            return Err(BuildError::NoNodes);
+        }
+        for (index, element) in self.elements.iter().enumerate() {
+            let expects_node = index.is_multiple_of(2);
+            if expects_node != matches!(element, Element::Node(_)) {
+                return Err(BuildError::NotAlternating(index));
+            }
+        }
+        if self.elements.len().is_multiple_of(2) {
+            return Err(BuildError::NotAlternating(self.elements.len() - 1));
+        }
+        let sequence_ids: Vec<u32> = (0..self.elements.len())
+            .map(|index| {
+                u32::try_from(index)
+                    .ok()
+                    .and_then(|index| self.first_sequence_id.checked_add(index))
+                    .ok_or(BuildError::SequenceIdOverflow(self.first_sequence_id))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let horizon_start = match &self.horizon_from {
+            Some(node_id) => {
+                let index = self
+                    .elements
+                    .iter()
+                    .position(|element| matches!(element, Element::Node(node) if &node.node_id == node_id))
+                    .ok_or_else(|| BuildError::HorizonNodeNotFound(node_id.clone()))?;
+                if index == 0 {
+                    return Err(BuildError::EmptyBase);
+                }
+                // The edge leading to the first horizon node is not released either.
+                index - 1
+            }
+            None => self.elements.len(),
+        };
+
+        let node_ids: Vec<String> = self
+            .elements
+            .iter()
+            .filter_map(|element| match element {
+                Element::Node(node) => Some(node.node_id.clone()),
+                Element::Edge(_) => None,
+            })
+            .collect();
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for (index, element) in self.elements.into_iter().enumerate() {
+            let sequence_id = sequence_ids[index];
+            let released = index < horizon_start;
+            match element {
+                Element::Node(mut node) => {
+                    node.sequence_id = sequence_id;
+                    node.released = released;
+                    nodes.push(node);
+                }
+                Element::Edge(mut edge) => {
+                    edge.sequence_id = sequence_id;
+                    edge.released = released;
+                    edge.start_node_id = node_ids[index / 2].clone();
+                    edge.end_node_id = node_ids[index / 2 + 1].clone();
+                    edges.push(edge);
+                }
+            }
+        }
+
+        Ok(Order {
+            header: self.header,
+            order_id: self.order_id,
+            order_update_id: self.order_update_id,
+            nodes,
+            edges,
+        })
+    }
+}
+
+/// Builds an [`InstantActions`] message.
+///
+/// Predefined actions get an action ID made of their action type, the header ID and their
+/// position in the message, which is unique as long as header IDs are not reused.
+pub struct InstantActionsBuilder {
+    header: Header,
+    actions: Vec<Action>,
+}
+
+impl InstantActionsBuilder {
+    /// Starts an empty instant actions message.
+    pub fn new(header: Header) -> Self {
+        InstantActionsBuilder {
+            header,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Appends a generic action as is.
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Appends a predefined action with a generated action ID.
+    pub fn predefined(mut self, action: PredefinedAction, blocking_type: BlockingType) -> Self {
+        let action_id = format!(
+            "{}-{}-{}",
+            action.action_type(),
+            self.header.header_id,
+            self.actions.len()
+        );
+        self.actions
+            .push(action.into_action(action_id, blocking_type));
+        self
+    }
+
+    /// Finishes the message.
+    pub fn build(self) -> InstantActions {
+        InstantActions {
+            header: self.header,
+            instant_actions: self.actions,
+        }
+    }
+}
+
+/// Builds a [`State`] as reported by an AGV.
+///
+/// Without further calls the AGV is idle: no order, not driving and no errors.
+pub struct StateBuilder {
+    state: State,
+}
+
+impl StateBuilder {
+    /// Starts an idle state in the given operating mode.
+    pub fn new(header: Header, operating_mode: OperatingMode) -> Self {
+        StateBuilder {
+            state: State {
+                header,
+                order_id: None,
+                order_update_id: None,
+                last_node_id: None,
+                last_node_sequence_id: None,
+                node_states: Vec::new(),
+                edge_states: Vec::new(),
+                agv_position: None,
+                velocity: None,
+                loads: None,
+                driving: false,
+                paused: None,
+                new_base_request: None,
+                distance_since_last_node: None,
+                operating_mode,
+                action_states: Vec::new(),
+                errors: Vec::new(),
+                information: None,
+                battery_state: None,
+                safety_state: None,
+            },
+        }
+    }
+
+    /// Reports the given order as accepted, with the AGV standing on its first node.
+    ///
+    /// All nodes and edges after the first node are reported as node and edge states, and every
+    /// action of the nodes and edges as waiting.
+    pub fn order(mut self, order: &Order) -> Self {
+        self.state.order_id = Some(order.order_id.clone());
+        self.state.order_update_id = Some(order.order_update_id);
+        if let Some(first) = order.nodes.first() {
+            self.state.last_node_id = Some(first.node_id.clone());
+            self.state.last_node_sequence_id = Some(first.sequence_id);
+        }
+        self.state.node_states = order.nodes.iter().skip(1).map(node_state).collect();
+        self.state.edge_states = order.edges.iter().map(edge_state).collect();
+        let node_actions = order.nodes.iter().flat_map(|node| &node.actions);
+        let edge_actions = order.edges.iter().flat_map(|edge| &edge.actions);
+        self.state.action_states = node_actions.chain(edge_actions).map(waiting).collect();
+        self
+    }
+
+    /// Reports the status of an action, replacing the status reported for the same action ID.
+    pub fn action_state(mut self, action_state: ActionState) -> Self {
+        match self
+            .state
+            .action_states
+            .iter_mut()
+            .find(|reported| reported.action_id == action_state.action_id)
+        {
+            Some(reported) => *reported = action_state,
+            None => self.state.action_states.push(action_state),
+        }
+        self
+    }
+
+    /// Reports the node with the given sequence ID as reached.
+    ///
+    /// Node and edge states up to and including the sequence ID are removed.
+    pub fn reached(mut self, node_id: impl Into<String>, sequence_id: u32) -> Self {
+        self.state.last_node_id = Some(node_id.into());
+        self.state.last_node_sequence_id = Some(sequence_id);
+        self.state
+            .node_states
+            .retain(|node| node.sequence_id > sequence_id);
+        self.state
+            .edge_states
+            .retain(|edge| edge.sequence_id > sequence_id);
+        self.state.distance_since_last_node = Some(0.0);
+        self
+    }
+
+    /// Sets the position of the AGV.
+    pub fn position(mut self, position: AgvPosition) -> Self {
+        self.state.agv_position = Some(position);
+        self
+    }
+
+    /// Sets the velocity of the AGV.
+    pub fn velocity(mut self, velocity: Velocity) -> Self {
+        self.state.velocity = Some(velocity);
+        self
+    }
+
+    /// Sets whether the AGV is driving.
+    pub fn driving(mut self, driving: bool) -> Self {
+        self.state.driving = driving;
+        self
+    }
+
+    /// Sets whether the AGV is paused.
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.state.paused = Some(paused);
+        self
+    }
+
+    /// Adds a load on the AGV.
+    pub fn load(mut self, load: Load) -> Self {
+        self.state.loads.get_or_insert_with(Vec::new).push(load);
+        self
+    }
+
+    /// Adds an error.
+    pub fn error(mut self, error: Error) -> Self {
+        self.state.errors.push(error);
+        self
+    }
+
+    /// Adds an information message.
+    pub fn information(mut self, info: Info) -> Self {
+        self.state
+            .information
+            .get_or_insert_with(Vec::new)
+            .push(info);
+        self
+    }
+
+    /// Sets the battery state.
+    pub fn battery(mut self, battery_state: BatteryState) -> Self {
+        self.state.battery_state = Some(battery_state);
+        self
+    }
+
+    /// Sets the safety state.
+    pub fn safety(mut self, safety_state: SafetyState) -> Self {
+        self.state.safety_state = Some(safety_state);
+        self
+    }
+
+    /// Finishes the state.
+    pub fn build(self) -> State {
+        self.state
+    }
+}
+
+fn waiting(action: &Action) -> ActionState {
+    ActionState {
+        action_id: action.action_id.clone(),
+        action_type: Some(action.action_type.clone()),
+        action_description: None,
+        action_status: ActionStatus::Waiting,
+        result_description: None,
+    }
+}
+
+fn node_state(node: &Node) -> NodeState {
+    NodeState {
+        node_id: node.node_id.clone(),
+        sequence_id: node.sequence_id,
+        node_description: None,
+        released: node.released,
+        node_position: node.node_position.clone(),
+        actions: node.actions.clone(),
+    }
+}
+
+fn edge_state(edge: &Edge) -> EdgeState {
+    EdgeState {
+        edge_id: edge.edge_id.clone(),
+        sequence_id: edge.sequence_id,
+        edge_description: None,
+        released: edge.released,
+        trajectory: edge.trajectory.clone(),
+        actions: edge.actions.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            header_id: 1,
+            timestamp: String::new(),
+            version: "2.0.0".into(),
+            manufacturer: "Acme".into(),
+            serial_number: "agv-1".into(),
+        }
+    }
+
+    fn action(action_id: &str) -> Action {
+        Action {
+            action_id: action_id.into(),
+            action_type: "pick".into(),
+            blocking_type: BlockingType::Hard,
+            action_parameters: None,
+        }
+    }
+
+    #[test]
+    fn adds_actions_to_the_last_node_or_edge() {
+        let order = OrderBuilder::new(header(), "order-1")
+            .node("N1")
+            .action(action("a1"))
+            .edge("E1")
+            .action(action("a2"))
+            .node("N2")
+            .build()
+            .unwrap();
+        assert_eq!(order.nodes[0].actions[0].action_id, "a1");
+        assert_eq!(order.edges[0].actions[0].action_id, "a2");
+    }
+
+    #[test]
+    fn rejects_actions_before_any_node() {
+        let result = OrderBuilder::new(header(), "order-1")
+            .action(action("a1"))
+            .action(action("a2"))
+            .node("N1")
+            .build();
+        assert_eq!(
+            result.unwrap_err(),
+            BuildError::ActionWithoutElement("a1".into())
+        );
+    }
+
+    #[test]
+    fn reports_the_actions_of_an_order_as_waiting() {
+        let order = OrderBuilder::new(header(), "order-1")
+            .node("N1")
+            .action(action("a1"))
+            .edge("E1")
+            .action(action("a2"))
+            .node("N2")
+            .action(action("a3"))
+            .build()
+            .unwrap();
+        let state = StateBuilder::new(header(), OperatingMode::Automatic)
+            .order(&order)
+            .action_state(ActionState {
+                action_id: "a1".into(),
+                action_type: Some("pick".into()),
+                action_description: None,
+                action_status: ActionStatus::Finished,
+                result_description: None,
+            })
+            .build();
+        let statuses: Vec<(&str, ActionStatus)> = state
+            .action_states
+            .iter()
+            .map(|action_state| {
+                (
+                    action_state.action_id.as_str(),
+                    action_state.action_status.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("a1", ActionStatus::Finished),
+                ("a3", ActionStatus::Waiting),
+                ("a2", ActionStatus::Waiting),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_sequence_ids_past_the_largest_one() {
+        let result = OrderBuilder::new(header(), "order-1")
+            .first_sequence_id(u32::MAX - 1)
+            .node("N1")
+            .edge("E1")
+            .node("N2")
+            .build();
+        assert_eq!(
+            result.unwrap_err(),
+            BuildError::SequenceIdOverflow(u32::MAX - 1)
+        );
+
+        let order = OrderBuilder::new(header(), "order-1")
+            .first_sequence_id(u32::MAX - 3)
+            .node("N1")
+            .edge("E1")
+            .node("N2")
+            .build()
+            .unwrap();
+        assert_eq!(order.nodes[1].sequence_id, u32::MAX - 1);
+    }
+}
@@ -1,7 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// The header is part of each VDA 5050 message.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Header {
     /// Unique continuous number, can be used to identify lost messages.
@@ -20,8 +22,18 @@ pub struct Header {
     pub serial_number: String,
 }
 
+impl Header {
+    /// The timestamp parsed as UTC, or `None` if it is not valid ISO 8601.
+    pub fn timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+}
+
 /// An action that is to be executed by the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Action {
     /// Unique identifier for the action.
@@ -39,7 +51,8 @@ pub struct Action {
 }
 
 /// Defines if the action is blocking.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum BlockingType {
     /// The AGV can execute the next action in parallel.
     #[serde(rename = "NONE")]
@@ -50,10 +63,15 @@ pub enum BlockingType {
     /// The AGV has to wait for the action to finish before it can start the next one.
     #[serde(rename = "HARD")]
     Hard,
+    /// A blocking type not defined by the specification, kept as received.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
 
 /// The position of a node in a map.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct NodePosition {
     /// X-coordinate described in the world coordinate system. In [m].
@@ -75,6 +93,7 @@ pub struct NodePosition {
 
 /// A trajectory for the AGV to follow, based on a NURBS curve.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Trajectory {
     /// Header ID of the trajectory.
@@ -87,8 +106,65 @@ pub struct Trajectory {
     pub control_points: Vec<ControlPoint>,
 }
 
+impl Trajectory {
+    /// Points on the curve at evenly spaced parameters, the given number per control point plus
+    /// the end, or `None` if the degree, knot vector and control points do not fit together.
+    pub fn sample(&self, samples_per_control_point: usize) -> Option<Vec<(f64, f64)>> {
+        let degree = self.degree as usize;
+        let count = self.control_points.len();
+        if degree == 0 || count <= degree || self.knot_vector.len() != count + degree + 1 {
+            return None;
+        }
+        let knots = &self.knot_vector;
+        let (first, last) = (knots[degree], knots[count]);
+        if last <= first {
+            return None;
+        }
+        let samples = count * samples_per_control_point.max(1);
+        (0..=samples)
+            .map(|i| self.de_boor(first + (last - first) * i as f64 / samples as f64))
+            .collect()
+    }
+
+    /// Evaluates the curve at the parameter `u` with the algorithm of de Boor, in homogeneous
+    /// coordinates to respect the weights.
+    fn de_boor(&self, u: f64) -> Option<(f64, f64)> {
+        let degree = self.degree as usize;
+        let knots = &self.knot_vector;
+        let count = self.control_points.len();
+        let span = (degree..count).rfind(|&i| knots[i] <= u).unwrap_or(degree);
+        let mut points: Vec<(f64, f64, f64)> = (0..=degree)
+            .map(|j| {
+                let point = &self.control_points[span - degree + j];
+                let weight = point.weight.unwrap_or(1.0);
+                (point.x * weight, point.y * weight, weight)
+            })
+            .collect();
+        for r in 1..=degree {
+            for j in (r..=degree).rev() {
+                let left = knots[span - degree + j];
+                let right = knots[span + 1 + j - r];
+                let alpha = if right > left {
+                    (u - left) / (right - left)
+                } else {
+                    0.0
+                };
+                let (previous, current) = (points[j - 1], points[j]);
+                points[j] = (
+                    (1.0 - alpha) * previous.0 + alpha * current.0,
+                    (1.0 - alpha) * previous.1 + alpha * current.1,
+                    (1.0 - alpha) * previous.2 + alpha * current.2,
+                );
+            }
+        }
+        let (x, y, weight) = points[degree];
+        (weight != 0.0).then(|| (x / weight, y / weight))
+    }
+}
+
 /// A control point of a spline.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ControlPoint {
     /// X-coordinate described in the world coordinate system.
@@ -104,6 +180,7 @@ pub struct ControlPoint {
 
 /// The position of the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct AgvPosition {
     /// X-coordinate of the AGV in the world coordinate system. In [m].
@@ -128,6 +205,7 @@ pub struct AgvPosition {
 
 /// The velocity of the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Velocity {
     /// Velocity in X direction. In [m/s].
@@ -143,6 +221,7 @@ pub struct Velocity {
 
 /// A point in 3D space.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Point {
     /// X coordinate of the point. In [m].
     pub x: f64,
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 /// This message is sent by the AGV to the master control to indicate its connection state.
 /// It is also sent by the broker as a last will to indicate that the connection to the AGV was lost.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Connection {
     #[serde(flatten)]
@@ -17,7 +18,8 @@ pub struct Connection {
 }
 
 /// The state of the connection.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ConnectionState {
     /// The AGV is online and connected to the master control.
     #[serde(rename = "ONLINE")]
@@ -28,5 +30,9 @@ pub enum ConnectionState {
     /// The connection to the AGV was lost.
     #[serde(rename = "CONNECTIONBROKEN")]
     ConnectionBroken,
+    /// A connection state not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
 
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 ///
 /// The factsheet is sent by the AGV to the master control to provide information about its capabilities.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Factsheet {
     #[serde(flatten)]
@@ -24,12 +25,16 @@ pub struct Factsheet {
     pub load_dimensions: Option<Dimensions>,
     /// The dimensions of the AGV.
     pub agv_dimensions: Dimensions,
+    /// The speed and acceleration limits of the AGV.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub physical_parameters: Option<PhysicalParameters>,
     /// A list of actions that the AGV can perform.
     pub actions: Vec<ActionDefinition>,
 }
 
 /// The kinematic of the AGV.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum AgvKinematic {
     /// A differential drive.
     #[serde(rename = "DIFFERENTIAL")]
@@ -40,10 +45,15 @@ pub enum AgvKinematic {
     /// An omnidirectional drive.
     #[serde(rename = "OMNIDRIVE")]
     Omnidrive,
+    /// A vendor specific kinematic.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
 
 /// The dimensions of an object.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Dimensions {
     /// The length of the object in [m].
@@ -55,8 +65,30 @@ pub struct Dimensions {
     pub height: Option<f64>,
 }
 
+/// The speed and acceleration limits of the AGV.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalParameters {
+    /// The minimal controlled continuous speed of the AGV in [m/s].
+    pub speed_min: f64,
+    /// The maximum speed of the AGV in [m/s].
+    pub speed_max: f64,
+    /// The minimal controlled continuous rotation speed of the AGV in [rad/s].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angular_speed_min: Option<f64>,
+    /// The maximum rotation speed of the AGV in [rad/s].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angular_speed_max: Option<f64>,
+    /// The maximum acceleration with maximum load in [m/s²].
+    pub acceleration_max: f64,
+    /// The maximum deceleration with maximum load in [m/s²].
+    pub deceleration_max: f64,
+}
+
 /// The definition of an action.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ActionDefinition {
     /// The type of the action.
@@ -71,7 +103,8 @@ pub struct ActionDefinition {
 }
 
 /// The scope of an action.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ActionScope {
     /// The action can be executed at a node.
     #[serde(rename = "NODE")]
@@ -82,4 +115,8 @@ pub enum ActionScope {
     /// The action can be executed as an instant action.
     #[serde(rename = "INSTANT")]
     Instant,
+    /// A scope not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
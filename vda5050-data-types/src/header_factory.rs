@@ -0,0 +1,207 @@
+use crate::common::Header;
+use crate::topic::Topic;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::sync::{Arc, Mutex};
+
+/// The VDA 5050 version written into headers unless configured otherwise.
+pub const DEFAULT_VERSION: &str = "2.0.0";
+
+/// A source of the current time for header timestamps.
+pub trait Clock: Send + Sync {
+    /// The current time in UTC.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// A shared clock, so tests can keep a handle to a [`ManualClock`] after handing it to a factory.
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+/// The system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests and replays.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// Creates a clock standing at the given time.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the current time forward.
+    pub fn advance(&self, duration: chrono::Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Creates the headers for all messages of one AGV.
+///
+/// Every topic has its own header ID sequence starting at 0, as receivers check for gaps per topic.
+/// The factory can be shared between threads, each call to [`HeaderFactory::next`] returns a
+/// distinct header ID. The header ID and the timestamp are taken under one lock, so a later header
+/// ID on a topic never gets an earlier timestamp.
+pub struct HeaderFactory {
+    version: String,
+    manufacturer: String,
+    serial_number: String,
+    clock: Box<dyn Clock>,
+    next_ids: Mutex<[u32; Topic::ALL.len()]>,
+}
+
+impl HeaderFactory {
+    /// Creates a factory for the given AGV using the system clock.
+    pub fn new(manufacturer: impl Into<String>, serial_number: impl Into<String>) -> Self {
+        HeaderFactory {
+            version: DEFAULT_VERSION.to_string(),
+            manufacturer: manufacturer.into(),
+            serial_number: serial_number.into(),
+            clock: Box::new(SystemClock),
+            next_ids: Default::default(),
+        }
+    }
+
+    /// Sets the VDA 5050 version written into the headers.
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Replaces the clock used for the timestamps.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Creates the header for the next message on the given topic.
+    pub fn next(&self, topic: Topic) -> Header {
+        let (header_id, now) = {
+            let mut next_ids = self.next_ids.lock().unwrap();
+            let header_id = next_ids[topic as usize];
+            next_ids[topic as usize] = header_id.wrapping_add(1);
+            (header_id, self.clock.now())
+        };
+        Header {
+            header_id,
+            timestamp: now.to_rfc3339_opts(SecondsFormat::Millis, true),
+            version: self.version.clone(),
+            manufacturer: self.manufacturer.clone(),
+            serial_number: self.serial_number.clone(),
+        }
+    }
+
+    /// The header ID the next message on the given topic will get.
+    pub fn peek_header_id(&self, topic: Topic) -> u32 {
+        self.next_ids.lock().unwrap()[topic as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start() -> DateTime<Utc> {
+        "2024-01-01T00:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn counts_header_ids_per_topic() {
+        let factory = HeaderFactory::new("Acme", "agv-1");
+        assert_eq!(factory.next(Topic::State).header_id, 0);
+        assert_eq!(factory.next(Topic::State).header_id, 1);
+        assert_eq!(factory.next(Topic::Visualization).header_id, 0);
+        assert_eq!(factory.next(Topic::State).header_id, 2);
+        assert_eq!(factory.peek_header_id(Topic::State), 3);
+        assert_eq!(factory.peek_header_id(Topic::Order), 0);
+    }
+
+    #[test]
+    fn stamps_the_agv_the_version_and_the_time_of_the_clock() {
+        let clock = Arc::new(ManualClock::new(start()));
+        let factory = HeaderFactory::new("Acme", "agv-1")
+            .with_version("2.1.0")
+            .with_clock(clock.clone());
+        let header = factory.next(Topic::Connection);
+        assert_eq!(header.version, "2.1.0");
+        assert_eq!(header.manufacturer, "Acme");
+        assert_eq!(header.serial_number, "agv-1");
+        assert_eq!(header.timestamp, "2024-01-01T00:00:00.000Z");
+
+        clock.advance(chrono::Duration::milliseconds(1250));
+        assert_eq!(
+            factory.next(Topic::Connection).timestamp,
+            "2024-01-01T00:00:01.250Z"
+        );
+        assert_eq!(
+            HeaderFactory::new("Acme", "agv-1")
+                .next(Topic::State)
+                .version,
+            DEFAULT_VERSION
+        );
+    }
+
+    /// A clock that moves one millisecond on every reading.
+    struct TickingClock(Mutex<DateTime<Utc>>);
+
+    impl Clock for TickingClock {
+        fn now(&self) -> DateTime<Utc> {
+            let mut now = self.0.lock().unwrap();
+            *now += chrono::Duration::milliseconds(1);
+            *now
+        }
+    }
+
+    #[test]
+    fn later_header_ids_never_get_earlier_timestamps() {
+        let factory = Arc::new(
+            HeaderFactory::new("Acme", "agv-1").with_clock(TickingClock(Mutex::new(start()))),
+        );
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let factory = factory.clone();
+                std::thread::spawn(move || {
+                    (0..250)
+                        .map(|_| factory.next(Topic::State))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut headers: Vec<Header> = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect();
+        headers.sort_by_key(|header| header.header_id);
+        let ids: Vec<u32> = headers.iter().map(|header| header.header_id).collect();
+        assert_eq!(ids, (0..1000).collect::<Vec<_>>());
+        assert!(
+            headers
+                .windows(2)
+                .all(|pair| pair[0].timestamp < pair[1].timestamp)
+        );
+    }
+}
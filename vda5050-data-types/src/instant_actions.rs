@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 /// Instant actions are actions that are executed immediately by the AGV,
 /// without being part of an order.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct InstantActions {
     #[serde(flatten)]
@@ -1,7 +1,14 @@
+pub mod builders;
 pub mod common;
 pub mod connection;
 pub mod factsheet;
+pub mod header_factory;
 pub mod instant_actions;
+pub mod message;
 pub mod order;
+pub mod predefined_actions;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod state;
+pub mod topic;
 pub mod visualization;
@@ -0,0 +1,92 @@
+use crate::common::Header;
+use crate::connection::Connection;
+use crate::factsheet::Factsheet;
+use crate::instant_actions::InstantActions;
+use crate::order::Order;
+use crate::state::State;
+use crate::topic::Topic;
+use crate::visualization::Visualization;
+use serde::Serialize;
+
+/// Any VDA 5050 message.
+///
+/// The payload does not identify its message type, so decoding needs the topic it was published on.
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum Message {
+    /// An order from master control.
+    Order(Order),
+    /// Instant actions from master control.
+    InstantActions(InstantActions),
+    /// The state of an AGV.
+    State(State),
+    /// The visualization message of an AGV.
+    Visualization(Visualization),
+    /// The connection state of an AGV.
+    Connection(Connection),
+    /// The factsheet of an AGV.
+    Factsheet(Factsheet),
+}
+
+impl Message {
+    /// Decodes a JSON payload received on the given topic.
+    pub fn decode(topic: Topic, payload: &[u8]) -> Result<Self, serde_json::Error> {
+        Ok(match topic {
+            Topic::Order => Message::Order(serde_json::from_slice(payload)?),
+            Topic::InstantActions => Message::InstantActions(serde_json::from_slice(payload)?),
+            Topic::State => Message::State(serde_json::from_slice(payload)?),
+            Topic::Visualization => Message::Visualization(serde_json::from_slice(payload)?),
+            Topic::Connection => Message::Connection(serde_json::from_slice(payload)?),
+            Topic::Factsheet => Message::Factsheet(serde_json::from_slice(payload)?),
+        })
+    }
+
+    /// Decodes an already parsed JSON payload received on the given topic.
+    pub fn from_value(topic: Topic, payload: serde_json::Value) -> Result<Self, serde_json::Error> {
+        Ok(match topic {
+            Topic::Order => Message::Order(serde_json::from_value(payload)?),
+            Topic::InstantActions => Message::InstantActions(serde_json::from_value(payload)?),
+            Topic::State => Message::State(serde_json::from_value(payload)?),
+            Topic::Visualization => Message::Visualization(serde_json::from_value(payload)?),
+            Topic::Connection => Message::Connection(serde_json::from_value(payload)?),
+            Topic::Factsheet => Message::Factsheet(serde_json::from_value(payload)?),
+        })
+    }
+
+    /// The topic the message is published on.
+    pub fn topic(&self) -> Topic {
+        match self {
+            Message::Order(_) => Topic::Order,
+            Message::InstantActions(_) => Topic::InstantActions,
+            Message::State(_) => Topic::State,
+            Message::Visualization(_) => Topic::Visualization,
+            Message::Connection(_) => Topic::Connection,
+            Message::Factsheet(_) => Topic::Factsheet,
+        }
+    }
+
+    /// The header of the message.
+    pub fn header(&self) -> &Header {
+        match self {
+            Message::Order(order) => &order.header,
+            Message::InstantActions(instant_actions) => &instant_actions.header,
+            Message::State(state) => &state.header,
+            Message::Visualization(visualization) => &visualization.header,
+            Message::Connection(connection) => &connection.header,
+            Message::Factsheet(factsheet) => &factsheet.header,
+        }
+    }
+
+    /// The header of the message, for changing it before the message is sent again.
+    pub fn header_mut(&mut self) -> &mut Header {
+        match self {
+            Message::Order(order) => &mut order.header,
+            Message::InstantActions(instant_actions) => &mut instant_actions.header,
+            Message::State(state) => &mut state.header,
+            Message::Visualization(visualization) => &mut visualization.header,
+            Message::Connection(connection) => &mut connection.header,
+            Message::Factsheet(factsheet) => &mut factsheet.header,
+        }
+    }
+}
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 /// The order is a list of nodes and edges that the AGV has to traverse.
 /// The AGV is expected to traverse the nodes and edges in the order of their sequenceId.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Order {
     #[serde(flatten)]
@@ -28,6 +29,7 @@ pub struct Order {
 
 /// A node in the order graph.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Node {
     /// Unique identifier for the node.
@@ -48,6 +50,7 @@ pub struct Node {
 
 /// An edge that connects two nodes in the order graph.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Edge {
     /// Unique identifier for the edge.
@@ -83,5 +86,26 @@ pub struct Edge {
     pub orientation: Option<f64>,
     /// The direction of the AGV on the edge.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub direction: Option<String>,
+    pub direction: Option<EdgeDirection>,
+}
+
+/// The direction at junctions for line-guided or wire-guided AGVs.
+///
+/// The specification leaves the values vehicle specific, so only the common examples are named.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum EdgeDirection {
+    /// Turn left at the junction.
+    #[serde(rename = "left")]
+    Left,
+    /// Turn right at the junction.
+    #[serde(rename = "right")]
+    Right,
+    /// Go straight at the junction.
+    #[serde(rename = "straight")]
+    Straight,
+    /// A vehicle specific direction, e.g. a frequency like "433MHz".
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
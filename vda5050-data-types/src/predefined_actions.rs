@@ -0,0 +1,416 @@
+use crate::common::{Action, BlockingType};
+use crate::factsheet::ActionScope;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// An action predefined by the VDA 5050 specification, with typed parameters.
+///
+/// Converting a generic [`Action`] validates the action type and its parameters.
+/// Vendor specific actions are rejected with [`ActionError::UnknownActionType`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PredefinedAction {
+    /// Activates the pause mode. No more AGV driving movements.
+    StartPause,
+    /// Deactivates the pause mode. Movement and all other actions will be resumed.
+    StopPause,
+    /// Activates the charging process.
+    StartCharging,
+    /// Deactivates the charging process to send a new order.
+    StopCharging,
+    /// Resets (overrides) the pose of the AGV with the given parameters.
+    InitPosition(InitPosition),
+    /// Requests the AGV to send a new state report.
+    StateRequest,
+    /// Requests the AGV to generate and store a log report.
+    LogReport {
+        /// Identification of the reason why the log report was requested.
+        reason: String,
+    },
+    /// Requests the AGV to pick a load.
+    Pick(LoadHandling),
+    /// Requests the AGV to drop a load.
+    Drop(LoadHandling),
+    /// The AGV detects the object, e.g. a load or a charging spot.
+    DetectObject {
+        /// The type of the object.
+        object_type: Option<String>,
+    },
+    /// On a node, the AGV will position exactly on a target.
+    FinePositioning {
+        /// The type of the station.
+        station_type: Option<String>,
+        /// The name of the station.
+        station_name: Option<String>,
+    },
+    /// The AGV has to wait for a trigger on the AGV, e.g. a button press.
+    WaitForTrigger {
+        /// The type of the trigger.
+        trigger_type: String,
+    },
+    /// The AGV stops as soon as possible and cancels the current order.
+    CancelOrder,
+    /// Requests the AGV to send a factsheet.
+    FactsheetRequest,
+}
+
+/// The parameters of an `initPosition` action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitPosition {
+    /// X-coordinate in the world coordinate system. In [m].
+    pub x: f64,
+    /// Y-coordinate in the world coordinate system. In [m].
+    pub y: f64,
+    /// Orientation of the AGV in [rad].
+    pub theta: f64,
+    /// Identifier of the map.
+    pub map_id: String,
+    /// The node the AGV is located at after the initialization.
+    pub last_node_id: String,
+}
+
+/// The parameters of a `pick` or `drop` action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadHandling {
+    /// The load handling device that is used, e.g. in case of multiple forks.
+    pub lhd: Option<String>,
+    /// The type of the station, e.g. "floor" or "rack".
+    pub station_type: String,
+    /// The name of the station.
+    pub station_name: Option<String>,
+    /// The type of the load.
+    pub load_type: String,
+    /// The ID of the load.
+    pub load_id: Option<String>,
+    /// The height of the load handling device in [m].
+    pub height: Option<f64>,
+    /// The depth of the load handling device in [m], e.g. for forklifts.
+    pub depth: Option<f64>,
+    /// The side of the load handling, e.g. "conveyor left".
+    pub side: Option<String>,
+}
+
+/// Reasons why an [`Action`] is not a valid [`PredefinedAction`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionError {
+    /// The action type is not predefined by the specification.
+    UnknownActionType(String),
+    /// The action parameters are neither a list of key-value pairs nor an object.
+    MalformedParameters {
+        /// The type of the action.
+        action_type: String,
+    },
+    /// A required parameter is missing.
+    MissingParameter {
+        /// The type of the action.
+        action_type: String,
+        /// The key of the missing parameter.
+        key: &'static str,
+    },
+    /// A parameter has the wrong type.
+    InvalidParameter {
+        /// The type of the action.
+        action_type: String,
+        /// The key of the invalid parameter.
+        key: &'static str,
+        /// The expected JSON type of the parameter.
+        expected: &'static str,
+    },
+    /// The action is used in a scope it is not allowed in.
+    ScopeNotAllowed {
+        /// The type of the action.
+        action_type: String,
+        /// The scope the action was used in.
+        scope: ActionScope,
+    },
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::UnknownActionType(action_type) => {
+                write!(f, "'{action_type}' is not a predefined action")
+            }
+            ActionError::MalformedParameters { action_type } => {
+                write!(f, "{action_type}: malformed action parameters")
+            }
+            ActionError::MissingParameter { action_type, key } => {
+                write!(f, "{action_type}: missing parameter '{key}'")
+            }
+            ActionError::InvalidParameter {
+                action_type,
+                key,
+                expected,
+            } => write!(f, "{action_type}: parameter '{key}' must be a {expected}"),
+            ActionError::ScopeNotAllowed { action_type, scope } => {
+                write!(f, "{action_type}: not allowed in scope {scope:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+impl PredefinedAction {
+    /// The action type as used in [`Action::action_type`].
+    pub fn action_type(&self) -> &'static str {
+        match self {
+            PredefinedAction::StartPause => "startPause",
+            PredefinedAction::StopPause => "stopPause",
+            PredefinedAction::StartCharging => "startCharging",
+            PredefinedAction::StopCharging => "stopCharging",
+            PredefinedAction::InitPosition(_) => "initPosition",
+            PredefinedAction::StateRequest => "stateRequest",
+            PredefinedAction::LogReport { .. } => "logReport",
+            PredefinedAction::Pick(_) => "pick",
+            PredefinedAction::Drop(_) => "drop",
+            PredefinedAction::DetectObject { .. } => "detectObject",
+            PredefinedAction::FinePositioning { .. } => "finePositioning",
+            PredefinedAction::WaitForTrigger { .. } => "waitForTrigger",
+            PredefinedAction::CancelOrder => "cancelOrder",
+            PredefinedAction::FactsheetRequest => "factsheetRequest",
+        }
+    }
+
+    /// Whether the specification allows the action in the given scope.
+    pub fn allows_scope(&self, scope: &ActionScope) -> bool {
+        match self {
+            PredefinedAction::StartPause
+            | PredefinedAction::StopPause
+            | PredefinedAction::StateRequest
+            | PredefinedAction::LogReport { .. }
+            | PredefinedAction::CancelOrder
+            | PredefinedAction::FactsheetRequest => matches!(scope, ActionScope::Instant),
+            PredefinedAction::StartCharging
+            | PredefinedAction::StopCharging
+            | PredefinedAction::InitPosition(_) => {
+                matches!(scope, ActionScope::Instant | ActionScope::Node)
+            }
+            PredefinedAction::Pick(_)
+            | PredefinedAction::Drop(_)
+            | PredefinedAction::DetectObject { .. }
+            | PredefinedAction::FinePositioning { .. } => {
+                matches!(scope, ActionScope::Node | ActionScope::Edge)
+            }
+            PredefinedAction::WaitForTrigger { .. } => matches!(scope, ActionScope::Node),
+        }
+    }
+
+    /// Checks that the action is allowed in the given scope.
+    pub fn check_scope(&self, scope: ActionScope) -> Result<(), ActionError> {
+        if self.allows_scope(&scope) {
+            Ok(())
+        } else {
+            Err(ActionError::ScopeNotAllowed {
+                action_type: self.action_type().to_string(),
+                scope,
+            })
+        }
+    }
+
+    /// Converts the action into a generic [`Action`] with the parameters as key-value pairs.
+    pub fn into_action(self, action_id: String, blocking_type: BlockingType) -> Action {
+        let action_type = self.action_type().to_string();
+        let mut parameters = Vec::new();
+        let mut push = |key: &str, value: Value| {
+            parameters.push(serde_json::json!({ "key": key, "value": value }));
+        };
+        match self {
+            PredefinedAction::InitPosition(init) => {
+                push("x", init.x.into());
+                push("y", init.y.into());
+                push("theta", init.theta.into());
+                push("mapId", init.map_id.into());
+                push("lastNodeId", init.last_node_id.into());
+            }
+            PredefinedAction::LogReport { reason } => push("reason", reason.into()),
+            PredefinedAction::Pick(load) | PredefinedAction::Drop(load) => {
+                if let Some(lhd) = load.lhd {
+                    push("lhd", lhd.into());
+                }
+                push("stationType", load.station_type.into());
+                if let Some(station_name) = load.station_name {
+                    push("stationName", station_name.into());
+                }
+                push("loadType", load.load_type.into());
+                if let Some(load_id) = load.load_id {
+                    push("loadId", load_id.into());
+                }
+                if let Some(height) = load.height {
+                    push("height", height.into());
+                }
+                if let Some(depth) = load.depth {
+                    push("depth", depth.into());
+                }
+                if let Some(side) = load.side {
+                    push("side", side.into());
+                }
+            }
+            PredefinedAction::DetectObject { object_type } => {
+                if let Some(object_type) = object_type {
+                    push("objectType", object_type.into());
+                }
+            }
+            PredefinedAction::FinePositioning {
+                station_type,
+                station_name,
+            } => {
+                if let Some(station_type) = station_type {
+                    push("stationType", station_type.into());
+                }
+                if let Some(station_name) = station_name {
+                    push("stationName", station_name.into());
+                }
+            }
+            PredefinedAction::WaitForTrigger { trigger_type } => {
+                push("triggerType", trigger_type.into())
+            }
+            PredefinedAction::StartPause
+            | PredefinedAction::StopPause
+            | PredefinedAction::StartCharging
+            | PredefinedAction::StopCharging
+            | PredefinedAction::StateRequest
+            | PredefinedAction::CancelOrder
+            | PredefinedAction::FactsheetRequest => {}
+        }
+        Action {
+            action_id,
+            action_type,
+            blocking_type,
+            action_parameters: (!parameters.is_empty()).then_some(Value::Array(parameters)),
+        }
+    }
+}
+
+impl TryFrom<&Action> for PredefinedAction {
+    type Error = ActionError;
+
+    fn try_from(action: &Action) -> Result<Self, Self::Error> {
+        let params = Parameters::of(action)?;
+        let action = match action.action_type.as_str() {
+            "startPause" => PredefinedAction::StartPause,
+            "stopPause" => PredefinedAction::StopPause,
+            "startCharging" => PredefinedAction::StartCharging,
+            "stopCharging" => PredefinedAction::StopCharging,
+            "initPosition" => PredefinedAction::InitPosition(InitPosition {
+                x: params.required_number("x")?,
+                y: params.required_number("y")?,
+                theta: params.required_number("theta")?,
+                map_id: params.required_string("mapId")?,
+                last_node_id: params.required_string("lastNodeId")?,
+            }),
+            "stateRequest" => PredefinedAction::StateRequest,
+            "logReport" => PredefinedAction::LogReport {
+                reason: params.required_string("reason")?,
+            },
+            "pick" => PredefinedAction::Pick(LoadHandling::from_parameters(&params)?),
+            "drop" => PredefinedAction::Drop(LoadHandling::from_parameters(&params)?),
+            "detectObject" => PredefinedAction::DetectObject {
+                object_type: params.string("objectType")?,
+            },
+            "finePositioning" => PredefinedAction::FinePositioning {
+                station_type: params.string("stationType")?,
+                station_name: params.string("stationName")?,
+            },
+            "waitForTrigger" => PredefinedAction::WaitForTrigger {
+                trigger_type: params.required_string("triggerType")?,
+            },
+            "cancelOrder" => PredefinedAction::CancelOrder,
+            "factsheetRequest" => PredefinedAction::FactsheetRequest,
+            other => return Err(ActionError::UnknownActionType(other.to_string())),
+        };
+        Ok(action)
+    }
+}
+
+impl LoadHandling {
+    fn from_parameters(params: &Parameters) -> Result<Self, ActionError> {
+        Ok(LoadHandling {
+            lhd: params.string("lhd")?,
+            station_type: params.required_string("stationType")?,
+            station_name: params.string("stationName")?,
+            load_type: params.required_string("loadType")?,
+            load_id: params.string("loadId")?,
+            height: params.number("height")?,
+            depth: params.number("depth")?,
+            side: params.string("side")?,
+        })
+    }
+}
+
+/// The action parameters as a map from key to value.
+///
+/// The specification transmits them as a list of `{ "key": .., "value": .. }` objects,
+/// some AGVs send a plain JSON object instead, which is accepted as well.
+struct Parameters<'a> {
+    action_type: &'a str,
+    values: Map<String, Value>,
+}
+
+impl<'a> Parameters<'a> {
+    fn of(action: &'a Action) -> Result<Self, ActionError> {
+        let malformed = || ActionError::MalformedParameters {
+            action_type: action.action_type.clone(),
+        };
+        let values = match &action.action_parameters {
+            None | Some(Value::Null) => Map::new(),
+            Some(Value::Object(values)) => values.clone(),
+            Some(Value::Array(pairs)) => pairs
+                .iter()
+                .map(|pair| {
+                    let key = pair
+                        .get("key")
+                        .and_then(Value::as_str)
+                        .ok_or_else(malformed)?;
+                    let value = pair.get("value").cloned().unwrap_or(Value::Null);
+                    Ok((key.to_string(), value))
+                })
+                .collect::<Result<_, ActionError>>()?,
+            Some(_) => return Err(malformed()),
+        };
+        Ok(Parameters {
+            action_type: &action.action_type,
+            values,
+        })
+    }
+
+    fn string(&self, key: &'static str) -> Result<Option<String>, ActionError> {
+        match self.values.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(self.invalid(key, "string")),
+        }
+    }
+
+    fn number(&self, key: &'static str) -> Result<Option<f64>, ActionError> {
+        match self.values.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => value
+                .as_f64()
+                .map(Some)
+                .ok_or_else(|| self.invalid(key, "number")),
+        }
+    }
+
+    fn required_string(&self, key: &'static str) -> Result<String, ActionError> {
+        self.string(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn required_number(&self, key: &'static str) -> Result<f64, ActionError> {
+        self.number(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn missing(&self, key: &'static str) -> ActionError {
+        ActionError::MissingParameter {
+            action_type: self.action_type.to_string(),
+            key,
+        }
+    }
+
+    fn invalid(&self, key: &'static str, expected: &'static str) -> ActionError {
+        ActionError::InvalidParameter {
+            action_type: self.action_type.to_string(),
+            key,
+            expected,
+        }
+    }
+}
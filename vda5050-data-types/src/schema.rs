@@ -0,0 +1,407 @@
+//! JSON Schema generation for the VDA 5050 messages and comparison against the official schemas.
+//!
+//! The schemas are derived from the Rust types, so comparing them with the official schema files
+//! shows where the Rust model deviates from the specification.
+use crate::connection::Connection;
+use crate::factsheet::Factsheet;
+use crate::instant_actions::InstantActions;
+use crate::order::Order;
+use crate::state::State;
+use crate::visualization::Visualization;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// The generated JSON Schema of every message, keyed by the name of the official schema file
+/// without extension (e.g. "instantActions").
+pub fn message_schemas() -> Vec<(&'static str, Value)> {
+    vec![
+        ("connection", schemars::schema_for!(Connection).to_value()),
+        ("factsheet", schemars::schema_for!(Factsheet).to_value()),
+        (
+            "instantActions",
+            schemars::schema_for!(InstantActions).to_value(),
+        ),
+        ("order", schemars::schema_for!(Order).to_value()),
+        ("state", schemars::schema_for!(State).to_value()),
+        (
+            "visualization",
+            schemars::schema_for!(Visualization).to_value(),
+        ),
+    ]
+}
+
+/// A single deviation of a generated schema from the official schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaDifference {
+    /// Path of the affected field, e.g. "nodes[].actions[].blockingType".
+    pub path: String,
+    /// The kind of the deviation.
+    pub kind: DifferenceKind,
+}
+
+/// The kind of deviation between a generated and an official schema.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifferenceKind {
+    /// The field is defined in the official schema but missing in the Rust type.
+    MissingField,
+    /// The field exists in the Rust type but not in the official schema.
+    UnexpectedField,
+    /// The field is required in one schema and optional in the other.
+    Optionality {
+        /// Whether the official schema requires the field.
+        official_required: bool,
+    },
+    /// The official schema defines a closed set of values, but the Rust type accepts any value.
+    EnumExpected {
+        /// The values allowed by the official schema.
+        values: Vec<String>,
+    },
+    /// Both schemas define a closed set of values, but the sets differ.
+    EnumMismatch {
+        /// Values allowed by the official schema but not by the Rust type.
+        missing: Vec<String>,
+        /// Values allowed by the Rust type but not by the official schema.
+        unexpected: Vec<String>,
+    },
+    /// The official schema defines an object, but the Rust type accepts any JSON value.
+    Untyped,
+}
+
+impl fmt::Display for SchemaDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DifferenceKind::MissingField => write!(f, "{}: missing field", self.path),
+            DifferenceKind::UnexpectedField => {
+                write!(f, "{}: field not defined in the official schema", self.path)
+            }
+            DifferenceKind::Optionality {
+                official_required: true,
+            } => {
+                write!(
+                    f,
+                    "{}: required by the official schema but optional",
+                    self.path
+                )
+            }
+            DifferenceKind::Optionality {
+                official_required: false,
+            } => {
+                write!(
+                    f,
+                    "{}: optional in the official schema but required",
+                    self.path
+                )
+            }
+            DifferenceKind::EnumExpected { values } => write!(
+                f,
+                "{}: free value, official schema allows only [{}]",
+                self.path,
+                values.join(", ")
+            ),
+            DifferenceKind::EnumMismatch {
+                missing,
+                unexpected,
+            } => write!(
+                f,
+                "{}: enum mismatch, missing [{}], unexpected [{}]",
+                self.path,
+                missing.join(", "),
+                unexpected.join(", ")
+            ),
+            DifferenceKind::Untyped => {
+                write!(
+                    f,
+                    "{}: untyped, official schema defines an object",
+                    self.path
+                )
+            }
+        }
+    }
+}
+
+/// Compares a generated schema with the official schema of the same message.
+///
+/// Both schemas may use local `$ref`s, which are resolved against their own root.
+pub fn diff(generated: &Value, official: &Value) -> Vec<SchemaDifference> {
+    let mut differences = Vec::new();
+    compare(
+        generated,
+        official,
+        generated,
+        official,
+        "",
+        &mut differences,
+    );
+    differences
+}
+
+fn compare(
+    generated_root: &Value,
+    official_root: &Value,
+    generated: &Value,
+    official: &Value,
+    path: &str,
+    differences: &mut Vec<SchemaDifference>,
+) {
+    let generated = resolve(
+        generated_root,
+        without_null(resolve(generated_root, generated)),
+    );
+    let official = resolve(
+        official_root,
+        without_null(resolve(official_root, official)),
+    );
+    let mut report = |kind| {
+        differences.push(SchemaDifference {
+            path: path.to_string(),
+            kind,
+        })
+    };
+
+    if let Some(official_values) = enum_values(official) {
+        match enum_values(generated) {
+            None => report(DifferenceKind::EnumExpected {
+                values: official_values.into_iter().collect(),
+            }),
+            Some(generated_values) if generated_values != official_values => {
+                report(DifferenceKind::EnumMismatch {
+                    missing: official_values
+                        .difference(&generated_values)
+                        .cloned()
+                        .collect(),
+                    unexpected: generated_values
+                        .difference(&official_values)
+                        .cloned()
+                        .collect(),
+                })
+            }
+            Some(_) => {}
+        }
+        return;
+    }
+
+    if let Some(official_properties) = official.get("properties").and_then(Value::as_object) {
+        let Some(generated_properties) = generated.get("properties").and_then(Value::as_object)
+        else {
+            report(DifferenceKind::Untyped);
+            return;
+        };
+        let official_required = required(official);
+        let generated_required = required(generated);
+        for (name, official_property) in official_properties {
+            let field_path = join(path, name);
+            let Some(generated_property) = generated_properties.get(name) else {
+                differences.push(SchemaDifference {
+                    path: field_path,
+                    kind: DifferenceKind::MissingField,
+                });
+                continue;
+            };
+            let official_required = official_required.contains(name.as_str());
+            if official_required != generated_required.contains(name.as_str()) {
+                differences.push(SchemaDifference {
+                    path: field_path.clone(),
+                    kind: DifferenceKind::Optionality { official_required },
+                });
+            }
+            compare(
+                generated_root,
+                official_root,
+                generated_property,
+                official_property,
+                &field_path,
+                differences,
+            );
+        }
+        for name in generated_properties.keys() {
+            if !official_properties.contains_key(name) {
+                differences.push(SchemaDifference {
+                    path: join(path, name),
+                    kind: DifferenceKind::UnexpectedField,
+                });
+            }
+        }
+        return;
+    }
+
+    if let Some(official_items) = official.get("items") {
+        match generated.get("items") {
+            Some(generated_items) => compare(
+                generated_root,
+                official_root,
+                generated_items,
+                official_items,
+                &format!("{path}[]"),
+                differences,
+            ),
+            None if is_unconstrained(generated) => report(DifferenceKind::Untyped),
+            None => {}
+        }
+    }
+}
+
+/// Follows local references of the form `#/$defs/Name` or `#/definitions/Name`.
+fn resolve<'a>(root: &'a Value, mut schema: &'a Value) -> &'a Value {
+    while let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+        {
+            Some(target) => schema = target,
+            None => break,
+        }
+    }
+    schema
+}
+
+/// Removes the `null` alternative that is generated for `Option` fields.
+fn without_null(schema: &Value) -> &Value {
+    let alternatives = schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(Value::as_array);
+    if let Some(alternatives) = alternatives {
+        let non_null: Vec<&Value> = alternatives.iter().filter(|s| !is_null(s)).collect();
+        if non_null.len() == 1 && alternatives.len() == 2 {
+            return non_null[0];
+        }
+    }
+    schema
+}
+
+fn is_null(schema: &Value) -> bool {
+    schema.get("type").and_then(Value::as_str) == Some("null")
+}
+
+fn is_unconstrained(schema: &Value) -> bool {
+    match schema {
+        Value::Bool(true) => true,
+        Value::Object(map) => map.keys().all(|key| key == "description" || key == "title"),
+        _ => false,
+    }
+}
+
+/// Collects the allowed values of a schema, either from `enum` or from `oneOf`/`anyOf`
+/// alternatives that each consist of a `const` or an `enum`.
+fn enum_values(schema: &Value) -> Option<BTreeSet<String>> {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return Some(values.iter().filter_map(as_enum_value).collect());
+    }
+    if let Some(value) = schema.get("const") {
+        return as_enum_value(value).map(|value| BTreeSet::from([value]));
+    }
+    let alternatives = schema
+        .get("oneOf")
+        .or_else(|| schema.get("anyOf"))
+        .and_then(Value::as_array)?;
+    let mut values = BTreeSet::new();
+    for alternative in alternatives {
+        values.extend(enum_values(alternative)?);
+    }
+    Some(values)
+}
+
+fn as_enum_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+fn required(schema: &Value) -> BTreeSet<&str> {
+    schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// The official schemas, vendored so the comparison runs without network access. They are
+    /// still a transcription of the published files, see `schemas/README.md`.
+    const OFFICIAL: &[(&str, &str)] = &[
+        ("connection", include_str!("../schemas/connection.schema")),
+        ("factsheet", include_str!("../schemas/factsheet.schema")),
+        (
+            "instantActions",
+            include_str!("../schemas/instantActions.schema"),
+        ),
+        ("order", include_str!("../schemas/order.schema")),
+        ("state", include_str!("../schemas/state.schema")),
+        (
+            "visualization",
+            include_str!("../schemas/visualization.schema"),
+        ),
+    ];
+    const KNOWN_DIFFERENCES: &str = include_str!("../schemas/known-differences.txt");
+
+    #[test]
+    fn every_message_has_an_official_schema() {
+        let vendored: BTreeSet<&str> = OFFICIAL.iter().map(|(name, _)| *name).collect();
+        let generated: BTreeSet<&str> = message_schemas().iter().map(|(name, _)| *name).collect();
+        assert_eq!(vendored, generated);
+    }
+
+    #[test]
+    fn derived_schemas_match_the_official_schemas() {
+        let known: BTreeSet<&str> = KNOWN_DIFFERENCES
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        let mut found = BTreeSet::new();
+        for (name, generated) in message_schemas() {
+            let (_, official) = OFFICIAL
+                .iter()
+                .find(|(official_name, _)| *official_name == name)
+                .expect("vendored schema");
+            let official: Value = serde_json::from_str(official).expect("valid official schema");
+            for difference in diff(&generated, &official) {
+                found.insert(format!("{name}: {difference}"));
+            }
+        }
+        let found: BTreeSet<&str> = found.iter().map(String::as_str).collect();
+        let new: Vec<_> = found.difference(&known).collect();
+        let fixed: Vec<_> = known.difference(&found).collect();
+        assert!(
+            new.is_empty() && fixed.is_empty(),
+            "update schemas/known-differences.txt\nnew differences: {new:#?}\nno longer found: {fixed:#?}"
+        );
+    }
+
+    #[test]
+    fn optional_references_are_compared() {
+        let generated = serde_json::json!({
+            "properties": {
+                "position": {"anyOf": [{"$ref": "#/$defs/Position"}, {"type": "null"}]}
+            },
+            "$defs": {"Position": {"properties": {"x": {"type": "number"}}}}
+        });
+        let official = serde_json::json!({
+            "properties": {
+                "position": {"properties": {"x": {"type": "number"}, "y": {"type": "number"}}}
+            }
+        });
+        let paths: Vec<_> = diff(&generated, &official)
+            .into_iter()
+            .map(|difference| (difference.path, difference.kind))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![("position.y".to_string(), DifferenceKind::MissingField)]
+        );
+    }
+}
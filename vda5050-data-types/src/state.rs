@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 /// The state of the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct State {
     #[serde(flatten)]
@@ -47,6 +48,9 @@ pub struct State {
     pub distance_since_last_node: Option<f64>,
     /// The operating mode of the AGV.
     pub operating_mode: OperatingMode,
+    /// The state of all actions of the current order and of received instant actions.
+    /// Finished actions may be removed once a new order is received.
+    pub action_states: Vec<ActionState>,
     /// A list of errors that occurred on the AGV.
     pub errors: Vec<Error>,
     /// A list of information messages from the AGV.
@@ -55,24 +59,40 @@ pub struct State {
     /// The state of the AGV's battery.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_state: Option<BatteryState>,
+    /// The state of the AGV's safety devices.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_state: Option<SafetyState>,
 }
 
 /// The operating mode of the AGV.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum OperatingMode {
     /// The AGV is operating in automatic mode.
     #[serde(rename = "AUTOMATIC")]
     Automatic,
+    /// The AGV is operating in semi-automatic mode.
+    /// Master control is in charge of the orders, but the driving speed is controlled by the HMI.
+    #[serde(rename = "SEMIAUTOMATIC")]
+    Semiautomatic,
     /// The AGV is operating in manual mode.
     #[serde(rename = "MANUAL")]
     Manual,
     /// The AGV is in service mode.
     #[serde(rename = "SERVICE")]
     Service,
+    /// The AGV is being taught, e.g. mapping is done by a master control.
+    #[serde(rename = "TEACHIN")]
+    Teachin,
+    /// An operating mode reported by the AGV that is not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
 
 /// The state of a node.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct NodeState {
     /// The ID of the node.
@@ -93,6 +113,7 @@ pub struct NodeState {
 
 /// The state of an edge.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct EdgeState {
     /// The ID of the edge.
@@ -111,8 +132,65 @@ pub struct EdgeState {
     pub actions: Vec<Action>,
 }
 
+/// The state of an action.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ActionState {
+    /// The ID of the action, as given in the order or instant actions message.
+    pub action_id: String,
+    /// The type of the action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action_type: Option<String>,
+    /// A description of the action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action_description: Option<String>,
+    /// The current status of the action.
+    pub action_status: ActionStatus,
+    /// A description of the result, e.g. the result of an RFID read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_description: Option<String>,
+}
+
+/// The status of an action.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ActionStatus {
+    /// The action was received, but the node where it triggers was not yet reached
+    /// or the edge where it is active was not yet entered.
+    #[serde(rename = "WAITING")]
+    Waiting,
+    /// The action was triggered and preparatory measures are initiated.
+    #[serde(rename = "INITIALIZING")]
+    Initializing,
+    /// The action is running.
+    #[serde(rename = "RUNNING")]
+    Running,
+    /// The action is paused because of a pause instant action or an external trigger.
+    #[serde(rename = "PAUSED")]
+    Paused,
+    /// The action is finished.
+    #[serde(rename = "FINISHED")]
+    Finished,
+    /// The action could not be finished.
+    #[serde(rename = "FAILED")]
+    Failed,
+    /// An action status not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
+}
+
+impl ActionStatus {
+    /// Whether the action reached a final status that must not change anymore.
+    pub fn is_final(&self) -> bool {
+        matches!(self, ActionStatus::Finished | ActionStatus::Failed)
+    }
+}
+
 /// The state of a load on the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Load {
     /// The ID of the load.
@@ -123,7 +201,7 @@ pub struct Load {
     pub load_type: Option<String>,
     /// The position of the load.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub load_position: Option<String>,
+    pub load_position: Option<LoadPosition>,
     /// The weight of the load in [kg].
     #[serde(skip_serializing_if = "Option::is_none")]
     pub weight: Option<f64>,
@@ -135,8 +213,27 @@ pub struct Load {
     pub bounding_box: Option<Vec<Point>>,
 }
 
+/// The load handling unit of the AGV that carries a load.
+///
+/// The specification only gives examples, so vehicle specific positions are kept as received.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum LoadPosition {
+    /// The front load handling unit.
+    #[serde(rename = "front")]
+    Front,
+    /// The back load handling unit.
+    #[serde(rename = "back")]
+    Back,
+    /// A vehicle specific load position, e.g. "positionC1".
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
+}
+
 /// A reference to a bounding box.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct BoundingBoxReference {
     /// The x coordinate of the bounding box reference.
@@ -152,6 +249,7 @@ pub struct BoundingBoxReference {
 
 /// An error that occurred on the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Error {
     /// The type of the error.
@@ -166,7 +264,8 @@ pub struct Error {
 }
 
 /// The level of the error.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ErrorLevel {
     /// The AGV can continue its operation.
     #[serde(rename = "WARNING")]
@@ -174,10 +273,15 @@ pub enum ErrorLevel {
     /// The AGV cannot continue its operation.
     #[serde(rename = "FATAL")]
     Fatal,
+    /// An error level not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
 
 /// A reference to an error.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ErrorReference {
     /// The key of the reference.
@@ -188,6 +292,7 @@ pub struct ErrorReference {
 
 /// An information message from the AGV.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Info {
     /// The type of the information message.
@@ -196,11 +301,28 @@ pub struct Info {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub info_description: Option<String>,
     /// The level of the information message.
-    pub info_level: String,
+    pub info_level: InfoLevel,
+}
+
+/// The level of an information message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum InfoLevel {
+    /// Used for debugging.
+    #[serde(rename = "DEBUG")]
+    Debug,
+    /// Used for visualization.
+    #[serde(rename = "INFO")]
+    Info,
+    /// An information level not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
 }
 
 /// The state of the AGV's battery.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct BatteryState {
     /// The charge of the battery in percent. Range: [0.0 ... 100.0].
@@ -218,3 +340,36 @@ pub struct BatteryState {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reach: Option<f64>,
 }
+
+/// The state of the AGV's safety devices.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyState {
+    /// The type of the active emergency stop.
+    pub e_stop: EStop,
+    /// Indicates if a protective field is violated.
+    pub field_violation: bool,
+}
+
+/// The type of an emergency stop.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum EStop {
+    /// Released automatically once the cause is gone, e.g. by a protective field.
+    #[serde(rename = "AUTOACK")]
+    AutoAck,
+    /// Has to be acknowledged manually at the AGV.
+    #[serde(rename = "MANUAL")]
+    Manual,
+    /// Triggered and acknowledged remotely.
+    #[serde(rename = "REMOTE")]
+    Remote,
+    /// No emergency stop is active.
+    #[serde(rename = "NONE")]
+    None,
+    /// An emergency stop type not defined by the specification.
+    #[serde(untagged)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    Unknown(String),
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The MQTT topics defined by VDA 5050, i.e. the last level of `interface/version/manufacturer/serialNumber/topic`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum Topic {
+    /// Master control to AGV: orders.
+    Order,
+    /// Master control to AGV: actions to be executed immediately.
+    InstantActions,
+    /// AGV to master control: the state of the AGV.
+    State,
+    /// AGV to master control: higher frequency position for visualization only.
+    Visualization,
+    /// AGV and broker to master control: the connection state.
+    Connection,
+    /// AGV to master control: the factsheet of the AGV.
+    Factsheet,
+}
+
+impl Topic {
+    /// All topics, in the order of the specification.
+    pub const ALL: [Topic; 6] = [
+        Topic::Order,
+        Topic::InstantActions,
+        Topic::State,
+        Topic::Visualization,
+        Topic::Connection,
+        Topic::Factsheet,
+    ];
+
+    /// The name of the topic as used in the MQTT topic.
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::Order => "order",
+            Topic::InstantActions => "instantActions",
+            Topic::State => "state",
+            Topic::Visualization => "visualization",
+            Topic::Connection => "connection",
+            Topic::Factsheet => "factsheet",
+        }
+    }
+}
+
+impl Topic {
+    /// Extracts the topic from a full MQTT topic like `uagv/v2/Acme/agv-1/state`.
+    pub fn from_mqtt_topic(mqtt_topic: &str) -> Option<Topic> {
+        mqtt_topic.rsplit('/').next()?.parse().ok()
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Topic::ALL
+            .into_iter()
+            .find(|topic| topic.name() == name)
+            .ok_or_else(|| format!("unknown VDA 5050 topic '{name}'"))
+    }
+}
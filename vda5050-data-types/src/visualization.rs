@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 /// A message for visualization purposes.
 /// This message is not safety-relevant and should not be used for control.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Visualization {
     #[serde(flatten)]
@@ -24,6 +25,7 @@ pub struct Visualization {
 
 /// A visualization object.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct VisualizationObject {
     /// The type of the visualization object.
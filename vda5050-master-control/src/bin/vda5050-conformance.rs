@@ -0,0 +1,164 @@
+//! Runs the VDA 5050 conformance suite against an AGV and reports the outcome of every test case.
+use clap::{Parser, ValueEnum};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use vda5050_data_types::header_factory::SystemClock;
+use vda5050_master_control::conformance::{self, ConformanceConfig, TestCase};
+use vda5050_master_control::link::{Link, MqttLink, SimulatedLink};
+use vda5050_master_control::session::MasterControl;
+use vda5050_simulator::config::VehicleConfig;
+use vda5050_simulator::mqtt::BrokerOptions;
+
+#[derive(Parser)]
+#[command(name = "vda5050-conformance", version, about)]
+struct Args {
+    /// The manufacturer of the AGV.
+    #[arg(long, default_value = "Simulator")]
+    manufacturer: String,
+    /// The serial number of the AGV.
+    #[arg(long, default_value = "sim-1")]
+    serial_number: String,
+    /// Host name of the MQTT broker.
+    #[arg(long, default_value = "localhost")]
+    host: String,
+    /// Port of the MQTT broker.
+    #[arg(long, default_value_t = 1883)]
+    port: u16,
+    /// The first level of the MQTT topics.
+    #[arg(long, default_value = "uagv")]
+    interface: String,
+    /// The major version in the MQTT topics.
+    #[arg(long, default_value = "v2")]
+    topic_version: String,
+    /// Runs the suite against a simulated AGV in this process instead of a broker, optionally
+    /// configured by a vehicle configuration as JSON.
+    #[arg(long)]
+    simulate: Option<Option<PathBuf>>,
+    /// The track and timeouts of the suite as JSON.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Runs only these test cases, e.g. `orderAccepted,cancelOrder`.
+    #[arg(long, value_delimiter = ',')]
+    only: Vec<TestCase>,
+    /// Write the report as JUnit XML to this file.
+    #[arg(long)]
+    junit: Option<PathBuf>,
+    /// Write the recording of the session to this file in JSON Lines format.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// The output format of the report.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+    let config: ConformanceConfig = match &args.config {
+        Some(path) => match read_json(path) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("error: {err}");
+                return ExitCode::from(2);
+            }
+        },
+        None => ConformanceConfig::default(),
+    };
+    let broker = BrokerOptions {
+        host: args.host.clone(),
+        port: args.port,
+        interface: args.interface.clone(),
+        version: args.topic_version.clone(),
+        ..BrokerOptions::default()
+    };
+
+    let link = match &args.simulate {
+        Some(vehicle) => {
+            let mut vehicle: VehicleConfig = match vehicle {
+                Some(path) => match read_json(path) {
+                    Ok(vehicle) => vehicle,
+                    Err(err) => {
+                        eprintln!("error: {err}");
+                        return ExitCode::from(2);
+                    }
+                },
+                None => VehicleConfig::default(),
+            };
+            vehicle.manufacturer = args.manufacturer.clone();
+            vehicle.serial_number = args.serial_number.clone();
+            Link::Simulated(SimulatedLink::start(
+                vehicle,
+                broker.tick,
+                Arc::new(SystemClock),
+            ))
+        }
+        None => Link::Mqtt(MqttLink::connect(
+            &broker,
+            &args.manufacturer,
+            &args.serial_number,
+        )),
+    };
+    let mut master = MasterControl::new(link, &args.manufacturer, &args.serial_number, broker);
+    let test_cases = if args.only.is_empty() {
+        TestCase::ALL.to_vec()
+    } else {
+        args.only.clone()
+    };
+    let report = conformance::run(
+        &test_cases,
+        &config,
+        &mut master,
+        &args.manufacturer,
+        &args.serial_number,
+    )
+    .await;
+
+    match args.format {
+        Format::Text => print!("{}", report.to_text()),
+        Format::Json => println!("{}", serde_json::to_string(&report).unwrap_or_default()),
+    }
+    if let Some(path) = &args.junit
+        && let Err(err) = std::fs::write(path, report.to_junit())
+    {
+        eprintln!("error: failed to write {}: {err}", path.display());
+        return ExitCode::FAILURE;
+    }
+    if let Some(path) = &args.output {
+        let written = File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for entry in master.entries() {
+                serde_json::to_writer(&mut writer, entry)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()
+        });
+        if let Err(err) = written {
+            eprintln!("error: failed to write {}: {err}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &PathBuf) -> Result<T, String> {
+    std::fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_slice(&json).map_err(|err| err.to_string()))
+        .map_err(|err| format!("failed to read {}: {err}", path.display()))
+}
@@ -0,0 +1,155 @@
+//! Runs an acceptance script against an AGV, records the session and analyzes the recording.
+use clap::{Parser, ValueEnum};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use vda5050_analysis::checks::{self, CheckConfig};
+use vda5050_analysis::report::Report;
+use vda5050_data_types::header_factory::SystemClock;
+use vda5050_master_control::link::{Link, MqttLink, SimulatedLink};
+use vda5050_master_control::script::{self, Script};
+use vda5050_master_control::session::MasterControl;
+use vda5050_simulator::config::VehicleConfig;
+use vda5050_simulator::mqtt::BrokerOptions;
+
+#[derive(Parser)]
+#[command(name = "vda5050-master", version, about)]
+struct Args {
+    /// The acceptance script as JSON.
+    script: PathBuf,
+    /// Overrides the manufacturer of the script.
+    #[arg(long)]
+    manufacturer: Option<String>,
+    /// Overrides the serial number of the script.
+    #[arg(long)]
+    serial_number: Option<String>,
+    /// Host name of the MQTT broker.
+    #[arg(long, default_value = "localhost")]
+    host: String,
+    /// Port of the MQTT broker.
+    #[arg(long, default_value_t = 1883)]
+    port: u16,
+    /// The first level of the MQTT topics.
+    #[arg(long, default_value = "uagv")]
+    interface: String,
+    /// The major version in the MQTT topics.
+    #[arg(long, default_value = "v2")]
+    topic_version: String,
+    /// Runs the script against a simulated AGV in this process instead of a broker, optionally
+    /// configured by a vehicle configuration as JSON.
+    #[arg(long)]
+    simulate: Option<Option<PathBuf>>,
+    /// Write the recording of the session to this file in JSON Lines format.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// The output format of the script and analysis reports.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+    let mut script: Script = match read_json(&args.script) {
+        Ok(script) => script,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(manufacturer) = &args.manufacturer {
+        script.manufacturer = manufacturer.clone();
+    }
+    if let Some(serial_number) = &args.serial_number {
+        script.serial_number = serial_number.clone();
+    }
+    let broker = BrokerOptions {
+        host: args.host.clone(),
+        port: args.port,
+        interface: args.interface.clone(),
+        version: args.topic_version.clone(),
+        ..BrokerOptions::default()
+    };
+
+    let link = match &args.simulate {
+        Some(config) => {
+            let mut config: VehicleConfig = match config {
+                Some(path) => match read_json(path) {
+                    Ok(config) => config,
+                    Err(err) => {
+                        eprintln!("error: {err}");
+                        return ExitCode::from(2);
+                    }
+                },
+                None => VehicleConfig::default(),
+            };
+            config.manufacturer = script.manufacturer.clone();
+            config.serial_number = script.serial_number.clone();
+            Link::Simulated(SimulatedLink::start(
+                config,
+                broker.tick,
+                Arc::new(SystemClock),
+            ))
+        }
+        None => Link::Mqtt(MqttLink::connect(
+            &broker,
+            &script.manufacturer,
+            &script.serial_number,
+        )),
+    };
+    let mut master = MasterControl::new(link, &script.manufacturer, &script.serial_number, broker);
+    let script_report = script::run(&script, &mut master).await;
+
+    let recording = master.recording();
+    let findings = checks::run_all(&recording, &CheckConfig::default());
+    let report = Report::new(
+        vec![args.script.display().to_string()],
+        &recording,
+        findings,
+    );
+    let output = match args.format {
+        Format::Text => format!("{}\n{}", script_report.to_text(), report.to_text()),
+        Format::Json => {
+            serde_json::json!({ "script": script_report, "analysis": report }).to_string()
+        }
+    };
+    println!("{output}");
+
+    if let Some(path) = &args.output {
+        let written = File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for entry in master.entries() {
+                serde_json::to_writer(&mut writer, entry)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()
+        });
+        if let Err(err) = written {
+            eprintln!("error: failed to write {}: {err}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    if script_report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &PathBuf) -> Result<T, String> {
+    std::fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_slice(&json).map_err(|err| err.to_string()))
+        .map_err(|err| format!("failed to read {}: {err}", path.display()))
+}
@@ -0,0 +1,138 @@
+//! Conditions on what an AGV reports, which master control waits for.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::factsheet::Factsheet;
+use vda5050_data_types::state::{ActionStatus, State};
+
+/// What master control knows about the AGV: the latest message of every topic.
+#[derive(Debug, Clone, Default)]
+pub struct AgvView {
+    /// The latest state.
+    pub state: Option<State>,
+    /// The latest connection state.
+    pub connection: Option<ConnectionState>,
+    /// The latest factsheet.
+    pub factsheet: Option<Factsheet>,
+}
+
+/// A condition on the latest messages of the AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Condition {
+    /// The AGV reached the node with the given ID last.
+    LastNodeId(String),
+    /// The action with the given ID has the given status.
+    ActionStatus {
+        /// The action.
+        action_id: String,
+        /// The expected status.
+        status: ActionStatus,
+    },
+    /// The AGV works on the order with the given ID.
+    OrderId(String),
+    /// The AGV works on the order with the given ID and has nothing left to do for it: no nodes
+    /// left and every reported action finished or failed.
+    OrderFinished(String),
+    /// The AGV drives or stands.
+    Driving(bool),
+    /// The AGV is paused or not.
+    Paused(bool),
+    /// The AGV reports an error of the given type.
+    Error(String),
+    /// The AGV reports no errors.
+    NoErrors,
+    /// The connection has the given state.
+    Connection(ConnectionState),
+    /// A factsheet was received.
+    Factsheet,
+    /// All of the conditions hold.
+    AllOf(Vec<Condition>),
+}
+
+impl Condition {
+    /// Whether the condition holds for the latest messages.
+    pub fn matches(&self, view: &AgvView) -> bool {
+        let state = view.state.as_ref();
+        match self {
+            Condition::LastNodeId(node_id) => {
+                state.is_some_and(|state| state.last_node_id.as_ref() == Some(node_id))
+            }
+            Condition::ActionStatus { action_id, status } => state.is_some_and(|state| {
+                state
+                    .action_states
+                    .iter()
+                    .any(|action| &action.action_id == action_id && &action.action_status == status)
+            }),
+            Condition::OrderId(order_id) => {
+                state.is_some_and(|state| state.order_id.as_ref() == Some(order_id))
+            }
+            Condition::OrderFinished(order_id) => state.is_some_and(|state| {
+                state.order_id.as_ref() == Some(order_id)
+                    && state.node_states.is_empty()
+                    && state
+                        .action_states
+                        .iter()
+                        .all(|action| action.action_status.is_final())
+            }),
+            Condition::Driving(driving) => state.is_some_and(|state| state.driving == *driving),
+            Condition::Paused(paused) => {
+                state.is_some_and(|state| state.paused.unwrap_or(false) == *paused)
+            }
+            Condition::Error(error_type) => state.is_some_and(|state| {
+                state
+                    .errors
+                    .iter()
+                    .any(|error| &error.error_type == error_type)
+            }),
+            Condition::NoErrors => state.is_some_and(|state| state.errors.is_empty()),
+            Condition::Connection(connection_state) => {
+                view.connection.as_ref() == Some(connection_state)
+            }
+            Condition::Factsheet => view.factsheet.is_some(),
+            Condition::AllOf(conditions) => {
+                conditions.iter().all(|condition| condition.matches(view))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::LastNodeId(node_id) => write!(f, "last node {node_id}"),
+            Condition::ActionStatus { action_id, status } => {
+                write!(f, "action {action_id} {}", wire_name(status))
+            }
+            Condition::OrderId(order_id) => write!(f, "order {order_id}"),
+            Condition::OrderFinished(order_id) => write!(f, "order {order_id} finished"),
+            Condition::Driving(true) => write!(f, "driving"),
+            Condition::Driving(false) => write!(f, "standing"),
+            Condition::Paused(true) => write!(f, "paused"),
+            Condition::Paused(false) => write!(f, "not paused"),
+            Condition::Error(error_type) => write!(f, "error {error_type}"),
+            Condition::NoErrors => write!(f, "no errors"),
+            Condition::Connection(connection_state) => {
+                write!(f, "connection {}", wire_name(connection_state))
+            }
+            Condition::Factsheet => write!(f, "factsheet received"),
+            Condition::AllOf(conditions) => {
+                for (index, condition) in conditions.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " and ")?;
+                    }
+                    write!(f, "{condition}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The name of an enum value as it appears in the messages, e.g. `FINISHED`.
+fn wire_name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
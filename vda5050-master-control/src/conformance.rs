@@ -0,0 +1,771 @@
+//! A conformance suite that checks how an AGV implements the VDA 5050 protocol: accepting,
+//! rejecting and updating orders, cancelling them, instant actions, the state report on every
+//! event, the last will of the connection and the factsheet request.
+//!
+//! The AGV has to stand on the first node of the track with no active order. Every test case
+//! drives the track there and back, so the AGV ends where it started, except for the cancelled
+//! order, which leaves the AGV where it stopped.
+use crate::condition::Condition;
+use crate::session::MasterControl;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::Instant;
+use vda5050_data_types::builders::{InstantActionsBuilder, OrderBuilder};
+use vda5050_data_types::common::{BlockingType, NodePosition};
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::Order;
+use vda5050_data_types::predefined_actions::PredefinedAction;
+use vda5050_data_types::state::ActionStatus;
+use vda5050_data_types::topic::Topic;
+
+/// A node of the track the AGV drives during the tests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackNode {
+    /// The ID of the node.
+    pub node_id: String,
+    /// X-coordinate in [m].
+    pub x: f64,
+    /// Y-coordinate in [m].
+    pub y: f64,
+}
+
+/// Where the AGV drives during the tests and how long it may take to answer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConformanceConfig {
+    /// The map of the track.
+    pub map_id: String,
+    /// At least three nodes connected by edges in this order. The AGV stands on the first.
+    pub track: Vec<TrackNode>,
+    /// How long to wait for the AGV to do what a test case expects, in seconds.
+    pub timeout_secs: f64,
+    /// How long the AGV may take to publish a state after an event, in seconds.
+    pub event_response_secs: f64,
+}
+
+impl Default for ConformanceConfig {
+    fn default() -> Self {
+        let node = |node_id: &str, x, y| TrackNode {
+            node_id: node_id.to_string(),
+            x,
+            y,
+        };
+        ConformanceConfig {
+            map_id: "map".to_string(),
+            track: vec![
+                node("N1", 0.0, 0.0),
+                node("N2", 3.0, 0.0),
+                node("N3", 3.0, 2.0),
+            ],
+            timeout_secs: 60.0,
+            event_response_secs: 1.0,
+        }
+    }
+}
+
+/// A test case of the suite.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TestCase {
+    /// The AGV accepts a valid order, reports it at once and finishes it.
+    OrderAccepted,
+    /// The AGV stops at the end of the base and continues with an update stitched to it.
+    OrderUpdateStitching,
+    /// The AGV rejects an update older than the current one with an `orderUpdateError`.
+    OrderUpdateOutdated,
+    /// The AGV rejects an order that does not alternate nodes and edges with a
+    /// `validationError` and keeps its current order.
+    OrderRejectedValidation,
+    /// The AGV rejects an order that starts far away from it with a `noRouteError`.
+    OrderRejectedNoRoute,
+    /// The AGV pauses on `startPause` and resumes on `stopPause`.
+    InstantActions,
+    /// The AGV publishes a state at once on `stateRequest`.
+    StateRequest,
+    /// The AGV publishes its factsheet on `factsheetRequest`.
+    FactsheetRequest,
+    /// The AGV stops and drops the rest of the order on `cancelOrder`.
+    CancelOrder,
+    /// The AGV fails `cancelOrder` with a `noOrderToCancel` error when it has no order.
+    CancelWithoutOrder,
+    /// The broker publishes the last will `CONNECTIONBROKEN` when the connection breaks, and the
+    /// AGV comes back `ONLINE`.
+    ConnectionLastWill,
+}
+
+impl TestCase {
+    /// Every test case in the order the suite runs them.
+    pub const ALL: [TestCase; 11] = [
+        TestCase::OrderAccepted,
+        TestCase::OrderUpdateStitching,
+        TestCase::OrderUpdateOutdated,
+        TestCase::OrderRejectedValidation,
+        TestCase::OrderRejectedNoRoute,
+        TestCase::InstantActions,
+        TestCase::StateRequest,
+        TestCase::FactsheetRequest,
+        TestCase::CancelOrder,
+        TestCase::CancelWithoutOrder,
+        TestCase::ConnectionLastWill,
+    ];
+
+    /// The ID of the test case, e.g. `orderAccepted`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            TestCase::OrderAccepted => "orderAccepted",
+            TestCase::OrderUpdateStitching => "orderUpdateStitching",
+            TestCase::OrderUpdateOutdated => "orderUpdateOutdated",
+            TestCase::OrderRejectedValidation => "orderRejectedValidation",
+            TestCase::OrderRejectedNoRoute => "orderRejectedNoRoute",
+            TestCase::InstantActions => "instantActions",
+            TestCase::StateRequest => "stateRequest",
+            TestCase::FactsheetRequest => "factsheetRequest",
+            TestCase::CancelOrder => "cancelOrder",
+            TestCase::CancelWithoutOrder => "cancelWithoutOrder",
+            TestCase::ConnectionLastWill => "connectionLastWill",
+        }
+    }
+
+    /// What the test case checks.
+    pub fn description(&self) -> &'static str {
+        match self {
+            TestCase::OrderAccepted => "accepts a valid order, reports it at once and finishes it",
+            TestCase::OrderUpdateStitching => {
+                "stops at the end of the base and continues with a stitched update"
+            }
+            TestCase::OrderUpdateOutdated => "rejects an outdated order update",
+            TestCase::OrderRejectedValidation => "rejects an invalid order and keeps its order",
+            TestCase::OrderRejectedNoRoute => "rejects an order it cannot reach",
+            TestCase::InstantActions => "pauses and resumes on instant actions",
+            TestCase::StateRequest => "publishes a state on stateRequest",
+            TestCase::FactsheetRequest => "publishes its factsheet on factsheetRequest",
+            TestCase::CancelOrder => "stops and drops the order on cancelOrder",
+            TestCase::CancelWithoutOrder => "fails cancelOrder without an order",
+            TestCase::ConnectionLastWill => "has its last will published when disconnected",
+        }
+    }
+}
+
+impl fmt::Display for TestCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for TestCase {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        TestCase::ALL
+            .into_iter()
+            .find(|test_case| test_case.id() == id)
+            .ok_or_else(|| format!("unknown test case {id}"))
+    }
+}
+
+/// The outcome of a test case.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TestStatus {
+    /// The AGV did what the test case expects.
+    Passed,
+    /// The AGV did not.
+    Failed,
+    /// The test case cannot run against this AGV.
+    Skipped,
+}
+
+/// The outcome of a test case and how long it took.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TestResult {
+    /// The test case.
+    pub test_case: TestCase,
+    /// The outcome.
+    pub status: TestStatus,
+    /// How long the test case took in seconds.
+    pub elapsed_secs: f64,
+    /// Why the test case failed or was skipped.
+    pub message: Option<String>,
+}
+
+/// The outcome of the suite for one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConformanceReport {
+    /// The manufacturer of the AGV.
+    pub manufacturer: String,
+    /// The serial number of the AGV.
+    pub serial_number: String,
+    /// The outcome of every test case that ran.
+    pub results: Vec<TestResult>,
+}
+
+impl ConformanceReport {
+    /// Whether no test case failed.
+    pub fn passed(&self) -> bool {
+        self.results
+            .iter()
+            .all(|result| result.status != TestStatus::Failed)
+    }
+
+    fn count(&self, status: TestStatus) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.status == status)
+            .count()
+    }
+
+    /// A human readable list of the test cases.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "VDA 5050 conformance of {}/{}\n",
+            self.manufacturer, self.serial_number
+        );
+        for result in &self.results {
+            let status = match result.status {
+                TestStatus::Passed => "PASS",
+                TestStatus::Failed => "FAIL",
+                TestStatus::Skipped => "SKIP",
+            };
+            let _ = writeln!(
+                text,
+                "{status} {:<24} {} ({:.1} s)",
+                result.test_case.id(),
+                result.test_case.description(),
+                result.elapsed_secs
+            );
+            if let Some(message) = &result.message {
+                let _ = writeln!(text, "     {message}");
+            }
+        }
+        let _ = writeln!(
+            text,
+            "{} passed, {} failed, {} skipped",
+            self.count(TestStatus::Passed),
+            self.count(TestStatus::Failed),
+            self.count(TestStatus::Skipped)
+        );
+        text
+    }
+
+    /// The report as a JUnit XML test suite with one test case per test case of the suite.
+    pub fn to_junit(&self) -> String {
+        let agv = format!("{}/{}", self.manufacturer, self.serial_number);
+        let total: f64 = self.results.iter().map(|result| result.elapsed_secs).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"vda5050-conformance\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{total:.3}\">",
+            self.results.len(),
+            self.count(TestStatus::Failed),
+            self.count(TestStatus::Skipped)
+        );
+        for result in &self.results {
+            let _ = write!(
+                xml,
+                "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape(&agv),
+                result.test_case.id(),
+                result.elapsed_secs
+            );
+            let message = escape(result.message.as_deref().unwrap_or_default());
+            match result.status {
+                TestStatus::Passed => xml.push_str("/>\n"),
+                TestStatus::Failed => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n    <failure message=\"{message}\">{}</failure>\n  </testcase>",
+                        escape(result.test_case.description())
+                    );
+                }
+                TestStatus::Skipped => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n    <skipped message=\"{message}\"/>\n  </testcase>"
+                    );
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+/// Runs the test cases in the given order against the AGV of the session. Unlike a script, the
+/// suite goes on after a failed test case.
+pub async fn run(
+    test_cases: &[TestCase],
+    config: &ConformanceConfig,
+    master: &mut MasterControl,
+    manufacturer: &str,
+    serial_number: &str,
+) -> ConformanceReport {
+    let mut suite = Suite {
+        master,
+        config,
+        orders: 0,
+    };
+    let mut results = Vec::new();
+    for &test_case in test_cases {
+        let start = Instant::now();
+        let outcome = if config.track.len() < 3 {
+            Err(Outcome::Skipped(
+                "the track needs at least three nodes".to_string(),
+            ))
+        } else {
+            suite.run(test_case).await
+        };
+        let (status, message) = match outcome {
+            Ok(()) => (TestStatus::Passed, None),
+            Err(Outcome::Failed(message)) => (TestStatus::Failed, Some(message)),
+            Err(Outcome::Skipped(message)) => (TestStatus::Skipped, Some(message)),
+        };
+        results.push(TestResult {
+            test_case,
+            status,
+            elapsed_secs: start.elapsed().as_secs_f64(),
+            message,
+        });
+    }
+    ConformanceReport {
+        manufacturer: manufacturer.to_string(),
+        serial_number: serial_number.to_string(),
+        results,
+    }
+}
+
+/// Why a test case did not pass.
+enum Outcome {
+    Failed(String),
+    Skipped(String),
+}
+
+impl From<String> for Outcome {
+    fn from(message: String) -> Self {
+        Outcome::Failed(message)
+    }
+}
+
+struct Suite<'a> {
+    master: &'a mut MasterControl,
+    config: &'a ConformanceConfig,
+    /// How many orders the suite sent, to give every order a new ID.
+    orders: usize,
+}
+
+impl Suite<'_> {
+    async fn run(&mut self, test_case: TestCase) -> Result<(), Outcome> {
+        match test_case {
+            TestCase::OrderAccepted => self.order_accepted().await,
+            TestCase::OrderUpdateStitching => self.order_update_stitching().await,
+            TestCase::OrderUpdateOutdated => self.order_update_outdated().await,
+            TestCase::OrderRejectedValidation => self.order_rejected_validation().await,
+            TestCase::OrderRejectedNoRoute => self.order_rejected_no_route().await,
+            TestCase::InstantActions => self.instant_actions().await,
+            TestCase::StateRequest => self.state_request().await,
+            TestCase::FactsheetRequest => self.factsheet_request().await,
+            TestCase::CancelOrder => self.cancel_order().await,
+            TestCase::CancelWithoutOrder => self.cancel_without_order().await,
+            TestCase::ConnectionLastWill => self.connection_last_will().await,
+        }
+    }
+
+    async fn order_accepted(&mut self) -> Result<(), Outcome> {
+        let order_id = self.order_id();
+        let order = self.order(&order_id, 0, 0, &self.round_trip(), None)?;
+        self.send(Message::Order(order))?;
+        self.expect_response(&Condition::OrderId(order_id.clone()))
+            .await?;
+        self.expect(&Condition::OrderFinished(order_id)).await?;
+        let start = &self.config.track[0].node_id;
+        self.expect(&Condition::LastNodeId(start.clone())).await?;
+        Ok(())
+    }
+
+    async fn order_update_stitching(&mut self) -> Result<(), Outcome> {
+        let route = self.round_trip();
+        let order_id = self.order_id();
+        let base = self.order(&order_id, 0, 0, &route, Some(&route[2].node_id))?;
+        self.send(Message::Order(base))?;
+        // The AGV has to stop at the end of the base and wait there for the update.
+        let end_of_base = Condition::AllOf(vec![
+            Condition::OrderId(order_id.clone()),
+            Condition::LastNodeId(route[1].node_id.clone()),
+            Condition::Driving(false),
+        ]);
+        self.expect(&end_of_base).await?;
+        self.settle().await?;
+        if !end_of_base.matches(self.master.view()) {
+            return Err(format!("the AGV left {} without an update", route[1].node_id).into());
+        }
+
+        let update = self.order(&order_id, 1, 2, &route[1..], None)?;
+        self.send(Message::Order(update))?;
+        self.expect(&Condition::OrderFinished(order_id)).await?;
+        let order_update_id = self
+            .master
+            .view()
+            .state
+            .as_ref()
+            .and_then(|state| state.order_update_id);
+        if order_update_id != Some(1) {
+            return Err(
+                format!("the state reports order update {order_update_id:?} instead of 1").into(),
+            );
+        }
+        let start = &self.config.track[0].node_id;
+        self.expect(&Condition::LastNodeId(start.clone())).await?;
+        Ok(())
+    }
+
+    async fn order_update_outdated(&mut self) -> Result<(), Outcome> {
+        let start = self.config.track[..1].to_vec();
+        let order_id = self.order_id();
+        // An order on the node the AGV stands on, which it finishes at once.
+        let current = self.order(&order_id, 1, 0, &start, None)?;
+        self.send(Message::Order(current))?;
+        self.expect(&Condition::OrderFinished(order_id.clone()))
+            .await?;
+        let outdated = self.order(&order_id, 0, 0, &start, None)?;
+        self.send(Message::Order(outdated.clone()))?;
+        self.expect_rejection(&outdated, "orderUpdateError").await
+    }
+
+    async fn order_rejected_validation(&mut self) -> Result<(), Outcome> {
+        let order_id = self.order_id();
+        let mut order = self.order(&order_id, 0, 0, &self.config.track[..2], None)?;
+        order.edges.clear();
+        self.send(Message::Order(order.clone()))?;
+        self.expect_rejection(&order, "validationError").await
+    }
+
+    async fn order_rejected_no_route(&mut self) -> Result<(), Outcome> {
+        let order_id = self.order_id();
+        // A copy of the track far away on the same map.
+        let far_away: Vec<TrackNode> = self.config.track[..2]
+            .iter()
+            .map(|node| TrackNode {
+                node_id: format!("{}-far", node.node_id),
+                x: node.x + 1000.0,
+                y: node.y + 1000.0,
+            })
+            .collect();
+        let order = self.order(&order_id, 0, 0, &far_away, None)?;
+        self.send(Message::Order(order.clone()))?;
+        self.expect_rejection(&order, "noRouteError").await
+    }
+
+    async fn instant_actions(&mut self) -> Result<(), Outcome> {
+        let pause = self.instant_action(PredefinedAction::StartPause)?;
+        self.expect_response(&Condition::AllOf(vec![
+            Condition::Paused(true),
+            finished(&pause),
+        ]))
+        .await?;
+        let resume = self.instant_action(PredefinedAction::StopPause)?;
+        self.expect_response(&Condition::AllOf(vec![
+            Condition::Paused(false),
+            finished(&resume),
+        ]))
+        .await?;
+        Ok(())
+    }
+
+    async fn state_request(&mut self) -> Result<(), Outcome> {
+        // Only a state published after the request counts.
+        self.master.forget(Topic::State);
+        let action_id = self.instant_action(PredefinedAction::StateRequest)?;
+        self.expect_response(&finished(&action_id)).await?;
+        Ok(())
+    }
+
+    async fn factsheet_request(&mut self) -> Result<(), Outcome> {
+        self.master.forget(Topic::Factsheet);
+        let action_id = self.instant_action(PredefinedAction::FactsheetRequest)?;
+        self.expect(&Condition::AllOf(vec![
+            Condition::Factsheet,
+            finished(&action_id),
+        ]))
+        .await?;
+        Ok(())
+    }
+
+    async fn cancel_order(&mut self) -> Result<(), Outcome> {
+        let order_id = self.order_id();
+        let order = self.order(&order_id, 0, 0, &self.round_trip(), None)?;
+        self.send(Message::Order(order))?;
+        self.expect(&Condition::AllOf(vec![
+            Condition::OrderId(order_id.clone()),
+            Condition::Driving(true),
+        ]))
+        .await?;
+        let action_id = self.instant_action(PredefinedAction::CancelOrder)?;
+        self.expect(&Condition::AllOf(vec![
+            finished(&action_id),
+            Condition::Driving(false),
+        ]))
+        .await?;
+        let state = self.master.view().state.as_ref();
+        let left = state.map_or(0, |state| state.node_states.len() + state.edge_states.len());
+        if left > 0 {
+            return Err(
+                format!("the state still reports {left} nodes and edges of the order").into(),
+            );
+        }
+        if state.and_then(|state| state.order_id.as_ref()) != Some(&order_id) {
+            return Err(format!("the state no longer reports order {order_id}").into());
+        }
+        Ok(())
+    }
+
+    async fn cancel_without_order(&mut self) -> Result<(), Outcome> {
+        let action_id = self.instant_action(PredefinedAction::CancelOrder)?;
+        self.expect(&Condition::AllOf(vec![
+            Condition::ActionStatus {
+                action_id,
+                status: ActionStatus::Failed,
+            },
+            Condition::Error("noOrderToCancel".to_string()),
+        ]))
+        .await?;
+        Ok(())
+    }
+
+    async fn connection_last_will(&mut self) -> Result<(), Outcome> {
+        self.master.forget(Topic::Connection);
+        if !self.master.interrupt() {
+            return Err(Outcome::Skipped(
+                "the connection of a real AGV cannot be broken from here".to_string(),
+            ));
+        }
+        self.expect(&Condition::Connection(ConnectionState::ConnectionBroken))
+            .await?;
+        self.expect(&Condition::Connection(ConnectionState::Online))
+            .await?;
+        Ok(())
+    }
+
+    /// Expects the AGV to report the rejection of the order with the given error, referencing
+    /// the order, while keeping the order it had.
+    async fn expect_rejection(&mut self, order: &Order, error_type: &str) -> Result<(), Outcome> {
+        let order_id = &order.order_id;
+        self.expect(&Condition::Error(error_type.to_string()))
+            .await?;
+        let Some(state) = &self.master.view().state else {
+            return Ok(());
+        };
+        let references_order = state.errors.iter().any(|error| {
+            error.error_type == error_type
+                && error.error_references.iter().any(|reference| {
+                    reference.reference_key == "orderId" && &reference.reference_value == order_id
+                })
+        });
+        if !references_order {
+            return Err(format!("the {error_type} does not reference order {order_id}").into());
+        }
+        if state.order_id.as_ref() == Some(order_id)
+            && state.order_update_id == Some(order.order_update_id)
+        {
+            return Err(format!(
+                "the AGV took the rejected order {order_id}/{}",
+                order.order_update_id
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Waits for the condition within the timeout of the suite.
+    async fn expect(&mut self, condition: &Condition) -> Result<Duration, Outcome> {
+        let timeout = Duration::from_secs_f64(self.config.timeout_secs.max(0.0));
+        match self.master.wait_for(condition, timeout).await {
+            Ok(Some(elapsed)) => Ok(elapsed),
+            Ok(None) => Err(format!("no {condition} within {} s", self.config.timeout_secs).into()),
+            Err(err) => Err(err.to_string().into()),
+        }
+    }
+
+    /// Waits for the condition, which the AGV has to report in the state it publishes on the
+    /// event that was just sent.
+    async fn expect_response(&mut self, condition: &Condition) -> Result<(), Outcome> {
+        let elapsed = self.expect(condition).await?.as_secs_f64();
+        if elapsed > self.config.event_response_secs {
+            return Err(format!(
+                "{condition} after {elapsed:.1} s, expected within {} s",
+                self.config.event_response_secs
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Gives the AGV the time to react before checking that it did not.
+    async fn settle(&mut self) -> Result<(), Outcome> {
+        let duration = Duration::from_secs_f64(self.config.event_response_secs.max(0.0) * 2.0);
+        self.master
+            .wait(duration)
+            .await
+            .map_err(|err| err.to_string().into())
+    }
+
+    fn send(&mut self, message: Message) -> Result<(), Outcome> {
+        self.master
+            .send(message)
+            .map_err(|err| err.to_string().into())
+    }
+
+    fn order_id(&mut self) -> String {
+        self.orders += 1;
+        format!("conformance-{}", self.orders)
+    }
+
+    /// The track there and back.
+    fn round_trip(&self) -> Vec<TrackNode> {
+        let track = &self.config.track;
+        track
+            .iter()
+            .chain(track.iter().rev().skip(1))
+            .cloned()
+            .collect()
+    }
+
+    fn order(
+        &self,
+        order_id: &str,
+        order_update_id: u32,
+        first_sequence_id: u32,
+        route: &[TrackNode],
+        horizon_from: Option<&str>,
+    ) -> Result<Order, Outcome> {
+        let mut builder = OrderBuilder::new(self.master.header(Topic::Order), order_id)
+            .order_update_id(order_update_id)
+            .first_sequence_id(first_sequence_id);
+        for (index, node) in route.iter().enumerate() {
+            if index > 0 {
+                builder = builder.edge(format!("{}-{}", route[index - 1].node_id, node.node_id));
+            }
+            let position = NodePosition {
+                x: node.x,
+                y: node.y,
+                map_id: self.config.map_id.clone(),
+                theta: None,
+                allowed_deviation_xy: None,
+                allowed_deviation_theta: None,
+            };
+            builder = builder.node_at(&node.node_id, position);
+        }
+        if let Some(node_id) = horizon_from {
+            builder = builder.horizon_from(node_id);
+        }
+        builder.build().map_err(|err| err.to_string().into())
+    }
+
+    /// Sends a predefined instant action and returns its action ID.
+    fn instant_action(&mut self, action: PredefinedAction) -> Result<String, Outcome> {
+        let message = InstantActionsBuilder::new(self.master.header(Topic::InstantActions))
+            .predefined(action, BlockingType::Hard)
+            .build();
+        let action_id = message.instant_actions[0].action_id.clone();
+        self.send(Message::InstantActions(message))?;
+        Ok(action_id)
+    }
+}
+
+fn finished(action_id: &str) -> Condition {
+    Condition::ActionStatus {
+        action_id: action_id.to_string(),
+        status: ActionStatus::Finished,
+    }
+}
+
+/// Escapes text for the attributes and the text of the JUnit report.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::{Link, SimulatedLink};
+    use chrono::{DateTime, Utc};
+    use std::sync::Arc;
+    use vda5050_data_types::header_factory::Clock;
+    use vda5050_simulator::config::VehicleConfig;
+    use vda5050_simulator::mqtt::BrokerOptions;
+
+    /// A clock that follows the time of the runtime, so that the simulated AGV moves while the
+    /// paused time of a test jumps ahead.
+    struct RuntimeClock {
+        start: DateTime<Utc>,
+        origin: Instant,
+    }
+
+    impl Clock for RuntimeClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.start + self.origin.elapsed()
+        }
+    }
+
+    async fn run_against(vehicle: VehicleConfig) -> ConformanceReport {
+        let clock = Arc::new(RuntimeClock {
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            origin: Instant::now(),
+        });
+        let broker = BrokerOptions::default();
+        let (manufacturer, serial_number) =
+            (vehicle.manufacturer.clone(), vehicle.serial_number.clone());
+        let link = Link::Simulated(SimulatedLink::start(vehicle, broker.tick, clock));
+        let mut master = MasterControl::new(link, &manufacturer, &serial_number, broker);
+        run(
+            &TestCase::ALL,
+            &ConformanceConfig::default(),
+            &mut master,
+            &manufacturer,
+            &serial_number,
+        )
+        .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_simulated_agv_passes_every_test_case() {
+        let report = run_against(VehicleConfig::default()).await;
+        assert_eq!(report.results.len(), TestCase::ALL.len());
+        assert!(report.passed(), "{}", report.to_text());
+        assert!(
+            report
+                .results
+                .iter()
+                .all(|result| result.status == TestStatus::Passed)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_agv_that_accepts_any_start_fails_the_suite() {
+        let vehicle = VehicleConfig {
+            max_start_deviation: f64::INFINITY,
+            ..VehicleConfig::default()
+        };
+        let report = run_against(vehicle).await;
+        assert!(!report.passed());
+        let no_route = report
+            .results
+            .iter()
+            .find(|result| result.test_case == TestCase::OrderRejectedNoRoute)
+            .unwrap();
+        assert_eq!(no_route.status, TestStatus::Failed);
+        assert_eq!(
+            no_route.message.as_deref(),
+            Some("no error noRouteError within 60 s")
+        );
+        assert!(report.to_junit().contains("<failure"));
+    }
+}
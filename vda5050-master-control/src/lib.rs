@@ -0,0 +1,11 @@
+//! A scriptable VDA 5050 master control, to run AGVs through acceptance scripts.
+//!
+//! [`session::MasterControl`] sends orders and instant actions to one AGV over a [`link::Link`],
+//! waits for [`condition::Condition`]s on what the AGV reports and records the session for the
+//! analysis. [`script::run`] executes a [`script::Script`] with it, and [`conformance::run`] the
+//! test cases of the conformance suite.
+pub mod condition;
+pub mod conformance;
+pub mod link;
+pub mod script;
+pub mod session;
@@ -0,0 +1,269 @@
+//! The connection between master control and one AGV: an MQTT broker, or a simulated AGV in the
+//! same process.
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use vda5050_data_types::connection::{Connection, ConnectionState};
+use vda5050_data_types::header_factory::Clock;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+use vda5050_simulator::config::VehicleConfig;
+use vda5050_simulator::mqtt::BrokerOptions;
+use vda5050_simulator::vehicle::SimulatedAgv;
+
+/// The topics master control subscribes to.
+const AGV_TOPICS: [Topic; 4] = [
+    Topic::State,
+    Topic::Visualization,
+    Topic::Connection,
+    Topic::Factsheet,
+];
+
+/// Why master control cannot talk to the AGV.
+#[derive(Debug)]
+pub enum LinkError {
+    /// A message could not be handed to the MQTT client.
+    Client(rumqttc::ClientError),
+    /// A message could not be encoded.
+    Encode(serde_json::Error),
+    /// The connection is gone for good.
+    Closed,
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Client(err) => write!(f, "MQTT client error: {err}"),
+            LinkError::Encode(err) => write!(f, "failed to encode a message: {err}"),
+            LinkError::Closed => write!(f, "the connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<rumqttc::ClientError> for LinkError {
+    fn from(err: rumqttc::ClientError) -> Self {
+        LinkError::Client(err)
+    }
+}
+
+impl From<serde_json::Error> for LinkError {
+    fn from(err: serde_json::Error) -> Self {
+        LinkError::Encode(err)
+    }
+}
+
+/// The connection to one AGV.
+#[allow(clippy::large_enum_variant)]
+pub enum Link {
+    /// Through an MQTT broker.
+    Mqtt(MqttLink),
+    /// To a simulated AGV in the same process.
+    Simulated(SimulatedLink),
+}
+
+impl Link {
+    /// Sends a message to the AGV.
+    pub fn send(&mut self, message: &Message) -> Result<(), LinkError> {
+        match self {
+            Link::Mqtt(link) => link.send(message),
+            Link::Simulated(link) => {
+                link.send(message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Breaks the connection of a simulated AGV, which reconnects at once, so that the broker
+    /// publishes its last will. Returns `false` for a real AGV, whose connection cannot be broken
+    /// from here.
+    pub fn interrupt(&mut self) -> bool {
+        match self {
+            Link::Mqtt(_) => false,
+            Link::Simulated(link) => {
+                link.interrupt();
+                true
+            }
+        }
+    }
+
+    /// Waits for the next message of the AGV. Cancelling the future loses no message.
+    pub async fn receive(&mut self) -> Result<Message, LinkError> {
+        match self {
+            Link::Mqtt(link) => link.receive().await,
+            Link::Simulated(link) => Ok(link.receive().await),
+        }
+    }
+}
+
+/// The connection to an AGV through an MQTT broker.
+pub struct MqttLink {
+    client: AsyncClient,
+    topic: Box<dyn Fn(Topic) -> String + Send>,
+    received: mpsc::UnboundedReceiver<Message>,
+    eventloop: tokio::task::JoinHandle<()>,
+}
+
+impl MqttLink {
+    /// Connects to the broker and subscribes to the topics of the AGV. The client reconnects on
+    /// its own when the connection is lost.
+    pub fn connect(broker: &BrokerOptions, manufacturer: &str, serial_number: &str) -> Self {
+        let topic = {
+            let broker = broker.clone();
+            let (manufacturer, serial_number) =
+                (manufacturer.to_string(), serial_number.to_string());
+            move |topic: Topic| broker.topic(&manufacturer, &serial_number, topic)
+        };
+        let mut options = MqttOptions::new(
+            format!("master-{manufacturer}-{serial_number}"),
+            &broker.host,
+            broker.port,
+        );
+        options.set_keep_alive(Duration::from_secs(5));
+        let (client, mut eventloop) = AsyncClient::new(options, 256);
+        let (sender, received) = mpsc::unbounded_channel();
+
+        let subscriber = client.clone();
+        let subscriptions: Vec<String> = AGV_TOPICS.into_iter().map(&topic).collect();
+        let eventloop = tokio::spawn(async move {
+            // Whether the subscriptions of the current connection were handed to the client.
+            let mut subscribed = false;
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tokio::select! {
+                    event = eventloop.poll() => match event {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            match subscribe(&subscriber, &subscriptions) {
+                                Ok(done) => subscribed = done,
+                                Err(err) => {
+                                    tracing::warn!("master control: failed to subscribe: {err}");
+                                    break;
+                                }
+                            }
+                        }
+                        Ok(Event::Incoming(Packet::Publish(publish))) => {
+                            let Some(topic) = Topic::from_mqtt_topic(&publish.topic) else {
+                                continue;
+                            };
+                            // Messages that cannot be decoded are left to the analysis of the
+                            // recording of the broker.
+                            if let Ok(message) = Message::decode(topic, &publish.payload)
+                                && sender.send(message).is_err()
+                            {
+                                break;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            tracing::warn!("master control: connection error: {err}");
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                    },
+                    _ = ticker.tick(), if !subscribed => {
+                        match subscribe(&subscriber, &subscriptions) {
+                            Ok(done) => subscribed = done,
+                            Err(err) => {
+                                tracing::warn!("master control: failed to subscribe: {err}");
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        MqttLink {
+            client,
+            topic: Box::new(topic),
+            received,
+            eventloop,
+        }
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), LinkError> {
+        let payload = serde_json::to_vec(message)?;
+        self.client.try_publish(
+            (self.topic)(message.topic()),
+            QoS::AtMostOnce,
+            false,
+            payload,
+        )?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Message, LinkError> {
+        self.received.recv().await.ok_or(LinkError::Closed)
+    }
+}
+
+impl Drop for MqttLink {
+    fn drop(&mut self) {
+        self.eventloop.abort();
+    }
+}
+
+/// Subscribes to the topics of the AGV. Returns `false` if a request does not fit into the queue
+/// of the client, to be tried again on the next tick or connect.
+fn subscribe(client: &AsyncClient, subscriptions: &[String]) -> Result<bool, rumqttc::ClientError> {
+    for subscription in subscriptions {
+        match client.try_subscribe(subscription, QoS::AtMostOnce) {
+            Ok(()) => {}
+            Err(rumqttc::ClientError::TryRequest(_)) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+/// A simulated AGV driven in real time by the link.
+pub struct SimulatedLink {
+    agv: SimulatedAgv,
+    last_will: Connection,
+    ticker: tokio::time::Interval,
+    pending: VecDeque<Message>,
+}
+
+impl SimulatedLink {
+    /// Starts the AGV, which comes online and publishes its factsheet like after a connect. The
+    /// AGV moves and stamps its messages by the given clock.
+    pub fn start(config: VehicleConfig, tick: Duration, clock: Arc<dyn Clock>) -> Self {
+        let agv = SimulatedAgv::new(config, clock);
+        // Registered at connect time like the last will of the MQTT client.
+        let last_will = agv.connection(ConnectionState::ConnectionBroken);
+        let pending = VecDeque::from([
+            Message::Connection(agv.connection(ConnectionState::Online)),
+            Message::Factsheet(agv.factsheet()),
+        ]);
+        SimulatedLink {
+            agv,
+            last_will,
+            ticker: tokio::time::interval(tick),
+            pending,
+        }
+    }
+
+    fn send(&mut self, message: &Message) {
+        self.agv.handle(message.clone());
+    }
+
+    fn interrupt(&mut self) {
+        self.pending
+            .push_back(Message::Connection(self.last_will.clone()));
+        let online = self.agv.connection(ConnectionState::Online);
+        self.pending.push_back(Message::Connection(online));
+    }
+
+    async fn receive(&mut self) -> Message {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return message;
+            }
+            self.ticker.tick().await;
+            self.pending.extend(self.agv.poll());
+        }
+    }
+}
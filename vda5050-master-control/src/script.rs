@@ -0,0 +1,458 @@
+//! Acceptance scripts: the orders and instant actions master control sends, the conditions it waits
+//! for in between and how long that may take.
+//!
+//! A script is a JSON file with a list of steps. It stops at the first step that fails.
+use crate::condition::Condition;
+use crate::session::MasterControl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+use vda5050_data_types::builders::{InstantActionsBuilder, OrderBuilder};
+use vda5050_data_types::common::{Action, BlockingType, NodePosition};
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::{Edge, Node, Order};
+use vda5050_data_types::topic::Topic;
+
+/// An action of a script. Actions without an ID get one made of their type and a counter.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptAction {
+    /// Type of the action.
+    pub action_type: String,
+    /// Unique identifier of the action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_id: Option<String>,
+    /// Whether the action blocks driving, hard blocking if not given.
+    #[serde(default = "hard")]
+    pub blocking_type: BlockingType,
+    /// The parameters of the action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_parameters: Option<serde_json::Value>,
+}
+
+fn hard() -> BlockingType {
+    BlockingType::Hard
+}
+
+/// A node of an order in a script.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptNode {
+    /// Unique identifier of the node.
+    pub node_id: String,
+    /// The position of the node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_position: Option<NodePosition>,
+    /// The actions at the node.
+    #[serde(default)]
+    pub actions: Vec<ScriptAction>,
+}
+
+/// An edge of an order in a script.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptEdge {
+    /// Unique identifier of the edge.
+    pub edge_id: String,
+    /// The highest speed allowed on the edge in [m/s].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_speed: Option<f64>,
+    /// The actions on the edge.
+    #[serde(default)]
+    pub actions: Vec<ScriptAction>,
+}
+
+/// An order sent by a script.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderStep {
+    /// Unique identifier of the order.
+    pub order_id: String,
+    /// The order update ID, 0 for a new order.
+    #[serde(default)]
+    pub order_update_id: u32,
+    /// The sequence ID of the first node.
+    #[serde(default)]
+    pub first_sequence_id: u32,
+    /// The nodes.
+    pub nodes: Vec<ScriptNode>,
+    /// The edges between the nodes. Edges named after their nodes are used if not given.
+    #[serde(default)]
+    pub edges: Vec<ScriptEdge>,
+    /// The first node of the horizon, all nodes are released if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub horizon_from: Option<String>,
+}
+
+/// What a step does.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(
+    tag = "step",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum StepKind {
+    /// Sends an order.
+    Order(OrderStep),
+    /// Sends instant actions.
+    InstantActions {
+        /// The actions.
+        actions: Vec<ScriptAction>,
+    },
+    /// Waits until a condition holds.
+    WaitFor {
+        /// The condition.
+        condition: Condition,
+        /// The step fails if the condition does not hold within this many seconds.
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: f64,
+        /// The step fails if the condition holds before this many seconds passed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        not_before_secs: Option<f64>,
+        /// The label of an earlier step the times are measured from, this step if not given.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<String>,
+    },
+    /// Waits for the given time while the messages of the AGV are recorded.
+    Wait {
+        /// The time to wait in seconds.
+        secs: f64,
+    },
+}
+
+fn default_timeout_secs() -> f64 {
+    60.0
+}
+
+/// A step of a script.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Step {
+    /// A name for the step, which later steps can measure times from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// What the step does.
+    #[serde(flatten)]
+    pub kind: StepKind,
+}
+
+/// An acceptance script for one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Script {
+    /// Name of the AGV manufacturer.
+    pub manufacturer: String,
+    /// Serial number of the AGV.
+    pub serial_number: String,
+    /// The steps, executed in order.
+    pub steps: Vec<Step>,
+}
+
+/// The outcome of a step.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StepStatus {
+    /// The step did what it should.
+    Passed,
+    /// The step failed.
+    Failed,
+    /// The step was not executed because an earlier step failed.
+    Skipped,
+}
+
+/// The outcome of a step and how long it took.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StepReport {
+    /// The label of the step.
+    pub label: Option<String>,
+    /// What the step did.
+    pub description: String,
+    /// The outcome.
+    pub status: StepStatus,
+    /// How long the step took in seconds, measured from the step given by `since` for waits.
+    pub elapsed_secs: Option<f64>,
+    /// Why the step failed.
+    pub message: Option<String>,
+}
+
+/// The outcome of a script.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptReport {
+    /// The outcome of every step.
+    pub steps: Vec<StepReport>,
+}
+
+impl ScriptReport {
+    /// Whether every step passed.
+    pub fn passed(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| step.status == StepStatus::Passed)
+    }
+
+    /// A human readable list of the steps.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            let status = match step.status {
+                StepStatus::Passed => "PASS",
+                StepStatus::Failed => "FAIL",
+                StepStatus::Skipped => "SKIP",
+            };
+            let elapsed = step
+                .elapsed_secs
+                .map(|secs| format!(" ({secs:.1} s)"))
+                .unwrap_or_default();
+            let _ = writeln!(
+                text,
+                "{status} {:>3} {}{elapsed}",
+                index + 1,
+                step.description
+            );
+            if let Some(message) = &step.message {
+                let _ = writeln!(text, "         {message}");
+            }
+        }
+        let passed = self
+            .steps
+            .iter()
+            .filter(|step| step.status == StepStatus::Passed)
+            .count();
+        let _ = writeln!(text, "{passed} of {} steps passed", self.steps.len());
+        text
+    }
+}
+
+/// Runs the script against the AGV of the session.
+pub async fn run(script: &Script, master: &mut MasterControl) -> ScriptReport {
+    let mut runner = Runner {
+        master,
+        labels: HashMap::new(),
+        generated_ids: 0,
+    };
+    let mut report = ScriptReport::default();
+    let mut failed = false;
+    for step in &script.steps {
+        let description = describe(&step.kind);
+        if failed {
+            report.steps.push(StepReport {
+                label: step.label.clone(),
+                description,
+                status: StepStatus::Skipped,
+                elapsed_secs: None,
+                message: None,
+            });
+            continue;
+        }
+        let start = Instant::now();
+        if let Some(label) = &step.label {
+            runner.labels.insert(label.clone(), start);
+        }
+        let (elapsed, result) = runner.execute(&step.kind, start).await;
+        failed = result.is_err();
+        report.steps.push(StepReport {
+            label: step.label.clone(),
+            description,
+            status: if failed {
+                StepStatus::Failed
+            } else {
+                StepStatus::Passed
+            },
+            elapsed_secs: Some(elapsed.unwrap_or_else(|| start.elapsed()).as_secs_f64()),
+            message: result.err(),
+        });
+    }
+    report
+}
+
+fn describe(kind: &StepKind) -> String {
+    match kind {
+        StepKind::Order(order) => format!(
+            "send order {}/{} with {} nodes",
+            order.order_id,
+            order.order_update_id,
+            order.nodes.len()
+        ),
+        StepKind::InstantActions { actions } => {
+            let types: Vec<&str> = actions
+                .iter()
+                .map(|action| action.action_type.as_str())
+                .collect();
+            format!("send instant actions {}", types.join(", "))
+        }
+        StepKind::WaitFor {
+            condition, since, ..
+        } => match since {
+            Some(since) => format!("wait for {condition} since {since}"),
+            None => format!("wait for {condition}"),
+        },
+        StepKind::Wait { secs } => format!("wait {secs} s"),
+    }
+}
+
+struct Runner<'a> {
+    master: &'a mut MasterControl,
+    /// When the labelled steps started.
+    labels: HashMap<String, Instant>,
+    generated_ids: usize,
+}
+
+impl Runner<'_> {
+    /// Executes a step and returns the time to report, if it differs from the duration of the
+    /// step.
+    async fn execute(
+        &mut self,
+        kind: &StepKind,
+        start: Instant,
+    ) -> (Option<Duration>, Result<(), String>) {
+        match kind {
+            StepKind::Order(order) => {
+                let result = self
+                    .order(order)
+                    .and_then(|order| self.send(Message::Order(order)));
+                (None, result)
+            }
+            StepKind::InstantActions { actions } => {
+                let mut builder =
+                    InstantActionsBuilder::new(self.master.header(Topic::InstantActions));
+                for action in actions {
+                    builder = builder.action(self.action(action));
+                }
+                (None, self.send(Message::InstantActions(builder.build())))
+            }
+            StepKind::WaitFor {
+                condition,
+                timeout_secs,
+                not_before_secs,
+                since,
+            } => {
+                let since = match since {
+                    Some(label) => match self.labels.get(label) {
+                        Some(since) => *since,
+                        None => return (None, Err(format!("no earlier step is labelled {label}"))),
+                    },
+                    None => start,
+                };
+                let timeout =
+                    Duration::from_secs_f64(timeout_secs.max(0.0)).saturating_sub(since.elapsed());
+                match self.master.wait_for(condition, timeout).await {
+                    Ok(Some(_)) => {
+                        let elapsed = since.elapsed();
+                        let result = match not_before_secs {
+                            Some(not_before) if elapsed.as_secs_f64() < *not_before => {
+                                Err(format!(
+                                    "{condition} after {:.1} s, expected not before {not_before} s",
+                                    elapsed.as_secs_f64()
+                                ))
+                            }
+                            _ => Ok(()),
+                        };
+                        (Some(elapsed), result)
+                    }
+                    Ok(None) => (
+                        Some(since.elapsed()),
+                        Err(format!("no {condition} within {timeout_secs} s")),
+                    ),
+                    Err(err) => (None, Err(err.to_string())),
+                }
+            }
+            StepKind::Wait { secs } => {
+                let duration = Duration::from_secs_f64(secs.max(0.0));
+                (
+                    None,
+                    self.master
+                        .wait(duration)
+                        .await
+                        .map_err(|err| err.to_string()),
+                )
+            }
+        }
+    }
+
+    fn send(&mut self, message: Message) -> Result<(), String> {
+        self.master.send(message).map_err(|err| err.to_string())
+    }
+
+    fn order(&mut self, step: &OrderStep) -> Result<Order, String> {
+        let OrderStep {
+            order_id,
+            order_update_id,
+            first_sequence_id,
+            nodes,
+            edges,
+            horizon_from,
+        } = step;
+        if !edges.is_empty() && edges.len() + 1 != nodes.len() {
+            return Err(format!(
+                "{} nodes need {} edges, the script gives {}",
+                nodes.len(),
+                nodes.len().saturating_sub(1),
+                edges.len()
+            ));
+        }
+        let mut builder = OrderBuilder::new(self.master.header(Topic::Order), order_id)
+            .order_update_id(*order_update_id)
+            .first_sequence_id(*first_sequence_id);
+        for (index, node) in nodes.iter().enumerate() {
+            if index > 0 {
+                let edge = edges.get(index - 1);
+                let edge_id = edge.map_or_else(
+                    || format!("{}-{}", nodes[index - 1].node_id, node.node_id),
+                    |edge| edge.edge_id.clone(),
+                );
+                let actions = edge.map_or_else(Vec::new, |edge| {
+                    edge.actions
+                        .iter()
+                        .map(|action| self.action(action))
+                        .collect()
+                });
+                builder = builder.edge_with(Edge {
+                    edge_id,
+                    sequence_id: 0,
+                    released: true,
+                    start_node_id: String::new(),
+                    end_node_id: String::new(),
+                    actions,
+                    trajectory: None,
+                    length: None,
+                    max_speed: edge.and_then(|edge| edge.max_speed),
+                    max_height: None,
+                    min_height: None,
+                    orientation: None,
+                    direction: None,
+                });
+            }
+            builder = builder.node_with(Node {
+                node_id: node.node_id.clone(),
+                sequence_id: 0,
+                released: true,
+                node_position: node.node_position.clone(),
+                actions: node
+                    .actions
+                    .iter()
+                    .map(|action| self.action(action))
+                    .collect(),
+            });
+        }
+        if let Some(node_id) = horizon_from {
+            builder = builder.horizon_from(node_id);
+        }
+        builder.build().map_err(|err| err.to_string())
+    }
+
+    fn action(&mut self, action: &ScriptAction) -> Action {
+        let action_id = action.action_id.clone().unwrap_or_else(|| {
+            self.generated_ids += 1;
+            format!("{}-{}", action.action_type, self.generated_ids)
+        });
+        Action {
+            action_id,
+            action_type: action.action_type.clone(),
+            blocking_type: action.blocking_type.clone(),
+            action_parameters: action.action_parameters.clone(),
+        }
+    }
+}
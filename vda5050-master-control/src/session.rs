@@ -0,0 +1,142 @@
+//! A master control session with one AGV, which records every message sent and received.
+use crate::condition::{AgvView, Condition};
+use crate::link::{Link, LinkError};
+use chrono::Utc;
+use std::time::Duration;
+use tokio::time::Instant;
+use vda5050_analysis::recording::{RecordEntry, Recording};
+use vda5050_data_types::common::Header;
+use vda5050_data_types::header_factory::HeaderFactory;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+use vda5050_simulator::mqtt::BrokerOptions;
+
+/// Master control for one AGV.
+pub struct MasterControl {
+    link: Link,
+    headers: HeaderFactory,
+    broker: BrokerOptions,
+    view: AgvView,
+    entries: Vec<RecordEntry>,
+}
+
+impl MasterControl {
+    /// Starts a session with the given AGV. The broker options name the topics of the recording.
+    pub fn new(link: Link, manufacturer: &str, serial_number: &str, broker: BrokerOptions) -> Self {
+        MasterControl {
+            link,
+            headers: HeaderFactory::new(manufacturer, serial_number),
+            broker,
+            view: AgvView::default(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// The header for the next message on the given topic, to build orders and instant actions
+    /// with.
+    pub fn header(&self, topic: Topic) -> Header {
+        self.headers.next(topic)
+    }
+
+    /// The latest messages of the AGV.
+    pub fn view(&self) -> &AgvView {
+        &self.view
+    }
+
+    /// Forgets the latest message of the given topic, so that a condition on it only holds for a
+    /// message received from now on.
+    pub fn forget(&mut self, topic: Topic) {
+        match topic {
+            Topic::State => self.view.state = None,
+            Topic::Connection => self.view.connection = None,
+            Topic::Factsheet => self.view.factsheet = None,
+            _ => {}
+        }
+    }
+
+    /// Breaks the connection of the AGV if the link can, see [`Link::interrupt`].
+    pub fn interrupt(&mut self) -> bool {
+        self.link.interrupt()
+    }
+
+    /// Sends an order or instant actions to the AGV.
+    pub fn send(&mut self, message: Message) -> Result<(), LinkError> {
+        self.link.send(&message)?;
+        self.record(&message);
+        Ok(())
+    }
+
+    /// Processes the messages of the AGV until the condition holds and returns how long that took,
+    /// or `None` if it did not hold within the timeout.
+    pub async fn wait_for(
+        &mut self,
+        condition: &Condition,
+        timeout: Duration,
+    ) -> Result<Option<Duration>, LinkError> {
+        let start = Instant::now();
+        loop {
+            if condition.matches(&self.view) {
+                return Ok(Some(start.elapsed()));
+            }
+            let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+                return Ok(None);
+            };
+            match tokio::time::timeout(remaining, self.link.receive()).await {
+                Ok(message) => self.receive(message?),
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+
+    /// Processes the messages of the AGV for the given time.
+    pub async fn wait(&mut self, duration: Duration) -> Result<(), LinkError> {
+        let end = Instant::now() + duration;
+        loop {
+            let Some(remaining) = end.checked_duration_since(Instant::now()) else {
+                return Ok(());
+            };
+            match tokio::time::timeout(remaining, self.link.receive()).await {
+                Ok(message) => self.receive(message?),
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+
+    /// Every message sent and received so far, in the order of sending and receiving.
+    pub fn entries(&self) -> &[RecordEntry] {
+        &self.entries
+    }
+
+    /// The session as a recording for the analysis.
+    pub fn recording(&self) -> Recording {
+        let mut recording = Recording::default();
+        for (index, entry) in self.entries.iter().enumerate() {
+            recording.push_entry(entry.clone(), format!("session:{}", index + 1));
+        }
+        recording.sort();
+        recording
+    }
+
+    fn receive(&mut self, message: Message) {
+        self.record(&message);
+        match message {
+            Message::State(state) => self.view.state = Some(state),
+            Message::Connection(connection) => {
+                self.view.connection = Some(connection.connection_state)
+            }
+            Message::Factsheet(factsheet) => self.view.factsheet = Some(factsheet),
+            _ => {}
+        }
+    }
+
+    fn record(&mut self, message: &Message) {
+        let header = message.header();
+        self.entries.push(RecordEntry {
+            received_at: Utc::now(),
+            topic: self
+                .broker
+                .topic(&header.manufacturer, &header.serial_number, message.topic()),
+            payload: serde_json::to_value(message).unwrap_or_default(),
+        });
+    }
+}
@@ -0,0 +1,70 @@
+//! Runs a fleet scenario in simulated time and writes the recording.
+use clap::Parser;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use vda5050_simulator::scenario::{Scenario, ScenarioRun};
+
+#[derive(Parser)]
+#[command(name = "vda5050-scenario", version, about)]
+struct Args {
+    /// The scenario as JSON.
+    scenario: PathBuf,
+    /// Where to write the JSON Lines recording.
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Where to write the injected faults as JSON, to compare the analysis with.
+    #[arg(long)]
+    injections: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let scenario: Scenario = match std::fs::read(&args.scenario)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_slice(&json).map_err(|err| err.to_string()))
+    {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            eprintln!("error: failed to read {}: {err}", args.scenario.display());
+            return ExitCode::from(2);
+        }
+    };
+    let run = match scenario.run() {
+        Ok(run) => run,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    if let Err(err) = write(&args, &run) {
+        eprintln!("error: {err}");
+        return ExitCode::FAILURE;
+    }
+    eprintln!(
+        "{} messages, {} injected faults",
+        run.entries.len(),
+        run.injections
+            .iter()
+            .filter(|injection| injection.start.is_some())
+            .count()
+    );
+    ExitCode::SUCCESS
+}
+
+fn write(args: &Args, run: &ScenarioRun) -> Result<(), String> {
+    let failed = |path: &PathBuf, err: &dyn std::fmt::Display| {
+        format!("failed to write {}: {err}", path.display())
+    };
+    File::create(&args.output)
+        .and_then(|file| run.write_jsonl(BufWriter::new(file)))
+        .map_err(|err| failed(&args.output, &err))?;
+    if let Some(path) = &args.injections {
+        let file = File::create(path).map_err(|err| failed(path, &err))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &run.injections)
+            .map_err(|err| failed(path, &err))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Runs a simulated AGV against an MQTT broker.
+use clap::Parser;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use vda5050_data_types::header_factory::SystemClock;
+use vda5050_simulator::config::VehicleConfig;
+use vda5050_simulator::mqtt::{self, BrokerOptions};
+use vda5050_simulator::vehicle::SimulatedAgv;
+
+#[derive(Parser)]
+#[command(name = "vda5050-simulator", version, about)]
+struct Args {
+    /// The vehicle configuration as JSON. Missing fields take their defaults.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Overrides the manufacturer of the configuration.
+    #[arg(long)]
+    manufacturer: Option<String>,
+    /// Overrides the serial number of the configuration.
+    #[arg(long)]
+    serial_number: Option<String>,
+    /// Host name of the MQTT broker.
+    #[arg(long, default_value = "localhost")]
+    host: String,
+    /// Port of the MQTT broker.
+    #[arg(long, default_value_t = 1883)]
+    port: u16,
+    /// The first level of the MQTT topics.
+    #[arg(long, default_value = "uagv")]
+    interface: String,
+    /// The major version in the MQTT topics.
+    #[arg(long, default_value = "v2")]
+    topic_version: String,
+    /// How often the simulation is advanced, in milliseconds.
+    #[arg(long, default_value_t = 50)]
+    tick_ms: u64,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+    let mut config = match &args.config {
+        Some(path) => match std::fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| serde_json::from_slice(&json).map_err(|err| err.to_string()))
+        {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("error: failed to read {}: {err}", path.display());
+                return ExitCode::from(2);
+            }
+        },
+        None => VehicleConfig::default(),
+    };
+    if let Some(manufacturer) = args.manufacturer {
+        config.manufacturer = manufacturer;
+    }
+    if let Some(serial_number) = args.serial_number {
+        config.serial_number = serial_number;
+    }
+    let broker = BrokerOptions {
+        host: args.host,
+        port: args.port,
+        interface: args.interface,
+        version: args.topic_version,
+        tick: std::time::Duration::from_millis(args.tick_ms),
+    };
+
+    let agv = SimulatedAgv::new(config, Arc::new(SystemClock));
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    match mqtt::run(agv, &broker, shutdown).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
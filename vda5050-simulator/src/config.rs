@@ -0,0 +1,142 @@
+//! The settings of a simulated AGV, usually read from a JSON file.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use vda5050_data_types::factsheet::{AgvKinematic, Dimensions, PhysicalParameters};
+
+/// How the simulated AGV executes actions of one type.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionBehavior {
+    /// How long the action runs, in seconds.
+    pub duration_secs: f64,
+    /// The probability in [0.0 ... 1.0] that the action fails at its end.
+    #[serde(default)]
+    pub failure_rate: f64,
+}
+
+impl Default for ActionBehavior {
+    fn default() -> Self {
+        ActionBehavior {
+            duration_secs: 1.0,
+            failure_rate: 0.0,
+        }
+    }
+}
+
+/// The battery of the simulated AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BatteryModel {
+    /// The state of charge at the start in [%].
+    pub charge: f64,
+    /// The charge used per meter driven in [%].
+    pub discharge_per_meter: f64,
+    /// The charge used per hour while standing in [%].
+    pub idle_discharge_per_hour: f64,
+    /// The charge gained per hour while charging in [%].
+    pub charge_per_hour: f64,
+}
+
+impl Default for BatteryModel {
+    fn default() -> Self {
+        BatteryModel {
+            charge: 90.0,
+            discharge_per_meter: 0.01,
+            idle_discharge_per_hour: 1.0,
+            charge_per_hour: 60.0,
+        }
+    }
+}
+
+/// Everything that makes up one simulated AGV.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VehicleConfig {
+    /// Name of the AGV manufacturer.
+    pub manufacturer: String,
+    /// Serial number of the AGV.
+    pub serial_number: String,
+    /// The VDA 5050 version written into the headers.
+    pub version: String,
+    /// The map the AGV starts on.
+    pub map_id: String,
+    /// The start position, X-coordinate in [m].
+    pub x: f64,
+    /// The start position, Y-coordinate in [m].
+    pub y: f64,
+    /// The start orientation in [rad].
+    pub theta: f64,
+    /// The node the AGV starts on, if any.
+    pub last_node_id: Option<String>,
+    /// The AGV type reported in the factsheet.
+    #[serde(rename = "type")]
+    pub type_field: String,
+    /// The kinematic, which decides whether the AGV turns on the spot before driving.
+    pub agv_kinematic: AgvKinematic,
+    /// The dimensions of the AGV, reported as factsheet and outline.
+    pub agv_dimensions: Dimensions,
+    /// The speed and acceleration limits.
+    pub physical_parameters: PhysicalParameters,
+    /// How far the AGV may be away from the first node of a new order, in [m], unless the node
+    /// allows a deviation itself.
+    pub max_start_deviation: f64,
+    /// The longest time between two state messages, in seconds. States are also sent on every
+    /// relevant change.
+    pub state_interval_secs: f64,
+    /// The time between two visualization messages in seconds, none are sent if not given.
+    pub visualization_interval_secs: Option<f64>,
+    /// The execution of every action type that is not listed in `actions`.
+    pub default_action: ActionBehavior,
+    /// The execution per action type.
+    pub actions: BTreeMap<String, ActionBehavior>,
+    /// The battery.
+    pub battery: BatteryModel,
+    /// The seed of the random numbers, so that failures can be reproduced.
+    pub seed: u64,
+}
+
+impl Default for VehicleConfig {
+    fn default() -> Self {
+        VehicleConfig {
+            manufacturer: "Simulator".to_string(),
+            serial_number: "sim-1".to_string(),
+            version: vda5050_data_types::header_factory::DEFAULT_VERSION.to_string(),
+            map_id: "map".to_string(),
+            x: 0.0,
+            y: 0.0,
+            theta: 0.0,
+            last_node_id: None,
+            type_field: "simulator".to_string(),
+            agv_kinematic: AgvKinematic::Differential,
+            agv_dimensions: Dimensions {
+                length: 1.2,
+                width: 0.8,
+                height: Some(0.4),
+            },
+            physical_parameters: PhysicalParameters {
+                speed_min: 0.05,
+                speed_max: 1.5,
+                angular_speed_min: None,
+                angular_speed_max: Some(1.0),
+                acceleration_max: 0.5,
+                deceleration_max: 0.8,
+            },
+            max_start_deviation: 0.5,
+            state_interval_secs: 1.0,
+            visualization_interval_secs: Some(0.5),
+            default_action: ActionBehavior::default(),
+            actions: BTreeMap::new(),
+            battery: BatteryModel::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl VehicleConfig {
+    /// The execution of the given action type.
+    pub fn action(&self, action_type: &str) -> &ActionBehavior {
+        self.actions
+            .get(action_type)
+            .unwrap_or(&self.default_action)
+    }
+}
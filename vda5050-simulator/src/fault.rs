@@ -0,0 +1,340 @@
+//! Faults injected into simulated AGVs, so that the analyzers can be checked against problems whose
+//! time and cause are known.
+//!
+//! Most faults change the messages on their way from the AGV to the broker. Connection breaks and
+//! fatal errors act on the AGV itself.
+use crate::vehicle::SimulatedAgv;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use vda5050_data_types::common::AgvPosition;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::state::{Error, ErrorLevel, ErrorReference};
+use vda5050_data_types::topic::Topic;
+
+/// A fault and when it happens.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Fault {
+    /// The serial number of the affected AGV, all AGVs if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    /// When the fault starts, in seconds from the start of the scenario.
+    pub at_secs: f64,
+    /// How long the fault lasts in seconds, until the end of the scenario if not given.
+    /// A fatal error lasts this long from the moment it is raised.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// What goes wrong.
+    #[serde(flatten)]
+    pub kind: FaultKind,
+}
+
+/// What goes wrong.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(
+    tag = "fault",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum FaultKind {
+    /// Messages the AGV publishes get lost.
+    Drop {
+        /// The topic whose messages get lost, all topics if not given.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        topic: Option<Topic>,
+        /// The probability in [0.0 ... 1.0] that a message gets lost.
+        #[serde(default = "always")]
+        probability: f64,
+    },
+    /// The header IDs skip ahead once and keep counting from there.
+    HeaderJump {
+        /// The topic whose header IDs skip, all topics if not given.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        topic: Option<Topic>,
+        /// How many header IDs are skipped.
+        by: u32,
+    },
+    /// The clock of the AGV is off, so the header timestamps are.
+    ClockSkew {
+        /// The offset at the start of the fault in [ms].
+        #[serde(default)]
+        offset_ms: f64,
+        /// How fast the offset grows in [ppm].
+        #[serde(default)]
+        drift_ppm: f64,
+    },
+    /// The reported positions scatter around the true position.
+    PositionNoise {
+        /// The standard deviation of the noise in x and y in [m].
+        std_dev: f64,
+    },
+    /// The AGV loses its connection: the broker publishes its last will, the AGV neither sends nor
+    /// receives anything and comes back online at the end of the fault.
+    ConnectionBreak,
+    /// The AGV reports a fatal error and stops when it reaches the given node.
+    FatalError {
+        /// The node the error is raised at.
+        node_id: String,
+        /// The error type reported.
+        #[serde(default = "fatal_error_type")]
+        error_type: String,
+    },
+}
+
+fn always() -> f64 {
+    1.0
+}
+
+fn fatal_error_type() -> String {
+    "simulatedFatalError".to_string()
+}
+
+/// When a fault was injected into an AGV and how many messages it affected, to compare the
+/// findings of an analysis with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Injection {
+    /// The serial number of the AGV.
+    pub serial_number: String,
+    /// The time the fault started, none if it never did, e.g. because the AGV never reached the
+    /// node of a fatal error.
+    pub start: Option<DateTime<Utc>>,
+    /// The time the fault ended, none if it lasted until the end of the scenario.
+    pub end: Option<DateTime<Utc>>,
+    /// The messages that were lost or changed.
+    pub messages: usize,
+    /// What went wrong.
+    #[serde(flatten)]
+    pub kind: FaultKind,
+}
+
+/// A change of the connection between AGV and broker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionChange {
+    /// The connection broke, the broker publishes the last will.
+    Broken,
+    /// The AGV is connected again.
+    Restored,
+}
+
+/// One fault of the AGV and what it did so far.
+struct Scheduled {
+    fault: Fault,
+    start: Option<DateTime<Utc>>,
+    /// The elapsed seconds at which the fault started, which differs from `at_secs` for fatal
+    /// errors.
+    started_secs: Option<f64>,
+    ended: bool,
+    messages: usize,
+}
+
+impl Scheduled {
+    fn is_active(&self, elapsed: f64) -> bool {
+        let Some(started) = self.started_secs else {
+            return false;
+        };
+        !self.ended
+            && self
+                .fault
+                .duration_secs
+                .is_none_or(|duration| elapsed < started + duration)
+    }
+}
+
+/// Applies the faults of one AGV as the simulated time passes.
+pub struct FaultInjector {
+    serial_number: String,
+    faults: Vec<Scheduled>,
+    rng: StdRng,
+    connected: bool,
+}
+
+impl FaultInjector {
+    /// Picks the faults of the AGV with the given serial number.
+    pub fn new(serial_number: &str, faults: &[Fault], seed: u64) -> Self {
+        let faults = faults
+            .iter()
+            .filter(|fault| {
+                fault
+                    .serial_number
+                    .as_deref()
+                    .is_none_or(|serial| serial == serial_number)
+            })
+            .map(|fault| Scheduled {
+                fault: fault.clone(),
+                start: None,
+                started_secs: None,
+                ended: false,
+                messages: 0,
+            })
+            .collect();
+        FaultInjector {
+            serial_number: serial_number.to_string(),
+            faults,
+            rng: StdRng::seed_from_u64(seed),
+            connected: true,
+        }
+    }
+
+    /// Whether the AGV is connected to the broker, i.e. receives messages.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Starts and ends the faults at the given time, `elapsed` seconds into the scenario, and
+    /// applies those acting on the AGV itself. Returns how the connection changed, if it did.
+    pub fn update(
+        &mut self,
+        now: DateTime<Utc>,
+        elapsed: f64,
+        agv: &mut SimulatedAgv,
+    ) -> Option<ConnectionChange> {
+        for scheduled in &mut self.faults {
+            if scheduled.ended {
+                continue;
+            }
+            if scheduled.started_secs.is_some() {
+                if !scheduled.is_active(elapsed) {
+                    scheduled.ended = true;
+                    if let FaultKind::FatalError { error_type, .. } = &scheduled.fault.kind {
+                        agv.clear_errors(error_type);
+                    }
+                }
+                continue;
+            }
+            if elapsed < scheduled.fault.at_secs {
+                continue;
+            }
+            if let FaultKind::FatalError {
+                node_id,
+                error_type,
+            } = &scheduled.fault.kind
+            {
+                if agv.last_node_id() != Some(node_id) {
+                    continue;
+                }
+                agv.raise_error(Error {
+                    error_type: error_type.clone(),
+                    error_description: Some(format!("injected fatal error at node {node_id}")),
+                    error_level: ErrorLevel::Fatal,
+                    error_references: vec![ErrorReference {
+                        reference_key: "nodeId".to_string(),
+                        reference_value: node_id.clone(),
+                    }],
+                });
+            }
+            scheduled.started_secs = Some(elapsed);
+            scheduled.start = Some(now);
+        }
+
+        let connected = !self.faults.iter().any(|scheduled| {
+            scheduled.fault.kind == FaultKind::ConnectionBreak && scheduled.is_active(elapsed)
+        });
+        let change = match (self.connected, connected) {
+            (true, false) => Some(ConnectionChange::Broken),
+            (false, true) => Some(ConnectionChange::Restored),
+            _ => None,
+        };
+        self.connected = connected;
+        change
+    }
+
+    /// Passes a message the AGV publishes through the active faults. Returns `None` if the message
+    /// gets lost.
+    pub fn outgoing(&mut self, elapsed: f64, mut message: Message) -> Option<Message> {
+        let topic = message.topic();
+        for scheduled in &mut self.faults {
+            // Skipped header IDs stay skipped after the jump.
+            let applies = match &scheduled.fault.kind {
+                FaultKind::HeaderJump { .. } => scheduled.started_secs.is_some(),
+                _ => scheduled.is_active(elapsed),
+            };
+            if !applies {
+                continue;
+            }
+            match &scheduled.fault.kind {
+                FaultKind::Drop {
+                    topic: dropped,
+                    probability,
+                } => {
+                    if dropped.is_none_or(|dropped| dropped == topic)
+                        && self.rng.random_bool(probability.clamp(0.0, 1.0))
+                    {
+                        scheduled.messages += 1;
+                        return None;
+                    }
+                }
+                FaultKind::HeaderJump { topic: jumped, by } => {
+                    if jumped.is_none_or(|jumped| jumped == topic) {
+                        let header = message.header_mut();
+                        header.header_id = header.header_id.wrapping_add(*by);
+                        scheduled.messages += 1;
+                    }
+                }
+                FaultKind::ClockSkew {
+                    offset_ms,
+                    drift_ppm,
+                } => {
+                    let started = scheduled.started_secs.unwrap_or(elapsed);
+                    let skew_ms = offset_ms + drift_ppm * (elapsed - started) / 1000.0;
+                    let header = message.header_mut();
+                    if let Some(timestamp) = header.timestamp_utc() {
+                        let skewed = timestamp
+                            + chrono::Duration::microseconds((skew_ms * 1000.0).round() as i64);
+                        header.timestamp = skewed.to_rfc3339_opts(SecondsFormat::Millis, true);
+                        scheduled.messages += 1;
+                    }
+                }
+                FaultKind::PositionNoise { std_dev } => {
+                    let position = match &mut message {
+                        Message::State(state) => state.agv_position.as_mut(),
+                        Message::Visualization(visualization) => {
+                            visualization.agv_position.as_mut()
+                        }
+                        _ => None,
+                    };
+                    if let Some(position) = position {
+                        add_noise(&mut self.rng, position, *std_dev);
+                        scheduled.messages += 1;
+                    }
+                }
+                FaultKind::ConnectionBreak => {
+                    scheduled.messages += 1;
+                    return None;
+                }
+                FaultKind::FatalError { .. } => {}
+            }
+        }
+        Some(message)
+    }
+
+    /// What was injected, in the order the faults were given.
+    pub fn injections(&self) -> Vec<Injection> {
+        self.faults
+            .iter()
+            .map(|scheduled| Injection {
+                serial_number: self.serial_number.clone(),
+                start: scheduled.start,
+                end: scheduled
+                    .start
+                    .zip(scheduled.fault.duration_secs)
+                    .map(|(start, duration)| {
+                        start + chrono::Duration::milliseconds((duration * 1000.0) as i64)
+                    }),
+                messages: scheduled.messages,
+                kind: scheduled.fault.kind.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Moves the position by normally distributed offsets, drawn with the Box-Muller transform.
+fn add_noise(rng: &mut StdRng, position: &mut AgvPosition, std_dev: f64) {
+    let radius = (-2.0 * (1.0 - rng.random::<f64>()).ln()).sqrt() * std_dev;
+    let angle = 2.0 * PI * rng.random::<f64>();
+    position.x += radius * angle.cos();
+    position.y += radius * angle.sin();
+}
@@ -0,0 +1,89 @@
+//! The tracks the simulated AGV drives along: straight lines between nodes or NURBS trajectories.
+use vda5050_data_types::common::Trajectory;
+
+/// Where the AGV is and where it points to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    /// X-coordinate in the world coordinate system. In [m].
+    pub x: f64,
+    /// Y-coordinate in the world coordinate system. In [m].
+    pub y: f64,
+    /// Orientation in [rad].
+    pub theta: f64,
+}
+
+/// The number of straight pieces a trajectory is sampled into per control point.
+const SAMPLES_PER_CONTROL_POINT: usize = 16;
+
+/// A track sampled into short straight pieces, so that poses can be looked up by the distance
+/// driven along it.
+#[derive(Debug, Clone)]
+pub struct Track {
+    points: Vec<(f64, f64)>,
+    /// The distance from the start to every point.
+    distances: Vec<f64>,
+}
+
+impl Track {
+    /// A straight track between two points.
+    pub fn straight(from: (f64, f64), to: (f64, f64)) -> Self {
+        Track::through(vec![from, to])
+    }
+
+    /// The track of a NURBS trajectory, or `None` if its degree, knot vector and control points
+    /// do not fit together.
+    pub fn of_trajectory(trajectory: &Trajectory) -> Option<Self> {
+        trajectory
+            .sample(SAMPLES_PER_CONTROL_POINT)
+            .map(Track::through)
+    }
+
+    fn through(points: Vec<(f64, f64)>) -> Self {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                let previous = points[i - 1];
+                total += (point.0 - previous.0).hypot(point.1 - previous.1);
+            }
+            distances.push(total);
+        }
+        Track { points, distances }
+    }
+
+    /// The length of the track in [m].
+    pub fn length(&self) -> f64 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// The end of the track.
+    pub fn end(&self) -> (f64, f64) {
+        self.points.last().copied().unwrap_or((0.0, 0.0))
+    }
+
+    /// The position at the given distance from the start, with the direction of the track there.
+    pub fn pose_at(&self, distance: f64) -> Pose {
+        let distance = distance.clamp(0.0, self.length());
+        let piece = self
+            .distances
+            .windows(2)
+            .position(|pair| distance <= pair[1] && pair[1] > pair[0])
+            .unwrap_or(0);
+        let (Some(&start), Some(&end)) = (self.points.get(piece), self.points.get(piece + 1))
+        else {
+            let (x, y) = self.end();
+            return Pose { x, y, theta: 0.0 };
+        };
+        let piece_length = self.distances[piece + 1] - self.distances[piece];
+        let t = if piece_length > 0.0 {
+            (distance - self.distances[piece]) / piece_length
+        } else {
+            0.0
+        };
+        Pose {
+            x: start.0 + (end.0 - start.0) * t,
+            y: start.1 + (end.1 - start.1) * t,
+            theta: (end.1 - start.1).atan2(end.0 - start.0),
+        }
+    }
+}
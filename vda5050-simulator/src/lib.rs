@@ -0,0 +1,11 @@
+//! A simulated VDA 5050 AGV, to test master controls and the analysis tools without hardware.
+//!
+//! [`vehicle::SimulatedAgv`] executes orders and instant actions in the time of a clock,
+//! [`mqtt::run`] connects it to a broker. A [`scenario::Scenario`] runs a fleet of them with
+//! injected faults into a recording.
+pub mod config;
+pub mod fault;
+pub mod geometry;
+pub mod mqtt;
+pub mod scenario;
+pub mod vehicle;
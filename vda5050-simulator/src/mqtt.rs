@@ -0,0 +1,195 @@
+//! Connects a simulated AGV to an MQTT broker.
+use crate::vehicle::SimulatedAgv;
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use vda5050_data_types::connection::ConnectionState;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+
+/// Where the broker is and how the topics are named.
+#[derive(Debug, Clone)]
+pub struct BrokerOptions {
+    /// Host name of the broker.
+    pub host: String,
+    /// Port of the broker.
+    pub port: u16,
+    /// The first level of the topics, `uagv` unless the project chose another name.
+    pub interface: String,
+    /// The major version in the topics, e.g. `v2`.
+    pub version: String,
+    /// How often the simulation is advanced.
+    pub tick: Duration,
+}
+
+impl Default for BrokerOptions {
+    fn default() -> Self {
+        BrokerOptions {
+            host: "localhost".to_string(),
+            port: 1883,
+            interface: "uagv".to_string(),
+            version: "v2".to_string(),
+            tick: Duration::from_millis(50),
+        }
+    }
+}
+
+impl BrokerOptions {
+    /// The MQTT topic of an AGV, e.g. `uagv/v2/Acme/agv-1/state`.
+    pub fn topic(&self, manufacturer: &str, serial_number: &str, topic: Topic) -> String {
+        format!(
+            "{}/{}/{manufacturer}/{serial_number}/{topic}",
+            self.interface, self.version
+        )
+    }
+}
+
+/// Why the simulated AGV stopped.
+#[derive(Debug)]
+pub enum SimulatorError {
+    /// A message could not be handed to the MQTT client.
+    Client(rumqttc::ClientError),
+    /// A message could not be encoded.
+    Encode(serde_json::Error),
+}
+
+impl fmt::Display for SimulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulatorError::Client(err) => write!(f, "MQTT client error: {err}"),
+            SimulatorError::Encode(err) => write!(f, "failed to encode a message: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SimulatorError {}
+
+impl From<rumqttc::ClientError> for SimulatorError {
+    fn from(err: rumqttc::ClientError) -> Self {
+        SimulatorError::Client(err)
+    }
+}
+
+impl From<serde_json::Error> for SimulatorError {
+    fn from(err: serde_json::Error) -> Self {
+        SimulatorError::Encode(err)
+    }
+}
+
+/// Runs the AGV against the broker until `shutdown` completes.
+///
+/// The AGV registers `CONNECTIONBROKEN` as last will, publishes `ONLINE` and its factsheet on
+/// every connect, both retained, and `OFFLINE` before it disconnects. The client reconnects on
+/// its own when the connection is lost.
+pub async fn run(
+    mut agv: SimulatedAgv,
+    broker: &BrokerOptions,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), SimulatorError> {
+    let config = agv.config().clone();
+    let topic = |topic: Topic| broker.topic(&config.manufacturer, &config.serial_number, topic);
+
+    let mut options = MqttOptions::new(
+        format!("{}-{}", config.manufacturer, config.serial_number),
+        &broker.host,
+        broker.port,
+    );
+    options.set_keep_alive(Duration::from_secs(5));
+    options.set_last_will(LastWill::new(
+        topic(Topic::Connection),
+        serde_json::to_vec(&agv.connection(ConnectionState::ConnectionBroken))?,
+        QoS::AtLeastOnce,
+        true,
+    ));
+    let (client, mut eventloop) = AsyncClient::new(options, 256);
+    let mut ticker = tokio::time::interval(broker.tick);
+    // Whether the subscriptions of the current connection were handed to the client.
+    let mut subscribed = false;
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    subscribed = subscribe(&client, &topic)?;
+                    let online = Message::Connection(agv.connection(ConnectionState::Online));
+                    publish(&client, &topic, &online, true)?;
+                    publish(&client, &topic, &Message::Factsheet(agv.factsheet()), true)?;
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    let Some(received) = Topic::from_mqtt_topic(&publish.topic) else {
+                        continue;
+                    };
+                    match Message::decode(received, &publish.payload) {
+                        Ok(message) => agv.handle(message),
+                        Err(err) => agv.reject_invalid(received, err.to_string()),
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::warn!("{}: connection error: {err}", config.serial_number);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            },
+            _ = ticker.tick() => {
+                if !subscribed {
+                    subscribed = subscribe(&client, &topic)?;
+                }
+                for message in agv.poll() {
+                    publish(&client, &topic, &message, false)?;
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    let offline = Message::Connection(agv.connection(ConnectionState::Offline));
+    publish(&client, &topic, &offline, true)?;
+    client.try_disconnect()?;
+    drain(&mut eventloop).await;
+    Ok(())
+}
+
+/// Subscribes to orders and instant actions. Returns `false` if a request does not fit into the
+/// queue of the client, to be tried again on the next tick or connect.
+fn subscribe(
+    client: &AsyncClient,
+    topic: &impl Fn(Topic) -> String,
+) -> Result<bool, SimulatorError> {
+    for subscription in [Topic::Order, Topic::InstantActions] {
+        match client.try_subscribe(topic(subscription), QoS::AtMostOnce) {
+            Ok(()) => {}
+            Err(rumqttc::ClientError::TryRequest(_)) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Publishes a message on its topic. Messages that do not fit into the queue of the client, e.g.
+/// while it is disconnected, are dropped like a real AGV drops outdated states.
+fn publish(
+    client: &AsyncClient,
+    topic: &impl Fn(Topic) -> String,
+    message: &Message,
+    retain: bool,
+) -> Result<(), SimulatorError> {
+    let payload = serde_json::to_vec(message)?;
+    match client.try_publish(topic(message.topic()), QoS::AtMostOnce, retain, payload) {
+        Ok(()) | Err(rumqttc::ClientError::TryRequest(_)) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Lets the event loop send the remaining messages and the disconnect, for at most two seconds.
+async fn drain(eventloop: &mut EventLoop) {
+    let _ = tokio::time::timeout(Duration::from_secs(2), async {
+        while let Ok(event) = eventloop.poll().await {
+            if let Event::Outgoing(rumqttc::Outgoing::Disconnect) = event {
+                break;
+            }
+        }
+    })
+    .await;
+}
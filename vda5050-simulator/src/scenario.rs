@@ -0,0 +1,616 @@
+//! Fleet scenarios: several simulated AGVs on one map, the orders master control sends them and the
+//! faults injected on the way, run in simulated time into a recording.
+//!
+//! A scenario is a JSON file. The recording it produces can be analyzed and shown like one captured
+//! in a plant, and the [`Injection`]s tell which findings the analysis should report.
+use crate::config::VehicleConfig;
+use crate::fault::{ConnectionChange, Fault, FaultInjector, Injection};
+use crate::mqtt::BrokerOptions;
+use crate::vehicle::SimulatedAgv;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use vda5050_analysis::recording::RecordEntry;
+use vda5050_data_types::builders::{BuildError, OrderBuilder};
+use vda5050_data_types::common::{Action, NodePosition, Trajectory};
+use vda5050_data_types::connection::{Connection, ConnectionState};
+use vda5050_data_types::header_factory::{Clock, HeaderFactory, ManualClock};
+use vda5050_data_types::instant_actions::InstantActions;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::{Edge, Order};
+use vda5050_data_types::topic::Topic;
+
+/// A node of the scenario map.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MapNode {
+    /// Unique identifier of the node.
+    pub node_id: String,
+    /// X-coordinate in [m].
+    pub x: f64,
+    /// Y-coordinate in [m].
+    pub y: f64,
+}
+
+/// A directed edge of the scenario map.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MapEdge {
+    /// Unique identifier of the edge.
+    pub edge_id: String,
+    /// The node the edge starts at.
+    pub start_node_id: String,
+    /// The node the edge ends at.
+    pub end_node_id: String,
+    /// The highest speed allowed on the edge in [m/s].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_speed: Option<f64>,
+    /// The NURBS trajectory, a straight line between the nodes if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trajectory: Option<Trajectory>,
+    /// Whether the edge may also be driven from the end to the start node.
+    #[serde(default)]
+    pub bidirectional: bool,
+}
+
+/// The map all AGVs of a scenario drive on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenarioMap {
+    /// The map ID reported in positions.
+    pub map_id: String,
+    /// The nodes.
+    pub nodes: Vec<MapNode>,
+    /// The edges between the nodes.
+    pub edges: Vec<MapEdge>,
+}
+
+impl ScenarioMap {
+    /// The node with the given ID.
+    pub fn node(&self, node_id: &str) -> Option<&MapNode> {
+        self.nodes.iter().find(|node| node.node_id == node_id)
+    }
+
+    /// The edge leading from one node to another, if any.
+    pub fn edge(&self, from: &str, to: &str) -> Option<&MapEdge> {
+        self.edges.iter().find(|edge| {
+            (edge.start_node_id == from && edge.end_node_id == to)
+                || (edge.bidirectional && edge.start_node_id == to && edge.end_node_id == from)
+        })
+    }
+}
+
+/// An order master control sends at a given time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledOrder {
+    /// When the order is sent, in seconds from the start of the scenario.
+    pub at_secs: f64,
+    /// The serial number of the AGV the order is sent to.
+    pub serial_number: String,
+    /// Unique identifier of the order.
+    pub order_id: String,
+    /// The order update ID, 0 for a new order.
+    #[serde(default)]
+    pub order_update_id: u32,
+    /// The sequence ID of the first node. Updates start at the last released node of the order.
+    #[serde(default)]
+    pub first_sequence_id: u32,
+    /// The nodes to drive through. Consecutive nodes must be connected by an edge of the map.
+    pub route: Vec<String>,
+    /// How many nodes of the route are released, all if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released: Option<usize>,
+    /// The actions per node ID, executed at the first visit of the node in the route.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, Vec<Action>>,
+}
+
+/// Instant actions master control sends at a given time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledInstantActions {
+    /// When the actions are sent, in seconds from the start of the scenario.
+    pub at_secs: f64,
+    /// The serial number of the AGV the actions are sent to.
+    pub serial_number: String,
+    /// The actions.
+    pub actions: Vec<Action>,
+}
+
+/// A fleet scenario.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Scenario {
+    /// The simulated start time. Set it to get the same recording on every run, the current time is
+    /// used otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<DateTime<Utc>>,
+    /// How long the scenario runs, in seconds.
+    pub duration_secs: f64,
+    /// The simulation step in milliseconds.
+    #[serde(default = "default_tick_ms")]
+    pub tick_ms: u64,
+    /// The time from publishing a message to its capture, in milliseconds.
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// The first level of the MQTT topics.
+    #[serde(default = "default_interface")]
+    pub interface: String,
+    /// The major version in the MQTT topics.
+    #[serde(default = "default_topic_version")]
+    pub topic_version: String,
+    /// The seed of the random numbers of the faults.
+    #[serde(default)]
+    pub seed: u64,
+    /// The map.
+    pub map: ScenarioMap,
+    /// The AGVs. An AGV that starts on a node of the map is placed at the node.
+    pub vehicles: Vec<VehicleConfig>,
+    /// The orders, in any order.
+    #[serde(default)]
+    pub orders: Vec<ScheduledOrder>,
+    /// The instant actions, in any order.
+    #[serde(default)]
+    pub instant_actions: Vec<ScheduledInstantActions>,
+    /// The faults.
+    #[serde(default)]
+    pub faults: Vec<Fault>,
+}
+
+fn default_tick_ms() -> u64 {
+    100
+}
+
+fn default_interface() -> String {
+    "uagv".to_string()
+}
+
+fn default_topic_version() -> String {
+    "v2".to_string()
+}
+
+/// Why a scenario cannot be run.
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// A message or fault names an AGV that is not part of the scenario.
+    UnknownVehicle(String),
+    /// An AGV starts on or a route leads through a node that is not on the map.
+    UnknownNode(String),
+    /// Two consecutive nodes of a route are not connected by an edge.
+    NoEdge {
+        /// The order of the route.
+        order_id: String,
+        /// The node the missing edge starts at.
+        from: String,
+        /// The node the missing edge ends at.
+        to: String,
+    },
+    /// An order cannot be built from its route.
+    Order {
+        /// The order.
+        order_id: String,
+        /// What is wrong with it.
+        error: BuildError,
+    },
+    /// The duration or the simulation step is not positive.
+    InvalidTiming,
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::UnknownVehicle(serial_number) => {
+                write!(f, "unknown vehicle '{serial_number}'")
+            }
+            ScenarioError::UnknownNode(node_id) => write!(f, "node '{node_id}' is not on the map"),
+            ScenarioError::NoEdge { order_id, from, to } => {
+                write!(f, "order {order_id}: no edge leads from {from} to {to}")
+            }
+            ScenarioError::Order { order_id, error } => write!(f, "order {order_id}: {error}"),
+            ScenarioError::InvalidTiming => {
+                write!(f, "the duration and the simulation step must be positive")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// The outcome of a scenario run.
+#[derive(Debug, Clone, Default)]
+pub struct ScenarioRun {
+    /// Every message published by master control, the AGVs and the broker, sorted by capture
+    /// time.
+    pub entries: Vec<RecordEntry>,
+    /// The faults per AGV, in the order of the vehicles.
+    pub injections: Vec<Injection>,
+}
+
+impl ScenarioRun {
+    /// Writes the messages as a JSON Lines recording.
+    pub fn write_jsonl<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in &self.entries {
+            serde_json::to_writer(&mut writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// A message master control sends at a given time.
+struct Scheduled {
+    at_secs: f64,
+    vehicle: usize,
+    message: MasterMessage,
+}
+
+enum MasterMessage {
+    Order(Order),
+    InstantActions(Vec<Action>),
+}
+
+/// An AGV of the running scenario.
+struct Vehicle {
+    agv: SimulatedAgv,
+    faults: FaultInjector,
+    /// Creates the headers of the messages master control sends to the AGV.
+    master: HeaderFactory,
+    /// Registered once before the first connect, like the MQTT client does.
+    last_will: Connection,
+}
+
+impl Scenario {
+    /// Runs the scenario in simulated time.
+    pub fn run(&self) -> Result<ScenarioRun, ScenarioError> {
+        if !self.duration_secs.is_finite() || self.duration_secs <= 0.0 || self.tick_ms == 0 {
+            return Err(ScenarioError::InvalidTiming);
+        }
+        let start = self.start.unwrap_or_else(Utc::now);
+        let clock = Arc::new(ManualClock::new(start));
+        let broker = BrokerOptions {
+            interface: self.interface.clone(),
+            version: self.topic_version.clone(),
+            tick: Duration::from_millis(self.tick_ms),
+            ..BrokerOptions::default()
+        };
+
+        let mut vehicles = Vec::with_capacity(self.vehicles.len());
+        for (index, config) in self.vehicles.iter().enumerate() {
+            let mut config = config.clone();
+            config.map_id = self.map.map_id.clone();
+            if let Some(node_id) = &config.last_node_id {
+                let node = self
+                    .map
+                    .node(node_id)
+                    .ok_or_else(|| ScenarioError::UnknownNode(node_id.clone()))?;
+                (config.x, config.y) = (node.x, node.y);
+            }
+            let master = HeaderFactory::new(&config.manufacturer, &config.serial_number)
+                .with_version(&config.version)
+                .with_clock(clock.clone());
+            let faults = FaultInjector::new(
+                &config.serial_number,
+                &self.faults,
+                self.seed.wrapping_add(index as u64),
+            );
+            let agv = SimulatedAgv::new(config, clock.clone());
+            vehicles.push(Vehicle {
+                last_will: agv.connection(ConnectionState::ConnectionBroken),
+                agv,
+                faults,
+                master,
+            });
+        }
+        for fault in &self.faults {
+            if let Some(serial_number) = &fault.serial_number {
+                self.vehicle(serial_number)?;
+            }
+        }
+        let mut schedule = self.schedule()?;
+        schedule.sort_by(|a, b| a.at_secs.total_cmp(&b.at_secs));
+        let mut schedule = schedule.into_iter().peekable();
+
+        let latency = chrono::Duration::milliseconds(self.latency_ms as i64);
+        let mut entries = Vec::new();
+        let mut record = |now: DateTime<Utc>, message: &Message| {
+            let header = message.header();
+            entries.push(RecordEntry {
+                received_at: now + latency,
+                topic: broker.topic(&header.manufacturer, &header.serial_number, message.topic()),
+                payload: serde_json::to_value(message).unwrap_or_default(),
+            });
+        };
+
+        let steps = (self.duration_secs * 1000.0 / self.tick_ms as f64).ceil() as u64;
+        for step in 0..=steps {
+            let elapsed = (step * self.tick_ms) as f64 / 1000.0;
+            clock.set(start + chrono::Duration::milliseconds((step * self.tick_ms) as i64));
+            let now = clock.now();
+
+            for vehicle in &mut vehicles {
+                match vehicle.faults.update(now, elapsed, &mut vehicle.agv) {
+                    Some(ConnectionChange::Broken) => {
+                        // The broker publishes the last will, untouched by the faults of the AGV.
+                        record(now, &Message::Connection(vehicle.last_will.clone()));
+                    }
+                    Some(ConnectionChange::Restored) => {
+                        let online = vehicle.agv.connection(ConnectionState::Online);
+                        if let Some(message) = vehicle
+                            .faults
+                            .outgoing(elapsed, Message::Connection(online))
+                        {
+                            record(now, &message);
+                        }
+                    }
+                    None => {}
+                }
+                if step == 0 {
+                    let online = vehicle.agv.connection(ConnectionState::Online);
+                    let factsheet = vehicle.agv.factsheet();
+                    for message in [Message::Connection(online), Message::Factsheet(factsheet)] {
+                        if let Some(message) = vehicle.faults.outgoing(elapsed, message) {
+                            record(now, &message);
+                        }
+                    }
+                }
+            }
+
+            while let Some(scheduled) = schedule.next_if(|scheduled| scheduled.at_secs <= elapsed) {
+                let vehicle = &mut vehicles[scheduled.vehicle];
+                let message = match scheduled.message {
+                    MasterMessage::Order(mut order) => {
+                        order.header = vehicle.master.next(Topic::Order);
+                        Message::Order(order)
+                    }
+                    MasterMessage::InstantActions(actions) => {
+                        Message::InstantActions(InstantActions {
+                            header: vehicle.master.next(Topic::InstantActions),
+                            instant_actions: actions,
+                        })
+                    }
+                };
+                record(now, &message);
+                if vehicle.faults.is_connected() {
+                    vehicle.agv.handle(message);
+                }
+            }
+
+            for vehicle in &mut vehicles {
+                for message in vehicle.agv.poll() {
+                    if let Some(message) = vehicle.faults.outgoing(elapsed, message) {
+                        record(now, &message);
+                    }
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.received_at);
+        Ok(ScenarioRun {
+            entries,
+            injections: vehicles
+                .iter()
+                .flat_map(|vehicle| vehicle.faults.injections())
+                .collect(),
+        })
+    }
+
+    fn vehicle(&self, serial_number: &str) -> Result<usize, ScenarioError> {
+        self.vehicles
+            .iter()
+            .position(|vehicle| vehicle.serial_number == serial_number)
+            .ok_or_else(|| ScenarioError::UnknownVehicle(serial_number.to_string()))
+    }
+
+    /// Builds every order and instant action of the scenario. The headers are set when the messages
+    /// are sent, so that their header IDs follow the order of sending.
+    fn schedule(&self) -> Result<Vec<Scheduled>, ScenarioError> {
+        let mut schedule = Vec::new();
+        for scheduled in &self.orders {
+            let vehicle = self.vehicle(&scheduled.serial_number)?;
+            let order = self.build_order(scheduled)?;
+            schedule.push(Scheduled {
+                at_secs: scheduled.at_secs,
+                vehicle,
+                message: MasterMessage::Order(order),
+            });
+        }
+        for scheduled in &self.instant_actions {
+            schedule.push(Scheduled {
+                at_secs: scheduled.at_secs,
+                vehicle: self.vehicle(&scheduled.serial_number)?,
+                message: MasterMessage::InstantActions(scheduled.actions.clone()),
+            });
+        }
+        Ok(schedule)
+    }
+
+    fn build_order(&self, scheduled: &ScheduledOrder) -> Result<Order, ScenarioError> {
+        // The header is replaced when the order is sent.
+        let header = HeaderFactory::new("", "").next(Topic::Order);
+        let mut builder = OrderBuilder::new(header, &scheduled.order_id)
+            .order_update_id(scheduled.order_update_id)
+            .first_sequence_id(scheduled.first_sequence_id);
+        for (index, node_id) in scheduled.route.iter().enumerate() {
+            let node = self
+                .map
+                .node(node_id)
+                .ok_or_else(|| ScenarioError::UnknownNode(node_id.clone()))?;
+            if index > 0 {
+                let previous = &scheduled.route[index - 1];
+                let edge =
+                    self.map
+                        .edge(previous, node_id)
+                        .ok_or_else(|| ScenarioError::NoEdge {
+                            order_id: scheduled.order_id.clone(),
+                            from: previous.clone(),
+                            to: node_id.clone(),
+                        })?;
+                builder = builder.edge_with(Edge {
+                    edge_id: edge.edge_id.clone(),
+                    sequence_id: 0,
+                    released: true,
+                    start_node_id: String::new(),
+                    end_node_id: String::new(),
+                    actions: Vec::new(),
+                    // A trajectory only fits the direction it was drawn in.
+                    trajectory: edge
+                        .trajectory
+                        .clone()
+                        .filter(|_| edge.start_node_id == *previous),
+                    length: None,
+                    max_speed: edge.max_speed,
+                    max_height: None,
+                    min_height: None,
+                    orientation: None,
+                    direction: None,
+                });
+            }
+            builder = builder.node_at(
+                node_id,
+                NodePosition {
+                    x: node.x,
+                    y: node.y,
+                    map_id: self.map.map_id.clone(),
+                    theta: None,
+                    allowed_deviation_xy: None,
+                    allowed_deviation_theta: None,
+                },
+            );
+            let first_visit = !scheduled.route[..index].contains(node_id);
+            for action in scheduled
+                .actions
+                .get(node_id)
+                .into_iter()
+                .flatten()
+                .filter(|_| first_visit)
+            {
+                builder = builder.action(action.clone());
+            }
+        }
+        if let Some(released) = scheduled.released
+            && let Some(node_id) = scheduled.route.get(released)
+        {
+            builder = builder.horizon_from(node_id);
+        }
+        builder.build().map_err(|error| ScenarioError::Order {
+            order_id: scheduled.order_id.clone(),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::FaultKind;
+    use vda5050_analysis::checks::{self, Check, CheckConfig, Finding};
+    use vda5050_analysis::recording::Recording;
+
+    /// Whether a finding of the check about the AGV within the given seconds after `from` says
+    /// `text`.
+    fn reported(
+        findings: &[Finding],
+        check: Check,
+        injection: &Injection,
+        from: DateTime<Utc>,
+        within_secs: i64,
+        text: &str,
+    ) -> bool {
+        findings.iter().any(|finding| {
+            finding.check == check
+                && finding
+                    .agv
+                    .as_ref()
+                    .is_some_and(|agv| agv.serial_number == injection.serial_number)
+                && finding.time.is_some_and(|time| {
+                    time >= from && time <= from + chrono::Duration::seconds(within_secs)
+                })
+                && finding.message.contains(text)
+        })
+    }
+
+    #[test]
+    fn the_analysis_finds_the_injected_faults() {
+        let scenario: Scenario =
+            serde_json::from_str(include_str!("../scenarios/faults.json")).unwrap();
+        let run = scenario.run().unwrap();
+        let mut recording = Recording::default();
+        for (index, entry) in run.entries.iter().enumerate() {
+            recording.push_entry(entry.clone(), format!("faults.jsonl:{}", index + 1));
+        }
+        assert!(recording.failures.is_empty(), "{:?}", recording.failures);
+        let findings = checks::run_all(&recording, &CheckConfig::default());
+
+        assert_eq!(run.injections.len(), scenario.faults.len());
+        for injection in &run.injections {
+            let start = injection.start.expect("every fault of the scenario starts");
+            let found = match &injection.kind {
+                FaultKind::Drop { .. } => reported(
+                    &findings,
+                    Check::HeaderGaps,
+                    injection,
+                    injection.end.unwrap(),
+                    5,
+                    &format!("state: {} message(s) missing", injection.messages),
+                ),
+                FaultKind::HeaderJump { by, .. } => reported(
+                    &findings,
+                    Check::HeaderGaps,
+                    injection,
+                    start,
+                    5,
+                    &format!("state: {by} message(s) missing"),
+                ),
+                FaultKind::ClockSkew { .. } => reported(
+                    &findings,
+                    Check::Timing,
+                    injection,
+                    start,
+                    5,
+                    "ahead of the capture host",
+                ),
+                // No check looks at the positions.
+                FaultKind::PositionNoise { .. } => injection.messages > 0,
+                FaultKind::ConnectionBreak => {
+                    let last_will = recording.messages.iter().any(|recorded| {
+                        recorded.agv.serial_number == injection.serial_number
+                            && recorded.received_at >= start
+                            && recorded.received_at <= start + chrono::Duration::seconds(1)
+                            && matches!(
+                                &recorded.message,
+                                Message::Connection(connection)
+                                    if connection.connection_state
+                                        == ConnectionState::ConnectionBroken
+                            )
+                    });
+                    last_will
+                        && reported(
+                            &findings,
+                            Check::HeaderGaps,
+                            injection,
+                            injection.end.unwrap(),
+                            5,
+                            "state: ",
+                        )
+                }
+                FaultKind::FatalError {
+                    node_id,
+                    error_type,
+                } => reported(
+                    &findings,
+                    Check::FatalErrors,
+                    injection,
+                    start,
+                    1,
+                    &format!("fatal error {error_type} at nodeId {node_id}"),
+                ),
+            };
+            assert!(found, "{injection:?} not found in {findings:#?}");
+        }
+    }
+}
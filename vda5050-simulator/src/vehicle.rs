@@ -0,0 +1,1211 @@
+//! A simulated AGV that executes orders and instant actions as time passes.
+//!
+//! The AGV does not talk to a broker itself: messages for it are passed to
+//! [`SimulatedAgv::handle`] and [`SimulatedAgv::poll`] returns the messages it publishes. Time
+//! comes from a [`Clock`], so the same AGV runs in real time against a broker or in simulated time
+//! without one.
+use crate::config::VehicleConfig;
+use crate::geometry::{Pose, Track};
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+use std::sync::Arc;
+use vda5050_data_types::builders::StateBuilder;
+use vda5050_data_types::common::{Action, AgvPosition, BlockingType, Point, Velocity};
+use vda5050_data_types::connection::{Connection, ConnectionState};
+use vda5050_data_types::factsheet::{ActionDefinition, ActionScope, AgvKinematic, Factsheet};
+use vda5050_data_types::header_factory::{Clock, HeaderFactory};
+use vda5050_data_types::instant_actions::InstantActions;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::Order;
+use vda5050_data_types::predefined_actions::{ActionError, PredefinedAction};
+use vda5050_data_types::state::{
+    ActionState, ActionStatus, BatteryState, EStop, Error, ErrorLevel, ErrorReference, Load,
+    OperatingMode, SafetyState, State,
+};
+use vda5050_data_types::topic::Topic;
+use vda5050_data_types::visualization::Visualization;
+
+/// The heading difference in [rad] below which a differential drive does not turn on the spot.
+const HEADING_TOLERANCE: f64 = 0.05;
+
+/// Where an action was given.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    /// At the node with the sequence ID.
+    Node(u32),
+    /// On the edge with the sequence ID.
+    Edge(u32),
+    /// As an instant action.
+    Instant,
+}
+
+/// An action and how far its execution got.
+struct Execution {
+    action: Action,
+    scope: Scope,
+    status: ActionStatus,
+    remaining_secs: f64,
+    fails: bool,
+    result: Option<String>,
+}
+
+impl Execution {
+    fn is_running(&self) -> bool {
+        matches!(
+            self.status,
+            ActionStatus::Initializing | ActionStatus::Running | ActionStatus::Paused
+        )
+    }
+
+    fn blocks_driving(&self) -> bool {
+        self.action.blocking_type != BlockingType::None
+    }
+}
+
+/// What the AGV is doing to get to the next node.
+enum Motion {
+    Standing,
+    /// A differential drive turns towards the edge before driving it.
+    Turning {
+        edge: usize,
+        track: Track,
+        target: f64,
+    },
+    Driving {
+        edge: usize,
+        track: Track,
+        distance: f64,
+    },
+}
+
+/// A simulated AGV.
+pub struct SimulatedAgv {
+    config: VehicleConfig,
+    clock: Arc<dyn Clock>,
+    headers: HeaderFactory,
+    rng: StdRng,
+    last_step: DateTime<Utc>,
+    last_state: Option<DateTime<Utc>>,
+    last_visualization: Option<DateTime<Utc>>,
+    state_changed: bool,
+    factsheet_requested: bool,
+
+    map_id: String,
+    pose: Pose,
+    speed: f64,
+    omega: f64,
+    distance_since_last_node: f64,
+    paused: bool,
+    charging: bool,
+    battery_charge: f64,
+    loads: Vec<Load>,
+    errors: Vec<Error>,
+    order: Option<Order>,
+    last_node: Option<(String, u32)>,
+    motion: Motion,
+    executions: Vec<Execution>,
+}
+
+impl SimulatedAgv {
+    /// Places the AGV at the start position of the configuration.
+    pub fn new(config: VehicleConfig, clock: Arc<dyn Clock>) -> Self {
+        let headers = HeaderFactory::new(&config.manufacturer, &config.serial_number)
+            .with_version(&config.version)
+            .with_clock(clock.clone());
+        SimulatedAgv {
+            rng: StdRng::seed_from_u64(config.seed),
+            last_step: clock.now(),
+            clock,
+            headers,
+            last_state: None,
+            last_visualization: None,
+            state_changed: true,
+            factsheet_requested: false,
+            map_id: config.map_id.clone(),
+            pose: Pose {
+                x: config.x,
+                y: config.y,
+                theta: config.theta,
+            },
+            speed: 0.0,
+            omega: 0.0,
+            distance_since_last_node: 0.0,
+            paused: false,
+            charging: false,
+            battery_charge: config.battery.charge,
+            loads: Vec::new(),
+            errors: Vec::new(),
+            order: None,
+            last_node: config.last_node_id.clone().map(|node_id| (node_id, 0)),
+            motion: Motion::Standing,
+            executions: Vec::new(),
+            config,
+        }
+    }
+
+    /// The configuration of the AGV.
+    pub fn config(&self) -> &VehicleConfig {
+        &self.config
+    }
+
+    /// Where the AGV is.
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    /// The node the AGV reached last, if any.
+    pub fn last_node_id(&self) -> Option<&str> {
+        self.last_node.as_ref().map(|(node_id, _)| node_id.as_str())
+    }
+
+    /// Reports an error in the state. The AGV stands still as long as a fatal error is reported.
+    pub fn raise_error(&mut self, error: Error) {
+        self.step();
+        self.errors.push(error);
+        self.state_changed = true;
+    }
+
+    /// Removes the reported errors of the given type.
+    pub fn clear_errors(&mut self, error_type: &str) {
+        self.step();
+        self.errors.retain(|error| error.error_type != error_type);
+        self.state_changed = true;
+    }
+
+    /// Processes a message sent to the AGV. Messages of other topics are ignored.
+    pub fn handle(&mut self, message: Message) {
+        self.step();
+        match message {
+            Message::Order(order) => self.accept_order(order),
+            Message::InstantActions(instant_actions) => self.start_instant_actions(instant_actions),
+            _ => {}
+        }
+        self.state_changed = true;
+    }
+
+    /// Reports a message on the given topic that could not be decoded. The error replaces the one
+    /// of an earlier invalid message on the topic, so that the errors do not pile up.
+    pub fn reject_invalid(&mut self, topic: Topic, description: String) {
+        self.step();
+        self.errors.retain(|error| {
+            error.error_type != "validationError"
+                || !error.error_references.iter().any(|reference| {
+                    reference.reference_key == "topic" && reference.reference_value == topic.name()
+                })
+        });
+        self.errors.push(Error {
+            error_type: "validationError".to_string(),
+            error_description: Some(format!("invalid {topic} message: {description}")),
+            error_level: ErrorLevel::Warning,
+            error_references: vec![reference("topic", topic.name())],
+        });
+        self.state_changed = true;
+    }
+
+    /// Advances the simulation to the current time and returns the messages that are due: a state
+    /// on every relevant change and at least every state interval, a visualization every
+    /// visualization interval and a factsheet when it was requested.
+    pub fn poll(&mut self) -> Vec<Message> {
+        self.step();
+        let now = self.clock.now();
+        let mut messages = Vec::new();
+        let state_due = self
+            .last_state
+            .is_none_or(|last| secs(now - last) >= self.config.state_interval_secs);
+        if self.state_changed || state_due {
+            messages.push(Message::State(self.state()));
+        }
+        if let Some(interval) = self.config.visualization_interval_secs
+            && self
+                .last_visualization
+                .is_none_or(|last| secs(now - last) >= interval)
+        {
+            self.last_visualization = Some(now);
+            messages.push(Message::Visualization(self.visualization()));
+        }
+        if self.factsheet_requested {
+            self.factsheet_requested = false;
+            messages.push(Message::Factsheet(self.factsheet()));
+        }
+        messages
+    }
+
+    /// The current state.
+    pub fn state(&mut self) -> State {
+        self.last_state = Some(self.clock.now());
+        self.state_changed = false;
+        let mut builder =
+            StateBuilder::new(self.headers.next(Topic::State), OperatingMode::Automatic)
+                .position(self.position())
+                .velocity(self.velocity())
+                .driving(self.speed > 0.0 || self.omega != 0.0)
+                .paused(self.paused)
+                .battery(BatteryState {
+                    battery_charge: self.battery_charge,
+                    battery_voltage: None,
+                    battery_current: None,
+                    charging: Some(self.charging),
+                    reach: None,
+                })
+                .safety(SafetyState {
+                    e_stop: EStop::None,
+                    field_violation: false,
+                });
+        if let Some(order) = &self.order {
+            builder = builder.order(order);
+        }
+        if let Some((node_id, sequence_id)) = &self.last_node {
+            builder = builder.reached(node_id.clone(), *sequence_id);
+        }
+        for load in &self.loads {
+            builder = builder.load(load.clone());
+        }
+        for error in &self.errors {
+            builder = builder.error(error.clone());
+        }
+        for execution in &self.executions {
+            builder = builder.action_state(ActionState {
+                action_id: execution.action.action_id.clone(),
+                action_type: Some(execution.action.action_type.clone()),
+                action_description: None,
+                action_status: execution.status.clone(),
+                result_description: execution.result.clone(),
+            });
+        }
+        let mut state = builder.build();
+        state.distance_since_last_node = Some(self.distance_since_last_node);
+        state
+    }
+
+    /// The current position for visualization.
+    pub fn visualization(&self) -> Visualization {
+        let (half_length, half_width) = (
+            self.config.agv_dimensions.length / 2.0,
+            self.config.agv_dimensions.width / 2.0,
+        );
+        let outline = [
+            (half_length, half_width),
+            (-half_length, half_width),
+            (-half_length, -half_width),
+            (half_length, -half_width),
+        ]
+        .into_iter()
+        .map(|(x, y)| Point { x, y, z: None })
+        .collect();
+        Visualization {
+            header: self.headers.next(Topic::Visualization),
+            agv_position: Some(self.position()),
+            agv_velocity: Some(self.velocity()),
+            agv_outline: Some(outline),
+            visualizations: None,
+        }
+    }
+
+    /// A connection message with the given state.
+    pub fn connection(&self, connection_state: ConnectionState) -> Connection {
+        let header = self.headers.next(Topic::Connection);
+        Connection {
+            last_state_change: header.timestamp.clone(),
+            header,
+            connection_state,
+        }
+    }
+
+    /// The factsheet, listing the predefined actions and all configured action types.
+    pub fn factsheet(&self) -> Factsheet {
+        let mut actions: Vec<ActionDefinition> = [
+            ("startPause", ActionScope::Instant),
+            ("stopPause", ActionScope::Instant),
+            ("startCharging", ActionScope::Instant),
+            ("stopCharging", ActionScope::Instant),
+            ("initPosition", ActionScope::Instant),
+            ("stateRequest", ActionScope::Instant),
+            ("factsheetRequest", ActionScope::Instant),
+            ("cancelOrder", ActionScope::Instant),
+        ]
+        .into_iter()
+        .map(|(action_type, scope)| ActionDefinition {
+            action_type: action_type.to_string(),
+            action_description: format!("Predefined action {action_type}."),
+            action_scopes: vec![scope],
+            result_description: None,
+        })
+        .collect();
+        for action_type in self.config.actions.keys() {
+            if actions
+                .iter()
+                .all(|action| &action.action_type != action_type)
+            {
+                actions.push(ActionDefinition {
+                    action_type: action_type.clone(),
+                    action_description: format!("Simulated action {action_type}."),
+                    action_scopes: vec![ActionScope::Node, ActionScope::Edge, ActionScope::Instant],
+                    result_description: None,
+                });
+            }
+        }
+        Factsheet {
+            header: self.headers.next(Topic::Factsheet),
+            type_field: self.config.type_field.clone(),
+            type_version: env!("CARGO_PKG_VERSION").to_string(),
+            agv_kinematic: self.config.agv_kinematic.clone(),
+            max_load: None,
+            load_dimensions: None,
+            agv_dimensions: self.config.agv_dimensions.clone(),
+            physical_parameters: Some(self.config.physical_parameters.clone()),
+            actions,
+        }
+    }
+
+    fn position(&self) -> AgvPosition {
+        AgvPosition {
+            x: self.pose.x,
+            y: self.pose.y,
+            theta: Some(self.pose.theta),
+            map_id: self.map_id.clone(),
+            position_initialized: Some(true),
+            localization_score: None,
+            deviation_range: None,
+        }
+    }
+
+    /// The velocity in the vehicle coordinate system.
+    fn velocity(&self) -> Velocity {
+        let heading = match &self.motion {
+            Motion::Driving {
+                track, distance, ..
+            } => track.pose_at(*distance).theta,
+            _ => self.pose.theta,
+        };
+        let relative = heading - self.pose.theta;
+        Velocity {
+            vx: Some(self.speed * relative.cos()),
+            vy: Some(self.speed * relative.sin()),
+            omega: Some(self.omega),
+        }
+    }
+
+    /// Moves the simulation forward to the current time.
+    fn step(&mut self) {
+        let now = self.clock.now();
+        let dt = secs(now - self.last_step).max(0.0);
+        self.last_step = now;
+        if dt == 0.0 {
+            return;
+        }
+        self.advance_executions(dt);
+        self.start_node_actions();
+        let driven = self.advance_motion(dt);
+
+        let battery = &self.config.battery;
+        if self.charging {
+            self.battery_charge += battery.charge_per_hour * dt / 3600.0;
+        } else {
+            self.battery_charge -= battery.discharge_per_meter * driven
+                + battery.idle_discharge_per_hour * dt / 3600.0;
+        }
+        self.battery_charge = self.battery_charge.clamp(0.0, 100.0);
+    }
+
+    fn accept_order(&mut self, order: Order) {
+        let reject = |agv: &mut SimulatedAgv, error_type: &str, description: String| {
+            agv.errors.push(Error {
+                error_type: error_type.to_string(),
+                error_description: Some(description),
+                error_level: ErrorLevel::Warning,
+                error_references: vec![
+                    reference("orderId", &order.order_id),
+                    reference("orderUpdateId", &order.order_update_id.to_string()),
+                ],
+            });
+        };
+        if order.nodes.is_empty() || order.nodes.len() != order.edges.len() + 1 {
+            return reject(
+                self,
+                "validationError",
+                "an order needs one more node than edges".to_string(),
+            );
+        }
+        if let Some(node) = order.nodes.iter().find(|node| node.node_position.is_none()) {
+            return reject(
+                self,
+                "noRouteError",
+                format!("node {} has no position", node.node_id),
+            );
+        }
+
+        let Some(current) = &self.order else {
+            return self.start_order(order);
+        };
+        if current.order_id != order.order_id {
+            if self.is_order_active() {
+                let description = format!("order {} is still active", current.order_id);
+                return reject(self, "orderUpdateError", description);
+            }
+            return self.start_order(order);
+        }
+        if order.order_update_id < current.order_update_id {
+            let description = format!(
+                "order update {} is older than the current update {}",
+                order.order_update_id, current.order_update_id
+            );
+            return reject(self, "orderUpdateError", description);
+        }
+        if order.order_update_id == current.order_update_id {
+            return;
+        }
+
+        // An update continues from the last released node of the current order.
+        let stitch = current
+            .nodes
+            .iter()
+            .filter(|node| node.released)
+            .max_by_key(|node| node.sequence_id);
+        let first = &order.nodes[0];
+        if stitch.is_none_or(|stitch| {
+            stitch.node_id != first.node_id || stitch.sequence_id != first.sequence_id
+        }) {
+            let description = format!(
+                "the update does not start at the last released node of order {}",
+                current.order_id
+            );
+            return reject(self, "orderUpdateError", description);
+        }
+        let stitch_sequence_id = first.sequence_id;
+        let mut merged = self.order.take().unwrap_or_else(|| order.clone());
+        merged.header = order.header;
+        merged.order_update_id = order.order_update_id;
+        merged
+            .nodes
+            .retain(|node| node.sequence_id <= stitch_sequence_id);
+        merged
+            .edges
+            .retain(|edge| edge.sequence_id < stitch_sequence_id);
+        for node in order.nodes.into_iter().skip(1) {
+            self.queue_actions(&node.actions, Scope::Node(node.sequence_id));
+            merged.nodes.push(node);
+        }
+        for edge in order.edges {
+            self.queue_actions(&edge.actions, Scope::Edge(edge.sequence_id));
+            merged.edges.push(edge);
+        }
+        self.order = Some(merged);
+        self.clear_warnings();
+    }
+
+    fn start_order(&mut self, order: Order) {
+        let first = &order.nodes[0];
+        if let Some(position) = &first.node_position {
+            let allowed = position
+                .allowed_deviation_xy
+                .unwrap_or(self.config.max_start_deviation)
+                .max(self.config.max_start_deviation);
+            let deviation = (position.x - self.pose.x).hypot(position.y - self.pose.y);
+            if position.map_id != self.map_id || deviation > allowed {
+                self.errors.push(Error {
+                    error_type: "noRouteError".to_string(),
+                    error_description: Some(format!(
+                        "the AGV is {deviation:.2} m away from the first node {}",
+                        first.node_id
+                    )),
+                    error_level: ErrorLevel::Warning,
+                    error_references: vec![
+                        reference("orderId", &order.order_id),
+                        reference("orderUpdateId", &order.order_update_id.to_string()),
+                        reference("nodeId", &first.node_id),
+                    ],
+                });
+                return;
+            }
+        }
+        self.clear_warnings();
+        self.executions
+            .retain(|execution| execution.scope == Scope::Instant && !execution.status.is_final());
+        for node in &order.nodes {
+            self.queue_actions(&node.actions, Scope::Node(node.sequence_id));
+        }
+        for edge in &order.edges {
+            self.queue_actions(&edge.actions, Scope::Edge(edge.sequence_id));
+        }
+        self.last_node = Some((first.node_id.clone(), first.sequence_id));
+        self.distance_since_last_node = 0.0;
+        self.motion = Motion::Standing;
+        self.order = Some(order);
+    }
+
+    /// Removes the errors of earlier orders. Fatal errors stay until they are cleared.
+    fn clear_warnings(&mut self) {
+        self.errors
+            .retain(|error| error.error_level == ErrorLevel::Fatal);
+    }
+
+    fn queue_actions(&mut self, actions: &[Action], scope: Scope) {
+        for action in actions {
+            let behavior = self.config.action(&action.action_type).clone();
+            self.executions.push(Execution {
+                action: action.clone(),
+                scope,
+                status: ActionStatus::Waiting,
+                remaining_secs: behavior.duration_secs,
+                fails: self.rng.random_bool(behavior.failure_rate.clamp(0.0, 1.0)),
+                result: None,
+            });
+        }
+    }
+
+    /// Whether the AGV still has nodes to drive to or order actions to finish.
+    fn is_order_active(&self) -> bool {
+        let Some(order) = &self.order else {
+            return false;
+        };
+        let last_sequence_id = self.last_node.as_ref().map_or(0, |(_, id)| *id);
+        order
+            .nodes
+            .iter()
+            .any(|node| node.sequence_id > last_sequence_id)
+            || self
+                .executions
+                .iter()
+                .any(|execution| execution.scope != Scope::Instant && !execution.status.is_final())
+    }
+
+    fn start_instant_actions(&mut self, instant_actions: InstantActions) {
+        for action in instant_actions.instant_actions {
+            let behavior = self.config.action(&action.action_type).clone();
+            let mut execution = Execution {
+                scope: Scope::Instant,
+                status: ActionStatus::Running,
+                remaining_secs: behavior.duration_secs,
+                fails: self.rng.random_bool(behavior.failure_rate.clamp(0.0, 1.0)),
+                result: None,
+                action,
+            };
+            match PredefinedAction::try_from(&execution.action) {
+                Ok(predefined) => self.run_predefined(&mut execution, predefined),
+                Err(ActionError::UnknownActionType(_)) => {}
+                Err(err) => {
+                    execution.status = ActionStatus::Failed;
+                    execution.result = Some(err.to_string());
+                }
+            }
+            self.executions.push(execution);
+        }
+    }
+
+    /// Executes the predefined instant actions that take effect at once.
+    fn run_predefined(&mut self, execution: &mut Execution, predefined: PredefinedAction) {
+        match predefined {
+            PredefinedAction::StartPause => {
+                self.paused = true;
+                self.speed = 0.0;
+                self.omega = 0.0;
+                finish(execution, ActionStatus::Finished);
+            }
+            PredefinedAction::StopPause => {
+                self.paused = false;
+                finish(execution, ActionStatus::Finished);
+            }
+            PredefinedAction::StartCharging => {
+                if self.speed > 0.0 {
+                    execution.result = Some("the AGV is driving".to_string());
+                    finish(execution, ActionStatus::Failed);
+                } else {
+                    self.charging = true;
+                    finish(execution, ActionStatus::Finished);
+                }
+            }
+            PredefinedAction::StopCharging => {
+                self.charging = false;
+                finish(execution, ActionStatus::Finished);
+            }
+            PredefinedAction::InitPosition(init) => {
+                self.map_id = init.map_id;
+                self.pose = Pose {
+                    x: init.x,
+                    y: init.y,
+                    theta: init.theta,
+                };
+                self.last_node = Some((init.last_node_id, 0));
+                finish(execution, ActionStatus::Finished);
+            }
+            PredefinedAction::StateRequest => finish(execution, ActionStatus::Finished),
+            PredefinedAction::FactsheetRequest => {
+                self.factsheet_requested = true;
+                finish(execution, ActionStatus::Finished);
+            }
+            PredefinedAction::CancelOrder => {
+                if !self.is_order_active() {
+                    self.errors.push(Error {
+                        error_type: "noOrderToCancel".to_string(),
+                        error_description: Some("there is no active order".to_string()),
+                        error_level: ErrorLevel::Warning,
+                        error_references: vec![reference("actionId", &execution.action.action_id)],
+                    });
+                    finish(execution, ActionStatus::Failed);
+                    return;
+                }
+                self.cancel_order();
+                finish(execution, ActionStatus::Finished);
+            }
+            // Load handling and the remaining actions take their configured time.
+            _ => {}
+        }
+    }
+
+    /// Stops the AGV and fails every action of the order that did not finish.
+    fn cancel_order(&mut self) {
+        self.speed = 0.0;
+        self.omega = 0.0;
+        self.motion = Motion::Standing;
+        for execution in &mut self.executions {
+            if execution.scope != Scope::Instant && !execution.status.is_final() {
+                execution.status = ActionStatus::Failed;
+                execution.result = Some("order cancelled".to_string());
+            }
+        }
+        // The AGV stays where it is, the nodes it did not reach are gone.
+        if let (Some(order), Some((_, last_sequence_id))) = (&mut self.order, &self.last_node) {
+            order
+                .nodes
+                .retain(|node| node.sequence_id <= *last_sequence_id);
+            order
+                .edges
+                .retain(|edge| edge.sequence_id < *last_sequence_id);
+        }
+    }
+
+    fn advance_executions(&mut self, dt: f64) {
+        if self.paused {
+            return;
+        }
+        let mut finished_loads: Vec<(bool, Option<String>, Option<String>)> = Vec::new();
+        for execution in &mut self.executions {
+            if !execution.is_running() {
+                continue;
+            }
+            execution.remaining_secs -= dt;
+            if execution.remaining_secs > 0.0 {
+                continue;
+            }
+            if execution.fails {
+                execution.status = ActionStatus::Failed;
+                execution.result = Some("simulated failure".to_string());
+            } else {
+                execution.status = ActionStatus::Finished;
+                if let Ok(predefined) = PredefinedAction::try_from(&execution.action) {
+                    match predefined {
+                        PredefinedAction::Pick(load) => {
+                            finished_loads.push((true, load.load_id, Some(load.load_type)))
+                        }
+                        PredefinedAction::Drop(load) => {
+                            finished_loads.push((false, load.load_id, Some(load.load_type)))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            self.state_changed = true;
+        }
+        for (picked, load_id, load_type) in finished_loads {
+            if picked {
+                self.loads.push(Load {
+                    load_id,
+                    load_type,
+                    load_position: None,
+                    weight: None,
+                    bounding_box_reference: None,
+                    bounding_box: None,
+                });
+            } else {
+                self.loads.clear();
+            }
+        }
+    }
+
+    /// Starts the waiting actions of the node the AGV stands on: actions without blocking at once,
+    /// soft blocking ones unless a hard blocking action runs, and a hard blocking one only when
+    /// nothing else runs.
+    fn start_node_actions(&mut self) {
+        if self.paused || !matches!(self.motion, Motion::Standing) {
+            return;
+        }
+        let Some((_, sequence_id)) = &self.last_node else {
+            return;
+        };
+        let scope = Scope::Node(*sequence_id);
+        for index in 0..self.executions.len() {
+            let execution = &self.executions[index];
+            if execution.scope != scope || execution.status != ActionStatus::Waiting {
+                continue;
+            }
+            let hard_running = self.executions.iter().any(|other| {
+                other.is_running() && other.action.blocking_type == BlockingType::Hard
+            });
+            if hard_running {
+                return;
+            }
+            if execution.action.blocking_type == BlockingType::Hard
+                && self.executions.iter().any(Execution::is_running)
+            {
+                return;
+            }
+            self.executions[index].status = ActionStatus::Running;
+            self.state_changed = true;
+        }
+    }
+
+    /// Moves the AGV along its order and returns the distance driven in [m].
+    fn advance_motion(&mut self, dt: f64) -> f64 {
+        if self.paused {
+            return 0.0;
+        }
+        if self
+            .errors
+            .iter()
+            .any(|error| error.error_level == ErrorLevel::Fatal)
+        {
+            // The AGV halts where it is and continues from there once the error is gone.
+            self.speed = 0.0;
+            self.omega = 0.0;
+            return 0.0;
+        }
+        match std::mem::replace(&mut self.motion, Motion::Standing) {
+            Motion::Standing => {
+                if let Some((edge, track)) = self.next_edge() {
+                    self.start_edge(edge, track);
+                }
+                0.0
+            }
+            Motion::Turning {
+                edge,
+                track,
+                target,
+            } => {
+                let angular_speed = self
+                    .config
+                    .physical_parameters
+                    .angular_speed_max
+                    .unwrap_or(1.0);
+                let difference = normalize(target - self.pose.theta);
+                let turn = angular_speed * dt;
+                if difference.abs() <= turn {
+                    self.pose.theta = target;
+                    self.omega = 0.0;
+                    self.motion = Motion::Driving {
+                        edge,
+                        track,
+                        distance: 0.0,
+                    };
+                } else {
+                    self.pose.theta = normalize(self.pose.theta + turn * difference.signum());
+                    self.omega = angular_speed * difference.signum();
+                    self.motion = Motion::Turning {
+                        edge,
+                        track,
+                        target,
+                    };
+                }
+                0.0
+            }
+            Motion::Driving {
+                edge,
+                track,
+                distance,
+            } => self.drive(edge, track, distance, dt),
+        }
+    }
+
+    /// The next edge to drive and its track, if the AGV may drive it now.
+    fn next_edge(&self) -> Option<(usize, Track)> {
+        let order = self.order.as_ref()?;
+        let (_, last_sequence_id) = self.last_node.as_ref()?;
+        if self.charging
+            || self.executions.iter().any(|execution| {
+                execution.blocks_driving()
+                    && (execution.is_running()
+                        || (execution.scope == Scope::Node(*last_sequence_id)
+                            && execution.status == ActionStatus::Waiting))
+            })
+        {
+            return None;
+        }
+        let index = order
+            .edges
+            .iter()
+            .position(|edge| edge.sequence_id == last_sequence_id + 1 && edge.released)?;
+        let edge = &order.edges[index];
+        let end = order
+            .nodes
+            .iter()
+            .find(|node| node.sequence_id == edge.sequence_id + 1 && node.released)?;
+        let end = end.node_position.as_ref()?;
+        let track = edge
+            .trajectory
+            .as_ref()
+            .and_then(Track::of_trajectory)
+            .unwrap_or_else(|| Track::straight((self.pose.x, self.pose.y), (end.x, end.y)));
+        Some((index, track))
+    }
+
+    fn start_edge(&mut self, edge: usize, track: Track) {
+        if let Some(order) = &self.order {
+            let scope = Scope::Edge(order.edges[edge].sequence_id);
+            for execution in &mut self.executions {
+                if execution.scope == scope && execution.status == ActionStatus::Waiting {
+                    execution.status = ActionStatus::Running;
+                }
+            }
+        }
+        self.state_changed = true;
+        let heading = track.pose_at(0.0).theta;
+        let needs_turn = self.config.agv_kinematic == AgvKinematic::Differential
+            && normalize(heading - self.pose.theta).abs() > HEADING_TOLERANCE
+            && track.length() > 0.0;
+        self.motion = if needs_turn {
+            Motion::Turning {
+                edge,
+                track,
+                target: heading,
+            }
+        } else {
+            Motion::Driving {
+                edge,
+                track,
+                distance: 0.0,
+            }
+        };
+    }
+
+    fn drive(&mut self, edge: usize, track: Track, distance: f64, dt: f64) -> f64 {
+        let Some(order) = &self.order else {
+            return 0.0;
+        };
+        let limits = &self.config.physical_parameters;
+        let max_speed = order.edges[edge]
+            .max_speed
+            .map_or(limits.speed_max, |max_speed| {
+                max_speed.min(limits.speed_max)
+            });
+        let end_sequence_id = order.edges[edge].sequence_id + 1;
+        // The AGV only rolls over nodes that neither end the released path nor hold it back.
+        let must_stop = !order
+            .edges
+            .iter()
+            .any(|next| next.sequence_id == end_sequence_id + 1 && next.released)
+            || self.executions.iter().any(|execution| {
+                execution.scope == Scope::Node(end_sequence_id) && execution.blocks_driving()
+            });
+
+        let remaining = track.length() - distance;
+        let braking_distance = self.speed * self.speed / (2.0 * limits.deceleration_max);
+        self.speed = if must_stop && remaining <= braking_distance {
+            (self.speed - limits.deceleration_max * dt).max(limits.speed_min)
+        } else if self.speed > max_speed {
+            (self.speed - limits.deceleration_max * dt).max(max_speed)
+        } else {
+            (self.speed + limits.acceleration_max * dt).min(max_speed)
+        };
+        let step = (self.speed * dt).min(remaining);
+        let distance = distance + step;
+        self.distance_since_last_node += step;
+        let pose = track.pose_at(distance);
+        self.pose.x = pose.x;
+        self.pose.y = pose.y;
+        if self.config.agv_kinematic != AgvKinematic::Omnidrive {
+            self.pose.theta = pose.theta;
+        }
+
+        if distance < track.length() {
+            self.motion = Motion::Driving {
+                edge,
+                track,
+                distance,
+            };
+            return step;
+        }
+        let edge_sequence_id = order.edges[edge].sequence_id;
+        if let Some(node) = order
+            .nodes
+            .iter()
+            .find(|node| node.sequence_id == end_sequence_id)
+        {
+            self.last_node = Some((node.node_id.clone(), node.sequence_id));
+        }
+        for execution in &mut self.executions {
+            if execution.scope == Scope::Edge(edge_sequence_id) && execution.is_running() {
+                execution.status = ActionStatus::Finished;
+            }
+        }
+        (self.pose.x, self.pose.y) = track.end();
+        self.distance_since_last_node = 0.0;
+        if must_stop {
+            self.speed = 0.0;
+        }
+        self.state_changed = true;
+        step
+    }
+}
+
+fn finish(execution: &mut Execution, status: ActionStatus) {
+    execution.status = status;
+    execution.remaining_secs = 0.0;
+}
+
+fn reference(key: &str, value: &str) -> ErrorReference {
+    ErrorReference {
+        reference_key: key.to_string(),
+        reference_value: value.to_string(),
+    }
+}
+
+/// An angle in (-π, π].
+fn normalize(angle: f64) -> f64 {
+    let angle = angle.rem_euclid(2.0 * PI);
+    if angle > PI { angle - 2.0 * PI } else { angle }
+}
+
+fn secs(duration: chrono::Duration) -> f64 {
+    duration.num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ActionBehavior;
+    use vda5050_data_types::builders::{InstantActionsBuilder, OrderBuilder};
+    use vda5050_data_types::common::NodePosition;
+    use vda5050_data_types::header_factory::ManualClock;
+
+    struct Test {
+        agv: SimulatedAgv,
+        clock: Arc<ManualClock>,
+        master: HeaderFactory,
+    }
+
+    impl Test {
+        /// An AGV at (0, 0) with a `wait` action that takes two seconds.
+        fn new() -> Self {
+            let clock = Arc::new(ManualClock::new("2024-01-01T00:00:00Z".parse().unwrap()));
+            let mut config = VehicleConfig::default();
+            config.actions.insert(
+                "wait".to_string(),
+                ActionBehavior {
+                    duration_secs: 2.0,
+                    failure_rate: 0.0,
+                },
+            );
+            Test {
+                agv: SimulatedAgv::new(config, clock.clone()),
+                master: HeaderFactory::new("Simulator", "sim-1").with_clock(clock.clone()),
+                clock,
+            }
+        }
+
+        /// An order through nodes on the x axis, starting at the given sequence ID.
+        fn order(&self, order_id: &str, order_update_id: u32, nodes: &[(&str, f64)]) -> Order {
+            let first_sequence_id = if order_update_id == 0 { 0 } else { 2 };
+            self.order_from(order_id, order_update_id, first_sequence_id, nodes, None)
+        }
+
+        fn order_from(
+            &self,
+            order_id: &str,
+            order_update_id: u32,
+            first_sequence_id: u32,
+            nodes: &[(&str, f64)],
+            action_at_first: Option<Action>,
+        ) -> Order {
+            let mut builder = OrderBuilder::new(self.master.next(Topic::Order), order_id)
+                .order_update_id(order_update_id)
+                .first_sequence_id(first_sequence_id);
+            for (index, (node_id, x)) in nodes.iter().enumerate() {
+                if index > 0 {
+                    builder = builder.edge(format!("e{index}"));
+                }
+                builder = builder.node_at(
+                    *node_id,
+                    NodePosition {
+                        x: *x,
+                        y: 0.0,
+                        map_id: "map".to_string(),
+                        theta: None,
+                        allowed_deviation_xy: None,
+                        allowed_deviation_theta: None,
+                    },
+                );
+                if index == 0
+                    && let Some(action) = &action_at_first
+                {
+                    builder = builder.action(action.clone());
+                }
+            }
+            builder.build().unwrap()
+        }
+
+        /// Lets the given seconds pass in steps of 100 ms and returns the last state.
+        fn run(&mut self, secs: u32) -> State {
+            for _ in 0..secs * 10 {
+                self.clock.advance(chrono::Duration::milliseconds(100));
+                self.agv.poll();
+            }
+            self.agv.state()
+        }
+    }
+
+    fn action(action_id: &str, action_type: &str) -> Action {
+        Action {
+            action_id: action_id.to_string(),
+            action_type: action_type.to_string(),
+            blocking_type: BlockingType::Hard,
+            action_parameters: None,
+        }
+    }
+
+    fn error_types(state: &State) -> Vec<&str> {
+        state
+            .errors
+            .iter()
+            .map(|error| error.error_type.as_str())
+            .collect()
+    }
+
+    fn status(state: &State, action_id: &str) -> ActionStatus {
+        state
+            .action_states
+            .iter()
+            .find(|action_state| action_state.action_id == action_id)
+            .map(|action_state| action_state.action_status.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn accepts_and_drives_an_order() {
+        let mut test = Test::new();
+        let order = test.order("o1", 0, &[("A", 0.0), ("B", 2.0)]);
+        test.agv.handle(Message::Order(order));
+
+        let state = test.run(10);
+        assert_eq!(error_types(&state), Vec::<&str>::new());
+        assert_eq!(state.order_id.as_deref(), Some("o1"));
+        assert_eq!(state.last_node_id.as_deref(), Some("B"));
+        assert_eq!(state.last_node_sequence_id, Some(2));
+        assert!(state.node_states.is_empty());
+        assert!((test.agv.pose().x - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_orders_it_cannot_take() {
+        let mut test = Test::new();
+        let away = test.order("o1", 0, &[("A", 5.0), ("B", 6.0)]);
+        test.agv.handle(Message::Order(away));
+        assert_eq!(error_types(&test.agv.state()), ["noRouteError"]);
+
+        let order = test.order("o2", 0, &[("A", 0.0), ("B", 10.0)]);
+        test.agv.handle(Message::Order(order));
+        test.run(1);
+        let other = test.order("o3", 0, &[("A", 0.0), ("B", 10.0)]);
+        test.agv.handle(Message::Order(other));
+        let state = test.agv.state();
+        assert_eq!(error_types(&state), ["orderUpdateError"]);
+        assert_eq!(state.order_id.as_deref(), Some("o2"));
+    }
+
+    #[test]
+    fn stitches_updates_to_the_last_released_node() {
+        let mut test = Test::new();
+        let base = test.order("o1", 0, &[("A", 0.0), ("B", 2.0)]);
+        test.agv.handle(Message::Order(base));
+        test.run(1);
+
+        let detached = test.order_from("o1", 1, 0, &[("A", 0.0), ("C", 4.0)], None);
+        test.agv.handle(Message::Order(detached));
+        assert_eq!(error_types(&test.agv.state()), ["orderUpdateError"]);
+
+        let update = test.order("o1", 1, &[("B", 2.0), ("C", 4.0)]);
+        test.agv.handle(Message::Order(update));
+        let state = test.run(15);
+        assert_eq!(error_types(&state), Vec::<&str>::new());
+        assert_eq!(state.order_update_id, Some(1));
+        assert_eq!(state.last_node_id.as_deref(), Some("C"));
+        assert_eq!(state.last_node_sequence_id, Some(4));
+    }
+
+    #[test]
+    fn runs_node_actions_before_driving_on() {
+        let mut test = Test::new();
+        let order = test.order_from(
+            "o1",
+            0,
+            0,
+            &[("A", 0.0), ("B", 2.0)],
+            Some(action("wait-1", "wait")),
+        );
+        test.agv.handle(Message::Order(order));
+        assert_eq!(status(&test.agv.state(), "wait-1"), ActionStatus::Waiting);
+
+        let state = test.run(1);
+        assert_eq!(status(&state, "wait-1"), ActionStatus::Running);
+        assert!(!state.driving);
+
+        let state = test.run(2);
+        assert_eq!(status(&state, "wait-1"), ActionStatus::Finished);
+        assert_eq!(test.run(10).last_node_id.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn cancels_the_order_where_the_agv_is() {
+        let mut test = Test::new();
+        let order = test.order("o1", 0, &[("A", 0.0), ("B", 20.0)]);
+        test.agv.handle(Message::Order(order));
+        test.run(3);
+
+        let cancel = InstantActionsBuilder::new(test.master.next(Topic::InstantActions))
+            .predefined(PredefinedAction::CancelOrder, BlockingType::Hard)
+            .build();
+        let cancel_id = cancel.instant_actions[0].action_id.clone();
+        test.agv.handle(Message::InstantActions(cancel));
+        let x = test.agv.pose().x;
+        let state = test.run(2);
+        assert_eq!(status(&state, &cancel_id), ActionStatus::Finished);
+        assert!(!state.driving);
+        assert!(state.node_states.is_empty() && state.edge_states.is_empty());
+        assert_eq!(test.agv.pose().x, x);
+
+        // There is nothing left to cancel.
+        let again = InstantActionsBuilder::new(test.master.next(Topic::InstantActions))
+            .predefined(PredefinedAction::CancelOrder, BlockingType::Hard)
+            .build();
+        let again_id = again.instant_actions[0].action_id.clone();
+        test.agv.handle(Message::InstantActions(again));
+        let state = test.agv.state();
+        assert_eq!(status(&state, &again_id), ActionStatus::Failed);
+        assert_eq!(error_types(&state), ["noOrderToCancel"]);
+    }
+
+    #[test]
+    fn reports_only_the_last_invalid_message_per_topic() {
+        let mut test = Test::new();
+        test.agv
+            .reject_invalid(Topic::Order, "missing field `orderId`".to_string());
+        test.agv
+            .reject_invalid(Topic::Order, "missing field `nodes`".to_string());
+        test.agv
+            .reject_invalid(Topic::InstantActions, "expected a list".to_string());
+
+        let state = test.run(1);
+        let descriptions: Vec<&str> = state
+            .errors
+            .iter()
+            .filter_map(|error| error.error_description.as_deref())
+            .collect();
+        assert_eq!(
+            descriptions,
+            [
+                "invalid order message: missing field `nodes`",
+                "invalid instantActions message: expected a list"
+            ]
+        );
+    }
+}
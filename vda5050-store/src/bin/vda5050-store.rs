@@ -0,0 +1,193 @@
+//! Imports recordings into a message store, applies its retention and exports query results as
+//! recordings, e.g. to analyze a time range with `vda5050-analyze`.
+use clap::{Parser, ValueEnum};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use vda5050_analysis::recording::{AgvId, Recording};
+use vda5050_data_types::topic::Topic;
+use vda5050_store::Store;
+use vda5050_store::config::{StoreConfig, VacuumMode};
+use vda5050_store::query::{MessageQuery, StoredMessage};
+
+#[derive(Parser)]
+#[command(name = "vda5050-store", version, about)]
+struct Args {
+    /// The database file, created if it does not exist.
+    db: PathBuf,
+    /// Recording files in JSON Lines format, or pcap and pcapng captures of MQTT on port 1883, to
+    /// import.
+    #[arg(long, num_args = 1..)]
+    import: Vec<PathBuf>,
+    /// Delete messages received more than this many hours ago.
+    #[arg(long)]
+    max_age_hours: Option<u64>,
+    /// Delete the earliest stored messages beyond this many.
+    #[arg(long)]
+    max_messages: Option<u64>,
+    /// What happens to the space of deleted messages.
+    #[arg(long, value_enum, default_value_t = Vacuum::Incremental)]
+    vacuum: Vacuum,
+    /// Print the AGVs with their number of stored messages.
+    #[arg(long)]
+    agvs: bool,
+    /// Write the messages selected by the filters below as a JSON Lines recording.
+    #[arg(long)]
+    query: bool,
+    /// Only messages of this AGV, as `manufacturer/serialNumber`.
+    #[arg(long, requires = "query", value_parser = parse_agv)]
+    agv: Option<AgvId>,
+    /// Only messages on these topics, e.g. `state,order`.
+    #[arg(long, requires = "query", value_delimiter = ',')]
+    topic: Vec<Topic>,
+    /// Only messages received at or after this time (RFC 3339).
+    #[arg(long, requires = "query")]
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only messages received before this time (RFC 3339).
+    #[arg(long, requires = "query")]
+    to: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only orders and states with this order ID.
+    #[arg(long, requires = "query")]
+    order_id: Option<String>,
+    /// Only states reporting an error of this type.
+    #[arg(long, requires = "query")]
+    error_type: Option<String>,
+    /// The most messages to write.
+    #[arg(long, requires = "query")]
+    limit: Option<usize>,
+    /// Write the latest messages instead of the earliest when the limit cuts the result.
+    #[arg(long, requires = "limit")]
+    latest: bool,
+    /// Write the recording to a file instead of stdout.
+    #[arg(short, long, requires = "query")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Vacuum {
+    Never,
+    Incremental,
+    Full,
+}
+
+fn parse_agv(agv: &str) -> Result<AgvId, String> {
+    let (manufacturer, serial_number) = agv
+        .split_once('/')
+        .ok_or_else(|| format!("expected manufacturer/serialNumber, got '{agv}'"))?;
+    Ok(AgvId {
+        manufacturer: manufacturer.to_string(),
+        serial_number: serial_number.to_string(),
+    })
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let config = StoreConfig {
+        max_age_secs: args.max_age_hours.map(|hours| hours.saturating_mul(3600)),
+        max_messages: args.max_messages,
+        vacuum: match args.vacuum {
+            Vacuum::Never => VacuumMode::Never,
+            Vacuum::Incremental => VacuumMode::Incremental,
+            Vacuum::Full => VacuumMode::Full,
+        },
+    };
+    let mut store = match Store::open(&args.db, config) {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("error: failed to open {}: {err}", args.db.display());
+            return ExitCode::from(2);
+        }
+    };
+
+    for path in &args.import {
+        let mut recording = Recording::default();
+        if let Err(err) = recording.read_file(path) {
+            eprintln!("error: failed to read {}: {err}", path.display());
+            return ExitCode::from(2);
+        }
+        match store.insert_recording(&recording) {
+            Ok(summary) => eprintln!(
+                "{}: stored {} messages, skipped {} undecodable entries",
+                path.display(),
+                summary.stored,
+                summary.skipped
+            ),
+            Err(err) => {
+                eprintln!("error: failed to import {}: {err}", path.display());
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    if args.max_age_hours.is_some() || args.max_messages.is_some() {
+        match store.apply_retention() {
+            Ok(deleted) => eprintln!("retention deleted {deleted} messages"),
+            Err(err) => {
+                eprintln!("error: failed to apply the retention: {err}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    if args.agvs {
+        match store.agvs() {
+            Ok(agvs) => {
+                for agv in agvs {
+                    println!(
+                        "{} {} messages from {} to {}",
+                        agv.agv,
+                        agv.messages,
+                        agv.first_received_at.to_rfc3339(),
+                        agv.last_received_at.to_rfc3339()
+                    );
+                }
+            }
+            Err(err) => {
+                eprintln!("error: failed to list the AGVs: {err}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    if args.query {
+        let query = MessageQuery {
+            agv: args.agv,
+            topics: args.topic,
+            from: args.from,
+            to: args.to,
+            order_id: args.order_id,
+            error_type: args.error_type,
+            after_sequence: None,
+            by_sequence: false,
+            latest: args.latest,
+            limit: args.limit,
+        };
+        let messages = match store.query(&query) {
+            Ok(messages) => messages,
+            Err(err) => {
+                eprintln!("error: query failed: {err}");
+                return ExitCode::from(2);
+            }
+        };
+        let written = match &args.output {
+            Some(path) => {
+                File::create(path).and_then(|file| write_entries(BufWriter::new(file), &messages))
+            }
+            None => write_entries(io::stdout().lock(), &messages),
+        };
+        if let Err(err) = written {
+            eprintln!("error: failed to write the messages: {err}");
+            return ExitCode::from(2);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn write_entries<W: Write>(mut writer: W, messages: &[StoredMessage]) -> io::Result<()> {
+    for message in messages {
+        serde_json::to_writer(&mut writer, &message.to_entry())?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
@@ -0,0 +1,32 @@
+//! Which messages the store keeps and how it gives back space.
+use serde::{Deserialize, Serialize};
+
+/// How the store gives back the space of deleted messages to the file system.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum VacuumMode {
+    /// Keeps the space for new messages, the file never shrinks.
+    Never,
+    /// Frees the pages of deleted messages right after retention, without rewriting the file.
+    /// Only takes effect for databases created by the store.
+    #[default]
+    Incremental,
+    /// Rewrites the whole file after retention deleted messages, which is slow for large stores.
+    Full,
+}
+
+/// Which messages the store keeps. Messages beyond either limit are deleted when the retention
+/// is applied.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreConfig {
+    /// Messages received longer ago than this many seconds are deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// Beyond this many messages, the earliest stored are deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_messages: Option<u64>,
+    /// What happens to the space of deleted messages.
+    #[serde(default)]
+    pub vacuum: VacuumMode,
+}
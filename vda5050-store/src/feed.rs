@@ -0,0 +1,233 @@
+//! Reading live updates for one client from the store.
+use crate::live::{LiveBatch, LiveMessage, LiveSubscription, LiveUpdate};
+use crate::query::{MessageQuery, StoredMessage};
+use crate::store::{Store, StoreError};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
+use vda5050_analysis::recording::AgvId;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+
+/// The most messages read from the store at once while catching up.
+const PAGE_SIZE: usize = 5000;
+
+/// The live updates of one client. The store is the buffer: a client that reads slowly never makes
+/// messages pile up, it skips to the latest update of every AGV the next time it reads.
+#[derive(Debug, Clone)]
+pub struct LiveFeed {
+    subscription: LiveSubscription,
+    after_sequence: Option<u64>,
+    /// The receive time of the last update sent per AGV and topic, so that nothing older follows,
+    /// e.g. from a recording imported meanwhile.
+    sent: HashMap<(AgvId, Topic), DateTime<Utc>>,
+    /// The latest message per AGV and topic of the pages read for the next batch.
+    pending: HashMap<(AgvId, Topic), StoredMessage>,
+    /// The messages of the pages read for the next batch that newer ones replaced.
+    pending_skipped: usize,
+}
+
+impl LiveFeed {
+    /// The lowest rate a client may ask for, in updates per second.
+    pub const MIN_RATE_HZ: f64 = 0.1;
+    /// The highest rate a client may ask for, in updates per second.
+    pub const MAX_RATE_HZ: f64 = 30.0;
+
+    /// Starts after the sequence number of the subscription, or with the latest update of every
+    /// AGV without one.
+    pub fn new(subscription: LiveSubscription) -> Self {
+        LiveFeed {
+            after_sequence: subscription.after_sequence,
+            subscription,
+            sent: HashMap::new(),
+            pending: HashMap::new(),
+            pending_skipped: 0,
+        }
+    }
+
+    /// Changes the rate and the AGVs, keeping the position.
+    pub fn resubscribe(&mut self, subscription: LiveSubscription) {
+        self.subscription = subscription;
+    }
+
+    /// The time between two batches that keeps to the rate of the subscription.
+    pub fn period(&self) -> Duration {
+        let rate = self.subscription.max_rate_hz;
+        let rate = if rate.is_finite() {
+            rate.clamp(LiveFeed::MIN_RATE_HZ, LiveFeed::MAX_RATE_HZ)
+        } else {
+            LiveFeed::MAX_RATE_HZ
+        };
+        Duration::from_secs_f64(1.0 / rate)
+    }
+
+    /// Whether the store holds messages the client has not seen, given the latest sequence number
+    /// of the store.
+    pub fn is_behind(&self, last_sequence: u64) -> bool {
+        self.after_sequence
+            .is_none_or(|after_sequence| last_sequence > after_sequence)
+    }
+
+    /// Reads one page of the messages stored since the last batch. Once the last page is read,
+    /// returns the next batch: the latest state and visualization of every AGV of all those pages.
+    /// Until then returns `None`, and the caller should let go of the store before reading the
+    /// next page.
+    pub fn next_batch(&mut self, store: &Store) -> Result<Option<LiveBatch>, StoreError> {
+        let Some(mut after_sequence) = self.after_sequence else {
+            return self.snapshot(store).map(Some);
+        };
+        let page = store.query(&MessageQuery {
+            topics: vec![Topic::State, Topic::Visualization],
+            after_sequence: Some(after_sequence),
+            by_sequence: true,
+            limit: Some(PAGE_SIZE),
+            ..MessageQuery::default()
+        })?;
+        let complete = page.len() < PAGE_SIZE;
+        for message in page {
+            after_sequence = after_sequence.max(message.sequence);
+            if !self.wants(&message.agv) {
+                continue;
+            }
+            let key = (message.agv.clone(), message.topic);
+            match self.pending.get(&key) {
+                Some(newer) if newer.received_at > message.received_at => {
+                    self.pending_skipped += 1;
+                }
+                Some(_) => {
+                    self.pending_skipped += 1;
+                    self.pending.insert(key, message);
+                }
+                None => {
+                    self.pending.insert(key, message);
+                }
+            }
+        }
+        self.after_sequence = Some(after_sequence);
+        if !complete {
+            return Ok(None);
+        }
+        let latest = std::mem::take(&mut self.pending).into_values().collect();
+        let skipped = std::mem::take(&mut self.pending_skipped);
+        Ok(Some(self.batch(latest, after_sequence, skipped)))
+    }
+
+    /// The latest state and visualization of every AGV, to start from.
+    fn snapshot(&mut self, store: &Store) -> Result<LiveBatch, StoreError> {
+        let last_sequence = store.last_sequence()?.unwrap_or(0);
+        let mut messages = Vec::new();
+        for stored in store.agvs()? {
+            if !self.wants(&stored.agv) {
+                continue;
+            }
+            for topic in [Topic::State, Topic::Visualization] {
+                messages.extend(store.query(&MessageQuery {
+                    agv: Some(stored.agv.clone()),
+                    topics: vec![topic],
+                    latest: true,
+                    limit: Some(1),
+                    ..MessageQuery::default()
+                })?);
+            }
+        }
+        self.after_sequence = Some(last_sequence);
+        Ok(self.batch(messages, last_sequence, 0))
+    }
+
+    fn wants(&self, agv: &AgvId) -> bool {
+        self.subscription.agvs.is_empty() || self.subscription.agvs.contains(agv)
+    }
+
+    /// Decodes the messages that are newer than what was sent before.
+    fn batch(
+        &mut self,
+        mut messages: Vec<StoredMessage>,
+        last_sequence: u64,
+        mut skipped: usize,
+    ) -> LiveBatch {
+        messages.sort_by_key(|message| message.sequence);
+        let mut updates = Vec::new();
+        for message in messages {
+            let key = (message.agv.clone(), message.topic);
+            if self
+                .sent
+                .get(&key)
+                .is_some_and(|sent| *sent >= message.received_at)
+            {
+                skipped += 1;
+                continue;
+            }
+            let decoded = match message.decode() {
+                Ok(Message::State(state)) => LiveMessage::State(state),
+                Ok(Message::Visualization(visualization)) => {
+                    LiveMessage::Visualization(visualization)
+                }
+                _ => continue,
+            };
+            self.sent.insert(key, message.received_at);
+            updates.push(LiveUpdate {
+                sequence: message.sequence,
+                received_at: message.received_at,
+                agv: message.agv,
+                message: decoded,
+            });
+        }
+        LiveBatch {
+            updates,
+            last_sequence,
+            skipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StoreConfig;
+    use serde_json::json;
+    use vda5050_analysis::recording::RecordEntry;
+
+    fn state(header_id: usize) -> RecordEntry {
+        RecordEntry {
+            received_at: DateTime::UNIX_EPOCH + chrono::Duration::milliseconds(header_id as i64),
+            topic: "uagv/v2/acme/agv1/state".to_string(),
+            payload: json!({
+                "headerId": header_id,
+                "timestamp": "2024-01-01T00:00:00Z",
+                "version": "2.0.0",
+                "manufacturer": "acme",
+                "serialNumber": "agv1",
+                "orderId": "",
+                "orderUpdateId": 0,
+                "nodeStates": [],
+                "edgeStates": [],
+                "driving": false,
+                "operatingMode": "AUTOMATIC",
+                "actionStates": [],
+                "errors": [],
+            }),
+        }
+    }
+
+    #[test]
+    fn reads_one_page_per_call_and_sends_the_latest_update() {
+        let mut store = Store::open_in_memory(StoreConfig::default()).unwrap();
+        let entries: Vec<_> = (0..PAGE_SIZE + 2).map(state).collect();
+        store.insert_all(&entries).unwrap();
+        let mut feed = LiveFeed::new(LiveSubscription {
+            after_sequence: Some(0),
+            ..LiveSubscription::default()
+        });
+
+        assert!(feed.next_batch(&store).unwrap().is_none());
+        let batch = feed.next_batch(&store).unwrap().unwrap();
+        assert_eq!(batch.updates.len(), 1);
+        assert_eq!(batch.skipped, PAGE_SIZE + 1);
+        assert_eq!(batch.last_sequence, (PAGE_SIZE + 2) as u64);
+        let LiveMessage::State(state) = &batch.updates[0].message else {
+            panic!("expected a state");
+        };
+        assert_eq!(state.header.header_id as usize, PAGE_SIZE + 1);
+        assert!(!feed.is_behind(batch.last_sequence));
+    }
+}
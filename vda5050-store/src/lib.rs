@@ -0,0 +1,24 @@
+//! Persists VDA 5050 messages in an embedded SQLite database, so that the HMI can look back
+//! beyond a single recording.
+//!
+//! [`Store`] decodes the messages it is given, e.g. from recordings or from the embedded broker,
+//! and keeps them with indexes on the AGV, the topic, the receive time, the order ID and the
+//! error types of states. [`query::MessageQuery`] selects messages by these, and
+//! [`config::StoreConfig`] limits how long and how many messages are kept. [`LiveFeed`] reads the
+//! latest state and visualization of every AGV as they are stored, for clients that follow the
+//! fleet live.
+//!
+//! Without the `server` feature only the query, configuration and live update types are built.
+pub mod config;
+pub mod live;
+pub mod query;
+
+#[cfg(feature = "server")]
+mod feed;
+#[cfg(feature = "server")]
+mod store;
+
+#[cfg(feature = "server")]
+pub use feed::LiveFeed;
+#[cfg(feature = "server")]
+pub use store::{Store, StoreError};
@@ -0,0 +1,70 @@
+//! Live updates of the AGVs for clients that follow the fleet as messages are stored.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use vda5050_analysis::recording::AgvId;
+use vda5050_data_types::state::State;
+use vda5050_data_types::visualization::Visualization;
+
+/// What a client of the live updates asks for. Sending a new subscription on an open connection
+/// changes the rate and the AGVs without losing the position.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveSubscription {
+    /// Resume after this sequence number, e.g. the last one received before the connection was
+    /// lost. Without it, the updates start with the latest state and visualization of every AGV.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_sequence: Option<u64>,
+    /// The most updates per AGV and topic per second. Updates in between are skipped in favor of
+    /// the latest one.
+    pub max_rate_hz: f64,
+    /// Only updates of these AGVs, of all AGVs if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agvs: Vec<AgvId>,
+}
+
+impl Default for LiveSubscription {
+    fn default() -> Self {
+        LiveSubscription {
+            after_sequence: None,
+            max_rate_hz: 5.0,
+            agvs: Vec::new(),
+        }
+    }
+}
+
+/// A decoded message of a live update.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::large_enum_variant)]
+pub enum LiveMessage {
+    /// The state of the AGV.
+    State(State),
+    /// The visualization message of the AGV.
+    Visualization(Visualization),
+}
+
+/// The latest state or visualization of an AGV.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveUpdate {
+    /// The sequence number of the message in the store.
+    pub sequence: u64,
+    /// The time the message was received.
+    pub received_at: DateTime<Utc>,
+    /// The AGV that sent the message.
+    pub agv: AgvId,
+    /// The decoded message.
+    pub message: LiveMessage,
+}
+
+/// The updates sent to a client at once, at most one per AGV and topic.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveBatch {
+    /// The updates, ordered by sequence number.
+    pub updates: Vec<LiveUpdate>,
+    /// The sequence number to resume after, which covers the skipped updates as well.
+    pub last_sequence: u64,
+    /// The number of updates skipped because a later one of the same AGV and topic was sent.
+    pub skipped: usize,
+}
@@ -0,0 +1,205 @@
+//! Queries on the store and the messages they return.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use vda5050_analysis::recording::{AgvId, RecordEntry};
+use vda5050_data_types::connection::Connection;
+use vda5050_data_types::factsheet::Factsheet;
+use vda5050_data_types::instant_actions::InstantActions;
+use vda5050_data_types::message::Message;
+use vda5050_data_types::order::Order;
+use vda5050_data_types::state::State;
+use vda5050_data_types::topic::Topic;
+use vda5050_data_types::visualization::Visualization;
+
+/// Selects stored messages. Every field that is set narrows the result.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageQuery {
+    /// Only messages sent to or by this AGV.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agv: Option<AgvId>,
+    /// Only messages on these topics, all topics if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<Topic>,
+    /// Only messages received at or after this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    /// Only messages received before this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+    /// Only orders and states with this order ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<String>,
+    /// Only states reporting an error of this type.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<String>,
+    /// Only messages stored after the one with this sequence number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_sequence: Option<u64>,
+    /// Order by sequence number instead of receive time, e.g. to page through the messages in
+    /// the order they were stored.
+    #[serde(default)]
+    pub by_sequence: bool,
+    /// Return the latest messages instead of the earliest when the limit cuts the result.
+    #[serde(default)]
+    pub latest: bool,
+    /// The most messages to return.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// A message as stored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredMessage {
+    /// The number of the message in the store, increasing in the order messages were stored.
+    pub sequence: u64,
+    /// The time the message was received.
+    pub received_at: DateTime<Utc>,
+    /// The full MQTT topic the message was published on.
+    pub mqtt_topic: String,
+    /// The topic of the message.
+    pub topic: Topic,
+    /// The AGV the message was sent to or by.
+    pub agv: AgvId,
+    /// The header ID of the message.
+    pub header_id: u32,
+    /// The order ID of an order or state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<String>,
+    /// The error types of a state.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub error_types: Vec<String>,
+    /// The JSON payload as published.
+    pub payload: Value,
+}
+
+impl StoredMessage {
+    /// The message as a line of a recording file.
+    pub fn to_entry(&self) -> RecordEntry {
+        RecordEntry {
+            received_at: self.received_at,
+            topic: self.mqtt_topic.clone(),
+            payload: self.payload.clone(),
+        }
+    }
+
+    /// Decodes the payload.
+    pub fn decode(&self) -> Result<Message, serde_json::Error> {
+        Message::from_value(self.topic, self.payload.clone())
+    }
+}
+
+/// A decoded message with its place in the store.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Stored<T> {
+    /// The sequence number of the message in the store.
+    pub sequence: u64,
+    /// The time the message was received.
+    pub received_at: DateTime<Utc>,
+    /// The decoded message.
+    pub message: T,
+}
+
+/// A decoded message of any topic. Unlike [`Message`], it is tagged with its topic when
+/// serialized, so that clients can decode it again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::large_enum_variant)]
+pub enum TaggedMessage {
+    /// An order from master control.
+    Order(Order),
+    /// Instant actions from master control.
+    InstantActions(InstantActions),
+    /// The state of an AGV.
+    State(State),
+    /// The visualization message of an AGV.
+    Visualization(Visualization),
+    /// The connection state of an AGV.
+    Connection(Connection),
+    /// The factsheet of an AGV.
+    Factsheet(Factsheet),
+}
+
+impl From<Message> for TaggedMessage {
+    fn from(message: Message) -> Self {
+        match message {
+            Message::Order(order) => TaggedMessage::Order(order),
+            Message::InstantActions(instant_actions) => {
+                TaggedMessage::InstantActions(instant_actions)
+            }
+            Message::State(state) => TaggedMessage::State(state),
+            Message::Visualization(visualization) => TaggedMessage::Visualization(visualization),
+            Message::Connection(connection) => TaggedMessage::Connection(connection),
+            Message::Factsheet(factsheet) => TaggedMessage::Factsheet(factsheet),
+        }
+    }
+}
+
+impl From<TaggedMessage> for Message {
+    fn from(message: TaggedMessage) -> Self {
+        match message {
+            TaggedMessage::Order(order) => Message::Order(order),
+            TaggedMessage::InstantActions(instant_actions) => {
+                Message::InstantActions(instant_actions)
+            }
+            TaggedMessage::State(state) => Message::State(state),
+            TaggedMessage::Visualization(visualization) => Message::Visualization(visualization),
+            TaggedMessage::Connection(connection) => Message::Connection(connection),
+            TaggedMessage::Factsheet(factsheet) => Message::Factsheet(factsheet),
+        }
+    }
+}
+
+/// An order as stored: every update sent and the latest state of the AGV working on it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredOrder {
+    /// The AGV the order was sent to.
+    pub agv: AgvId,
+    /// The order ID.
+    pub order_id: String,
+    /// Every update of the order, ordered by receive time.
+    pub updates: Vec<Stored<Order>>,
+    /// The latest state of the AGV that refers to the order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_state: Option<Stored<State>>,
+}
+
+/// A time range of received messages, open where a bound is not given.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeWindow {
+    /// The start of the window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    /// The end of the window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// The stored messages of one AGV.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredAgv {
+    /// The AGV.
+    pub agv: AgvId,
+    /// The number of stored messages.
+    pub messages: u64,
+    /// When the earliest stored message was received.
+    pub first_received_at: DateTime<Utc>,
+    /// When the latest stored message was received.
+    pub last_received_at: DateTime<Utc>,
+}
+
+/// What importing messages into the store did.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    /// The number of messages stored.
+    pub stored: usize,
+    /// The number of entries that were not stored because they could not be decoded.
+    pub skipped: usize,
+}
@@ -0,0 +1,596 @@
+//! The SQLite database behind the store: schema, ingestion, queries and retention.
+use crate::config::{StoreConfig, VacuumMode};
+use crate::query::{ImportSummary, MessageQuery, Stored, StoredAgv, StoredMessage, StoredOrder};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, Transaction, params, params_from_iter};
+use std::fmt;
+use std::path::Path;
+use vda5050_analysis::recording::{AgvId, RecordEntry, Recording};
+use vda5050_data_types::message::Message;
+use vda5050_data_types::topic::Topic;
+
+/// The version of the schema below, kept in `PRAGMA user_version`.
+const SCHEMA_VERSION: i64 = 1;
+
+/// Times are stored as microseconds since the Unix epoch, so that ranges are compared as numbers.
+/// The IDs are the sequence numbers and never reused, even after the latest messages were deleted.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    received_at INTEGER NOT NULL,
+    mqtt_topic TEXT NOT NULL,
+    topic TEXT NOT NULL,
+    manufacturer TEXT NOT NULL,
+    serial_number TEXT NOT NULL,
+    header_id INTEGER NOT NULL,
+    order_id TEXT,
+    payload TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS message_errors (
+    message_id INTEGER NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    error_type TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_agv ON messages (manufacturer, serial_number, received_at);
+CREATE INDEX IF NOT EXISTS messages_topic ON messages (topic, received_at);
+CREATE INDEX IF NOT EXISTS messages_received_at ON messages (received_at);
+CREATE INDEX IF NOT EXISTS messages_order_id ON messages (order_id) WHERE order_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS message_errors_type ON message_errors (error_type, message_id);
+CREATE INDEX IF NOT EXISTS message_errors_message ON message_errors (message_id);
+";
+
+/// Why the store failed.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The database could not be opened, read or written.
+    Sqlite(rusqlite::Error),
+    /// A stored payload is not valid JSON.
+    Json(serde_json::Error),
+    /// The database was written by a newer version of the store.
+    UnknownSchema(i64),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(err) => write!(f, "database error: {err}"),
+            StoreError::Json(err) => write!(f, "invalid stored payload: {err}"),
+            StoreError::UnknownSchema(version) => {
+                write!(f, "unknown schema version {version}, the store is too old")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> Self {
+        StoreError::Json(err)
+    }
+}
+
+/// Decoded messages in an SQLite database, indexed by AGV, topic, receive time, order ID and
+/// error type.
+pub struct Store {
+    connection: Connection,
+    config: StoreConfig,
+}
+
+impl Store {
+    /// Opens the database file, creating it and its tables if needed.
+    pub fn open<P: AsRef<Path>>(path: P, config: StoreConfig) -> Result<Self, StoreError> {
+        let store = Store::init(Connection::open(path)?, config)?;
+        // Readers do not block the writer, and a crash loses at most the last transactions.
+        store
+            .connection
+            .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        store
+            .connection
+            .pragma_update(None, "synchronous", "NORMAL")?;
+        Ok(store)
+    }
+
+    /// Opens a store that lives in memory only, e.g. for a single analysis.
+    pub fn open_in_memory(config: StoreConfig) -> Result<Self, StoreError> {
+        Store::init(Connection::open_in_memory()?, config)
+    }
+
+    fn init(connection: Connection, config: StoreConfig) -> Result<Self, StoreError> {
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(StoreError::UnknownSchema(version));
+        }
+        connection.pragma_update(None, "foreign_keys", true)?;
+        if version == 0 {
+            // Has to be set before the first table is created to take effect.
+            connection.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+            connection.execute_batch(SCHEMA)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        Ok(Store { connection, config })
+    }
+
+    /// Stores an entry of a recording and returns its sequence number, or `None` if it is not a
+    /// VDA 5050 message and was not stored.
+    pub fn insert(&mut self, entry: &RecordEntry) -> Result<Option<u64>, StoreError> {
+        let transaction = self.connection.transaction()?;
+        let sequence = insert_entry(&transaction, entry)?;
+        transaction.commit()?;
+        Ok(sequence)
+    }
+
+    /// Stores many entries in one transaction, which is much faster than one by one.
+    pub fn insert_all<'a, I>(&mut self, entries: I) -> Result<ImportSummary, StoreError>
+    where
+        I: IntoIterator<Item = &'a RecordEntry>,
+    {
+        let transaction = self.connection.transaction()?;
+        let mut summary = ImportSummary::default();
+        for entry in entries {
+            match insert_entry(&transaction, entry)? {
+                Some(_) => summary.stored += 1,
+                None => summary.skipped += 1,
+            }
+        }
+        transaction.commit()?;
+        Ok(summary)
+    }
+
+    /// Stores the decoded messages of a recording with their payloads as published. Its decode
+    /// failures are counted as skipped.
+    pub fn insert_recording(&mut self, recording: &Recording) -> Result<ImportSummary, StoreError> {
+        let transaction = self.connection.transaction()?;
+        for recorded in &recording.messages {
+            let payload = serde_json::to_string(&recorded.payload)?;
+            insert_message(
+                &transaction,
+                recorded.received_at,
+                &recorded.mqtt_topic,
+                &recorded.message,
+                &payload,
+            )?;
+        }
+        transaction.commit()?;
+        Ok(ImportSummary {
+            stored: recording.messages.len(),
+            skipped: recording.failures.len(),
+        })
+    }
+
+    /// The messages selected by the query, ordered by receive time or sequence number.
+    pub fn query(&self, query: &MessageQuery) -> Result<Vec<StoredMessage>, StoreError> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(agv) = &query.agv {
+            conditions.push("m.manufacturer = ? AND m.serial_number = ?".to_string());
+            values.push(SqlValue::Text(agv.manufacturer.clone()));
+            values.push(SqlValue::Text(agv.serial_number.clone()));
+        }
+        if !query.topics.is_empty() {
+            let placeholders = vec!["?"; query.topics.len()].join(", ");
+            conditions.push(format!("m.topic IN ({placeholders})"));
+            values.extend(
+                query
+                    .topics
+                    .iter()
+                    .map(|topic| SqlValue::Text(topic.name().to_string())),
+            );
+        }
+        if let Some(from) = query.from {
+            conditions.push("m.received_at >= ?".to_string());
+            values.push(SqlValue::Integer(from.timestamp_micros()));
+        }
+        if let Some(to) = query.to {
+            conditions.push("m.received_at < ?".to_string());
+            values.push(SqlValue::Integer(to.timestamp_micros()));
+        }
+        if let Some(order_id) = &query.order_id {
+            conditions.push("m.order_id = ?".to_string());
+            values.push(SqlValue::Text(order_id.clone()));
+        }
+        if let Some(error_type) = &query.error_type {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM message_errors e WHERE e.message_id = m.id AND e.error_type = ?)"
+                    .to_string(),
+            );
+            values.push(SqlValue::Text(error_type.clone()));
+        }
+        if let Some(after_sequence) = query.after_sequence {
+            conditions.push("m.id > ?".to_string());
+            values.push(SqlValue::Integer(after_sequence as i64));
+        }
+
+        let mut sql = "SELECT m.id, m.received_at, m.mqtt_topic, m.topic, m.manufacturer, \
+                       m.serial_number, m.header_id, m.order_id, m.payload FROM messages m"
+            .to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(match (query.by_sequence, query.latest) {
+            (false, false) => " ORDER BY m.received_at, m.id",
+            (false, true) => " ORDER BY m.received_at DESC, m.id DESC",
+            (true, false) => " ORDER BY m.id",
+            (true, true) => " ORDER BY m.id DESC",
+        });
+        if let Some(limit) = query.limit {
+            sql.push_str(" LIMIT ?");
+            values.push(SqlValue::Integer(limit as i64));
+        }
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, u32>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, String>(8)?,
+            ))
+        })?;
+        let mut errors = self.connection.prepare_cached(
+            "SELECT error_type FROM message_errors WHERE message_id = ? ORDER BY rowid",
+        )?;
+        let mut messages = Vec::new();
+        for row in rows {
+            let (
+                id,
+                received_at,
+                mqtt_topic,
+                topic,
+                manufacturer,
+                serial_number,
+                header_id,
+                order_id,
+                payload,
+            ) = row?;
+            // Only the store writes the database, so the topic names and times are valid.
+            let Ok(topic) = topic.parse::<Topic>() else {
+                continue;
+            };
+            let error_types = if topic == Topic::State {
+                errors
+                    .query_map([id], |row| row.get(0))?
+                    .collect::<Result<_, _>>()?
+            } else {
+                Vec::new()
+            };
+            messages.push(StoredMessage {
+                sequence: id as u64,
+                received_at: from_micros(received_at),
+                mqtt_topic,
+                topic,
+                agv: AgvId {
+                    manufacturer,
+                    serial_number,
+                },
+                header_id,
+                order_id,
+                error_types,
+                payload: serde_json::from_str(&payload)?,
+            });
+        }
+        if query.latest {
+            messages.reverse();
+        }
+        Ok(messages)
+    }
+
+    /// The messages selected by the query as a recording, e.g. to analyze a time range.
+    pub fn recording(&self, query: &MessageQuery) -> Result<Recording, StoreError> {
+        let mut recording = Recording::default();
+        for message in self.query(query)? {
+            let source = format!("store:{}", message.sequence);
+            recording.push_entry(message.to_entry(), source);
+        }
+        Ok(recording)
+    }
+
+    /// The messages selected by the query, decoded. Messages that no longer decode, e.g. after
+    /// the data types changed, are left out.
+    pub fn messages(&self, query: &MessageQuery) -> Result<Vec<Stored<Message>>, StoreError> {
+        Ok(self
+            .query(query)?
+            .into_iter()
+            .filter_map(|stored| {
+                Some(Stored {
+                    sequence: stored.sequence,
+                    received_at: stored.received_at,
+                    message: stored.decode().ok()?,
+                })
+            })
+            .collect())
+    }
+
+    /// The updates of an order and the latest state of the AGV that refers to it, `None` if no
+    /// update of the order is stored. An order ID names one order of one AGV, states of other
+    /// AGVs with the same order ID are ignored.
+    pub fn order(&self, order_id: &str) -> Result<Option<StoredOrder>, StoreError> {
+        let updates = self.query(&MessageQuery {
+            topics: vec![Topic::Order],
+            order_id: Some(order_id.to_string()),
+            ..MessageQuery::default()
+        })?;
+        let Some(agv) = updates.first().map(|update| update.agv.clone()) else {
+            return Ok(None);
+        };
+        let updates = updates
+            .iter()
+            .filter(|update| update.agv == agv)
+            .filter_map(|update| match update.decode() {
+                Ok(Message::Order(order)) => Some(Stored {
+                    sequence: update.sequence,
+                    received_at: update.received_at,
+                    message: order,
+                }),
+                _ => None,
+            })
+            .collect();
+        let last_state = self
+            .query(&MessageQuery {
+                agv: Some(agv.clone()),
+                topics: vec![Topic::State],
+                order_id: Some(order_id.to_string()),
+                latest: true,
+                limit: Some(1),
+                ..MessageQuery::default()
+            })?
+            .into_iter()
+            .find_map(|state| match state.decode() {
+                Ok(Message::State(message)) => Some(Stored {
+                    sequence: state.sequence,
+                    received_at: state.received_at,
+                    message,
+                }),
+                _ => None,
+            });
+        Ok(Some(StoredOrder {
+            agv,
+            order_id: order_id.to_string(),
+            updates,
+            last_state,
+        }))
+    }
+
+    /// Every AGV with stored messages, ordered by manufacturer and serial number.
+    pub fn agvs(&self) -> Result<Vec<StoredAgv>, StoreError> {
+        let mut statement = self.connection.prepare(
+            "SELECT manufacturer, serial_number, COUNT(*), MIN(received_at), MAX(received_at) \
+             FROM messages GROUP BY manufacturer, serial_number \
+             ORDER BY manufacturer, serial_number",
+        )?;
+        let agvs = statement
+            .query_map([], |row| {
+                Ok(StoredAgv {
+                    agv: AgvId {
+                        manufacturer: row.get(0)?,
+                        serial_number: row.get(1)?,
+                    },
+                    messages: row.get::<_, i64>(2)? as u64,
+                    first_received_at: from_micros(row.get(3)?),
+                    last_received_at: from_micros(row.get(4)?),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(agvs)
+    }
+
+    /// Which messages the store keeps.
+    pub fn config(&self) -> &StoreConfig {
+        &self.config
+    }
+
+    /// Changes which messages the store keeps, from the next [`Store::apply_retention`] on.
+    pub fn set_config(&mut self, config: StoreConfig) {
+        self.config = config;
+    }
+
+    /// The number of stored messages.
+    pub fn len(&self) -> Result<u64, StoreError> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+
+    /// Whether no message is stored.
+    pub fn is_empty(&self) -> Result<bool, StoreError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The sequence number of the latest stored message, `None` if the store is empty.
+    pub fn last_sequence(&self) -> Result<Option<u64>, StoreError> {
+        let id: Option<i64> =
+            self.connection
+                .query_row("SELECT MAX(id) FROM messages", [], |row| row.get(0))?;
+        Ok(id.map(|id| id as u64))
+    }
+
+    /// Deletes the messages beyond the maximum age and count of the configuration, vacuums as
+    /// configured and returns the number of deleted messages.
+    pub fn apply_retention(&mut self) -> Result<u64, StoreError> {
+        let transaction = self.connection.transaction()?;
+        let mut deleted = 0;
+        if let Some(max_age_secs) = self.config.max_age_secs {
+            let oldest = i64::try_from(max_age_secs)
+                .ok()
+                .and_then(|secs| {
+                    Utc::now().checked_sub_signed(chrono::Duration::try_seconds(secs)?)
+                })
+                .map_or(i64::MIN, |oldest| oldest.timestamp_micros());
+            deleted +=
+                transaction.execute("DELETE FROM messages WHERE received_at < ?", [oldest])?;
+        }
+        if let Some(max_messages) = self.config.max_messages {
+            // Keeps the latest messages in the order they were stored.
+            let first_kept: Option<i64> = if max_messages == 0 {
+                Some(i64::MAX)
+            } else {
+                transaction
+                    .query_row(
+                        "SELECT id FROM messages ORDER BY id DESC LIMIT 1 OFFSET ?",
+                        [max_messages as i64 - 1],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+            };
+            if let Some(first_kept) = first_kept {
+                deleted +=
+                    transaction.execute("DELETE FROM messages WHERE id < ?", [first_kept])?;
+            }
+        }
+        transaction.commit()?;
+        if deleted > 0 {
+            match self.config.vacuum {
+                VacuumMode::Never => {}
+                VacuumMode::Incremental => {
+                    // Every step of the pragma frees one page.
+                    let mut statement = self.connection.prepare("PRAGMA incremental_vacuum")?;
+                    let mut rows = statement.query([])?;
+                    while rows.next()?.is_some() {}
+                }
+                VacuumMode::Full => self.vacuum()?,
+            }
+        }
+        Ok(deleted as u64)
+    }
+
+    /// Rewrites the database file to give back the space of deleted messages.
+    pub fn vacuum(&self) -> Result<(), StoreError> {
+        self.connection.execute_batch("VACUUM")?;
+        Ok(())
+    }
+}
+
+fn from_micros(micros: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_micros(micros).unwrap_or_default()
+}
+
+/// Decodes and stores an entry, `None` if it is not a VDA 5050 message.
+fn insert_entry(transaction: &Transaction, entry: &RecordEntry) -> Result<Option<u64>, StoreError> {
+    let Some(topic) = Topic::from_mqtt_topic(&entry.topic) else {
+        return Ok(None);
+    };
+    let Ok(message) = Message::from_value(topic, entry.payload.clone()) else {
+        return Ok(None);
+    };
+    let payload = serde_json::to_string(&entry.payload)?;
+    let sequence = insert_message(
+        transaction,
+        entry.received_at,
+        &entry.topic,
+        &message,
+        &payload,
+    )?;
+    Ok(Some(sequence))
+}
+
+fn insert_message(
+    transaction: &Transaction,
+    received_at: DateTime<Utc>,
+    mqtt_topic: &str,
+    message: &Message,
+    payload: &str,
+) -> Result<u64, StoreError> {
+    let header = message.header();
+    let order_id = match message {
+        Message::Order(order) => Some(order.order_id.as_str()),
+        Message::State(state) => state.order_id.as_deref(),
+        _ => None,
+    };
+    transaction
+        .prepare_cached(
+            "INSERT INTO messages (received_at, mqtt_topic, topic, manufacturer, serial_number, \
+             header_id, order_id, payload) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )?
+        .execute(params![
+            received_at.timestamp_micros(),
+            mqtt_topic,
+            message.topic().name(),
+            header.manufacturer,
+            header.serial_number,
+            header.header_id,
+            order_id,
+            payload,
+        ])?;
+    let id = transaction.last_insert_rowid();
+    if let Message::State(state) = message {
+        let mut insert_error = transaction
+            .prepare_cached("INSERT INTO message_errors (message_id, error_type) VALUES (?, ?)")?;
+        for error in &state.errors {
+            insert_error.execute(params![id, error.error_type])?;
+        }
+    }
+    Ok(id as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(header_id: u32, received_at: DateTime<Utc>) -> RecordEntry {
+        RecordEntry {
+            received_at,
+            topic: "uagv/v2/acme/agv1/state".to_string(),
+            payload: json!({
+                "headerId": header_id,
+                "timestamp": "2024-01-01T00:00:00Z",
+                "version": "2.0.0",
+                "manufacturer": "acme",
+                "serialNumber": "agv1",
+                "orderId": "",
+                "orderUpdateId": 0,
+                "nodeStates": [],
+                "edgeStates": [],
+                "driving": false,
+                "operatingMode": "AUTOMATIC",
+                "actionStates": [],
+                "errors": [],
+            }),
+        }
+    }
+
+    #[test]
+    fn sequence_numbers_are_not_reused_after_retention() {
+        let mut store = Store::open_in_memory(StoreConfig {
+            max_age_secs: Some(3600),
+            ..StoreConfig::default()
+        })
+        .unwrap();
+        store.insert(&entry(0, Utc::now())).unwrap();
+        // Stored last but received long ago, so the retention deletes the latest ID.
+        let deleted = store
+            .insert(&entry(1, DateTime::UNIX_EPOCH))
+            .unwrap()
+            .unwrap();
+        assert_eq!(store.apply_retention().unwrap(), 1);
+        let next = store.insert(&entry(2, Utc::now())).unwrap().unwrap();
+        assert!(next > deleted, "{next} reuses {deleted}");
+    }
+
+    #[test]
+    fn imports_recordings_with_the_payloads_as_published() {
+        let mut store = Store::open_in_memory(StoreConfig::default()).unwrap();
+        let mut entry = entry(0, Utc::now());
+        entry.payload["vendorField"] = json!("kept");
+        let mut recording = Recording::default();
+        recording.push_entry(entry.clone(), "session.jsonl:1".to_string());
+        store.insert_recording(&recording).unwrap();
+
+        let stored = store.query(&MessageQuery::default()).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].payload, entry.payload);
+    }
+}